@@ -5,38 +5,58 @@ use sqlparser::ast::{self};
 use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    error::ParseError, malformed_query, query_metadata::FromClauseIdentifier, unsupported,
+    dialect::{CaseFolding, Dialect},
+    error::{ErrorCode, ParseError, UnsupportedFeature},
+    identifier::Identifier,
+    malformed_query,
+    query_metadata::FromClauseIdentifier,
+    unsupported,
 };
 
-use super::support::{case_fold_identifier, extract_qualified_column, remove_outer_parens};
+use super::support::{
+    case_fold_identifier, extract_qualified_column, remove_outer_parens, render_identifier,
+};
 
 /// An aggregation that's computed over the values of a column.
 ///
 /// Represents an occurrence of an aggregation such as `function(column)`
 /// within the `SELECT` clause of a query.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema, IntoParams)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
 pub struct Aggregation {
     /// The function used as aggregator of column's values.
     pub function: KoronFunction,
     /// The name of the column on which the function is executed.
-    pub column: String,
+    #[schema(value_type = String)]
+    #[param(value_type = String)]
+    pub column: Identifier,
     /// The alias that's assigned to the result of the function: `function(column) AS alias`.
     pub alias: Option<String>,
+    /// The window of a running aggregate (e.g. `SUM(a) OVER (ORDER BY ts ROWS UNBOUNDED
+    /// PRECEDING)`), if the feature policy allows it (see
+    /// [`crate::policy::FeaturePolicy::allow_running_aggregates`]): the function is then computed
+    /// for each row over the rows up to it, and the query returns a row per row of the table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(value_type = Option<Object>)]
+    pub window: Option<WindowSpec>,
 }
 
 impl Aggregation {
     pub(crate) fn extract(
         from_clause_identifier: FromClauseIdentifier<'_>,
         projection: &[ast::SelectItem],
+        case_folding: CaseFolding,
+        case_insensitive_functions: bool,
+        allow_running_aggregates: bool,
     ) -> Result<Self, ParseError> {
         let multiple_aggregations = || {
-            Err(unsupported!("the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.".to_string()))
+            Err(unsupported!(UnsupportedFeature::Projection, "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.".to_string()))
         };
         //check if single operation in the projection
         let (expr, alias) = match projection {
             [ast::SelectItem::UnnamedExpr(expr)] => (expr, None),
             [ast::SelectItem::ExprWithAlias { expr, alias }] => {
-                (expr, Some(case_fold_identifier(alias)))
+                (expr, Some(case_fold_identifier(alias, case_folding)))
             }
             _ => {
                 return multiple_aggregations();
@@ -58,29 +78,167 @@ impl Aggregation {
             filter,
             null_treatment,
         } = function;
-        if over.is_some() {
-            return Err(unsupported!("window functions (OVER).".to_string()));
-        }
+        let window = match over {
+            Some(over) if allow_running_aggregates => Some(WindowSpec::extract(
+                from_clause_identifier,
+                over,
+                case_folding,
+            )?),
+            Some(_) => {
+                return Err(unsupported!(
+                    UnsupportedFeature::WindowFunction,
+                    "window functions (OVER).".to_string()
+                ))
+            }
+            None => None,
+        };
         if *distinct {
-            return Err(unsupported!("DISTINCT.".to_string()));
+            return Err(unsupported!(
+                UnsupportedFeature::Distinct,
+                "DISTINCT.".to_string()
+            ));
         }
         if !order_by.is_empty() {
-            return Err(unsupported!("ORDER BY.".to_string()));
+            return Err(unsupported!(
+                UnsupportedFeature::AggregationOrderBy,
+                "ORDER BY.".to_string()
+            ));
         }
         if filter.is_some() {
-            return Err(unsupported!("FILTER.".to_string()));
+            return Err(unsupported!(
+                UnsupportedFeature::AggregationFilter,
+                "FILTER.".to_string()
+            ));
         }
         if null_treatment.is_some() {
-            return Err(unsupported!("IGNORE NULLS.".to_string()));
+            return Err(unsupported!(
+                UnsupportedFeature::NullTreatment,
+                "IGNORE NULLS.".to_string()
+            ));
         }
         //check if it is a supported function
-        let (function, column) =
-            Self::validate_function_and_arguments(from_clause_identifier, name, args)?;
+        let (function, column) = Self::validate_function_and_arguments(
+            from_clause_identifier,
+            name,
+            args,
+            case_folding,
+            case_insensitive_functions,
+        )?;
+        //the median has no window form in the dialects that compute it
+        if window.is_some() && function == KoronFunction::Median {
+            return Err(unsupported!(
+                UnsupportedFeature::WindowFunction,
+                format!("the {function} function as a running aggregate (OVER).")
+            ));
+        }
 
         Ok(Self {
             function,
             column,
             alias,
+            window,
+        })
+    }
+
+    // rebuilds `function(column) [AS alias]` as a SELECT clause item
+    pub(crate) fn to_select_item(
+        &self,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> ast::SelectItem {
+        self.wrap_select_item(dialect, quote_style, |function| function)
+    }
+
+    // rebuilds `function(column) [AS alias]`, with `wrap` applied to the function call
+    pub(crate) fn wrap_select_item(
+        &self,
+        dialect: Dialect,
+        quote_style: Option<char>,
+        wrap: impl FnOnce(ast::Expr) -> ast::Expr,
+    ) -> ast::SelectItem {
+        let function = ast::Expr::Function(ast::Function {
+            name: ast::ObjectName(vec![ast::Ident::new(self.function.to_string())]),
+            args: vec![ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(
+                ast::Expr::Identifier(self.column.render(dialect, quote_style)),
+            ))],
+            filter: None,
+            null_treatment: None,
+            over: self
+                .window
+                .as_ref()
+                .map(|window| window.to_ast(dialect, quote_style)),
+            distinct: false,
+            special: false,
+            order_by: Vec::default(),
+        });
+        match &self.alias {
+            Some(alias) => ast::SelectItem::ExprWithAlias {
+                expr: wrap(function),
+                alias: render_identifier(alias, dialect, quote_style),
+            },
+            None => ast::SelectItem::UnnamedExpr(wrap(function)),
+        }
+    }
+
+    // the dialect-specific expression computing the median of the column, if there's one
+    pub(crate) fn median_expr(
+        &self,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> Option<ast::Expr> {
+        let column = ast::Expr::Identifier(self.column.render(dialect, quote_style));
+        let number = |value: &str| ast::Expr::Value(ast::Value::Number(value.to_string(), false));
+        match dialect {
+            Dialect::Snowflake => Some(function_call("MEDIAN", vec![column])),
+            // sqlparser can't represent WITHIN GROUP on ordinary functions, so the call is
+            // emitted as a special (i.e. printed verbatim, without parentheses) function
+            Dialect::PostgreSql => Some(ast::Expr::Function(ast::Function {
+                name: ast::ObjectName(vec![ast::Ident::new(format!(
+                    "PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY {column})"
+                ))]),
+                args: Vec::default(),
+                filter: None,
+                null_treatment: None,
+                over: None,
+                distinct: false,
+                special: true,
+                order_by: Vec::default(),
+            })),
+            Dialect::BigQuery => Some(ast::Expr::ArrayIndex {
+                obj: Box::new(function_call("APPROX_QUANTILES", vec![column, number("2")])),
+                indexes: vec![function_call("OFFSET", vec![number("1")])],
+            }),
+            Dialect::Generic | Dialect::MySql => None,
+        }
+    }
+
+    /// The partial aggregates to compute on each shard of the table so that the result of the
+    /// aggregation can be recombined from them (see [`Decomposition::combine`]), e.g. the sum and
+    /// the count of the column for an average.
+    ///
+    /// Returns `None` for the median, which can't be recombined from partial aggregates, and for
+    /// a running aggregate, which depends on the rows of the other shards.
+    #[must_use]
+    pub fn decompose(&self) -> Option<Decomposition> {
+        if self.window.is_some() {
+            return None;
+        }
+        let partials = match self.function {
+            KoronFunction::Sum => vec![PartialAggregate::Sum],
+            KoronFunction::Count => vec![PartialAggregate::Count],
+            KoronFunction::Min => vec![PartialAggregate::Min],
+            KoronFunction::Max => vec![PartialAggregate::Max],
+            KoronFunction::Average => vec![PartialAggregate::Sum, PartialAggregate::Count],
+            KoronFunction::Variance | KoronFunction::StandardDeviation => vec![
+                PartialAggregate::Sum,
+                PartialAggregate::SumOfSquares,
+                PartialAggregate::Count,
+            ],
+            KoronFunction::Median => return None,
+        };
+        Some(Decomposition {
+            function: self.function,
+            partials,
         })
     }
 
@@ -88,50 +246,67 @@ impl Aggregation {
         from_clause_identifier: FromClauseIdentifier<'_>,
         function_name: &ast::ObjectName,
         args: &[ast::FunctionArg],
-    ) -> Result<(KoronFunction, String), ParseError> {
+        case_folding: CaseFolding,
+        case_insensitive_functions: bool,
+    ) -> Result<(KoronFunction, Identifier), ParseError> {
         //closure that extracts column information from the statement
         let only_column_arg = |function| {
-            let column =
-                Self::extract_only_column_argument(from_clause_identifier, function_name, args)?;
+            let column = Self::extract_only_column_argument(
+                from_clause_identifier,
+                function_name,
+                args,
+                case_folding,
+            )?;
             Ok((function, column))
         };
 
         let ast::ObjectName(name_parts) = function_name;
-        if let [unqualified_name] = &name_parts[..] {
-            //currently only these four functions are supported by Koron
-            match &case_fold_identifier(unqualified_name)[..] {
-                "sum" => return only_column_arg(KoronFunction::Sum),
-                "count" => return only_column_arg(KoronFunction::Count),
-                "avg" => return only_column_arg(KoronFunction::Average),
-                "median" => return only_column_arg(KoronFunction::Median),
-                "variance" => return only_column_arg(KoronFunction::Variance),
-                "stddev" => return only_column_arg(KoronFunction::StandardDeviation),
-                "min" => return only_column_arg(KoronFunction::Min),
-                "max" => return only_column_arg(KoronFunction::Max),
-                _ => (),
+        let folded_name = match &name_parts[..] {
+            [unqualified_name] => Some(case_fold_identifier(unqualified_name, case_folding)),
+            _ => None,
+        };
+        //a quoted name only matches a builtin function if it is spelled in the folded case, unless
+        //the function names are matched whatever their case
+        if let Some(name) =
+            folded_name.filter(|name| case_insensitive_functions || case_folding.is_folded(name))
+        {
+            //currently only these functions are supported by Koron
+            if let Some(function) = KoronFunction::from_name(&name) {
+                return only_column_arg(function);
             }
         }
-        Err(unsupported!(format!(
-            "unrecognized or unsupported function: {function_name}."
-        )))
+        Err(unsupported!(
+            UnsupportedFeature::UnknownFunction(function_name.to_string()),
+            format!("unrecognized or unsupported function: {function_name}.")
+        ))
     }
 
     fn extract_only_column_argument(
         from_clause_identifier: FromClauseIdentifier<'_>,
         function_name: &ast::ObjectName,
         args: &[ast::FunctionArg],
-    ) -> Result<String, ParseError> {
+        case_folding: CaseFolding,
+    ) -> Result<Identifier, ParseError> {
         //currently only functions that takes as input a single column are supported (i.e. a single argument)
         match args {
             [arg] => {
                 let arg_expr = Self::extract_unnamed_argument(arg)?;
-                Self::extract_aggregated_column(from_clause_identifier, function_name, arg_expr, "")
+                Self::extract_aggregated_column(
+                    from_clause_identifier,
+                    function_name,
+                    arg_expr,
+                    "",
+                    case_folding,
+                )
             }
-            _ => Err(malformed_query!(format!(
+            _ => Err(malformed_query!(
+                ErrorCode::WrongArgumentCount,
+                format!(
                 "the {function_name} function takes exactly 1 argument, but {} {verb} provided.",
                 args.len(),
                 verb = if args.len() == 1 { "is" } else { "are" },
-            ))),
+            )
+            )),
         }
     }
 
@@ -139,9 +314,10 @@ impl Aggregation {
         arg: &ast::FunctionArg,
     ) -> Result<&ast::FunctionArgExpr, ParseError> {
         match arg {
-            ast::FunctionArg::Named { .. } => Err(unsupported!(format!(
-                "named function arguments (such as {arg})."
-            ))),
+            ast::FunctionArg::Named { .. } => Err(unsupported!(
+                UnsupportedFeature::NamedArgument,
+                format!("named function arguments (such as {arg}).")
+            )),
             ast::FunctionArg::Unnamed(arg_expr) => Ok(arg_expr),
         }
     }
@@ -151,29 +327,280 @@ impl Aggregation {
         function_name: &ast::ObjectName,
         arg_expr: &ast::FunctionArgExpr,
         which_arg: &str,
-    ) -> Result<String, ParseError> {
+        case_folding: CaseFolding,
+    ) -> Result<Identifier, ParseError> {
         if let ast::FunctionArgExpr::Expr(expr) = arg_expr {
             match remove_outer_parens(expr) {
-                ast::Expr::Identifier(ident) => return Ok(case_fold_identifier(ident)),
+                ast::Expr::Identifier(ident) => return Ok(Identifier::fold(ident, case_folding)),
                 compound_identifier @ ast::Expr::CompoundIdentifier(name_parts) => {
                     return extract_qualified_column(
                         from_clause_identifier,
                         compound_identifier,
                         name_parts,
+                        case_folding,
                     );
                 }
                 _ => (),
             }
         }
-        Err(unsupported!(format!(
+        Err(unsupported!(
+                UnsupportedFeature::Argument,
+                format!(
                 "only a column name is supported as the {which_arg}{space}argument of the {function_name} function.",
                 space = if which_arg.is_empty() { "" } else { " " },
             )))
     }
 }
 
+/// The window of a running aggregate, the only form of window function supported.
+///
+/// It's written `OVER (ORDER BY column [ASC | DESC] ROWS UNBOUNDED PRECEDING)`, or with the
+/// equivalent frame `ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW`: the function is computed
+/// for each row over the rows up to it in the order of the column, e.g. a cumulative sum. It's
+/// displayed as the content of the OVER clause.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct WindowSpec {
+    /// The column the rows are ordered by.
+    #[schema(value_type = String)]
+    pub order_by: Identifier,
+    /// Whether the rows are in descending order.
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl WindowSpec {
+    // extracts the window of `over`, rejecting the other windows: the frame must be given, since
+    // the default one (RANGE) also includes the rows that tie with the current one
+    fn extract(
+        from_clause_identifier: FromClauseIdentifier<'_>,
+        over: &ast::WindowType,
+        case_folding: CaseFolding,
+    ) -> Result<Self, ParseError> {
+        let unsupported = || {
+            unsupported!(
+                UnsupportedFeature::WindowFunction,
+                format!("window functions other than a running aggregate (i.e., OVER {over}).")
+            )
+        };
+        let ast::WindowType::WindowSpec(ast::WindowSpec {
+            partition_by,
+            order_by,
+            window_frame:
+                Some(ast::WindowFrame {
+                    units: ast::WindowFrameUnits::Rows,
+                    start_bound: ast::WindowFrameBound::Preceding(None),
+                    end_bound: None | Some(ast::WindowFrameBound::CurrentRow),
+                }),
+        }) = over
+        else {
+            return Err(unsupported());
+        };
+        let [ast::OrderByExpr {
+            expr,
+            asc,
+            nulls_first: None,
+        }] = &order_by[..]
+        else {
+            return Err(unsupported());
+        };
+        if !partition_by.is_empty() {
+            return Err(unsupported());
+        }
+        let order_by = match remove_outer_parens(expr) {
+            ast::Expr::Identifier(ident) => Identifier::fold(ident, case_folding),
+            compound_identifier @ ast::Expr::CompoundIdentifier(name_parts) => {
+                extract_qualified_column(
+                    from_clause_identifier,
+                    compound_identifier,
+                    name_parts,
+                    case_folding,
+                )?
+            }
+            _ => return Err(unsupported()),
+        };
+        Ok(Self {
+            order_by,
+            descending: *asc == Some(false),
+        })
+    }
+
+    // the window as an OVER clause of the SQL parser, in its shorthand form
+    pub(crate) fn to_ast(&self, dialect: Dialect, quote_style: Option<char>) -> ast::WindowType {
+        ast::WindowType::WindowSpec(self.specification(dialect, quote_style))
+    }
+
+    fn specification(&self, dialect: Dialect, quote_style: Option<char>) -> ast::WindowSpec {
+        ast::WindowSpec {
+            partition_by: Vec::new(),
+            order_by: vec![ast::OrderByExpr {
+                expr: ast::Expr::Identifier(self.order_by.render(dialect, quote_style)),
+                asc: self.descending.then_some(false),
+                nulls_first: None,
+            }],
+            window_frame: Some(ast::WindowFrame {
+                units: ast::WindowFrameUnits::Rows,
+                start_bound: ast::WindowFrameBound::Preceding(None),
+                end_bound: None,
+            }),
+        }
+    }
+}
+
+impl Display for WindowSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.specification(Dialect::Generic, None).fmt(f)
+    }
+}
+
+/// An aggregate computed on each shard of a table, which the result of an aggregation is
+/// recombined from (see [`Aggregation::decompose`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
+pub enum PartialAggregate {
+    /// The sum of the column, `SUM(column)`.
+    Sum,
+    /// The sum of the squares of the column, `SUM(column * column)`.
+    SumOfSquares,
+    /// The number of values of the column, `COUNT(column)`.
+    Count,
+    /// The minimum of the column, `MIN(column)`.
+    Min,
+    /// The maximum of the column, `MAX(column)`.
+    Max,
+}
+
+impl PartialAggregate {
+    // the expression computing the partial aggregate of `column`
+    pub(crate) fn to_expr(self, column: ast::Expr) -> ast::Expr {
+        let (name, arg) = match self {
+            Self::Sum => ("SUM", column),
+            Self::SumOfSquares => (
+                "SUM",
+                ast::Expr::BinaryOp {
+                    left: Box::new(column.clone()),
+                    op: ast::BinaryOperator::Multiply,
+                    right: Box::new(column),
+                },
+            ),
+            Self::Count => ("COUNT", column),
+            Self::Min => ("MIN", column),
+            Self::Max => ("MAX", column),
+        };
+        function_call(name, vec![arg])
+    }
+}
+
+/// The partial aggregates the result of an aggregation is recombined from, e.g. to run a query
+/// over the shards of a table stored in several databases (see [`Aggregation::decompose`]).
+///
+/// The partial aggregates of each shard are computed by the query of
+/// [`crate::query_metadata::QueryMetadata::shard_query`], in the order of [`Self::partials`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct Decomposition {
+    /// The decomposed function.
+    pub function: KoronFunction,
+    /// The partial aggregates computed on each shard.
+    pub partials: Vec<PartialAggregate>,
+}
+
+impl Decomposition {
+    // the SELECT clause items computing the partial aggregates of `column` on a shard
+    pub(crate) fn select_items(
+        &self,
+        column: &Identifier,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> Vec<ast::SelectItem> {
+        let column = ast::Expr::Identifier(column.render(dialect, quote_style));
+        let items = self
+            .partials
+            .iter()
+            .map(|partial| ast::SelectItem::UnnamedExpr(partial.to_expr(column.clone())));
+        items.collect()
+    }
+
+    /// The state of the aggregation over a shard, from the row of its partial aggregates in the
+    /// order of [`Self::partials`], with `None` for NULL (e.g. the sum of no rows).
+    ///
+    /// The counts are computed by the database, so they're integers; the partial aggregates the
+    /// row is missing are taken as NULL.
+    #[must_use]
+    pub fn state(&self, row: &[Option<f64>]) -> AggregateState {
+        let value = |partial| {
+            let index = self.partials.iter().position(|other| *other == partial)?;
+            *row.get(index)?
+        };
+        let (sum, sum_of_squares) = (
+            value(PartialAggregate::Sum),
+            value(PartialAggregate::SumOfSquares),
+        );
+        let count = value(PartialAggregate::Count).map_or(0, to_count);
+        let variance = VarianceState {
+            sum: sum.unwrap_or_default(),
+            sum_of_squares: sum_of_squares.unwrap_or_default(),
+            count,
+        };
+        match self.function {
+            KoronFunction::Sum => AggregateState::Sum(SumState { sum }),
+            KoronFunction::Count => AggregateState::Count(CountState { count }),
+            KoronFunction::Average => AggregateState::Average(AverageState {
+                sum: sum.unwrap_or_default(),
+                count,
+            }),
+            KoronFunction::Median => AggregateState::Median(MedianState::default()),
+            KoronFunction::Variance => AggregateState::Variance(variance),
+            KoronFunction::StandardDeviation => AggregateState::StandardDeviation(variance),
+            KoronFunction::Min => AggregateState::Min(MinState {
+                min: value(PartialAggregate::Min),
+            }),
+            KoronFunction::Max => AggregateState::Max(MaxState {
+                max: value(PartialAggregate::Max),
+            }),
+        }
+    }
+
+    /// Recombines the result of the aggregation from the partial aggregates of each shard, in
+    /// the order of [`Self::partials`], by merging their states (see [`Self::state`]).
+    ///
+    /// Returns `None` if the result is NULL, e.g. the average of no rows (see
+    /// [`AggregateState::result`]). The values are numbers, so the minimum and the maximum of
+    /// other columns (e.g. strings) have to be recombined by comparing them as such.
+    #[must_use]
+    pub fn combine(&self, shards: &[Vec<Option<f64>>]) -> Option<f64> {
+        let mut state = self.function.initial_state();
+        for row in shards {
+            //the states of the rows are all of the same function
+            let merged = state.merge(&self.state(row));
+            debug_assert!(merged);
+        }
+        state.result()
+    }
+}
+
+// the call of the function `name` with `args`
+pub(crate) fn function_call(name: &str, args: Vec<ast::Expr>) -> ast::Expr {
+    ast::Expr::Function(ast::Function {
+        name: ast::ObjectName(vec![ast::Ident::new(name)]),
+        args: args
+            .into_iter()
+            .map(|arg| ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(arg)))
+            .collect(),
+        filter: None,
+        null_treatment: None,
+        over: None,
+        distinct: false,
+        special: false,
+        order_by: Vec::default(),
+    })
+}
+
 /// Represents a Koron aggregation / analytic function.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
 pub enum KoronFunction {
     /// The `sum` aggregation function.
     Sum,
@@ -194,24 +621,450 @@ pub enum KoronFunction {
     Max,
 }
 
+impl KoronFunction {
+    /// Returns all the supported functions.
+    #[must_use]
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Sum,
+            Self::Count,
+            Self::Average,
+            Self::Median,
+            Self::Variance,
+            Self::StandardDeviation,
+            Self::Min,
+            Self::Max,
+        ]
+    }
+
+    /// Returns the function named `name` in SQL, whatever its case (e.g. `avg` for
+    /// [`Self::Average`]), if it's supported.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .find(|function| function.sql_name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Returns the name of the function in SQL, e.g. `AVG` for [`Self::Average`].
+    #[must_use]
+    pub const fn sql_name(self) -> &'static str {
+        match self {
+            Self::Sum => "SUM",
+            Self::Count => "COUNT",
+            Self::Average => "AVG",
+            Self::Median => "MEDIAN",
+            Self::Variance => "VARIANCE",
+            Self::StandardDeviation => "STDDEV",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+        }
+    }
+
+    /// Returns the name of the function in English, e.g. `Standard deviation` for
+    /// [`Self::StandardDeviation`].
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Sum => "Sum",
+            Self::Count => "Count",
+            Self::Average => "Average",
+            Self::Median => "Median",
+            Self::Variance => "Variance",
+            Self::StandardDeviation => "Standard deviation",
+            Self::Min => "Minimum",
+            Self::Max => "Maximum",
+        }
+    }
+
+    /// Returns the number of arguments of the function, all of them columns.
+    #[must_use]
+    pub const fn arity(self) -> usize {
+        match self {
+            Self::Sum
+            | Self::Count
+            | Self::Average
+            | Self::Median
+            | Self::Variance
+            | Self::StandardDeviation
+            | Self::Min
+            | Self::Max => 1,
+        }
+    }
+
+    /// Returns `true` if the function only applies to numeric columns (COUNT, MIN and MAX apply
+    /// to any column).
+    #[must_use]
+    pub const fn requires_numeric_input(self) -> bool {
+        matches!(
+            self,
+            Self::Sum | Self::Average | Self::Median | Self::Variance | Self::StandardDeviation
+        )
+    }
+
+    // whether the results of the function are weighted by the number of values when they're
+    // recombined (e.g. averages), so that the metadata has a data count query
+    pub(crate) const fn is_weighted(self) -> bool {
+        matches!(
+            self,
+            Self::Average | Self::Variance | Self::StandardDeviation
+        )
+    }
+
+    /// Returns the state of the function over no values, which the states of the parts of a
+    /// column are merged into (see [`AggregateState::merge`]).
+    #[must_use]
+    pub const fn initial_state(self) -> AggregateState {
+        match self {
+            Self::Sum => AggregateState::Sum(SumState { sum: None }),
+            Self::Count => AggregateState::Count(CountState { count: 0 }),
+            Self::Average => AggregateState::Average(AverageState { sum: 0.0, count: 0 }),
+            Self::Median => AggregateState::Median(MedianState {
+                histogram: Vec::new(),
+            }),
+            Self::Variance => AggregateState::Variance(VarianceState::EMPTY),
+            Self::StandardDeviation => AggregateState::StandardDeviation(VarianceState::EMPTY),
+            Self::Min => AggregateState::Min(MinState { min: None }),
+            Self::Max => AggregateState::Max(MaxState { max: None }),
+        }
+    }
+}
+
 impl Display for KoronFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sql_name())
+    }
+}
+
+/// The state of a [`KoronFunction`] over some of the values of a column, e.g. on a shard of a
+/// table, which the states of the other values are merged into to compute the result.
+///
+/// The NULL values aren't aggregated, as in SQL. It's serialized as an object tagged with its
+/// `type`, e.g. `{ "type": "Average", "sum": 10.0, "count": 4 }` (`"average"` with the
+/// `camel-case` feature), so that the states can be sent from the sources to the executor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
+pub enum AggregateState {
+    /// The state of [`KoronFunction::Sum`].
+    Sum(SumState),
+    /// The state of [`KoronFunction::Count`].
+    Count(CountState),
+    /// The state of [`KoronFunction::Average`].
+    Average(AverageState),
+    /// The state of [`KoronFunction::Median`].
+    Median(MedianState),
+    /// The state of [`KoronFunction::Variance`].
+    Variance(VarianceState),
+    /// The state of [`KoronFunction::StandardDeviation`].
+    StandardDeviation(VarianceState),
+    /// The state of [`KoronFunction::Min`].
+    Min(MinState),
+    /// The state of [`KoronFunction::Max`].
+    Max(MaxState),
+}
+
+impl AggregateState {
+    /// Returns the function the state is of.
+    #[must_use]
+    pub const fn function(&self) -> KoronFunction {
+        match self {
+            Self::Sum(_) => KoronFunction::Sum,
+            Self::Count(_) => KoronFunction::Count,
+            Self::Average(_) => KoronFunction::Average,
+            Self::Median(_) => KoronFunction::Median,
+            Self::Variance(_) => KoronFunction::Variance,
+            Self::StandardDeviation(_) => KoronFunction::StandardDeviation,
+            Self::Min(_) => KoronFunction::Min,
+            Self::Max(_) => KoronFunction::Max,
+        }
+    }
+
+    /// Aggregates a (non-NULL) value.
+    pub fn add(&mut self, value: f64) {
+        match self {
+            Self::Sum(state) => state.add(value),
+            Self::Count(state) => state.add(value),
+            Self::Average(state) => state.add(value),
+            Self::Median(state) => state.add(value),
+            Self::Variance(state) | Self::StandardDeviation(state) => state.add(value),
+            Self::Min(state) => state.add(value),
+            Self::Max(state) => state.add(value),
+        }
+    }
+
+    /// Merges the state of other values of the column into this one.
+    ///
+    /// Returns `false`, leaving the state as it is, if `other` is the state of another function.
+    #[must_use = "the states of different functions aren't merged"]
+    pub fn merge(&mut self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Sum(state), Self::Sum(other)) => state.merge(other),
+            (Self::Count(state), Self::Count(other)) => state.merge(other),
+            (Self::Average(state), Self::Average(other)) => state.merge(other),
+            (Self::Median(state), Self::Median(other)) => state.merge(other),
+            (Self::Variance(state), Self::Variance(other))
+            | (Self::StandardDeviation(state), Self::StandardDeviation(other)) => {
+                state.merge(other);
+            }
+            (Self::Min(state), Self::Min(other)) => state.merge(other),
+            (Self::Max(state), Self::Max(other)) => state.merge(other),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the result of the function over the values aggregated, `None` if it's NULL
+    /// (e.g. the sum of no values).
+    ///
+    /// Like VARIANCE and STDDEV in PostgreSQL, Snowflake and BigQuery, the variance is the
+    /// sample one, NULL for less than two values.
+    #[must_use]
+    pub fn result(&self) -> Option<f64> {
         match self {
-            Self::Sum => write!(f, "SUM"),
-            Self::Count => write!(f, "COUNT"),
-            Self::Average => write!(f, "AVG"),
-            Self::Median => write!(f, "MEDIAN"),
-            Self::Variance => write!(f, "VARIANCE"),
-            Self::StandardDeviation => write!(f, "STDDEV"),
-            Self::Min => write!(f, "MIN"),
-            Self::Max => write!(f, "MAX"),
+            Self::Sum(state) => state.sum,
+            Self::Count(state) => Some(to_float(state.count)),
+            Self::Average(state) => state.result(),
+            Self::Median(state) => state.result(),
+            Self::Variance(state) => state.result(),
+            Self::StandardDeviation(state) => state.result().map(f64::sqrt),
+            Self::Min(state) => state.min,
+            Self::Max(state) => state.max,
+        }
+    }
+}
+
+/// The state of a sum: the sum of the values, if there are any.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct SumState {
+    /// The sum of the values, `None` if there are none.
+    pub sum: Option<f64>,
+}
+
+impl SumState {
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.sum = Some(self.sum.unwrap_or_default() + value);
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        if let Some(sum) = other.sum {
+            self.add(sum);
+        }
+    }
+}
+
+/// The state of a count: the number of values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct CountState {
+    /// The number of values.
+    pub count: u64,
+}
+
+impl CountState {
+    /// Aggregates a value.
+    pub const fn add(&mut self, _value: f64) {
+        self.count += 1;
+    }
+
+    /// Merges the state of other values into this one.
+    pub const fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+    }
+}
+
+/// The state of an average: the sum and the number of the values.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct AverageState {
+    /// The sum of the values.
+    pub sum: f64,
+    /// The number of values.
+    pub count: u64,
+}
+
+impl AverageState {
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// Returns the average, `None` if there are no values.
+    #[must_use]
+    pub fn result(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / to_float(self.count))
+    }
+}
+
+/// The state of a variance or a standard deviation: the sum, the sum of the squares and the
+/// number of the values.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct VarianceState {
+    /// The sum of the values.
+    pub sum: f64,
+    /// The sum of the squares of the values.
+    pub sum_of_squares: f64,
+    /// The number of values.
+    pub count: u64,
+}
+
+impl VarianceState {
+    const EMPTY: Self = Self {
+        sum: 0.0,
+        sum_of_squares: 0.0,
+        count: 0,
+    };
+
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.sum_of_squares += value * value;
+        self.count += 1;
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.sum += other.sum;
+        self.sum_of_squares += other.sum_of_squares;
+        self.count += other.count;
+    }
+
+    /// Returns the sample variance, `None` for less than two values.
+    #[must_use]
+    pub fn result(&self) -> Option<f64> {
+        let count = to_float(self.count);
+        let deviations = self.sum_of_squares - self.sum * self.sum / count;
+        //the rounding errors can't make it negative
+        (self.count > 1).then(|| (deviations / (count - 1.0)).max(0.0))
+    }
+}
+
+/// The state of a median: the histogram of the values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct MedianState {
+    /// The distinct values with their numbers of occurrences, sorted by value.
+    pub histogram: Vec<(f64, u64)>,
+}
+
+impl MedianState {
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.add_count(value, 1);
+    }
+
+    /// Aggregates `count` occurrences of a value.
+    pub fn add_count(&mut self, value: f64, count: u64) {
+        let position = self
+            .histogram
+            .binary_search_by(|(other, _)| other.total_cmp(&value));
+        match position {
+            Ok(index) => self.histogram[index].1 += count,
+            Err(_) if count == 0 => {}
+            Err(index) => self.histogram.insert(index, (value, count)),
+        }
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        for &(value, count) in &other.histogram {
+            self.add_count(value, count);
+        }
+    }
+
+    /// Returns the median, `None` if there are no values.
+    ///
+    /// Like `PERCENTILE_CONT(0.5)`, the median of an even number of values is the mean of the
+    /// two middle ones.
+    #[must_use]
+    pub fn result(&self) -> Option<f64> {
+        let total = self.histogram.iter().map(|(_, count)| count).sum::<u64>();
+        //the 0-based positions of the middle values, the same one for an odd count
+        let (low, high) = (total.checked_sub(1)? / 2, total / 2);
+        let value_at = |position| {
+            let mut seen = 0;
+            self.histogram.iter().find_map(|&(value, count)| {
+                seen += count;
+                (position < seen).then_some(value)
+            })
+        };
+        Some(value_at(low)?.midpoint(value_at(high)?))
+    }
+}
+
+/// The state of a minimum: the smallest value, if there are any.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct MinState {
+    /// The smallest value, `None` if there are none.
+    pub min: Option<f64>,
+}
+
+impl MinState {
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        if let Some(min) = other.min {
+            self.add(min);
+        }
+    }
+}
+
+/// The state of a maximum: the largest value, if there are any.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct MaxState {
+    /// The largest value, `None` if there are none.
+    pub max: Option<f64>,
+}
+
+impl MaxState {
+    /// Aggregates a value.
+    pub fn add(&mut self, value: f64) {
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    /// Merges the state of other values into this one.
+    pub fn merge(&mut self, other: &Self) {
+        if let Some(max) = other.max {
+            self.add(max);
         }
     }
 }
 
+// a count as a float, exact up to 2^53
+#[allow(clippy::cast_precision_loss)]
+const fn to_float(count: u64) -> f64 {
+    count as f64
+}
+
+// a count computed as a float, e.g. by a database; the fractional and negative parts are
+// dropped
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+const fn to_count(count: f64) -> u64 {
+    count as u64
+}
+
 #[cfg(test)]
 mod tests {
-    use super::KoronFunction;
+    use super::{AggregateState, Aggregation, KoronFunction, PartialAggregate};
 
     #[test]
     fn koron_fn_display() {
@@ -227,6 +1080,146 @@ mod tests {
         ];
         for (koron_fn, expected) in cases {
             assert_eq!(koron_fn.to_string(), expected.to_string());
+            assert!(KoronFunction::all().contains(&koron_fn));
+        }
+        assert_eq!(KoronFunction::all().len(), cases.len());
+    }
+
+    #[test]
+    fn from_name() {
+        for &function in KoronFunction::all() {
+            assert_eq!(
+                KoronFunction::from_name(function.sql_name()),
+                Some(function)
+            );
+            assert_eq!(function.arity(), 1);
+        }
+        assert_eq!(
+            KoronFunction::from_name("avg"),
+            Some(KoronFunction::Average)
+        );
+        assert_eq!(
+            KoronFunction::from_name("StdDev"),
+            Some(KoronFunction::StandardDeviation)
+        );
+        assert_eq!(KoronFunction::from_name("average"), None);
+        assert!(KoronFunction::Sum.requires_numeric_input());
+        assert!(!KoronFunction::Max.requires_numeric_input());
+    }
+
+    #[test]
+    fn decompose() {
+        let decompose = |function| {
+            let aggregation = Aggregation {
+                function,
+                column: "a".into(),
+                alias: None,
+                window: None,
+            };
+            aggregation.decompose()
+        };
+        assert_eq!(decompose(KoronFunction::Median), None);
+        let average = decompose(KoronFunction::Average).unwrap();
+        assert_eq!(
+            average.partials,
+            [PartialAggregate::Sum, PartialAggregate::Count]
+        );
+
+        //the values 1, 2 and 3 on a shard, 4 and 10 on another one, none on the last one
+        let values = [1.0, 2.0, 3.0, 4.0, 10.0];
+        let shard = |values: &[f64]| {
+            let sum = values.iter().sum::<f64>();
+            let squares = values.iter().map(|value| value * value).sum::<f64>();
+            let count = values.len() as f64;
+            let min = values.iter().copied().reduce(f64::min);
+            let max = values.iter().copied().reduce(f64::max);
+            (
+                values.first().map(|_| sum),
+                values.first().map(|_| squares),
+                count,
+                min,
+                max,
+            )
+        };
+        let shards = [shard(&values[..3]), shard(&values[3..]), shard(&[])];
+        let combine = |function| {
+            let decomposition = decompose(function).unwrap();
+            let rows = shards
+                .iter()
+                .map(|&(sum, squares, count, min, max)| {
+                    let partials = decomposition.partials.iter();
+                    partials
+                        .map(|partial| match partial {
+                            PartialAggregate::Sum => sum,
+                            PartialAggregate::SumOfSquares => squares,
+                            PartialAggregate::Count => Some(count),
+                            PartialAggregate::Min => min,
+                            PartialAggregate::Max => max,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            decomposition.combine(&rows)
+        };
+        assert_eq!(combine(KoronFunction::Sum), Some(20.0));
+        assert_eq!(combine(KoronFunction::Count), Some(5.0));
+        assert_eq!(combine(KoronFunction::Min), Some(1.0));
+        assert_eq!(combine(KoronFunction::Max), Some(10.0));
+        assert_eq!(combine(KoronFunction::Average), Some(4.0));
+        //the sample variance: (9 + 4 + 1 + 0 + 36) / 4
+        assert_eq!(combine(KoronFunction::Variance), Some(12.5));
+        let stddev = combine(KoronFunction::StandardDeviation).unwrap();
+        assert!((stddev - 12.5_f64.sqrt()).abs() < 1e-12);
+
+        //no rows at all
+        assert_eq!(average.combine(&[vec![None, Some(0.0)]]), None);
+        let variance = decompose(KoronFunction::Variance).unwrap();
+        assert_eq!(
+            variance.combine(&[vec![Some(2.0), Some(4.0), Some(1.0)]]),
+            None
+        );
+    }
+
+    #[test]
+    fn aggregate_states() {
+        let values = [4.0, -1.0, 2.5, 2.5, 10.0];
+        for &function in KoronFunction::all() {
+            let mut whole = function.initial_state();
+            let (mut left, mut right) = (function.initial_state(), function.initial_state());
+            for (index, &value) in values.iter().enumerate() {
+                whole.add(value);
+                if index % 2 == 0 {
+                    &mut left
+                } else {
+                    &mut right
+                }
+                .add(value);
+            }
+            assert_eq!(whole.function(), function);
+            assert!(left.merge(&right));
+            assert_eq!(left.result(), whole.result(), "{function}");
+            let json = serde_json::to_string(&left).unwrap();
+            assert_eq!(serde_json::from_str::<AggregateState>(&json).unwrap(), left);
         }
+        let result = |function: KoronFunction| {
+            let mut state = function.initial_state();
+            values.iter().for_each(|&value| state.add(value));
+            state.result()
+        };
+        assert_eq!(result(KoronFunction::Count), Some(5.0));
+        assert_eq!(result(KoronFunction::Average), Some(3.6));
+        assert_eq!(result(KoronFunction::Median), Some(2.5));
+        assert_eq!(result(KoronFunction::Min), Some(-1.0));
+        let variance = result(KoronFunction::Variance).unwrap();
+        //(0.16 + 21.16 + 1.21 + 1.21 + 40.96) / 4
+        assert!((variance - 16.175).abs() < 1e-9, "{variance}");
+
+        //no values
+        assert_eq!(KoronFunction::Sum.initial_state().result(), None);
+        assert_eq!(KoronFunction::Count.initial_state().result(), Some(0.0));
+        assert_eq!(KoronFunction::Median.initial_state().result(), None);
+        let mut state = KoronFunction::Sum.initial_state();
+        assert!(!state.merge(&KoronFunction::Count.initial_state()));
+        assert_eq!(state, KoronFunction::Sum.initial_state());
     }
 }
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
+use utoipa::ToSchema;
+
+use crate::{dialect::Dialect, support::normalize_query};
+
+/// A comment leading a query, or a hint passed in a comment anywhere in it.
+///
+/// Hints are written as `/*+ koron: key=value ... */`, with the entries separated by commas or
+/// whitespace, e.g. `SELECT /*+ koron: max_rows=10000 */ SUM(a) FROM t`. The other comments are
+/// only kept if they come before the query.
+///
+/// It's serialized as an object tagged with its `type`, e.g.
+/// `{ "type": "Hint", "key": "max_rows", "value": "10000" }` (`"hint"` with the `camel-case`
+/// feature).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum Annotation {
+    /// A comment before the query, without its delimiters and surrounding whitespace.
+    Comment {
+        /// The text of the comment.
+        text: String,
+    },
+    /// A hint, with an empty value if it has none (e.g. `/*+ koron: no_cache */`).
+    Hint {
+        /// The name of the hint.
+        key: String,
+        /// The value of the hint, as written.
+        value: String,
+    },
+}
+
+// collects the leading comments and the hints of `sql`, none if it can't be tokenized
+pub(crate) fn extract_annotations(sql: &str, dialect: Dialect) -> Vec<Annotation> {
+    let sql = normalize_query(sql, dialect);
+    let Ok(tokens) = Tokenizer::new(dialect.parser_dialect().as_ref(), &sql).tokenize() else {
+        return Vec::new();
+    };
+    let mut annotations = Vec::new();
+    let mut leading = true;
+    for token in &tokens {
+        let comment = match token {
+            Token::Whitespace(
+                Whitespace::SingleLineComment { comment, .. }
+                | Whitespace::MultiLineComment(comment),
+            ) => comment,
+            Token::Whitespace(_) => continue,
+            _ => {
+                leading = false;
+                continue;
+            }
+        };
+        let hints = comment
+            .strip_prefix('+')
+            .and_then(|hints| hints.trim_start().strip_prefix("koron:"));
+        if let Some(hints) = hints {
+            let entries = hints
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|entry| !entry.is_empty());
+            annotations.extend(entries.map(|entry| {
+                let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+                Annotation::Hint {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                }
+            }));
+        } else if leading {
+            annotations.push(Annotation::Comment {
+                text: comment.trim().to_string(),
+            });
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_annotations, Annotation};
+    use crate::dialect::Dialect;
+
+    #[test]
+    fn extract() {
+        let hint = |key: &str, value: &str| Annotation::Hint {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let comment = |text: &str| Annotation::Comment {
+            text: text.to_string(),
+        };
+        let sql = "-- daily revenue\n/* owner: finance */\n\
+                   SELECT /*+ koron: max_rows=10000, no_cache */ SUM(a) -- not leading\n\
+                   FROM t /*+ other: hint */";
+        assert_eq!(
+            extract_annotations(sql, Dialect::Generic),
+            vec![
+                comment("daily revenue"),
+                comment("owner: finance"),
+                hint("max_rows", "10000"),
+                hint("no_cache", "")
+            ]
+        );
+        //the comments in literals aren't annotations
+        let sql = "SELECT SUM(a) FROM t WHERE b = '/*+ koron: max_rows=1 */'";
+        assert_eq!(extract_annotations(sql, Dialect::Generic), vec![]);
+        let sql = "# koron\nSELECT SUM(a) FROM t";
+        assert_eq!(
+            extract_annotations(sql, Dialect::MySql),
+            vec![comment("koron")]
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! The `query-parser` command line tool (built with the `cli` feature).
+//!
+//! ```text
+//! query-parser check [--ndjson] [--split-on-semicolons] [--dialect <DIALECT>] <FILE>...
+//! query-parser repl [--dialect <DIALECT>]
+//! ```
+//!
+//! `check` parses the queries of the files (`-` for the standard input), one per line or
+//! separated by semicolons, and reports the ones that aren't supported, e.g. in CI over a
+//! repository of saved queries. It exits with 1 if any query is rejected.
+//!
+//! With `--ndjson`, one JSON object is streamed per query, with its metadata or its error,
+//! followed by a summary counting the errors by code.
+//!
+//! `repl` parses the queries typed one per line and prints their metadata as JSON with a summary
+//! of it, e.g. to explore the supported subset of SQL. Commands starting with a backslash change
+//! the dialect and the options as it runs (see `\help`).
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, Read, Write},
+    ops::ControlFlow,
+    process::ExitCode,
+};
+
+use koron_query_parser::{
+    config::ParseOptions, dialect::Dialect, error::ParseError, query_metadata::QueryMetadata,
+};
+use serde_json::{json, Value};
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+const USAGE: &str = "usage: query-parser check [--ndjson] [--split-on-semicolons] \
+                     [--dialect <generic|postgresql|mysql|snowflake|bigquery>] <FILE>...
+       query-parser repl [--dialect <generic|postgresql|mysql|snowflake|bigquery>]";
+const REPL_HELP: &str = "Type a query on one line to parse it, or a command:
+  \\dialect <DIALECT>      parse with another dialect
+  \\set <OPTION> <on|off>  turn an option on or off
+  \\options                show the dialect and the options
+  \\help                   show this help
+  \\quit                   exit";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "check" => check(args),
+        Some((command, args)) if command == "repl" => repl(args),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+struct CheckArgs {
+    ndjson: bool,
+    split_on_semicolons: bool,
+    dialect: Dialect,
+    files: Vec<String>,
+}
+
+impl CheckArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut check_args = Self {
+            ndjson: false,
+            split_on_semicolons: false,
+            dialect: Dialect::default(),
+            files: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--ndjson" => check_args.ndjson = true,
+                "--split-on-semicolons" => check_args.split_on_semicolons = true,
+                "--dialect" => {
+                    let dialect = args.next().ok_or_else(|| USAGE.to_string())?;
+                    check_args.dialect = parse_dialect(dialect)?;
+                }
+                "-" => check_args.files.push(arg.clone()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`.")),
+                _ => check_args.files.push(arg.clone()),
+            }
+        }
+        if check_args.files.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        Ok(check_args)
+    }
+}
+
+fn parse_dialect(name: &str) -> Result<Dialect, String> {
+    match &name.to_ascii_lowercase()[..] {
+        "generic" => Ok(Dialect::Generic),
+        "postgresql" | "postgres" => Ok(Dialect::PostgreSql),
+        "mysql" => Ok(Dialect::MySql),
+        "snowflake" => Ok(Dialect::Snowflake),
+        "bigquery" => Ok(Dialect::BigQuery),
+        _ => Err(format!("unknown dialect `{name}`.")),
+    }
+}
+
+// the queries checked so far, with the number of errors by code
+#[derive(Default)]
+struct Summary {
+    queries: usize,
+    rejected: usize,
+    errors: BTreeMap<&'static str, usize>,
+}
+
+impl Summary {
+    fn add(&mut self, result: &Result<QueryMetadata, ParseError>) {
+        self.queries += 1;
+        if let Err(error) = result {
+            self.rejected += 1;
+            *self.errors.entry(error.code().as_str()).or_default() += 1;
+        }
+    }
+
+    fn write(&self, out: &mut impl Write, ndjson: bool) -> io::Result<()> {
+        if ndjson {
+            let summary = json!({
+                "summary": {
+                    "queries": self.queries,
+                    "supported": self.queries - self.rejected,
+                    "rejected": self.rejected,
+                    "errors": self.errors,
+                }
+            });
+            return writeln!(out, "{summary}");
+        }
+        writeln!(
+            out,
+            "{} queries checked, {} rejected",
+            self.queries, self.rejected
+        )?;
+        for (code, count) in &self.errors {
+            writeln!(out, "  {code}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+fn check(args: &[String]) -> Result<bool, String> {
+    let args = CheckArgs::parse(args)?;
+    let options = ParseOptions::new()
+        .with_dialect(args.dialect)
+        .with_error_spans(true);
+    let mut summary = Summary::default();
+    let mut out = io::stdout().lock();
+    for file in &args.files {
+        let content = read(file).map_err(|error| format!("{file}: {error}"))?;
+        let queries = if args.split_on_semicolons {
+            split_statements(&content, args.dialect)
+        } else {
+            split_lines(&content, args.dialect)
+        };
+        for (line, sql_query) in queries {
+            let result = QueryMetadata::parse_with(&sql_query, &options);
+            summary.add(&result);
+            if args.ndjson {
+                let record = query_record(file, line, &sql_query, &result)?;
+                writeln!(out, "{record}")
+            } else if let Err(error) = &result {
+                writeln!(out, "{file}:{line}: {}", error.report(&sql_query))
+            } else {
+                Ok(())
+            }
+            .map_err(|error| error.to_string())?;
+        }
+    }
+    summary
+        .write(&mut out, args.ndjson)
+        .map_err(|error| error.to_string())?;
+    Ok(summary.rejected == 0)
+}
+
+fn read(file: &str) -> io::Result<String> {
+    if file == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        fs::read_to_string(file)
+    }
+}
+
+fn query_record(
+    file: &str,
+    line: u64,
+    sql_query: &str,
+    result: &Result<QueryMetadata, ParseError>,
+) -> Result<Value, String> {
+    let mut record = json!({ "file": file, "line": line, "query": sql_query });
+    match result {
+        Ok(metadata) => {
+            record["metadata"] = serde_json::to_value(metadata).map_err(|e| e.to_string())?;
+        }
+        Err(error) => {
+            record["error"] = json!({
+                "code": error.code().as_str(),
+                "message": error.to_string(),
+                "help": error.help(),
+                "span": error.span(),
+            });
+        }
+    }
+    Ok(record)
+}
+
+fn repl(args: &[String]) -> Result<bool, String> {
+    let dialect = match args {
+        [] => Dialect::default(),
+        [option, dialect] if option == "--dialect" => parse_dialect(dialect)?,
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut options = ParseOptions::new()
+        .with_dialect(dialect)
+        .with_error_spans(true);
+    let mut out = io::stdout().lock();
+    let mut lines = io::stdin().lock().lines();
+    let mut session = || -> io::Result<()> {
+        writeln!(out, "{REPL_HELP}")?;
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                return writeln!(out);
+            };
+            match evaluate(&mut options, line.trim()) {
+                ControlFlow::Continue(output) if output.is_empty() => {}
+                ControlFlow::Continue(output) => writeln!(out, "{output}")?,
+                ControlFlow::Break(()) => return Ok(()),
+            }
+        }
+    };
+    session().map_err(|error| error.to_string())?;
+    Ok(true)
+}
+
+// runs a line typed in the REPL, a query or a command, and returns what to print, if it doesn't
+// end the session
+fn evaluate(options: &mut ParseOptions, line: &str) -> ControlFlow<(), String> {
+    let Some(command) = line.strip_prefix('\\') else {
+        if is_blank(line, options.dialect()) {
+            return ControlFlow::Continue(String::new());
+        }
+        return ControlFlow::Continue(describe_query(line, options));
+    };
+    let words = command.split_whitespace().collect::<Vec<_>>();
+    let output = match words[..] {
+        ["quit" | "q"] => return ControlFlow::Break(()),
+        ["help" | "?"] => REPL_HELP.to_string(),
+        ["options"] => describe_options(options),
+        ["dialect", name] => parse_dialect(name).map_or_else(
+            |error| error,
+            |dialect| {
+                *options = options.clone().with_dialect(dialect);
+                describe_options(options)
+            },
+        ),
+        ["set", name, value] => set_option(options, name, value).map_or_else(
+            |error| error,
+            |updated| {
+                *options = updated;
+                describe_options(options)
+            },
+        ),
+        _ => format!("unknown command `\\{command}`, see `\\help`."),
+    };
+    ControlFlow::Continue(output)
+}
+
+// the metadata of `sql_query` as pretty JSON followed by its explanation, or the error report
+fn describe_query(sql_query: &str, options: &ParseOptions) -> String {
+    match QueryMetadata::parse_with(sql_query, options) {
+        Ok(metadata) => {
+            let json =
+                serde_json::to_string_pretty(&metadata).unwrap_or_else(|error| error.to_string());
+            format!("{json}\n-- {}", metadata.explain())
+        }
+        Err(error) => error.report(sql_query),
+    }
+}
+
+// the options the REPL can turn on or off, with their values
+const fn flags(options: &ParseOptions) -> [(&'static str, bool); 8] {
+    let policy = options.feature_policy();
+    [
+        ("order_by", policy.allow_order_by),
+        ("limit", policy.allow_limit),
+        ("distinct", policy.allow_distinct),
+        ("locking", policy.allow_locking),
+        ("table_hints", policy.allow_table_hints),
+        ("time_buckets", policy.allow_time_buckets),
+        ("running_aggregates", policy.allow_running_aggregates),
+        (
+            "case_insensitive_functions",
+            options.case_insensitive_functions(),
+        ),
+    ]
+}
+
+fn describe_options(options: &ParseOptions) -> String {
+    let flags = flags(options)
+        .map(|(name, value)| format!("\n{name}: {}", if value { "on" } else { "off" }));
+    format!("dialect: {:?}{}", options.dialect(), flags.concat())
+}
+
+fn set_option(options: &ParseOptions, name: &str, value: &str) -> Result<ParseOptions, String> {
+    let value = match value {
+        "on" | "true" => true,
+        "off" | "false" => false,
+        _ => return Err(format!("expected `on` or `off`, not `{value}`.")),
+    };
+    let mut policy = options.feature_policy();
+    let flag = match name {
+        "order_by" => &mut policy.allow_order_by,
+        "limit" => &mut policy.allow_limit,
+        "distinct" => &mut policy.allow_distinct,
+        "locking" => &mut policy.allow_locking,
+        "table_hints" => &mut policy.allow_table_hints,
+        "time_buckets" => &mut policy.allow_time_buckets,
+        "running_aggregates" => &mut policy.allow_running_aggregates,
+        "case_insensitive_functions" => {
+            return Ok(options.clone().with_case_insensitive_functions(value));
+        }
+        _ => {
+            let names = flags(options).map(|(name, _)| name).join(", ");
+            return Err(format!("unknown option `{name}`, expected one of {names}."));
+        }
+    };
+    *flag = value;
+    Ok(options.clone().with_feature_policy(policy))
+}
+
+// the non-blank lines of `content`, with their numbers
+fn split_lines(content: &str, dialect: Dialect) -> Vec<(u64, String)> {
+    (1..)
+        .zip(content.lines())
+        .filter(|(_, line)| !is_blank(line, dialect))
+        .map(|(number, line)| (number, line.to_string()))
+        .collect()
+}
+
+// the non-blank statements of `content` separated by semicolons, with the line they start at;
+// the whole content if it can't be tokenized, so that the error is reported
+fn split_statements(content: &str, dialect: Dialect) -> Vec<(u64, String)> {
+    let Ok(tokens) =
+        Tokenizer::new(dialect.parser_dialect().as_ref(), content).tokenize_with_location()
+    else {
+        return vec![(1, content.to_string())];
+    };
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut start = None;
+    for token in tokens {
+        match token.token {
+            Token::SemiColon => {
+                if let Some(line) = start.take() {
+                    statements.push((line, statement.trim_end().to_string()));
+                }
+                statement.clear();
+            }
+            //the leading whitespace and comments are left out
+            Token::Whitespace(_) if start.is_none() => {}
+            Token::Whitespace(_) => statement.push_str(&token.token.to_string()),
+            _ => {
+                start.get_or_insert(token.location.line);
+                statement.push_str(&token.token.to_string());
+            }
+        }
+    }
+    if let Some(line) = start {
+        statements.push((line, statement.trim_end().to_string()));
+    }
+    statements
+}
+
+// whether `sql` has only whitespace and comments
+fn is_blank(sql: &str, dialect: Dialect) -> bool {
+    Tokenizer::new(dialect.parser_dialect().as_ref(), sql)
+        .tokenize()
+        .is_ok_and(|tokens| {
+            tokens
+                .iter()
+                .all(|token| matches!(token, Token::Whitespace(_)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use koron_query_parser::{config::ParseOptions, dialect::Dialect};
+
+    use super::{evaluate, split_lines, split_statements};
+
+    #[test]
+    fn split() {
+        let content = "SELECT SUM(a) FROM t\n\n-- a comment\nSELECT MAX(b) FROM t LIMIT 1\n";
+        assert_eq!(
+            split_lines(content, Dialect::Generic),
+            vec![
+                (1, "SELECT SUM(a) FROM t".to_string()),
+                (4, "SELECT MAX(b) FROM t LIMIT 1".to_string())
+            ]
+        );
+        let content =
+            "SELECT SUM(a)\nFROM t WHERE b = ';';\n\n-- a comment\nSELECT MAX(b)\nFROM t;\n";
+        assert_eq!(
+            split_statements(content, Dialect::Generic),
+            vec![
+                (1, "SELECT SUM(a)\nFROM t WHERE b = ';'".to_string()),
+                (5, "SELECT MAX(b)\nFROM t".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn repl() {
+        let mut options = ParseOptions::new();
+        let mut evaluate = |line: &str| match evaluate(&mut options, line) {
+            ControlFlow::Continue(output) => Some(output),
+            ControlFlow::Break(()) => None,
+        };
+        let output = evaluate("SELECT SUM(a) FROM t WHERE b > 1").unwrap();
+        assert!(output.starts_with('{'), "{output}");
+        assert!(
+            output.ends_with("\n-- Sum of a from t where b > 1"),
+            "{output}"
+        );
+        assert_eq!(evaluate("-- a comment"), Some(String::new()));
+
+        let query = "SELECT SUM(a) FROM t ORDER BY 1";
+        assert!(evaluate(query).unwrap().starts_with("error["));
+        assert!(evaluate("\\set order_by on")
+            .unwrap()
+            .contains("order_by: on"));
+        assert!(evaluate(query).unwrap().starts_with('{'));
+
+        //a double-quoted name is a string in MySQL
+        let query = "SELECT SUM(\"a\") FROM t";
+        assert!(evaluate(query).unwrap().starts_with('{'));
+        assert!(evaluate("\\dialect mysql")
+            .unwrap()
+            .starts_with("dialect: MySql"));
+        assert!(evaluate(query).unwrap().starts_with("error["));
+
+        assert!(evaluate("\\set order_by maybe")
+            .unwrap()
+            .starts_with("expected"));
+        assert!(evaluate("\\set grouping on")
+            .unwrap()
+            .starts_with("unknown option"));
+        assert!(evaluate("\\frobnicate")
+            .unwrap()
+            .starts_with("unknown command"));
+        assert_eq!(evaluate("\\quit"), None);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sqlparser::{
+    keywords::Keyword,
+    tokenizer::{Token, Tokenizer},
+};
+
+use crate::{
+    annotation::extract_annotations, config::ParseOptions, error::ParseError,
+    query_metadata::QueryMetadata, support::normalize_query,
+};
+
+// the keywords whose case doesn't change the metadata, since they're always displayed in
+// uppercase in the derived queries
+const CASE_INSENSITIVE_KEYWORDS: &[Keyword] = &[
+    Keyword::SELECT,
+    Keyword::FROM,
+    Keyword::WHERE,
+    Keyword::AS,
+    Keyword::AND,
+    Keyword::OR,
+    Keyword::NOT,
+    Keyword::IS,
+    Keyword::NULL,
+    Keyword::TRUE,
+    Keyword::FALSE,
+];
+
+/// Parses queries with the same options, memoizing the results of the most recently parsed
+/// ones (e.g. for dashboards submitting the same queries on every refresh).
+///
+/// The queries are looked up regardless of their whitespace, comments and the case of the
+/// structural keywords (e.g. SELECT or AND), unless the errors are located (see
+/// [`ParseOptions::with_error_spans`]), since the locations depend on the layout of the queries.
+/// The annotations are extracted again from the comments of each query parsed (see
+/// [`QueryMetadata::annotations`]), so that a memoized result doesn't carry the hints of another
+/// query.
+#[derive(Clone, Debug)]
+pub struct CachedParser {
+    options: ParseOptions,
+    capacity: usize,
+    //the results by normalized query, with the time they were last used
+    entries: HashMap<String, (u64, Result<QueryMetadata, ParseError>)>,
+    //the normalized queries by the time they were last used, the least recent first
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl CachedParser {
+    /// Creates a parser using `options`, memoizing the results of up to `capacity` queries.
+    #[must_use]
+    pub fn new(options: ParseOptions, capacity: usize) -> Self {
+        Self {
+            options,
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Returns the options the queries are parsed with.
+    #[must_use]
+    pub const fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Returns the number of results currently memoized.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no result is memoized.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets all the memoized results.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Parses `sql_query` as [`QueryMetadata::parse_with`] does, unless the result of the same
+    /// query is memoized. The least recently used result is forgotten if there are too many.
+    pub fn parse(&mut self, sql_query: &str) -> Result<QueryMetadata, ParseError> {
+        let key = self.normalize(sql_query);
+        self.clock += 1;
+        if let Some((last_used, result)) = self.entries.get_mut(&key) {
+            self.recency.remove(last_used);
+            *last_used = self.clock;
+            self.recency.insert(self.clock, key);
+            return result.clone().map(|mut metadata| {
+                metadata.annotations = extract_annotations(sql_query, self.options.dialect());
+                metadata
+            });
+        }
+        let result = QueryMetadata::parse_with(sql_query, &self.options);
+        if self.capacity == 0 {
+            return result;
+        }
+        if self.entries.len() == self.capacity {
+            if let Some((_, least_recent)) = self.recency.pop_first() {
+                self.entries.remove(&least_recent);
+            }
+        }
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(key, (self.clock, result.clone()));
+        result
+    }
+
+    // the query without comments, with single spaces between its tokens and the keywords that
+    // don't affect the result in uppercase; the query as is if it can't be tokenized
+    fn normalize(&self, sql_query: &str) -> String {
+        if self.options.error_spans() {
+            return sql_query.to_string();
+        }
+        let dialect = self.options.dialect();
+        let sql = normalize_query(sql_query, dialect);
+        let Ok(tokens) = Tokenizer::new(dialect.parser_dialect().as_ref(), &sql).tokenize() else {
+            return sql_query.to_string();
+        };
+        let mut normalized = String::with_capacity(sql_query.len());
+        for token in tokens {
+            match token {
+                Token::Whitespace(_) => {
+                    if !normalized.is_empty() && !normalized.ends_with(' ') {
+                        normalized.push(' ');
+                    }
+                }
+                Token::Word(word)
+                    if word.quote_style.is_none()
+                        && CASE_INSENSITIVE_KEYWORDS.contains(&word.keyword) =>
+                {
+                    normalized.push_str(&word.value.to_ascii_uppercase());
+                }
+                token => normalized.push_str(&token.to_string()),
+            }
+        }
+        normalized.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedParser;
+    use crate::{config::ParseOptions, query_metadata::QueryMetadata};
+
+    #[test]
+    fn normalize() {
+        let parser = CachedParser::new(ParseOptions::new(), 1);
+        assert_eq!(
+            parser.normalize(
+                "select Sum(\"X\")  -- comment\n  from T\twhere y is not null and z = 'a  b';"
+            ),
+            "SELECT Sum(\"X\") FROM T WHERE y IS NOT NULL AND z = 'a  b';"
+        );
+        //the layout matters when the errors are located
+        let parser = CachedParser::new(ParseOptions::new().with_error_spans(true), 1);
+        assert_eq!(parser.normalize("select  1"), "select  1");
+    }
+
+    #[test]
+    fn least_recently_used() {
+        let options = ParseOptions::new();
+        let mut parser = CachedParser::new(options.clone(), 2);
+        let queries = [
+            "SELECT SUM(test_column_2) FROM test_table_1",
+            "SELECT COUNT(test_column_2) FROM test_table_1",
+            "SELECT MAX(test_column_2) FROM test_table_1 LIMIT 1",
+        ];
+        for query in queries {
+            assert_eq!(
+                parser.parse(query),
+                QueryMetadata::parse_with(query, &options)
+            );
+        }
+        assert_eq!(parser.len(), 2);
+        assert!(!parser.entries.contains_key(queries[0]));
+
+        //a hit makes the query the most recently used
+        assert_eq!(
+            parser.parse("select  COUNT(test_column_2)\nfrom test_table_1"),
+            QueryMetadata::parse_with(queries[1], &options)
+        );
+        assert!(parser.parse(queries[0]).is_ok());
+        assert!(parser.entries.contains_key(queries[1]));
+        assert!(!parser.entries.contains_key(queries[2]));
+
+        parser.clear();
+        assert!(parser.is_empty());
+        let mut parser = CachedParser::new(options, 0);
+        assert!(parser.parse(queries[0]).is_ok());
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn annotations_of_each_query() {
+        let options = ParseOptions::new();
+        let mut parser = CachedParser::new(options.clone(), 1);
+        for rows in ["10", "99999", "10"] {
+            let query = format!("/*+ koron: max_rows={rows} */ SELECT SUM(a) FROM t");
+            let metadata = parser.parse(&query).unwrap();
+            assert_eq!(
+                metadata,
+                QueryMetadata::parse_with(&query, &options).unwrap()
+            );
+            assert!(!metadata.annotations.is_empty());
+        }
+        assert_eq!(parser.len(), 1);
+        let metadata = parser.parse("SELECT SUM(a) FROM t").unwrap();
+        assert!(metadata.annotations.is_empty());
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    aggregation::KoronFunction, comparison::CompareOp, config::ParseOptions, policy::FeaturePolicy,
+    time_bucket::TimeGranularity,
+};
+
+// the rules of the grammar that don't depend on the options
+const VALUE_RULES: &str = r#"operand     ::= column [ json_path ] [ "COLLATE" name { "." name } ]
+json_path   ::= { "->" string } "->>" string
+value       ::= number | string | "TRUE" | "FALSE" | "NULL" | typed_value | system_time
+              | ( "+" | "-" ) value | value "||" value | "(" value ")"
+typed_value ::= ( "NUMERIC" | "DECIMAL" | "DEC" | "DATE" | "TIME" | "TIMESTAMP" | "DATETIME" )
+                string
+system_time ::= "CURRENT_DATE" | "CURRENT_TIME" | "CURRENT_TIMESTAMP" | "NOW" "(" ")"
+column      ::= [ [ [ name "." ] name "." ] name "." ] name
+table       ::= [ [ name "." ] name "." ] name
+alias       ::= name"#;
+
+/// What the parser accepts with some options, e.g. for a query builder to only offer the
+/// constructs that won't be rejected (see [`supported_features`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct FeatureReport {
+    /// The aggregation functions, in the order of [`KoronFunction::all`].
+    pub functions: Vec<FunctionReport>,
+    /// The comparison operators accepted in the WHERE clause.
+    pub operators: Vec<OperatorReport>,
+    /// The clauses accepted although the metadata doesn't represent them.
+    pub policy: FeaturePolicy,
+}
+
+/// An aggregation function accepted by the parser.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct FunctionReport {
+    /// The function.
+    pub function: KoronFunction,
+    /// The name of the function in SQL, e.g. `AVG`.
+    pub name: String,
+    /// The number of arguments of the function, all of them columns.
+    pub arity: usize,
+    /// Whether the function only applies to numeric columns.
+    pub numeric_only: bool,
+}
+
+/// A comparison operator accepted in the WHERE clause.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct OperatorReport {
+    /// The operator in SQL, e.g. `<=` or `IS NOT NULL`, or `[] =` for the elements of an array
+    /// column (e.g. `tags[1] = 'x'`) and `= ANY` for any of them (e.g. `'x' = ANY(tags)`).
+    pub operator: String,
+    /// Whether the column is compared to a constant, which follows the operator.
+    pub takes_value: bool,
+}
+
+/// Reports the functions, the operators and the clauses accepted when parsing with `options`.
+#[must_use]
+pub fn supported_features(options: &ParseOptions) -> FeatureReport {
+    let functions = KoronFunction::all()
+        .iter()
+        .map(|&function| FunctionReport {
+            function,
+            name: function.sql_name().to_string(),
+            arity: function.arity(),
+            numeric_only: function.requires_numeric_input(),
+        })
+        .collect();
+    let value = String::new;
+    let operators = [
+        CompareOp::Eq { value: value() },
+        CompareOp::NotEq { value: value() },
+        CompareOp::Lt { value: value() },
+        CompareOp::LtEq { value: value() },
+        CompareOp::Gt { value: value() },
+        CompareOp::GtEq { value: value() },
+        CompareOp::IsNull,
+        CompareOp::IsNotNull,
+        CompareOp::IsTrue,
+        CompareOp::IsNotTrue,
+        CompareOp::IsFalse,
+        CompareOp::IsNotFalse,
+        CompareOp::ElementEq {
+            index: 1,
+            value: value(),
+        },
+        CompareOp::ElementNotEq {
+            index: 1,
+            value: value(),
+        },
+        CompareOp::AnyEq { value: value() },
+        CompareOp::AllNotEq { value: value() },
+    ]
+    .iter()
+    .map(|comparison| OperatorReport {
+        operator: comparison.sql_symbol().to_string(),
+        takes_value: comparison.value().is_some(),
+    })
+    .collect();
+    FeatureReport {
+        functions,
+        operators,
+        policy: options.feature_policy(),
+    }
+}
+
+/// Describes in EBNF the queries accepted when parsing with `options`, e.g. to document the
+/// supported subset of SQL.
+///
+/// The grammar is derived from the same functions, operators and [`FeaturePolicy`] as the
+/// parser (see [`supported_features`]), so it's always up to date. The names, numbers and
+/// strings are the tokens of the dialect, an `expression` or a `hint` is any expression or table
+/// hint of the dialect, and the query may end with a semicolon. Some rules are checked once
+/// parsed and aren't in the grammar: a filter combined with AND or OR must reduce to a single
+/// comparison (e.g. `a > 1 AND a > 2`), the columns must be qualified with the table of the
+/// FROM clause, a selected time bucket must be written as in the GROUP BY clause, and a running
+/// aggregate (the aggregation with a window) can't be a median or be grouped by a time bucket.
+#[must_use]
+pub fn supported_grammar(options: &ParseOptions) -> String {
+    let FeatureReport {
+        functions,
+        operators,
+        policy,
+    } = supported_features(options);
+    let optional = |allowed: bool, clause: &str| {
+        if allowed {
+            format!(" [ {clause} ]")
+        } else {
+            String::new()
+        }
+    };
+    let terminals = |words: &str| {
+        words
+            .split_whitespace()
+            .map(|word| format!("\"{word}\""))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let query = format!(
+        "query       ::= \"SELECT\"{} {} from [ \"WHERE\" condition ]{}{}{}{} [ \";\" ]",
+        optional(policy.allow_distinct, "\"DISTINCT\""),
+        if policy.allow_time_buckets {
+            "selection"
+        } else {
+            "result"
+        },
+        optional(policy.allow_time_buckets, "\"GROUP\" \"BY\" time_bucket"),
+        optional(policy.allow_order_by, "order_by"),
+        optional(policy.allow_limit, "\"LIMIT\" positive_integer"),
+        optional(policy.allow_locking, "locking"),
+    );
+    let from = format!(
+        "from        ::= \"FROM\" table [ [ \"AS\" ] alias ]{}{}",
+        optional(
+            policy.allow_table_hints,
+            "\"WITH\" \"(\" hint { \",\" hint } \")\""
+        ),
+        optional(
+            options.dialect().supports_table_versions(),
+            "\"FOR\" \"SYSTEM_TIME\" \"AS\" \"OF\" \"TIMESTAMP\" string"
+        ),
+    );
+    let function = functions
+        .iter()
+        .map(|function| terminals(&function.name))
+        .collect::<Vec<_>>();
+    let window = optional(policy.allow_running_aggregates, "window");
+    let mut rules = vec![query, from];
+    if policy.allow_time_buckets {
+        rules.extend(time_bucket_rules());
+    }
+    if policy.allow_running_aggregates {
+        rules.push(WINDOW_RULE.to_string());
+    }
+    rules.extend([
+        "result      ::= aggregation [ [ \"AS\" ] alias ]".to_string(),
+        format!("aggregation ::= function \"(\" column \")\"{window} | \"(\" aggregation \")\""),
+        format!("function    ::= {}", function.join(" | ")),
+        "condition   ::= comparison | \"TRUE\" | \"FALSE\" | \"(\" condition \")\"\n              \
+         | condition ( \"AND\" | \"OR\" ) condition"
+            .to_string(),
+    ]);
+    //the comparisons, by form of operator
+    let comparisons = operators.iter().map(|operator| {
+        let symbol = operator.operator.as_str();
+        let quantified = symbol.ends_with(" ANY") || symbol.ends_with(" ALL");
+        match symbol.strip_prefix("[] ") {
+            Some(op) => {
+                let (element, op) = ("column \"[\" integer \"]\"", terminals(op));
+                format!("{element} {op} value | value {op} {element}")
+            }
+            None if quantified => format!("value {} \"(\" column \")\"", terminals(symbol)),
+            None if operator.takes_value => {
+                let op = terminals(symbol);
+                format!("operand {op} value | value {op} operand")
+            }
+            None => format!("operand {}", terminals(symbol)),
+        }
+    });
+    rules.push(format!(
+        "comparison  ::= {}",
+        comparisons.collect::<Vec<_>>().join("\n              | ")
+    ));
+    if policy.allow_order_by {
+        rules.push(
+            "order_by    ::= \"ORDER\" \"BY\" expression [ \"ASC\" | \"DESC\" ] \
+             { \",\" expression [ \"ASC\" | \"DESC\" ] }"
+                .to_string(),
+        );
+    }
+    if policy.allow_locking {
+        rules.push(
+            "locking     ::= \"FOR\" ( \"UPDATE\" | \"SHARE\" ) [ \"OF\" table ] \
+             [ \"NOWAIT\" | \"SKIP\" \"LOCKED\" ]"
+                .to_string(),
+        );
+    }
+    rules.push(VALUE_RULES.to_string());
+    rules.join("\n") + "\n"
+}
+
+// the window of the running aggregates, the only window function accepted
+const WINDOW_RULE: &str =
+    "window      ::= \"OVER\" \"(\" \"ORDER\" \"BY\" column [ \"ASC\" | \"DESC\" ]\n                \
+     \"ROWS\" ( \"UNBOUNDED\" \"PRECEDING\"\n                \
+     | \"BETWEEN\" \"UNBOUNDED\" \"PRECEDING\" \"AND\" \"CURRENT\" \"ROW\" ) \")\"";
+
+// the rules of the time buckets, with their units and functions derived from the granularities
+fn time_bucket_rules() -> Vec<String> {
+    let alternatives = |words: Vec<String>| words.join(" | ");
+    let granularities = TimeGranularity::all();
+    let units = granularities
+        .iter()
+        .map(|granularity| format!("\"'{granularity}'\""));
+    //the BigQuery weeks that start on Monday are the ISO weeks
+    let parts = granularities.iter().map(|granularity| match granularity {
+        TimeGranularity::Week => "\"ISOWEEK\"".to_string(),
+        granularity => format!("\"{}\"", granularity.unit().to_ascii_uppercase()),
+    });
+    //the ClickHouse weeks that start on Monday are the ones of `toMonday`
+    let functions = granularities.iter().map(|granularity| match granularity {
+        TimeGranularity::Week => "\"toMonday\"".to_string(),
+        granularity => {
+            let unit = granularity.unit();
+            format!(
+                "\"toStartOf{}{}\"",
+                unit[..1].to_ascii_uppercase(),
+                &unit[1..]
+            )
+        }
+    });
+    vec![
+        "selection   ::= [ bucket \",\" ] result | result \",\" bucket".to_string(),
+        "bucket      ::= time_bucket [ [ \"AS\" ] alias ]".to_string(),
+        "time_bucket ::= \"DATE_TRUNC\" \"(\" unit \",\" column \")\"\n              \
+         | ( \"DATE_TRUNC\" | \"TIMESTAMP_TRUNC\" | \"DATETIME_TRUNC\" )\n                \
+         \"(\" column \",\" date_part \")\"\n              \
+         | ( \"DATE\" | rounding ) \"(\" column \")\""
+            .to_string(),
+        format!("unit        ::= {}", alternatives(units.collect())),
+        format!("date_part   ::= {}", alternatives(parts.collect())),
+        format!("rounding    ::= {}", alternatives(functions.collect())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::supported_grammar;
+    use crate::{
+        aggregation::KoronFunction, config::ParseOptions, dialect::Dialect, policy::FeaturePolicy,
+        query_metadata::QueryMetadata,
+    };
+
+    #[test]
+    fn grammar() {
+        let grammar = supported_grammar(&ParseOptions::new());
+        for function in KoronFunction::all() {
+            assert!(grammar.contains(&format!("\"{}\"", function.sql_name())));
+        }
+        for rule in [
+            "operand \"<=\" value | value \"<=\" operand",
+            "operand \"IS\" \"NOT\" \"NULL\"",
+            "column \"[\" integer \"]\" \"=\" value",
+            "value \"=\" \"ANY\" \"(\" column \")\"",
+        ] {
+            assert!(grammar.contains(rule), "{rule} in {grammar}");
+        }
+        for clause in [
+            "DISTINCT",
+            "ORDER",
+            "LIMIT",
+            "UPDATE",
+            "WITH",
+            "SYSTEM_TIME",
+            "OVER",
+        ] {
+            assert!(!grammar.contains(clause), "{clause} in {grammar}");
+        }
+        //every rule is defined once
+        let assert_unique_rules = |grammar: &str| {
+            let defined = grammar
+                .lines()
+                .filter_map(|line| line.split_once(" ::= "))
+                .map(|(name, _)| name.trim())
+                .collect::<Vec<_>>();
+            let mut unique = defined.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), defined.len());
+        };
+        assert_unique_rules(&grammar);
+        assert!(!grammar.contains("GROUP"));
+
+        let policy = FeaturePolicy {
+            allow_order_by: true,
+            allow_limit: true,
+            allow_distinct: true,
+            allow_locking: true,
+            allow_table_hints: true,
+            allow_time_buckets: true,
+            allow_running_aggregates: true,
+        };
+        let options = ParseOptions::new()
+            .with_dialect(Dialect::BigQuery)
+            .with_feature_policy(policy);
+        let grammar = supported_grammar(&options);
+        for clause in [
+            "DISTINCT",
+            "order_by    ::=",
+            "LIMIT",
+            "locking     ::=",
+            "WITH",
+        ] {
+            assert!(grammar.contains(clause), "{clause} in {grammar}");
+        }
+        assert!(grammar.contains("\"SYSTEM_TIME\""));
+        for rule in [
+            "\"GROUP\" \"BY\" time_bucket",
+            "unit        ::= \"'second'\" | \"'minute'\"",
+            "\"ISOWEEK\"",
+            "\"toStartOfHour\" | \"toStartOfDay\" | \"toMonday\"",
+            "function \"(\" column \")\" [ window ]",
+            "\"UNBOUNDED\" \"PRECEDING\"",
+        ] {
+            assert!(grammar.contains(rule), "{rule} in {grammar}");
+        }
+        assert_unique_rules(&grammar);
+        //the clauses of the grammar are accepted
+        let query = "SELECT DISTINCT SUM(a) AS s, DATE_TRUNC(ts, ISOWEEK) AS w FROM d.t \
+                     FOR SYSTEM_TIME AS OF TIMESTAMP '2024-01-01 00:00:00' WHERE a > 1 \
+                     GROUP BY DATE_TRUNC(ts, ISOWEEK) ORDER BY s DESC LIMIT 10";
+        QueryMetadata::parse_with(query, &options).unwrap();
+        let query = "SELECT SUM(a) OVER (ORDER BY ts DESC ROWS BETWEEN UNBOUNDED PRECEDING AND \
+                     CURRENT ROW) FROM d.t";
+        QueryMetadata::parse_with(query, &options).unwrap();
+    }
+}
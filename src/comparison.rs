@@ -1,45 +1,59 @@
-use std::fmt::{self, Display};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+};
 
 use serde::{Deserialize, Serialize};
 use sqlparser::ast;
 use utoipa::ToSchema;
 
 use crate::{
-    error::ParseError, query_metadata::FromClauseIdentifier, support::case_fold_identifier,
+    dialect::CaseFolding,
+    error::{ErrorCode, ParseError, UnsupportedFeature},
+    expression::{Expression, Operator, Value},
+    identifier::Identifier,
+    malformed_query,
+    query_metadata::FromClauseIdentifier,
     unsupported,
 };
 
-use super::support::{extract_qualified_column, remove_outer_parens};
-
-#[must_use]
-pub const fn is_binary_operator_supported(op: &ast::BinaryOperator) -> bool {
-    matches!(
-        op,
-        &ast::BinaryOperator::Gt
-            | &ast::BinaryOperator::GtEq
-            | &ast::BinaryOperator::Lt
-            | &ast::BinaryOperator::LtEq
-            | &ast::BinaryOperator::Eq
-            | &ast::BinaryOperator::NotEq
-    )
+use super::support::{check_literal, extract_qualified_column, remove_outer_parens};
+
+raw_ast! {
+    #[must_use]
+    pub const fn is_binary_operator_supported(op: &ast::BinaryOperator) -> bool {
+        matches!(
+            op,
+            &ast::BinaryOperator::Gt
+                | &ast::BinaryOperator::GtEq
+                | &ast::BinaryOperator::Lt
+                | &ast::BinaryOperator::LtEq
+                | &ast::BinaryOperator::Eq
+                | &ast::BinaryOperator::NotEq
+        )
+    }
 }
 
-#[must_use]
-pub const fn is_expression_supported(op: &ast::Expr) -> bool {
-    matches!(
-        op,
-        &ast::Expr::IsNull(..)
-            | &ast::Expr::IsNotNull(..)
-            | &ast::Expr::IsTrue(..)
-            | &ast::Expr::IsNotTrue(..)
-            | &ast::Expr::IsFalse(..)
-            | &ast::Expr::IsNotFalse(..)
-    )
+raw_ast! {
+    #[must_use]
+    pub const fn is_expression_supported(op: &ast::Expr) -> bool {
+        matches!(
+            op,
+            &ast::Expr::IsNull(..)
+                | &ast::Expr::IsNotNull(..)
+                | &ast::Expr::IsTrue(..)
+                | &ast::Expr::IsNotTrue(..)
+                | &ast::Expr::IsFalse(..)
+                | &ast::Expr::IsNotFalse(..)
+        )
+    }
 }
 
 /// The comparison operation between the value of an unspecified column and some constant values.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
 #[serde(tag = "type")]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
 pub enum CompareOp {
     /// Check if column's value is less than `value`.
     Lt { value: String },
@@ -66,6 +80,16 @@ pub enum CompareOp {
     IsFalse,
     /// Check if column's value is not `false`.
     IsNotFalse,
+    /// Check if the element of the array column at `index` is equal to `value`, e.g.
+    /// `tags[1] = 'x'` (the arrays are indexed from 1 in PostgreSQL).
+    ElementEq { index: i64, value: String },
+    /// Check if the element of the array column at `index` is not equal to `value`.
+    ElementNotEq { index: i64, value: String },
+    /// Check if any element of the array column is equal to `value`, e.g. `'x' = ANY(tags)`.
+    AnyEq { value: String },
+    /// Check if every element of the array column is not equal to `value`, e.g.
+    /// `'x' <> ALL(tags)`.
+    AllNotEq { value: String },
 }
 
 impl Display for CompareOp {
@@ -83,10 +107,379 @@ impl Display for CompareOp {
             Self::IsNotTrue => write!(f, "Is not true"),
             Self::IsFalse => write!(f, "Is false"),
             Self::IsNotFalse => write!(f, "Is not false"),
+            Self::ElementEq { .. } => write!(f, "Element equal"),
+            Self::ElementNotEq { .. } => write!(f, "Element not equal"),
+            Self::AnyEq { .. } => write!(f, "Any equal"),
+            Self::AllNotEq { .. } => write!(f, "All not equal"),
+        }
+    }
+}
+
+/// The kind of SQL literal a comparison value was written as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum ValueType {
+    /// A numeric literal, e.g. `-1`, `2.5` or `NUMERIC '2.5'`.
+    Number,
+    /// A string literal, e.g. `'2021-04-02'`.
+    #[default]
+    String,
+    /// A boolean literal, i.e. `true` or `false`.
+    Boolean,
+    /// The `NULL` literal.
+    Null,
+    /// A date literal, e.g. `DATE '2021-04-02'`, whose value is the quoted string.
+    Date,
+    /// A time literal, e.g. `TIME '12:30:00'`, whose value is the quoted string.
+    Time,
+    /// A timestamp literal, e.g. `TIMESTAMP '2021-04-02 12:30:00'`, whose value is the quoted
+    /// string.
+    Timestamp,
+    /// The current date or time, e.g. `CURRENT_DATE` or `NOW()`, whose value is the SQL name of
+    /// the [`SystemTime`], to substitute when the query is run.
+    Symbolic,
+}
+
+/// A point in time known when the query is run, compared as a [`ValueType::Symbolic`] value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum SystemTime {
+    /// `CURRENT_DATE`.
+    CurrentDate,
+    /// `CURRENT_TIME`.
+    CurrentTime,
+    /// `CURRENT_TIMESTAMP`, also written `NOW()`.
+    CurrentTimestamp,
+}
+
+impl SystemTime {
+    /// Returns the point in time named `name` in SQL, whatever its case (e.g. `now` for
+    /// [`Self::CurrentTimestamp`]).
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::CurrentDate, Self::CurrentTime, Self::CurrentTimestamp]
+            .into_iter()
+            .find(|time| time.sql_name().eq_ignore_ascii_case(name))
+            .or_else(|| {
+                name.eq_ignore_ascii_case("NOW")
+                    .then_some(Self::CurrentTimestamp)
+            })
+    }
+
+    /// Returns the name of the point in time in SQL, e.g. `CURRENT_DATE`.
+    #[must_use]
+    pub const fn sql_name(self) -> &'static str {
+        match self {
+            Self::CurrentDate => "CURRENT_DATE",
+            Self::CurrentTime => "CURRENT_TIME",
+            Self::CurrentTimestamp => "CURRENT_TIMESTAMP",
+        }
+    }
+
+    // the point in time of `CURRENT_DATE`, `NOW()`, ..., without arguments nor clauses
+    pub(crate) fn from_function(function: &ast::Function) -> Option<Self> {
+        let ast::Function {
+            name: ast::ObjectName(name_parts),
+            args,
+            over: None,
+            distinct: false,
+            filter: None,
+            null_treatment: None,
+            order_by,
+            special: _,
+        } = function
+        else {
+            return None;
+        };
+        match &name_parts[..] {
+            [ast::Ident {
+                value,
+                quote_style: None,
+            }] if args.is_empty() && order_by.is_empty() => Self::from_name(value),
+            _ => None,
+        }
+    }
+}
+
+impl ValueType {
+    // the tag of the kind of literal in the fingerprints, which must never change since they're
+    // stored (unlike the Debug output or the description)
+    pub(crate) const fn fingerprint_tag(self) -> &'static str {
+        match self {
+            Self::Number => "NUMBER",
+            Self::String => "STRING",
+            Self::Boolean => "BOOLEAN",
+            Self::Null => "NULL",
+            Self::Date => "DATE",
+            Self::Time => "TIME",
+            Self::Timestamp => "TIMESTAMP",
+            Self::Symbolic => "SYMBOLIC",
+        }
+    }
+
+    // the kind of literal of `data_type 'value'`, if it's supported
+    pub(crate) const fn of_typed_string(data_type: &ast::DataType) -> Option<Self> {
+        match data_type {
+            ast::DataType::Numeric(_) | ast::DataType::Decimal(_) | ast::DataType::Dec(_) => {
+                Some(Self::Number)
+            }
+            ast::DataType::Date => Some(Self::Date),
+            ast::DataType::Time(..) => Some(Self::Time),
+            ast::DataType::Timestamp(..) | ast::DataType::Datetime(_) => Some(Self::Timestamp),
+            _ => None,
+        }
+    }
+
+    // checks that a value can be embedded in SQL as this kind of literal: strings without
+    // control characters, decimal numbers, and valid dates, times and timestamps
+    pub(crate) fn check(self, value: &str) -> Result<(), ParseError> {
+        let valid = match self {
+            Self::String => return check_literal(value),
+            Self::Number => normalize_number(value).is_ok(),
+            Self::Boolean | Self::Null => true,
+            Self::Date => is_date(value),
+            Self::Time => is_time(value),
+            Self::Timestamp => is_timestamp(value),
+            Self::Symbolic => SystemTime::from_name(value).is_some(),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(malformed_query!(
+                ErrorCode::InvalidLiteral,
+                format!("{value:?} isn't a valid {self}.")
+            ))
+        }
+    }
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::String => write!(f, "string"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Null => write!(f, "NULL"),
+            Self::Date => write!(f, "date"),
+            Self::Time => write!(f, "time"),
+            Self::Timestamp => write!(f, "timestamp"),
+            Self::Symbolic => write!(f, "current date or time"),
+        }
+    }
+}
+
+// the largest exponent of the numbers, which are written out in full once normalized
+const MAX_EXPONENT: i64 = 1000;
+
+// rewrites a decimal number in its canonical form, e.g. `1e3` as `1000`, `+1_000.50` as `1000.5`
+// and `-0.0` as `0`: without sign if it's positive, underscores, exponent, nor leading or trailing
+// zeros; the value is kept exactly, and the numbers that can't be written out are rejected
+pub(crate) fn normalize_number(number: &str) -> Result<String, ParseError> {
+    let invalid = || {
+        malformed_query!(
+            ErrorCode::InvalidLiteral,
+            format!("{number:?} isn't a valid number.")
+        )
+    };
+    let (negative, unsigned) = number.strip_prefix('-').map_or_else(
+        || (false, number.strip_prefix('+').unwrap_or(number)),
+        |unsigned| (true, unsigned),
+    );
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => {
+            let exponent = exponent.strip_prefix('+').unwrap_or(exponent);
+            let digits = exponent.strip_prefix('-').unwrap_or(exponent);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let exponent = exponent
+                .parse::<i64>()
+                .ok()
+                .filter(|exponent| exponent.abs() <= MAX_EXPONENT)
+                .ok_or_else(invalid)?;
+            (mantissa, exponent)
         }
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let integer = remove_digit_separators(integer).ok_or_else(invalid)?;
+    let fraction = remove_digit_separators(fraction).ok_or_else(invalid)?;
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    //the digits, with the decimal point moved by the exponent
+    let digits = format!("{integer}{fraction}");
+    let point = i64::try_from(integer.len()).map_err(|_| invalid())? + exponent;
+    let (integer, fraction) = if point <= 0 {
+        let zeros = usize::try_from(-point).map_err(|_| invalid())?;
+        (String::new(), format!("{}{digits}", "0".repeat(zeros)))
+    } else {
+        let point = usize::try_from(point).map_err(|_| invalid())?;
+        if point >= digits.len() {
+            let zeros = point - digits.len();
+            (format!("{digits}{}", "0".repeat(zeros)), String::new())
+        } else {
+            let (integer, fraction) = digits.split_at(point);
+            (integer.to_string(), fraction.to_string())
+        }
+    };
+    let integer = integer.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    let mut normalized = String::with_capacity(integer.len() + fraction.len() + 3);
+    if negative && !(integer.is_empty() && fraction.is_empty()) {
+        normalized.push('-');
+    }
+    normalized.push_str(if integer.is_empty() { "0" } else { integer });
+    if !fraction.is_empty() {
+        normalized.push('.');
+        normalized.push_str(fraction);
+    }
+    Ok(normalized)
+}
+
+// orders two normalized numbers (see `normalize_number`)
+pub(crate) fn compare_numbers(left: &str, right: &str) -> Ordering {
+    // orders the absolute values, whose integer parts have no leading zeros and fractional parts
+    // no trailing ones
+    let compare_unsigned = |left: &str, right: &str| {
+        let (left_integer, left_fraction) = left.split_once('.').unwrap_or((left, ""));
+        let (right_integer, right_fraction) = right.split_once('.').unwrap_or((right, ""));
+        left_integer
+            .len()
+            .cmp(&right_integer.len())
+            .then_with(|| left_integer.cmp(right_integer))
+            .then_with(|| left_fraction.cmp(right_fraction))
+    };
+    match (left.strip_prefix('-'), right.strip_prefix('-')) {
+        (None, None) => compare_unsigned(left, right),
+        (Some(left), Some(right)) => compare_unsigned(right, left),
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+    }
+}
+
+// evaluates the comparison of two constants (e.g. `1 = 1`), with the numbers normalized; only
+// numbers are ordered, the other values of the same type are only compared for equality, and a
+// comparison with NULL never holds; `None` if the constants can't be compared, or if the result
+// depends on the collation, i.e. for strings that aren't byte-equal (e.g. `'a' = 'A'` holds with
+// the case-insensitive collations of MySQL)
+pub(crate) fn compare_constants(
+    op: &ast::BinaryOperator,
+    (left, left_type): (&str, ValueType),
+    (right, right_type): (&str, ValueType),
+) -> Option<bool> {
+    if left_type == ValueType::Null || right_type == ValueType::Null {
+        return Some(false);
+    }
+    let ordering = match (left_type, right_type) {
+        (ValueType::Number, ValueType::Number) => compare_numbers(left, right),
+        (ValueType::String, ValueType::String) if left != right => return None,
+        (ValueType::String | ValueType::Boolean, _)
+            if left_type == right_type
+                && matches!(op, ast::BinaryOperator::Eq | ast::BinaryOperator::NotEq) =>
+        {
+            left.cmp(right)
+        }
+        _ => return None,
+    };
+    match op {
+        ast::BinaryOperator::Eq => Some(ordering.is_eq()),
+        ast::BinaryOperator::NotEq => Some(ordering.is_ne()),
+        ast::BinaryOperator::Lt => Some(ordering.is_lt()),
+        ast::BinaryOperator::LtEq => Some(ordering.is_le()),
+        ast::BinaryOperator::Gt => Some(ordering.is_gt()),
+        ast::BinaryOperator::GtEq => Some(ordering.is_ge()),
+        _ => None,
     }
 }
 
+// the digits of `digits` without the underscores between them, if they're all ASCII digits
+fn remove_digit_separators(digits: &str) -> Option<String> {
+    let valid = !digits.starts_with('_')
+        && !digits.ends_with('_')
+        && !digits.contains("__")
+        && digits.bytes().all(|b| b.is_ascii_digit() || b == b'_');
+    valid.then(|| digits.replace('_', ""))
+}
+
+// `YYYY-MM-DD`, with a day that exists in the month
+fn is_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return false;
+    }
+    let (Some(year), Some(month), Some(day)) = (
+        parse_digits(&value[..4]),
+        parse_digits(&value[5..7]),
+        parse_digits(&value[8..]),
+    ) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+// `HH:MM:SS`, with optional fractional seconds
+fn is_time(value: &str) -> bool {
+    let (time, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    let bytes = time.as_bytes();
+    if bytes.len() != 8 || bytes[2] != b':' || bytes[5] != b':' {
+        return false;
+    }
+    let in_range = |digits: &str, max| parse_digits(digits).is_some_and(|n| n <= max);
+    in_range(&time[..2], 23)
+        && in_range(&time[3..5], 59)
+        && in_range(&time[6..], 59)
+        && !fraction.is_empty()
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+// a date, optionally followed by a time (after a space or `T`) and a time zone (`Z`, `+HH` or
+// `+HH:MM`)
+fn is_timestamp(value: &str) -> bool {
+    let Some(date) = value.get(..10).filter(|date| is_date(date)) else {
+        return false;
+    };
+    let time = &value[date.len()..];
+    if time.is_empty() {
+        return true;
+    }
+    let Some(time) = time.strip_prefix([' ', 'T']) else {
+        return false;
+    };
+    let (time, zone) = time
+        .find(['Z', '+', '-'])
+        .map_or((time, ""), |index| time.split_at(index));
+    let zone_valid = match zone.strip_prefix(['+', '-']) {
+        _ if zone.is_empty() || zone == "Z" => true,
+        Some(offset) => {
+            let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "00"));
+            hours.len() == 2
+                && minutes.len() == 2
+                && parse_digits(hours).is_some_and(|hours| hours <= 14)
+                && parse_digits(minutes).is_some_and(|minutes| minutes <= 59)
+        }
+        None => false,
+    };
+    is_time(time) && zone_valid
+}
+
+// the number written with the ASCII digits of `digits`, if they're all digits
+fn parse_digits(digits: &str) -> Option<u32> {
+    digits
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| digits.parse().ok())
+        .flatten()
+}
+
 impl CompareOp {
     pub(crate) fn from_binary_operator(
         op: &ast::BinaryOperator,
@@ -105,12 +498,193 @@ impl CompareOp {
             ast::BinaryOperator::Eq => Self::Eq { value },
             ast::BinaryOperator::NotEq => Self::NotEq { value },
             _ => {
-                return Err(unsupported!(format!("the {op} operator.")));
+                return Err(unsupported!(
+                    UnsupportedFeature::Operator,
+                    format!("the {op} operator.")
+                ));
             }
         };
         Ok(comparison)
     }
 
+    /// Returns the constant the column is compared to, if any (e.g. `None` for `IS NULL`).
+    #[must_use]
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Self::Lt { value }
+            | Self::LtEq { value }
+            | Self::Gt { value }
+            | Self::GtEq { value }
+            | Self::Eq { value }
+            | Self::NotEq { value }
+            | Self::ElementEq { value, .. }
+            | Self::ElementNotEq { value, .. }
+            | Self::AnyEq { value }
+            | Self::AllNotEq { value } => Some(value),
+            Self::IsNull
+            | Self::IsNotNull
+            | Self::IsTrue
+            | Self::IsNotTrue
+            | Self::IsFalse
+            | Self::IsNotFalse => None,
+        }
+    }
+
+    /// Returns `true` if the comparison checks whether the column is `NULL` or not.
+    #[must_use]
+    pub const fn is_null_check(&self) -> bool {
+        matches!(self, Self::IsNull | Self::IsNotNull)
+    }
+
+    /// Returns the SQL operator of the comparison, e.g. `>=` or `IS NULL`.
+    #[must_use]
+    pub const fn sql_symbol(&self) -> &'static str {
+        match self {
+            Self::Lt { .. } => "<",
+            Self::LtEq { .. } => "<=",
+            Self::Gt { .. } => ">",
+            Self::GtEq { .. } => ">=",
+            Self::Eq { .. } => "=",
+            Self::NotEq { .. } => "<>",
+            Self::IsNull => "IS NULL",
+            Self::IsNotNull => "IS NOT NULL",
+            Self::IsTrue => "IS TRUE",
+            Self::IsNotTrue => "IS NOT TRUE",
+            Self::IsFalse => "IS FALSE",
+            Self::IsNotFalse => "IS NOT FALSE",
+            Self::ElementEq { .. } => "[] =",
+            Self::ElementNotEq { .. } => "[] <>",
+            Self::AnyEq { .. } => "= ANY",
+            Self::AllNotEq { .. } => "<> ALL",
+        }
+    }
+
+    /// Returns the opposite comparison, i.e. the one matching the rows that `NOT (column OP
+    /// value)` matches (e.g. `>=` for `<`).
+    #[must_use]
+    pub fn negate(&self) -> Self {
+        match self.clone() {
+            Self::Lt { value } => Self::GtEq { value },
+            Self::LtEq { value } => Self::Gt { value },
+            Self::Gt { value } => Self::LtEq { value },
+            Self::GtEq { value } => Self::Lt { value },
+            Self::Eq { value } => Self::NotEq { value },
+            Self::NotEq { value } => Self::Eq { value },
+            Self::IsNull => Self::IsNotNull,
+            Self::IsNotNull => Self::IsNull,
+            Self::IsTrue => Self::IsNotTrue,
+            Self::IsNotTrue => Self::IsTrue,
+            Self::IsFalse => Self::IsNotFalse,
+            Self::IsNotFalse => Self::IsFalse,
+            Self::ElementEq { index, value } => Self::ElementNotEq { index, value },
+            Self::ElementNotEq { index, value } => Self::ElementEq { index, value },
+            Self::AnyEq { value } => Self::AllNotEq { value },
+            Self::AllNotEq { value } => Self::AnyEq { value },
+        }
+    }
+
+    /// Returns `true` if every value matching the comparison also matches `other` (e.g. `> 2`
+    /// implies `> 1` and `IS NOT NULL`).
+    ///
+    /// The values are only ordered if `numbers` is `true`, i.e. if both are normalized numbers
+    /// (see [`ValueType::Number`]); otherwise, a comparison only implies itself and the checks
+    /// that follow from it regardless of the value.
+    #[must_use]
+    pub fn implies(&self, other: &Self, numbers: bool) -> bool {
+        if self == other {
+            return true;
+        }
+        match (self, other) {
+            //a comparison to a value never holds for NULL
+            (_, Self::IsNotNull) => {
+                self.value().is_some() || matches!(self, Self::IsTrue | Self::IsFalse)
+            }
+            (Self::IsTrue, Self::IsNotFalse)
+            | (Self::IsFalse, Self::IsNotTrue)
+            | (Self::IsNull, Self::IsNotTrue | Self::IsNotFalse) => true,
+            _ if !numbers => false,
+            (Self::Eq { value }, _) => other.value().is_some_and(|bound| {
+                let ordering = compare_numbers(value, bound);
+                match other {
+                    Self::Lt { .. } => ordering.is_lt(),
+                    Self::LtEq { .. } => ordering.is_le(),
+                    Self::Gt { .. } => ordering.is_gt(),
+                    Self::GtEq { .. } => ordering.is_ge(),
+                    Self::Eq { .. } => ordering.is_eq(),
+                    Self::NotEq { .. } => ordering.is_ne(),
+                    _ => false,
+                }
+            }),
+            (
+                Self::Gt { value },
+                Self::Gt { value: bound }
+                | Self::GtEq { value: bound }
+                | Self::NotEq { value: bound },
+            )
+            | (
+                Self::Lt { value: bound },
+                Self::Lt { value } | Self::LtEq { value } | Self::NotEq { value },
+            )
+            | (Self::GtEq { value }, Self::GtEq { value: bound })
+            | (Self::LtEq { value: bound }, Self::LtEq { value }) => {
+                compare_numbers(value, bound).is_ge()
+            }
+            (Self::GtEq { value }, Self::Gt { value: bound } | Self::NotEq { value: bound })
+            | (Self::LtEq { value: bound }, Self::Lt { value } | Self::NotEq { value }) => {
+                compare_numbers(value, bound).is_gt()
+            }
+            _ => false,
+        }
+    }
+
+    // rebuilds the comparison `operand OP value` as an expression
+    pub(crate) fn expression(
+        &self,
+        operand: &Expression,
+        value_type: Option<ValueType>,
+    ) -> Expression {
+        let value_type = value_type.unwrap_or_default();
+        let literal = |value: &str| Box::new(Expression::Value(Value::new(value, value_type)));
+        let compare = |left, op, value: &str| Expression::Compare {
+            left: Box::new(left),
+            op,
+            right: literal(value),
+        };
+        let is = |value, negated| Expression::Is {
+            expr: Box::new(operand.clone()),
+            value,
+            negated,
+        };
+        let element = |index: &i64| Expression::Element {
+            array: Box::new(operand.clone()),
+            index: *index,
+        };
+        let quantified = |op, any, compared: &str| Expression::Quantified {
+            left: literal(compared),
+            op,
+            any,
+            array: Box::new(operand.clone()),
+        };
+        match self {
+            Self::Lt { value } => compare(operand.clone(), Operator::Lt, value),
+            Self::LtEq { value } => compare(operand.clone(), Operator::LtEq, value),
+            Self::Gt { value } => compare(operand.clone(), Operator::Gt, value),
+            Self::GtEq { value } => compare(operand.clone(), Operator::GtEq, value),
+            Self::Eq { value } => compare(operand.clone(), Operator::Eq, value),
+            Self::NotEq { value } => compare(operand.clone(), Operator::NotEq, value),
+            Self::IsNull => is(Value::Null, false),
+            Self::IsNotNull => is(Value::Null, true),
+            Self::IsTrue => is(Value::Boolean(true), false),
+            Self::IsNotTrue => is(Value::Boolean(true), true),
+            Self::IsFalse => is(Value::Boolean(false), false),
+            Self::IsNotFalse => is(Value::Boolean(false), true),
+            Self::ElementEq { index, value } => compare(element(index), Operator::Eq, value),
+            Self::ElementNotEq { index, value } => compare(element(index), Operator::NotEq, value),
+            Self::AnyEq { value } => quantified(Operator::Eq, true, value),
+            Self::AllNotEq { value } => quantified(Operator::NotEq, false, value),
+        }
+    }
+
     pub(crate) fn from_expr(op: &ast::Expr) -> Result<Self, ParseError> {
         let comparison = match op {
             ast::Expr::IsNull(_) => Self::IsNull,
@@ -120,7 +694,10 @@ impl CompareOp {
             ast::Expr::IsFalse(_) => Self::IsFalse,
             ast::Expr::IsNotFalse(_) => Self::IsNotFalse,
             _ => {
-                return Err(unsupported!(format!("the {op} operator.")));
+                return Err(unsupported!(
+                    UnsupportedFeature::Operator,
+                    format!("the {op} operator.")
+                ));
             }
         };
         Ok(comparison)
@@ -129,43 +706,166 @@ impl CompareOp {
 
 #[derive(Debug)]
 pub(crate) enum ComparisonOperand<'a> {
-    Column(String),
+    // a column, with the path of the JSON field it's accessed at if any (e.g. `payload->>'key'`)
+    Column(Identifier, Option<Vec<String>>),
     // Other can be a static value, or another expression
     Other(&'a ast::Expr),
 }
 
 impl<'a> ComparisonOperand<'a> {
+    // identifies the operand, with the collation it's given if any (e.g. `name COLLATE "de_DE"`)
     pub(crate) fn from_expression(
         from_clause_identifier: FromClauseIdentifier<'_>,
         expr: &'a ast::Expr,
-    ) -> Result<Self, ParseError> {
+        case_folding: CaseFolding,
+    ) -> Result<(Self, Option<String>), ParseError> {
+        let (expr, collation) = remove_collation(remove_outer_parens(expr), case_folding)?;
         let expr = remove_outer_parens(expr);
-        match expr {
-            ast::Expr::Identifier(ident) => Ok(Self::Column(case_fold_identifier(ident))),
-            ast::Expr::CompoundIdentifier(name_parts) => {
-                extract_qualified_column(from_clause_identifier, expr, name_parts).map(Self::Column)
+        let (column, json_path) = match json_field(expr) {
+            Some((column, path)) => (column, Some(path)),
+            None => (expr, None),
+        };
+        let operand = match column {
+            ast::Expr::Identifier(ident) => {
+                Self::Column(Identifier::fold(ident, case_folding), json_path)
             }
-            _ => Ok(Self::Other(expr)),
-        }
+            ast::Expr::CompoundIdentifier(name_parts) => Self::Column(
+                extract_qualified_column(from_clause_identifier, column, name_parts, case_folding)?,
+                json_path,
+            ),
+            _ => Self::Other(expr),
+        };
+        Ok((operand, collation))
+    }
+}
+
+// splits the COLLATE clause off an operand, with the name of the collation as stored, i.e. case
+// folded like the other names and with its parts joined by dots (e.g. `pg_catalog.de_DE`)
+fn remove_collation(
+    expr: &ast::Expr,
+    case_folding: CaseFolding,
+) -> Result<(&ast::Expr, Option<String>), ParseError> {
+    let ast::Expr::Collate { expr, collation } = expr else {
+        return Ok((expr, None));
+    };
+    let parts = collation
+        .0
+        .iter()
+        .map(|part| Identifier::fold(part, case_folding).folded)
+        .collect::<Vec<_>>();
+    if parts
+        .iter()
+        .any(|part| part.is_empty() || part.contains('.'))
+    {
+        return Err(unsupported!(
+            UnsupportedFeature::Value,
+            format!("the collation {collation}.")
+        ));
+    }
+    Ok((expr, Some(parts.join("."))))
+}
+
+// splits the access to a JSON field as text, e.g. `payload->'a'->>'b'`, into the accessed
+// expression and the path of the field (`a`, `b`); the keys must be strings
+fn json_field(expr: &ast::Expr) -> Option<(&ast::Expr, Vec<String>)> {
+    let key = |expr: &ast::Expr| match remove_outer_parens(expr) {
+        ast::Expr::Value(ast::Value::SingleQuotedString(key)) => Some(key.clone()),
+        _ => None,
+    };
+    let ast::Expr::JsonAccess {
+        left,
+        operator: ast::JsonOperator::LongArrow,
+        right,
+    } = expr
+    else {
+        return None;
+    };
+    let mut path = vec![key(right)?];
+    let mut object = remove_outer_parens(left);
+    while let ast::Expr::JsonAccess {
+        left,
+        operator: ast::JsonOperator::Arrow,
+        right,
+    } = object
+    {
+        path.push(key(right)?);
+        object = remove_outer_parens(left);
     }
+    path.reverse();
+    Some((object, path))
 }
 
+// restores the precedence of the JSON operators, which the SQL parser gives the lowest one:
+// e.g. `payload ->> 'key' = 'ok'` is parsed as `payload ->> ('key' = 'ok')`, rebuilt as
+// `(payload ->> 'key') = 'ok'`, since the operator applies to the leftmost operand of its right
+// side; the recursion is bounded by the recursion limit of the SQL parser
+pub(crate) fn rebalance_json_access(expr: ast::Expr) -> ast::Expr {
+    let ast::Expr::JsonAccess {
+        left,
+        operator,
+        right,
+    } = expr
+    else {
+        return expr;
+    };
+    let access = |right| ast::Expr::JsonAccess {
+        left: left.clone(),
+        operator,
+        right,
+    };
+    match rebalance_json_access(*right) {
+        ast::Expr::BinaryOp {
+            left: operand,
+            op,
+            right,
+        } => ast::Expr::BinaryOp {
+            left: Box::new(rebalance_json_access(access(operand))),
+            op,
+            right,
+        },
+        //the chained accesses apply from left to right, e.g. `payload -> 'a' ->> 'b'`
+        ast::Expr::JsonAccess {
+            left: operand,
+            operator: next,
+            right,
+        } => rebalance_json_access(ast::Expr::JsonAccess {
+            left: Box::new(access(operand)),
+            operator: next,
+            right,
+        }),
+        ast::Expr::IsNull(operand) => ast::Expr::IsNull(Box::new(access(operand))),
+        ast::Expr::IsNotNull(operand) => ast::Expr::IsNotNull(Box::new(access(operand))),
+        ast::Expr::IsTrue(operand) => ast::Expr::IsTrue(Box::new(access(operand))),
+        ast::Expr::IsNotTrue(operand) => ast::Expr::IsNotTrue(Box::new(access(operand))),
+        ast::Expr::IsFalse(operand) => ast::Expr::IsFalse(Box::new(access(operand))),
+        ast::Expr::IsNotFalse(operand) => ast::Expr::IsNotFalse(Box::new(access(operand))),
+        right => access(Box::new(right)),
+    }
+}
+
+// the column of a comparison with its JSON path, the compared value, and whether the operands
+// are reversed (i.e. the value comes first)
+type ColumnComparison<'a> = (Identifier, Option<Vec<String>>, &'a ast::Expr, bool);
+
 pub(crate) fn analyze_comparison_operands<'a>(
     binary_expr: &'a ast::Expr,
     left: ComparisonOperand<'a>,
     right: ComparisonOperand<'a>,
-) -> Result<(String, &'a ast::Expr, bool), ParseError> {
+) -> Result<ColumnComparison<'a>, ParseError> {
     match (left, right) {
-        (ComparisonOperand::Column(column), ComparisonOperand::Other(value)) => {
-            Ok((column, value, false))
+        (ComparisonOperand::Column(column, json_path), ComparisonOperand::Other(value)) => {
+            Ok((column, json_path, value, false))
         }
-        (ComparisonOperand::Other(value), ComparisonOperand::Column(column)) => {
+        (ComparisonOperand::Other(value), ComparisonOperand::Column(column, json_path)) => {
             // keep on the left the column
-            Ok((column, value, true))
+            Ok((column, json_path, value, true))
         }
-        _ => Err(unsupported!(format!(
-            "{binary_expr}. Only comparisons between a column and a constant are supported.",
-        ))),
+        _ => Err(unsupported!(
+            UnsupportedFeature::Comparison,
+            format!(
+                "{binary_expr}. Only comparisons between a column and a constant are supported.",
+            )
+        )),
     }
 }
 
@@ -174,8 +874,11 @@ mod tests {
     use sqlparser::ast::Ident;
 
     use crate::{
-        comparison::{is_binary_operator_supported, is_expression_supported, CompareOp},
-        error::ParseError,
+        comparison::{
+            compare_constants, compare_numbers, is_binary_operator_supported,
+            is_expression_supported, normalize_number, CompareOp, ValueType,
+        },
+        error::{ParseError, UnsupportedFeature},
     };
 
     use super::ast;
@@ -281,7 +984,9 @@ mod tests {
             value: value.clone(),
         };
         let expected_error = ParseError::Unsupported {
+            feature: UnsupportedFeature::Operator,
             message: "the AND operator.".to_string(),
+            span: None,
         };
 
         let op = ast::BinaryOperator::Lt;
@@ -350,4 +1055,322 @@ mod tests {
         let result = CompareOp::from_expr(&op).unwrap();
         assert_eq!(expected_is_not_null, result);
     }
+
+    #[test]
+    fn test_accessors() {
+        let lt = CompareOp::Lt {
+            value: "1".to_string(),
+        };
+        assert_eq!(lt.value(), Some("1"));
+        assert!(!lt.is_null_check());
+        assert_eq!(CompareOp::IsNull.value(), None);
+        assert!(CompareOp::IsNull.is_null_check());
+        assert!(CompareOp::IsNotNull.is_null_check());
+        assert!(!CompareOp::IsTrue.is_null_check());
+    }
+
+    #[test]
+    fn test_symbol_and_negate() {
+        let value = "1".to_string();
+        let cases = [
+            (
+                CompareOp::Lt {
+                    value: value.clone(),
+                },
+                "<",
+                CompareOp::GtEq {
+                    value: value.clone(),
+                },
+            ),
+            (
+                CompareOp::LtEq {
+                    value: value.clone(),
+                },
+                "<=",
+                CompareOp::Gt {
+                    value: value.clone(),
+                },
+            ),
+            (
+                CompareOp::Eq {
+                    value: value.clone(),
+                },
+                "=",
+                CompareOp::NotEq { value },
+            ),
+            (CompareOp::IsNull, "IS NULL", CompareOp::IsNotNull),
+            (CompareOp::IsTrue, "IS TRUE", CompareOp::IsNotTrue),
+            (CompareOp::IsNotFalse, "IS NOT FALSE", CompareOp::IsFalse),
+            (
+                CompareOp::ElementEq {
+                    index: 2,
+                    value: "x".to_string(),
+                },
+                "[] =",
+                CompareOp::ElementNotEq {
+                    index: 2,
+                    value: "x".to_string(),
+                },
+            ),
+            (
+                CompareOp::AnyEq {
+                    value: "x".to_string(),
+                },
+                "= ANY",
+                CompareOp::AllNotEq {
+                    value: "x".to_string(),
+                },
+            ),
+        ];
+        for (comparison, symbol, negated) in cases {
+            assert_eq!(comparison.sql_symbol(), symbol);
+            assert_eq!(comparison.negate(), negated);
+            assert_eq!(negated.negate(), comparison);
+        }
+    }
+
+    #[test]
+    fn check_temporal_values() {
+        let valid = |value_type: ValueType, value| value_type.check(value).is_ok();
+        for date in ["2024-01-31", "2024-02-29", "2000-02-29", "0001-12-31"] {
+            assert!(valid(ValueType::Date, date), "{date}");
+        }
+        for date in [
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-1-01",
+            "24-01-01",
+            "2024/01/01",
+            "2024-01-01 00:00:00",
+        ] {
+            assert!(!valid(ValueType::Date, date), "{date}");
+        }
+        for time in ["00:00:00", "23:59:59", "12:30:00.125"] {
+            assert!(valid(ValueType::Time, time), "{time}");
+        }
+        for time in [
+            "24:00:00",
+            "12:60:00",
+            "12:30",
+            "12:30:00.",
+            "12:30:00.1x",
+            "1:30:00",
+        ] {
+            assert!(!valid(ValueType::Time, time), "{time}");
+        }
+        for timestamp in [
+            "2024-01-01",
+            "2024-01-01 12:30:00",
+            "2024-01-01T12:30:00.5",
+            "2024-01-01 12:30:00Z",
+            "2024-01-01 12:30:00+02",
+            "2024-01-01 12:30:00-05:30",
+        ] {
+            assert!(valid(ValueType::Timestamp, timestamp), "{timestamp}");
+        }
+        for timestamp in [
+            "2024-01-01 ",
+            "2024-01-01X12:30:00",
+            "2024-01-01 12:30",
+            "2024-01-01 12:30:00+2",
+            "2024-01-01 12:30:00+15:00",
+            "2024-01-01 12:30:00 UTC",
+            "2024-02-30 12:30:00",
+        ] {
+            assert!(!valid(ValueType::Timestamp, timestamp), "{timestamp}");
+        }
+        assert!(valid(ValueType::Number, "-1.5e3"));
+        assert!(!valid(ValueType::Number, "1.5.3"));
+    }
+
+    #[test]
+    fn normalize_numbers() {
+        let cases = [
+            ("0", "0"),
+            ("42", "42"),
+            ("+42", "42"),
+            ("-7", "-7"),
+            ("007", "7"),
+            ("3.50", "3.5"),
+            ("-0.250", "-0.25"),
+            (".5", "0.5"),
+            ("5.", "5"),
+            ("-0.0", "0"),
+            ("1e3", "1000"),
+            ("1E+3", "1000"),
+            ("2.5e-3", "0.0025"),
+            ("-12.5e1", "-125"),
+            ("123e-2", "1.23"),
+            ("0e10", "0"),
+            ("1_000", "1000"),
+            ("1_000.000_1", "1000.0001"),
+            (
+                "12345678901234567890.123456789",
+                "12345678901234567890.123456789",
+            ),
+        ];
+        for (number, normalized) in cases {
+            assert_eq!(
+                normalize_number(number),
+                Ok(normalized.to_string()),
+                "{number}"
+            );
+        }
+        for number in [
+            "", "-", ".", "e3", "1e", "1e+", "1.2.3", "1e3.5", "0x1f", "_1", "1_", "1__0", "1_.5",
+            "inf", "NaN", "1e1001", "--1", "1 ",
+        ] {
+            assert!(normalize_number(number).is_err(), "{number}");
+        }
+    }
+
+    #[test]
+    fn compare_constant_values() {
+        let sorted = [
+            "-100", "-12.5", "-12.25", "-1", "-0.5", "0", "0.05", "0.5", "1", "12", "100",
+        ];
+        for (i, left) in sorted.iter().enumerate() {
+            for (j, right) in sorted.iter().enumerate() {
+                assert_eq!(compare_numbers(left, right), i.cmp(&j), "{left} {right}");
+            }
+        }
+
+        let number = |value| (value, ValueType::Number);
+        let string = |value| (value, ValueType::String);
+        let op = |op| match op {
+            "=" => ast::BinaryOperator::Eq,
+            "<>" => ast::BinaryOperator::NotEq,
+            "<" => ast::BinaryOperator::Lt,
+            _ => ast::BinaryOperator::GtEq,
+        };
+        assert_eq!(
+            compare_constants(&op("="), number("1"), number("1")),
+            Some(true)
+        );
+        assert_eq!(
+            compare_constants(&op("<>"), number("1"), number("1")),
+            Some(false)
+        );
+        assert_eq!(
+            compare_constants(&op("<"), number("-2"), number("1")),
+            Some(true)
+        );
+        assert_eq!(
+            compare_constants(&op(">="), number("0.5"), number("0.25")),
+            Some(true)
+        );
+        assert_eq!(
+            compare_constants(&op("="), string("a"), string("a")),
+            Some(true)
+        );
+        assert_eq!(
+            compare_constants(&op("<>"), string("a"), string("a")),
+            Some(false)
+        );
+        //strings that differ may be equal in their collation (e.g. case-insensitive)
+        assert_eq!(compare_constants(&op("="), string("a"), string("A")), None);
+        assert_eq!(compare_constants(&op("<>"), string("a"), string("b")), None);
+        assert_eq!(compare_constants(&op("="), string("a"), string("a ")), None);
+        assert_eq!(
+            compare_constants(
+                &op("="),
+                ("true", ValueType::Boolean),
+                ("true", ValueType::Boolean)
+            ),
+            Some(true)
+        );
+        //NULL is never equal to anything, even NULL
+        assert_eq!(
+            compare_constants(
+                &op("="),
+                ("Null", ValueType::Null),
+                ("Null", ValueType::Null)
+            ),
+            Some(false)
+        );
+        //strings aren't ordered, since it depends on their collation
+        assert_eq!(compare_constants(&op("<"), string("a"), string("b")), None);
+        assert_eq!(compare_constants(&op("="), string("1"), number("1")), None);
+    }
+
+    #[test]
+    fn implied_comparisons() {
+        let value = |value: &str| value.to_string();
+        let implied = [
+            (
+                CompareOp::Gt { value: value("2") },
+                CompareOp::Gt { value: value("1") },
+            ),
+            (
+                CompareOp::Gt { value: value("2") },
+                CompareOp::GtEq { value: value("2") },
+            ),
+            (
+                CompareOp::GtEq { value: value("2") },
+                CompareOp::Gt {
+                    value: value("1.5"),
+                },
+            ),
+            (
+                CompareOp::Lt { value: value("-1") },
+                CompareOp::NotEq { value: value("-1") },
+            ),
+            (
+                CompareOp::LtEq { value: value("1") },
+                CompareOp::Lt { value: value("2") },
+            ),
+            (
+                CompareOp::Eq { value: value("1") },
+                CompareOp::NotEq { value: value("2") },
+            ),
+            (
+                CompareOp::Eq { value: value("1") },
+                CompareOp::LtEq { value: value("1") },
+            ),
+            (CompareOp::Lt { value: value("1") }, CompareOp::IsNotNull),
+            (CompareOp::IsNull, CompareOp::IsNotTrue),
+        ];
+        for (comparison, other) in implied {
+            assert!(comparison.implies(&other, true), "{comparison:?} {other:?}");
+        }
+        let not_implied = [
+            (
+                CompareOp::Gt { value: value("1") },
+                CompareOp::Gt { value: value("2") },
+            ),
+            (
+                CompareOp::GtEq { value: value("2") },
+                CompareOp::Gt { value: value("2") },
+            ),
+            (
+                CompareOp::Lt { value: value("1") },
+                CompareOp::Gt { value: value("0") },
+            ),
+            (
+                CompareOp::LtEq { value: value("1") },
+                CompareOp::NotEq { value: value("1") },
+            ),
+            (
+                CompareOp::NotEq { value: value("1") },
+                CompareOp::Gt { value: value("1") },
+            ),
+            (CompareOp::IsNotNull, CompareOp::IsTrue),
+        ];
+        for (comparison, other) in not_implied {
+            assert!(
+                !comparison.implies(&other, true),
+                "{comparison:?} {other:?}"
+            );
+        }
+        //strings aren't ordered
+        let (a, b) = (
+            CompareOp::Gt { value: value("b") },
+            CompareOp::Gt { value: value("a") },
+        );
+        assert!(!a.implies(&b, false));
+        assert!(a.implies(&a, false));
+    }
 }
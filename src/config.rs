@@ -0,0 +1,482 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    dialect::{CaseFolding, Dialect, TableQualifier},
+    error::RejectionHook,
+    identifier::IdentifierRules,
+    limits::Limits,
+    policy::{CrossDatabase, FeaturePolicy, Qualification, SensitiveColumns, TablePattern},
+    table::TableRewriter,
+};
+
+/// Options that control how a query is parsed and how the derived queries are generated.
+///
+/// The options are `Send + Sync` and cheap to clone: the table patterns and the sensitive
+/// columns are shared between the clones rather than copied, so that options built once can be
+/// cloned into each thread of a pool (or shared behind an [`Arc`]) without being rebuilt for
+/// every query.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    dialect: Dialect,
+    quote_style: Option<char>,
+    case_folding: Option<CaseFolding>,
+    table_qualifier: Option<TableQualifier>,
+    case_insensitive_functions: bool,
+    cast_to_text: bool,
+    pretty_print: bool,
+    always_quote: bool,
+    filter_pushdown: bool,
+    default_db: Option<String>,
+    default_schema: Option<String>,
+    allowed_tables: Option<Arc<[TablePattern]>>,
+    denied_tables: Arc<[TablePattern]>,
+    cross_database: CrossDatabase,
+    sensitive_columns: Arc<[SensitiveColumns]>,
+    error_spans: bool,
+    limits: Limits,
+    identifier_rules: IdentifierRules,
+    feature_policy: FeaturePolicy,
+    qualification: Qualification,
+    table_rewriter: Option<TableRewriter>,
+    on_reject: Option<OnReject>,
+}
+
+// the rejection hook, which can't derive Debug as a closure
+#[derive(Clone)]
+struct OnReject(RejectionHook);
+
+impl fmt::Debug for OnReject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RejectionHook")
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            dialect: Dialect::default(),
+            quote_style: None,
+            case_folding: None,
+            table_qualifier: None,
+            case_insensitive_functions: false,
+            cast_to_text: true,
+            pretty_print: false,
+            always_quote: false,
+            filter_pushdown: false,
+            default_db: None,
+            default_schema: None,
+            allowed_tables: None,
+            denied_tables: Arc::new([]),
+            cross_database: CrossDatabase::default(),
+            sensitive_columns: Arc::new([]),
+            error_spans: false,
+            limits: Limits::default(),
+            identifier_rules: IdentifierRules::default(),
+            feature_policy: FeaturePolicy::default(),
+            qualification: Qualification::default(),
+            table_rewriter: None,
+            on_reject: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Creates the default options: generic dialect, no quoting of generated identifiers and
+    /// aggregation results cast to text.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the dialect the query is written in.
+    #[must_use]
+    pub const fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Sets the quote character used for identifiers in the generated queries (e.g. `"` for
+    /// PostgreSQL, `` ` `` for MySQL).
+    #[must_use]
+    pub const fn with_quote_style(mut self, quote_style: Option<char>) -> Self {
+        self.quote_style = quote_style;
+        self
+    }
+
+    /// Overrides the case folding of unquoted identifiers implied by the dialect.
+    #[must_use]
+    pub const fn with_case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.case_folding = Some(case_folding);
+        self
+    }
+
+    /// Overrides what the first part of a two-part table name refers to, by default a database
+    /// in MySQL and BigQuery and a schema otherwise (see [`Dialect::table_qualifier`]).
+    #[must_use]
+    pub const fn with_table_qualifier(mut self, table_qualifier: TableQualifier) -> Self {
+        self.table_qualifier = Some(table_qualifier);
+        self
+    }
+
+    /// Sets whether quoted function names match the supported functions whatever their case
+    /// (e.g. `"SUM"(x)` in a dialect that folds to lowercase, as emitted by some Oracle and
+    /// Snowflake tools).
+    ///
+    /// Otherwise a quoted name is case-sensitive like any quoted identifier: it only matches if
+    /// it's spelled in the folded case of the dialect.
+    #[must_use]
+    pub const fn with_case_insensitive_functions(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive_functions = case_insensitive;
+        self
+    }
+
+    /// Sets whether the data aggregation query wraps the aggregation in `CAST(... AS TEXT)`.
+    ///
+    /// Callers that consume typed result sets can disable it to get the plain aggregation.
+    #[must_use]
+    pub const fn with_cast_to_text(mut self, cast_to_text: bool) -> Self {
+        self.cast_to_text = cast_to_text;
+        self
+    }
+
+    /// Sets whether the derived queries are laid out on multiple lines, with each clause on its
+    /// own line and its items indented.
+    #[must_use]
+    pub const fn with_pretty_print(mut self, pretty_print: bool) -> Self {
+        self.pretty_print = pretty_print;
+        self
+    }
+
+    /// Sets whether every identifier in the derived queries is quoted, with the dialect's quote
+    /// character unless a quote style is set explicitly.
+    ///
+    /// This keeps the generated SQL valid for mixed-case and reserved-word names (e.g. a column
+    /// named `order`).
+    #[must_use]
+    pub const fn with_always_quote(mut self, always_quote: bool) -> Self {
+        self.always_quote = always_quote;
+        self
+    }
+
+    /// Sets whether the filter is pushed down into the data extraction query as a WHERE clause,
+    /// so that only the matching rows are extracted.
+    #[must_use]
+    pub const fn with_filter_pushdown(mut self, filter_pushdown: bool) -> Self {
+        self.filter_pushdown = filter_pushdown;
+        self
+    }
+
+    /// Sets the database assumed for tables that don't specify one, as it's stored (i.e. already
+    /// case folded).
+    #[must_use]
+    pub fn with_default_db(mut self, db: impl Into<String>) -> Self {
+        self.default_db = Some(db.into());
+        self
+    }
+
+    /// Sets the schema assumed for tables that don't specify one, as it's stored (i.e. already
+    /// case folded).
+    #[must_use]
+    pub fn with_default_schema(mut self, schema: impl Into<String>) -> Self {
+        self.default_schema = Some(schema.into());
+        self
+    }
+
+    /// Restricts the tables that can be queried to the ones matching any of `patterns`; the
+    /// others are rejected with [`crate::error::ParseError::Forbidden`].
+    #[must_use]
+    pub fn with_allowed_tables(mut self, patterns: Vec<TablePattern>) -> Self {
+        self.allowed_tables = Some(patterns.into());
+        self
+    }
+
+    /// Forbids querying the tables matching any of `patterns`, even if they're allowed.
+    #[must_use]
+    pub fn with_denied_tables(mut self, patterns: Vec<TablePattern>) -> Self {
+        self.denied_tables = patterns.into();
+        self
+    }
+
+    /// Sets whether the tables of other databases than the session one (see
+    /// [`Self::with_default_db`]) can be queried; the rejected ones fail with
+    /// [`crate::error::ErrorCode::CrossDatabase`].
+    #[must_use]
+    pub fn with_cross_database(mut self, cross_database: CrossDatabase) -> Self {
+        self.cross_database = cross_database;
+        self
+    }
+
+    /// Declares columns that may never be aggregated nor filtered on: queries using them are
+    /// rejected with [`crate::error::ParseError::Forbidden`].
+    #[must_use]
+    pub fn with_sensitive_columns(mut self, sensitive_columns: Vec<SensitiveColumns>) -> Self {
+        self.sensitive_columns = sensitive_columns.into();
+        self
+    }
+
+    /// Sets whether the errors about malformed queries and unsupported constructs are located in
+    /// the query (see [`crate::error::ParseError::span`]), e.g. to underline the offending clause.
+    ///
+    /// Locating the errors requires tokenizing the query once more.
+    #[must_use]
+    pub const fn with_error_spans(mut self, error_spans: bool) -> Self {
+        self.error_spans = error_spans;
+        self
+    }
+
+    /// Sets the limits on the size of the queries, checked before they're parsed (see
+    /// [`Limits`]). The default ones are applied otherwise.
+    #[must_use]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the rules the names of the queries must follow (see [`IdentifierRules`]).
+    #[must_use]
+    pub const fn with_identifier_rules(mut self, identifier_rules: IdentifierRules) -> Self {
+        self.identifier_rules = identifier_rules;
+        self
+    }
+
+    /// Sets the clauses that are accepted although the metadata doesn't represent them (see
+    /// [`FeaturePolicy`]). None are by default.
+    #[must_use]
+    pub const fn with_feature_policy(mut self, feature_policy: FeaturePolicy) -> Self {
+        self.feature_policy = feature_policy;
+        self
+    }
+
+    /// Sets how strictly the qualified columns must match the table of the FROM clause (see
+    /// [`Qualification`]), [`Qualification::Standard`] by default.
+    #[must_use]
+    pub const fn with_qualification(mut self, qualification: Qualification) -> Self {
+        self.qualification = qualification;
+        self
+    }
+
+    /// Sets the function that maps the table of the query to the one read by the derived queries
+    /// (see [`TableRewriter`]).
+    ///
+    /// It's applied once the table is qualified with the defaults and the catalog and its access
+    /// is checked, so the policies apply to the table as queried. The metadata then holds the
+    /// rewritten table.
+    #[must_use]
+    pub const fn with_table_rewriter(mut self, table_rewriter: TableRewriter) -> Self {
+        self.table_rewriter = Some(table_rewriter);
+        self
+    }
+
+    /// Sets the function called when a query is rejected for an unsupported construct (see
+    /// [`RejectionHook`]).
+    ///
+    /// It's called once per rejected construct by the parsing functions of
+    /// [`crate::query_metadata::QueryMetadata`], including the ones collected by the lenient
+    /// parses; a result memoized by [`crate::cache::CachedParser`] isn't reported again.
+    #[must_use]
+    pub fn with_on_reject(mut self, on_reject: RejectionHook) -> Self {
+        self.on_reject = Some(OnReject(on_reject));
+        self
+    }
+
+    /// The dialect the query is written in.
+    #[must_use]
+    pub const fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// The quote character used for identifiers in the generated queries.
+    #[must_use]
+    pub const fn quote_style(&self) -> Option<char> {
+        self.quote_style
+    }
+
+    /// Whether quoted function names match the supported functions whatever their case.
+    #[must_use]
+    pub const fn case_insensitive_functions(&self) -> bool {
+        self.case_insensitive_functions
+    }
+
+    /// Whether the data aggregation query casts the aggregation result to text.
+    #[must_use]
+    pub const fn cast_to_text(&self) -> bool {
+        self.cast_to_text
+    }
+
+    /// Whether the derived queries are laid out on multiple lines.
+    #[must_use]
+    pub const fn pretty_print(&self) -> bool {
+        self.pretty_print
+    }
+
+    /// Whether every identifier in the derived queries is quoted.
+    #[must_use]
+    pub const fn always_quote(&self) -> bool {
+        self.always_quote
+    }
+
+    /// Whether the filter is pushed down into the data extraction query.
+    #[must_use]
+    pub const fn filter_pushdown(&self) -> bool {
+        self.filter_pushdown
+    }
+
+    /// The database assumed for tables that don't specify one.
+    #[must_use]
+    pub fn default_db(&self) -> Option<&str> {
+        self.default_db.as_deref()
+    }
+
+    /// The schema assumed for tables that don't specify one.
+    #[must_use]
+    pub fn default_schema(&self) -> Option<&str> {
+        self.default_schema.as_deref()
+    }
+
+    /// The patterns of the tables that can be queried, all of them if `None`.
+    #[must_use]
+    pub fn allowed_tables(&self) -> Option<&[TablePattern]> {
+        self.allowed_tables.as_deref()
+    }
+
+    /// The patterns of the tables that can't be queried.
+    #[must_use]
+    pub fn denied_tables(&self) -> &[TablePattern] {
+        &self.denied_tables
+    }
+
+    /// Whether the tables of other databases than the session one can be queried.
+    #[must_use]
+    pub const fn cross_database(&self) -> &CrossDatabase {
+        &self.cross_database
+    }
+
+    /// The columns that may never be aggregated nor filtered on.
+    #[must_use]
+    pub fn sensitive_columns(&self) -> &[SensitiveColumns] {
+        &self.sensitive_columns
+    }
+
+    /// Whether the errors are located in the query.
+    #[must_use]
+    pub const fn error_spans(&self) -> bool {
+        self.error_spans
+    }
+
+    /// The limits on the size of the queries.
+    #[must_use]
+    pub const fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// The rules the names of the queries must follow.
+    #[must_use]
+    pub const fn identifier_rules(&self) -> IdentifierRules {
+        self.identifier_rules
+    }
+
+    /// The clauses that are accepted although the metadata doesn't represent them.
+    #[must_use]
+    pub const fn feature_policy(&self) -> FeaturePolicy {
+        self.feature_policy
+    }
+
+    /// How strictly the qualified columns must match the table of the FROM clause.
+    #[must_use]
+    pub const fn qualification(&self) -> Qualification {
+        self.qualification
+    }
+
+    /// The function that maps the table of the query to the one read by the derived queries.
+    #[must_use]
+    pub const fn table_rewriter(&self) -> Option<TableRewriter> {
+        self.table_rewriter
+    }
+
+    /// The function called when a query is rejected for an unsupported construct.
+    #[must_use]
+    pub fn on_reject(&self) -> Option<&RejectionHook> {
+        self.on_reject.as_ref().map(|OnReject(on_reject)| on_reject)
+    }
+
+    // the same options without the rejection hook, e.g. for a parse whose errors are reported
+    // separately
+    pub(crate) fn without_on_reject(&self) -> Self {
+        Self {
+            on_reject: None,
+            ..self.clone()
+        }
+    }
+
+    /// The quote character actually used for identifiers in the derived queries: the explicit
+    /// quote style if any, otherwise the dialect's one when every identifier must be quoted.
+    #[must_use]
+    pub const fn effective_quote_style(&self) -> Option<char> {
+        match self.quote_style {
+            Some(quote_style) => Some(quote_style),
+            None if self.always_quote => Some(self.dialect.identifier_quote()),
+            None => None,
+        }
+    }
+
+    /// The case folding applied to unquoted identifiers: the explicit override if any,
+    /// otherwise the dialect's own.
+    #[must_use]
+    pub fn case_folding(&self) -> CaseFolding {
+        self.case_folding
+            .unwrap_or_else(|| self.dialect.case_folding())
+    }
+
+    /// What the first part of a two-part table name refers to: the explicit override if any,
+    /// otherwise the dialect's own.
+    #[must_use]
+    pub fn table_qualifier(&self) -> TableQualifier {
+        self.table_qualifier
+            .unwrap_or_else(|| self.dialect.table_qualifier())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseOptions;
+    use crate::{
+        dialect::{CaseFolding, Dialect},
+        policy::{SensitiveColumns, TablePattern},
+    };
+
+    #[test]
+    fn case_folding_override() {
+        let options = ParseOptions::new().with_dialect(Dialect::Snowflake);
+        assert_eq!(options.case_folding(), CaseFolding::Uppercase);
+
+        let options = options.with_case_folding(CaseFolding::Lowercase);
+        assert_eq!(options.case_folding(), CaseFolding::Lowercase);
+        assert_eq!(options.dialect(), Dialect::Snowflake);
+    }
+
+    #[test]
+    fn clones_share_lists() {
+        let pattern = || TablePattern {
+            table: "users".to_string(),
+            ..TablePattern::default()
+        };
+        let options = ParseOptions::new()
+            .with_allowed_tables(vec![pattern()])
+            .with_denied_tables(vec![pattern()])
+            .with_sensitive_columns(vec![SensitiveColumns {
+                table: pattern(),
+                columns: vec!["ssn".to_string()],
+            }]);
+        let clone = options.clone();
+        assert!(std::ptr::eq(
+            options.allowed_tables().unwrap(),
+            clone.allowed_tables().unwrap()
+        ));
+        assert!(std::ptr::eq(options.denied_tables(), clone.denied_tables()));
+        assert!(std::ptr::eq(
+            options.sensitive_columns(),
+            clone.sensitive_columns()
+        ));
+    }
+}
@@ -1,18 +1,97 @@
 use sqlparser::ast;
 
-use crate::{error::ParseError, unsupported};
+use crate::{
+    dialect::Dialect,
+    error::{ParseError, UnsupportedFeature},
+    limits::Limits,
+    policy::FeaturePolicy,
+    query_metadata::QueryMetadata,
+    support::parse_statements_within,
+    table::TableIdentWithAlias,
+    unsupported,
+};
 
-pub(crate) struct DestructuredQuery<'a> {
-    pub projection: &'a [ast::SelectItem], //i.e. select clause
-    pub from: &'a [ast::TableWithJoins],   //i.e. from clause
-    pub selection: Option<&'a ast::Expr>,  //i.e. where clause
+raw_ast! {
+    /// The clauses of a plain SELECT query, the lower-level step of the extraction of the metadata.
+    ///
+    /// A query is destructured only if it has none of the clauses that aren't supported (e.g. CTEs,
+    /// GROUP BY, ORDER BY, LIMIT, DISTINCT, window definitions): only the SELECT, FROM and WHERE
+    /// clauses are left, with the GROUP BY clause of a time bucket, and they're not checked yet
+    /// (see [`TableIdentWithAlias::extract`] for the FROM clause).
+    #[derive(Clone, Copy, Debug)]
+    pub struct DestructuredQuery<'a> {
+        /// The items of the SELECT clause.
+        pub projection: &'a [ast::SelectItem],
+        /// The tables of the FROM clause.
+        pub from: &'a [ast::TableWithJoins],
+        /// The condition of the WHERE clause, if any.
+        pub selection: Option<&'a ast::Expr>,
+        /// The expression of the GROUP BY clause if the query is grouped by a time bucket (see
+        /// [`FeaturePolicy::allow_time_buckets`]), empty otherwise. It's not checked yet (see
+        /// [`crate::time_bucket::TimeBucket`]).
+        pub group_by: &'a [ast::Expr],
+    }
+}
+
+/// Checks that `sql_query` is a single SELECT statement over a single table, without
+/// unsupported clauses.
+///
+/// The metadata isn't extracted, so the aggregation and the filter aren't checked. The default
+/// [`Limits`] apply.
+pub fn validate(sql_query: &str, dialect: Dialect) -> Result<(), ParseError> {
+    let statements = parse_statements_within(sql_query, dialect, Limits::default())?;
+    let query = QueryMetadata::extract_select_query(&statements)?;
+    let DestructuredQuery { from, .. } = DestructuredQuery::destructure(query)?;
+    TableIdentWithAlias::extract(from, dialect.case_folding())?;
+    Ok(())
 }
 
 impl<'a> DestructuredQuery<'a> {
+    /// Destructures `query`, failing at the first unsupported clause, or if it isn't a plain
+    /// SELECT (e.g. a set operation or VALUES).
     pub fn destructure(query: &'a ast::Query) -> Result<Self, ParseError> {
+        Self::destructure_with_policy(query, FeaturePolicy::default())
+    }
+
+    /// Like [`Self::destructure`], but the clauses allowed by `policy` are accepted (and left
+    /// out of the destructured query).
+    pub fn destructure_with_policy(
+        query: &'a ast::Query,
+        policy: FeaturePolicy,
+    ) -> Result<Self, ParseError> {
+        let mut unsupported = Vec::new();
+        let destructured = Self::destructure_lenient_with_policy(query, policy, &mut unsupported);
+        //report the first unsupported clause, as if the query was checked clause by clause
+        unsupported.into_iter().next().map_or(destructured, Err)
+    }
+
+    /// Like [`Self::destructure`], but the unsupported clauses are collected in `unsupported`
+    /// and ignored, failing only if the query isn't a plain SELECT.
+    #[cfg_attr(not(feature = "raw-ast"), allow(dead_code))]
+    pub fn destructure_lenient(
+        query: &'a ast::Query,
+        unsupported: &mut Vec<ParseError>,
+    ) -> Result<Self, ParseError> {
+        Self::destructure_lenient_with_policy(query, FeaturePolicy::default(), unsupported)
+    }
+
+    pub(crate) fn destructure_lenient_with_policy(
+        query: &'a ast::Query,
+        policy: FeaturePolicy,
+        unsupported: &mut Vec<ParseError>,
+    ) -> Result<Self, ParseError> {
+        Self::check_query_clauses(query, policy, unsupported);
+        Self::destructure_set_expr(&query.body, policy, unsupported)
+    }
+
+    fn check_query_clauses(
+        query: &ast::Query,
+        policy: FeaturePolicy,
+        unsupported: &mut Vec<ParseError>,
+    ) {
         let ast::Query {
             with,
-            body,
+            body: _,
             order_by,
             limit,
             offset,
@@ -23,65 +102,115 @@ impl<'a> DestructuredQuery<'a> {
         } = query;
 
         if with.is_some() {
-            return Err(unsupported!("CTEs (i.e., WITH clause).".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Cte,
+                "CTEs (i.e., WITH clause).".to_string()
+            ));
         }
-        if !order_by.is_empty() {
-            return Err(unsupported!("ORDER BY.".to_string()));
+        if !order_by.is_empty() && !policy.allow_order_by {
+            unsupported.push(unsupported!(
+                UnsupportedFeature::OrderBy,
+                "ORDER BY.".to_string()
+            ));
         }
-        if limit.is_some() {
-            return Err(unsupported!("LIMIT.".to_string()));
+        //a single aggregation yields a single row, which only a LIMIT 0 (or an expression) drops
+        if limit
+            .as_ref()
+            .is_some_and(|limit| !(policy.allow_limit && is_positive_number(limit)))
+        {
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Limit,
+                "LIMIT.".to_string()
+            ));
         }
         if offset.is_some() {
-            return Err(unsupported!("OFFSET.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Offset,
+                "OFFSET.".to_string()
+            ));
         }
         if fetch.is_some() {
-            return Err(unsupported!("FETCH.".to_string()));
-        }
-        if !locks.is_empty() {
-            return Err(unsupported!(format!(
-                "locking clauses (i.e., {}).",
-                locks
-                    .iter()
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            )));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Fetch,
+                "FETCH.".to_string()
+            ));
+        }
+        if !locks.is_empty() && !policy.allow_locking {
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Locking,
+                format!(
+                    "locking clauses (i.e., {}).",
+                    locks
+                        .iter()
+                        .map(std::string::ToString::to_string)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            ));
         }
         if !limit_by.is_empty() {
-            return Err(unsupported!(format!(
-                "limit by clauses (i.e., {}).",
-                limit_by
-                    .iter()
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            )));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::LimitBy,
+                format!(
+                    "limit by clauses (i.e., {}).",
+                    limit_by
+                        .iter()
+                        .map(std::string::ToString::to_string)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            ));
         }
         if for_clause.is_some() {
-            return Err(unsupported!("FOR clause.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::For,
+                "FOR clause.".to_string()
+            ));
         }
-
-        Self::destructure_set_expr(body)
     }
 
-    fn destructure_set_expr(set_expr: &'a ast::SetExpr) -> Result<Self, ParseError> {
-        match set_expr {
-            ast::SetExpr::Select(select) => Self::destructure_select(select),
-            ast::SetExpr::Query(query) => Self::destructure(query),
-            ast::SetExpr::SetOperation { op, .. } => {
-                Err(unsupported!(format!("set operations (i.e., {op}).")))
-            }
-            ast::SetExpr::Values(_) => Err(unsupported!("VALUES.".to_string())),
-            ast::SetExpr::Insert(_) | ast::SetExpr::Update(_) => Err(unsupported!(
-                "statements different from single SELECT statement.".to_string()
-            )),
-            ast::SetExpr::Table(_) => Err(unsupported!(
-                "TABLE (i.e., SELECT * FROM table_name).".to_string()
-            )),
+    //the nested queries (e.g. `((SELECT ...))`) are unwrapped in a loop rather than recursively,
+    //so that no nesting can exhaust the stack
+    fn destructure_set_expr(
+        mut set_expr: &'a ast::SetExpr,
+        policy: FeaturePolicy,
+        unsupported: &mut Vec<ParseError>,
+    ) -> Result<Self, ParseError> {
+        loop {
+            return match set_expr {
+                ast::SetExpr::Select(select) => {
+                    Ok(Self::destructure_select(select, policy, unsupported))
+                }
+                ast::SetExpr::Query(query) => {
+                    Self::check_query_clauses(query, policy, unsupported);
+                    set_expr = &query.body;
+                    continue;
+                }
+                ast::SetExpr::SetOperation { op, .. } => Err(unsupported!(
+                    UnsupportedFeature::SetOperation(op.to_string()),
+                    format!("set operations (i.e., {op}).")
+                )),
+                ast::SetExpr::Values(_) => Err(unsupported!(
+                    UnsupportedFeature::Values,
+                    "VALUES.".to_string()
+                )),
+                ast::SetExpr::Insert(_) | ast::SetExpr::Update(_) => Err(unsupported!(
+                    UnsupportedFeature::Statement,
+                    "statements different from single SELECT statement.".to_string()
+                )),
+                ast::SetExpr::Table(_) => Err(unsupported!(
+                    UnsupportedFeature::TableStatement,
+                    "TABLE (i.e., SELECT * FROM table_name).".to_string()
+                )),
+            };
         }
     }
 
-    fn destructure_select(select: &'a ast::Select) -> Result<Self, ParseError> {
+    fn destructure_select(
+        select: &'a ast::Select,
+        policy: FeaturePolicy,
+        unsupported: &mut Vec<ParseError>,
+    ) -> Self {
         let ast::Select {
             distinct,
             top,
@@ -99,51 +228,95 @@ impl<'a> DestructuredQuery<'a> {
             named_window,
         } = select;
 
-        if distinct.is_some() {
-            return Err(unsupported!("DISTINCT.".to_string()));
+        if distinct.is_some() && !policy.allow_distinct {
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Distinct,
+                "DISTINCT.".to_string()
+            ));
         }
         if top.is_some() {
-            return Err(unsupported!("TOP.".to_string()));
+            unsupported.push(unsupported!(UnsupportedFeature::Top, "TOP.".to_string()));
         }
         if into.is_some() {
-            return Err(unsupported!("SELECT INTO.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::SelectInto,
+                "SELECT INTO.".to_string()
+            ));
         }
         if !lateral_views.is_empty() {
-            return Err(unsupported!("LATERAL VIEW.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::LateralView,
+                "LATERAL VIEW.".to_string()
+            ));
         }
-        match group_by {
-            ast::GroupByExpr::All => return Err(unsupported!("ALL.".to_string())),
+        //a single expression may be a time bucket, which is checked once the table is known
+        let group_by = match group_by {
+            ast::GroupByExpr::All => {
+                unsupported.push(unsupported!(
+                    UnsupportedFeature::GroupBy,
+                    "ALL.".to_string()
+                ));
+                &[]
+            }
             ast::GroupByExpr::Expressions(exp) => {
-                if !exp.is_empty() {
-                    return Err(unsupported!("GROUP BY.".to_string()));
+                if exp.len() > 1 || !(exp.is_empty() || policy.allow_time_buckets) {
+                    unsupported.push(unsupported!(
+                        UnsupportedFeature::GroupBy,
+                        "GROUP BY.".to_string()
+                    ));
+                    &[]
+                } else {
+                    exp.as_slice()
                 }
             }
-        }
+        };
         if !cluster_by.is_empty() {
-            return Err(unsupported!("CLUSTER BY.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::ClusterBy,
+                "CLUSTER BY.".to_string()
+            ));
         }
         if !distribute_by.is_empty() {
-            return Err(unsupported!("DISTRIBUTE BY.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::DistributeBy,
+                "DISTRIBUTE BY.".to_string()
+            ));
         }
         if !sort_by.is_empty() {
-            return Err(unsupported!("SORT BY.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::SortBy,
+                "SORT BY.".to_string()
+            ));
         }
         if having.is_some() {
-            return Err(unsupported!("HAVING.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Having,
+                "HAVING.".to_string()
+            ));
         }
         if qualify.is_some() {
-            return Err(unsupported!("QUALIFY.".to_string()));
+            unsupported.push(unsupported!(
+                UnsupportedFeature::Qualify,
+                "QUALIFY.".to_string()
+            ));
         }
         if !named_window.is_empty() {
-            return Err(unsupported!(
+            unsupported.push(unsupported!(
+                UnsupportedFeature::NamedWindow,
                 "AS (OVER (PARTITION BY .. ORDER BY .. etc.)).".to_string()
             ));
         }
 
-        Ok(Self {
+        Self {
             projection,
             from,
             selection: selection.as_ref(),
-        })
+            group_by,
+        }
     }
 }
+
+fn is_positive_number(expr: &ast::Expr) -> bool {
+    matches!(expr, ast::Expr::Value(ast::Value::Number(number, _))
+        if number.parse::<u64>().is_ok_and(|number| number > 0))
+}
@@ -0,0 +1,788 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::dialect::{
+    self as sql_dialect, BigQueryDialect, GenericDialect, MySqlDialect, PostgreSqlDialect,
+    SnowflakeDialect,
+};
+use utoipa::ToSchema;
+
+/// The SQL dialect a query is written in.
+///
+/// The dialect drives both the syntax accepted by the underlying SQL parser and the way unquoted
+/// identifiers are folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum Dialect {
+    /// A permissive dialect that folds unquoted identifiers to lowercase.
+    #[default]
+    Generic,
+    /// PostgreSQL.
+    PostgreSql,
+    /// MySQL, which quotes identifiers with backticks.
+    MySql,
+    /// Snowflake, which folds unquoted identifiers to uppercase.
+    Snowflake,
+    /// BigQuery, which quotes identifiers with backticks and keeps their case.
+    BigQuery,
+}
+
+impl Dialect {
+    /// Returns how unquoted identifiers are folded in this dialect.
+    #[must_use]
+    pub const fn case_folding(self) -> CaseFolding {
+        match self {
+            Self::Generic | Self::PostgreSql | Self::MySql => CaseFolding::Lowercase,
+            Self::Snowflake => CaseFolding::Uppercase,
+            Self::BigQuery => CaseFolding::Preserve,
+        }
+    }
+
+    /// Returns the character used to quote identifiers in this dialect.
+    #[must_use]
+    pub const fn identifier_quote(self) -> char {
+        match self {
+            Self::Generic | Self::PostgreSql | Self::Snowflake => '"',
+            Self::MySql | Self::BigQuery => '`',
+        }
+    }
+
+    /// Returns what the first part of a two-part table name refers to in this dialect: a
+    /// database in MySQL and BigQuery (e.g. `mydb.events`), a schema otherwise.
+    #[must_use]
+    pub const fn table_qualifier(self) -> TableQualifier {
+        match self {
+            Self::Generic | Self::PostgreSql | Self::Snowflake => TableQualifier::Schema,
+            Self::MySql | Self::BigQuery => TableQualifier::Database,
+        }
+    }
+
+    /// Returns `true` if `word` is a reserved keyword in this dialect, whatever its case, i.e. it
+    /// must be quoted to be used as an identifier (e.g. `order`). The generic dialect reserves
+    /// the keywords of all the other ones, so that its queries can be sent to any of them.
+    #[must_use]
+    pub fn is_reserved_keyword(self, word: &str) -> bool {
+        let word = word.to_ascii_uppercase();
+        let reserved = |keywords: &[&str]| keywords.binary_search(&word.as_str()).is_ok();
+        match self {
+            Self::Generic => [
+                POSTGRESQL_KEYWORDS,
+                MYSQL_KEYWORDS,
+                SNOWFLAKE_KEYWORDS,
+                BIGQUERY_KEYWORDS,
+            ]
+            .into_iter()
+            .any(reserved),
+            Self::PostgreSql => reserved(POSTGRESQL_KEYWORDS),
+            Self::MySql => reserved(MYSQL_KEYWORDS),
+            Self::Snowflake => reserved(SNOWFLAKE_KEYWORDS),
+            Self::BigQuery => reserved(BIGQUERY_KEYWORDS),
+        }
+    }
+
+    /// Returns `true` if tables can be read as of a point in time in this dialect (i.e.
+    /// `FOR SYSTEM_TIME AS OF`, see [`crate::table::TableVersion`]).
+    #[must_use]
+    pub const fn supports_table_versions(self) -> bool {
+        matches!(self, Self::BigQuery)
+    }
+
+    raw_ast! {
+        /// Returns the dialect of the underlying SQL parser, e.g. to tokenize the queries the same
+        /// way they're parsed.
+        #[must_use]
+        pub fn parser_dialect(self) -> Box<dyn sql_dialect::Dialect> {
+            match self {
+                Self::Generic => Box::new(GenericDialect {}),
+                Self::PostgreSql => Box::new(PostgreSqlDialect {}),
+                Self::MySql => Box::new(MySqlDialect {}),
+                Self::Snowflake => Box::new(SnowflakeDialect),
+                Self::BigQuery => Box::new(BigQueryDialect),
+            }
+        }
+    }
+}
+
+/// How unquoted identifiers are normalized before being compared or stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum CaseFolding {
+    /// Fold unquoted identifiers to lowercase, like PostgreSQL does.
+    #[default]
+    Lowercase,
+    /// Fold unquoted identifiers to uppercase, like Snowflake does.
+    Uppercase,
+    /// Keep unquoted identifiers as written, like BigQuery does.
+    Preserve,
+}
+
+impl CaseFolding {
+    /// Folds `value` as if it were an unquoted identifier.
+    #[must_use]
+    pub fn fold(self, value: &str) -> String {
+        match self {
+            Self::Lowercase => value.to_ascii_lowercase(),
+            Self::Uppercase => value.to_ascii_uppercase(),
+            Self::Preserve => value.to_string(),
+        }
+    }
+
+    /// Returns `true` if folding `value` would leave it unchanged.
+    #[must_use]
+    pub fn is_folded(self, value: &str) -> bool {
+        match self {
+            Self::Lowercase => !value.chars().any(|c| c.is_ascii_uppercase()),
+            Self::Uppercase => !value.chars().any(|c| c.is_ascii_lowercase()),
+            Self::Preserve => true,
+        }
+    }
+}
+
+// the reserved keywords of PostgreSQL, see
+// https://www.postgresql.org/docs/current/sql-keywords-appendix.html
+const POSTGRESQL_KEYWORDS: &[&str] = &[
+    "ALL",
+    "ANALYSE",
+    "ANALYZE",
+    "AND",
+    "ANY",
+    "ARRAY",
+    "AS",
+    "ASC",
+    "ASYMMETRIC",
+    "AUTHORIZATION",
+    "BINARY",
+    "BOTH",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLLATION",
+    "COLUMN",
+    "CONCURRENTLY",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT_CATALOG",
+    "CURRENT_DATE",
+    "CURRENT_ROLE",
+    "CURRENT_SCHEMA",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "CURRENT_USER",
+    "DEFAULT",
+    "DEFERRABLE",
+    "DESC",
+    "DISTINCT",
+    "DO",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "FALSE",
+    "FETCH",
+    "FOR",
+    "FOREIGN",
+    "FREEZE",
+    "FROM",
+    "FULL",
+    "GRANT",
+    "GROUP",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INITIALLY",
+    "INNER",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "ISNULL",
+    "JOIN",
+    "LATERAL",
+    "LEADING",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+    "NATURAL",
+    "NOT",
+    "NOTNULL",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "ONLY",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVERLAPS",
+    "PLACING",
+    "PRIMARY",
+    "REFERENCES",
+    "RETURNING",
+    "RIGHT",
+    "SELECT",
+    "SESSION_USER",
+    "SIMILAR",
+    "SOME",
+    "SYMMETRIC",
+    "TABLE",
+    "TABLESAMPLE",
+    "THEN",
+    "TO",
+    "TRAILING",
+    "TRUE",
+    "UNION",
+    "UNIQUE",
+    "USER",
+    "USING",
+    "VARIADIC",
+    "VERBOSE",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+// the reserved keywords of MySQL 8, see
+// https://dev.mysql.com/doc/refman/8.0/en/keywords.html
+const MYSQL_KEYWORDS: &[&str] = &[
+    "ACCESSIBLE",
+    "ADD",
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ASENSITIVE",
+    "BEFORE",
+    "BETWEEN",
+    "BIGINT",
+    "BINARY",
+    "BLOB",
+    "BOTH",
+    "BY",
+    "CALL",
+    "CASCADE",
+    "CASE",
+    "CHANGE",
+    "CHAR",
+    "CHARACTER",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "CONDITION",
+    "CONSTRAINT",
+    "CONTINUE",
+    "CONVERT",
+    "CREATE",
+    "CROSS",
+    "CUBE",
+    "CUME_DIST",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "CURRENT_USER",
+    "CURSOR",
+    "DATABASE",
+    "DATABASES",
+    "DAY_HOUR",
+    "DAY_MICROSECOND",
+    "DAY_MINUTE",
+    "DAY_SECOND",
+    "DEC",
+    "DECIMAL",
+    "DECLARE",
+    "DEFAULT",
+    "DELAYED",
+    "DELETE",
+    "DENSE_RANK",
+    "DESC",
+    "DESCRIBE",
+    "DETERMINISTIC",
+    "DISTINCT",
+    "DISTINCTROW",
+    "DIV",
+    "DOUBLE",
+    "DROP",
+    "DUAL",
+    "EACH",
+    "ELSE",
+    "ELSEIF",
+    "EMPTY",
+    "ENCLOSED",
+    "ESCAPED",
+    "EXCEPT",
+    "EXISTS",
+    "EXIT",
+    "EXPLAIN",
+    "FALSE",
+    "FETCH",
+    "FIRST_VALUE",
+    "FLOAT",
+    "FLOAT4",
+    "FLOAT8",
+    "FOR",
+    "FORCE",
+    "FOREIGN",
+    "FROM",
+    "FULLTEXT",
+    "FUNCTION",
+    "GENERATED",
+    "GET",
+    "GRANT",
+    "GROUP",
+    "GROUPING",
+    "GROUPS",
+    "HAVING",
+    "HIGH_PRIORITY",
+    "HOUR_MICROSECOND",
+    "HOUR_MINUTE",
+    "HOUR_SECOND",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INDEX",
+    "INFILE",
+    "INNER",
+    "INOUT",
+    "INSENSITIVE",
+    "INSERT",
+    "INT",
+    "INT1",
+    "INT2",
+    "INT3",
+    "INT4",
+    "INT8",
+    "INTEGER",
+    "INTERSECT",
+    "INTERVAL",
+    "INTO",
+    "IO_AFTER_GTIDS",
+    "IO_BEFORE_GTIDS",
+    "IS",
+    "ITERATE",
+    "JOIN",
+    "JSON_TABLE",
+    "KEY",
+    "KEYS",
+    "KILL",
+    "LAG",
+    "LAST_VALUE",
+    "LATERAL",
+    "LEAD",
+    "LEADING",
+    "LEAVE",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LINEAR",
+    "LINES",
+    "LOAD",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+    "LOCK",
+    "LONG",
+    "LONGBLOB",
+    "LONGTEXT",
+    "LOOP",
+    "LOW_PRIORITY",
+    "MASTER_BIND",
+    "MASTER_SSL_VERIFY_SERVER_CERT",
+    "MATCH",
+    "MAXVALUE",
+    "MEDIUMBLOB",
+    "MEDIUMINT",
+    "MEDIUMTEXT",
+    "MIDDLEINT",
+    "MINUTE_MICROSECOND",
+    "MINUTE_SECOND",
+    "MOD",
+    "MODIFIES",
+    "NATURAL",
+    "NOT",
+    "NO_WRITE_TO_BINLOG",
+    "NTH_VALUE",
+    "NTILE",
+    "NULL",
+    "NUMERIC",
+    "OF",
+    "ON",
+    "OPTIMIZE",
+    "OPTIMIZER_COSTS",
+    "OPTION",
+    "OPTIONALLY",
+    "OR",
+    "ORDER",
+    "OUT",
+    "OUTER",
+    "OUTFILE",
+    "OVER",
+    "PARTITION",
+    "PERCENT_RANK",
+    "PRECISION",
+    "PRIMARY",
+    "PROCEDURE",
+    "PURGE",
+    "RANGE",
+    "RANK",
+    "READ",
+    "READS",
+    "READ_WRITE",
+    "REAL",
+    "RECURSIVE",
+    "REFERENCES",
+    "REGEXP",
+    "RELEASE",
+    "RENAME",
+    "REPEAT",
+    "REPLACE",
+    "REQUIRE",
+    "RESIGNAL",
+    "RESTRICT",
+    "RETURN",
+    "REVOKE",
+    "RIGHT",
+    "RLIKE",
+    "ROW",
+    "ROWS",
+    "ROW_NUMBER",
+    "SCHEMA",
+    "SCHEMAS",
+    "SECOND_MICROSECOND",
+    "SELECT",
+    "SENSITIVE",
+    "SEPARATOR",
+    "SET",
+    "SHOW",
+    "SIGNAL",
+    "SMALLINT",
+    "SPATIAL",
+    "SPECIFIC",
+    "SQL",
+    "SQLEXCEPTION",
+    "SQLSTATE",
+    "SQLWARNING",
+    "SQL_BIG_RESULT",
+    "SQL_CALC_FOUND_ROWS",
+    "SQL_SMALL_RESULT",
+    "SSL",
+    "STARTING",
+    "STORED",
+    "STRAIGHT_JOIN",
+    "SYSTEM",
+    "TABLE",
+    "TERMINATED",
+    "THEN",
+    "TINYBLOB",
+    "TINYINT",
+    "TINYTEXT",
+    "TO",
+    "TRAILING",
+    "TRIGGER",
+    "TRUE",
+    "UNDO",
+    "UNION",
+    "UNIQUE",
+    "UNLOCK",
+    "UNSIGNED",
+    "UPDATE",
+    "USAGE",
+    "USE",
+    "USING",
+    "UTC_DATE",
+    "UTC_TIME",
+    "UTC_TIMESTAMP",
+    "VALUES",
+    "VARBINARY",
+    "VARCHAR",
+    "VARCHARACTER",
+    "VARYING",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WHILE",
+    "WINDOW",
+    "WITH",
+    "WRITE",
+    "XOR",
+    "YEAR_MONTH",
+    "ZEROFILL",
+];
+
+// the reserved keywords of Snowflake, see
+// https://docs.snowflake.com/en/sql-reference/reserved-keywords
+const SNOWFLAKE_KEYWORDS: &[&str] = &[
+    "ACCOUNT",
+    "ALL",
+    "ALTER",
+    "AND",
+    "ANY",
+    "AS",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLUMN",
+    "CONNECT",
+    "CONNECTION",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "CURRENT_USER",
+    "DATABASE",
+    "DELETE",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "EXISTS",
+    "FALSE",
+    "FOLLOWING",
+    "FOR",
+    "FROM",
+    "FULL",
+    "GRANT",
+    "GROUP",
+    "GSCLUSTER",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INCREMENT",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "ISSUE",
+    "JOIN",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+    "MINUS",
+    "NATURAL",
+    "NOT",
+    "NULL",
+    "OF",
+    "ON",
+    "OR",
+    "ORDER",
+    "ORGANIZATION",
+    "QUALIFY",
+    "REGEXP",
+    "REVOKE",
+    "RIGHT",
+    "RLIKE",
+    "ROW",
+    "ROWS",
+    "SAMPLE",
+    "SCHEMA",
+    "SELECT",
+    "SET",
+    "SOME",
+    "START",
+    "TABLE",
+    "TABLESAMPLE",
+    "THEN",
+    "TO",
+    "TRIGGER",
+    "TRUE",
+    "TRY_CAST",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHENEVER",
+    "WHERE",
+    "WITH",
+];
+
+// the reserved keywords of BigQuery, see
+// https://cloud.google.com/bigquery/docs/reference/standard-sql/lexical#reserved_keywords
+const BIGQUERY_KEYWORDS: &[&str] = &[
+    "ALL",
+    "AND",
+    "ANY",
+    "ARRAY",
+    "AS",
+    "ASC",
+    "ASSERT_ROWS_MODIFIED",
+    "AT",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "COLLATE",
+    "CONTAINS",
+    "CREATE",
+    "CROSS",
+    "CUBE",
+    "CURRENT",
+    "DEFAULT",
+    "DEFINE",
+    "DESC",
+    "DISTINCT",
+    "ELSE",
+    "END",
+    "ENUM",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUDE",
+    "EXISTS",
+    "EXTRACT",
+    "FALSE",
+    "FETCH",
+    "FOLLOWING",
+    "FOR",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "GROUPING",
+    "GROUPS",
+    "HASH",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INNER",
+    "INTERSECT",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOOKUP",
+    "MERGE",
+    "NATURAL",
+    "NEW",
+    "NO",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OF",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PRECEDING",
+    "PROTO",
+    "QUALIFY",
+    "RANGE",
+    "RECURSIVE",
+    "RESPECT",
+    "RIGHT",
+    "ROLLUP",
+    "ROWS",
+    "SELECT",
+    "SET",
+    "SOME",
+    "STRUCT",
+    "TABLESAMPLE",
+    "THEN",
+    "TO",
+    "TREAT",
+    "TRUE",
+    "UNBOUNDED",
+    "UNION",
+    "UNNEST",
+    "USING",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHIN",
+];
+
+/// What the first part of a two-part table name (`a.b`) refers to.
+///
+/// Three-part names are always read as `db.schema.table`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum TableQualifier {
+    /// The schema of the table, like in PostgreSQL.
+    #[default]
+    Schema,
+    /// The database of the table, like in MySQL.
+    Database,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CaseFolding, Dialect, TableQualifier, BIGQUERY_KEYWORDS, MYSQL_KEYWORDS,
+        POSTGRESQL_KEYWORDS, SNOWFLAKE_KEYWORDS,
+    };
+
+    #[test]
+    fn identifier_quote() {
+        assert_eq!(Dialect::Generic.identifier_quote(), '"');
+        assert_eq!(Dialect::Snowflake.identifier_quote(), '"');
+        assert_eq!(Dialect::MySql.identifier_quote(), '`');
+        assert_eq!(Dialect::BigQuery.identifier_quote(), '`');
+    }
+
+    #[test]
+    fn case_folding() {
+        assert_eq!(Dialect::Generic.case_folding(), CaseFolding::Lowercase);
+        assert_eq!(Dialect::PostgreSql.case_folding(), CaseFolding::Lowercase);
+        assert_eq!(Dialect::Snowflake.case_folding(), CaseFolding::Uppercase);
+        assert_eq!(Dialect::MySql.case_folding(), CaseFolding::Lowercase);
+        assert_eq!(Dialect::BigQuery.case_folding(), CaseFolding::Preserve);
+
+        assert_eq!(CaseFolding::Lowercase.fold("Test_Table"), "test_table");
+        assert_eq!(CaseFolding::Uppercase.fold("Test_Table"), "TEST_TABLE");
+        assert!(CaseFolding::Uppercase.is_folded("SUM"));
+        assert!(!CaseFolding::Uppercase.is_folded("sum"));
+        assert!(CaseFolding::Lowercase.is_folded("sum"));
+        assert!(!CaseFolding::Lowercase.is_folded("Sum"));
+        assert_eq!(CaseFolding::Preserve.fold("Test_Table"), "Test_Table");
+        assert!(CaseFolding::Preserve.is_folded("Sum"));
+    }
+
+    #[test]
+    fn reserved_keywords() {
+        for keywords in [
+            POSTGRESQL_KEYWORDS,
+            MYSQL_KEYWORDS,
+            SNOWFLAKE_KEYWORDS,
+            BIGQUERY_KEYWORDS,
+        ] {
+            //sorted for the binary search
+            assert!(keywords.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        assert!(Dialect::PostgreSql.is_reserved_keyword("order"));
+        assert!(!Dialect::PostgreSql.is_reserved_keyword("key"));
+        assert!(Dialect::MySql.is_reserved_keyword("Key"));
+        assert!(Dialect::Snowflake.is_reserved_keyword("QUALIFY"));
+        assert!(!Dialect::Snowflake.is_reserved_keyword("limit"));
+        assert!(Dialect::BigQuery.is_reserved_keyword("struct"));
+        for keyword in ["order", "key", "qualify", "struct"] {
+            assert!(Dialect::Generic.is_reserved_keyword(keyword), "{keyword}");
+        }
+        assert!(!Dialect::Generic.is_reserved_keyword("amount"));
+    }
+
+    #[test]
+    fn table_qualifier() {
+        assert_eq!(Dialect::Generic.table_qualifier(), TableQualifier::Schema);
+        assert_eq!(
+            Dialect::PostgreSql.table_qualifier(),
+            TableQualifier::Schema
+        );
+        assert_eq!(Dialect::Snowflake.table_qualifier(), TableQualifier::Schema);
+        assert_eq!(Dialect::MySql.table_qualifier(), TableQualifier::Database);
+        assert_eq!(
+            Dialect::BigQuery.table_qualifier(),
+            TableQualifier::Database
+        );
+    }
+}
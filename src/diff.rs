@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    comparison::CompareOp, dialect::Dialect, filter::Filter, query_metadata::QueryMetadata,
+};
+
+/// A field of the metadata that can differ between two versions of a query (see [`diff`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
+pub enum MetadataField {
+    /// The table, e.g. `db.schema.table`.
+    Table,
+    /// The point in time the table is read at.
+    TableVersion,
+    /// The hints of the table, separated by commas.
+    TableHints,
+    /// The aggregation function, e.g. `SUM`.
+    Function,
+    /// The aggregated column.
+    Column,
+    /// The alias of the result.
+    Alias,
+    /// The window of a running aggregate, e.g. `ORDER BY ts ROWS UNBOUNDED PRECEDING`.
+    Window,
+    /// The time bucket the query is grouped by, e.g. `DATE_TRUNC('day', ts)`.
+    TimeBucket,
+    /// The whole filter, in SQL, when only one of the versions has one.
+    Filter,
+    /// The filtered column.
+    FilterColumn,
+    /// The path of the JSON field filtered, e.g. `a -> b`.
+    FilterJsonPath,
+    /// The comparison operator, e.g. `>=` or `[2] =` for an element of an array.
+    FilterOperator,
+    /// The value the column is compared to.
+    FilterValue,
+    /// The kind of literal of the value, e.g. `Number`.
+    FilterValueType,
+    /// The collation of the comparison.
+    FilterCollation,
+    /// Whether the filter never holds (see [`QueryMetadata::empty_result`]).
+    EmptyResult,
+}
+
+/// A field that differs, with its values in each version, `None` if it has none (e.g. no alias).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct FieldChange {
+    /// The field.
+    pub field: MetadataField,
+    /// The value in the first version.
+    pub before: Option<String>,
+    /// The value in the second version.
+    pub after: Option<String>,
+}
+
+/// The fields that differ between two versions of a query, in the order of [`MetadataField`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct MetadataDiff {
+    /// The fields that differ.
+    pub changes: Vec<FieldChange>,
+}
+
+impl MetadataDiff {
+    /// Whether the versions are the same.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The change of `field`, if it differs.
+    #[must_use]
+    pub fn change(&self, field: MetadataField) -> Option<&FieldChange> {
+        self.changes.iter().find(|change| change.field == field)
+    }
+}
+
+/// Reports the fields of the metadata that differ from `before` to `after`, e.g. to show what
+/// changed since a query was approved without comparing the SQL.
+///
+/// The names are compared as they're stored (i.e. case folded), and the generated queries, the
+/// aliases of the tables and the annotations aren't compared, so that rewriting a query
+/// differently (whitespace, keyword case, comments, ...) doesn't change anything.
+#[must_use]
+pub fn diff(before: &QueryMetadata, after: &QueryMetadata) -> MetadataDiff {
+    let table_version = |metadata: &QueryMetadata| {
+        let version = metadata.table_version.as_ref();
+        version.map(|version| version.as_of.clone())
+    };
+    let table_hints = |metadata: &QueryMetadata| {
+        let hints = &metadata.table_hints;
+        (!hints.is_empty()).then(|| hints.join(", "))
+    };
+    let time_bucket = |metadata: &QueryMetadata| {
+        let bucket = metadata.time_bucket.as_ref();
+        bucket.map(ToString::to_string)
+    };
+    let (aggregation, other) = (&before.aggregation, &after.aggregation);
+    let mut fields = vec![
+        (
+            MetadataField::Table,
+            Some(before.table.to_string()),
+            Some(after.table.to_string()),
+        ),
+        (
+            MetadataField::TableVersion,
+            table_version(before),
+            table_version(after),
+        ),
+        (
+            MetadataField::TableHints,
+            table_hints(before),
+            table_hints(after),
+        ),
+        (
+            MetadataField::Function,
+            Some(aggregation.function.sql_name().to_string()),
+            Some(other.function.sql_name().to_string()),
+        ),
+        (
+            MetadataField::Column,
+            Some(aggregation.column.to_string()),
+            Some(other.column.to_string()),
+        ),
+        (
+            MetadataField::Alias,
+            aggregation.alias.clone(),
+            other.alias.clone(),
+        ),
+        (
+            MetadataField::Window,
+            aggregation.window.as_ref().map(ToString::to_string),
+            other.window.as_ref().map(ToString::to_string),
+        ),
+        (
+            MetadataField::TimeBucket,
+            time_bucket(before),
+            time_bucket(after),
+        ),
+    ];
+    fields.extend(filter_fields(before.filter.as_ref(), after.filter.as_ref()));
+    fields.push((
+        MetadataField::EmptyResult,
+        Some(before.empty_result.to_string()),
+        Some(after.empty_result.to_string()),
+    ));
+    let changes = fields
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(field, before, after)| FieldChange {
+            field,
+            before,
+            after,
+        })
+        .collect();
+    MetadataDiff { changes }
+}
+
+// the fields of the filters with their values, the whole filters if only one is given
+fn filter_fields(
+    before: Option<&Filter>,
+    after: Option<&Filter>,
+) -> Vec<(MetadataField, Option<String>, Option<String>)> {
+    let (Some(filter), Some(other)) = (before, after) else {
+        let sql = |filter: &Filter| filter.to_sql(Dialect::Generic, None);
+        return vec![(MetadataField::Filter, before.map(sql), after.map(sql))];
+    };
+    let json_path = |filter: &Filter| {
+        let path = filter.json_path.as_ref();
+        path.map(|path| path.join(" -> "))
+    };
+    let value_type = |filter: &Filter| {
+        let value_type = filter.value_type;
+        value_type.map(|value_type| format!("{value_type:?}"))
+    };
+    vec![
+        (
+            MetadataField::FilterColumn,
+            Some(filter.column.to_string()),
+            Some(other.column.to_string()),
+        ),
+        (
+            MetadataField::FilterJsonPath,
+            json_path(filter),
+            json_path(other),
+        ),
+        (
+            MetadataField::FilterOperator,
+            Some(operator(&filter.comparison)),
+            Some(operator(&other.comparison)),
+        ),
+        (
+            MetadataField::FilterValue,
+            filter.comparison.value().map(str::to_string),
+            other.comparison.value().map(str::to_string),
+        ),
+        (
+            MetadataField::FilterValueType,
+            value_type(filter),
+            value_type(other),
+        ),
+        (
+            MetadataField::FilterCollation,
+            filter.collation.clone(),
+            other.collation.clone(),
+        ),
+    ]
+}
+
+// the operator of `comparison`, with the index of the element of an array it compares
+fn operator(comparison: &CompareOp) -> String {
+    let symbol = comparison.sql_symbol();
+    match comparison {
+        CompareOp::ElementEq { index, .. } | CompareOp::ElementNotEq { index, .. } => {
+            symbol.replacen("[]", &format!("[{index}]"), 1)
+        }
+        _ => symbol.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, FieldChange, MetadataField};
+    use crate::{
+        aggregation::{Aggregation, WindowSpec},
+        config::ParseOptions,
+        query_metadata::QueryMetadata,
+        time_bucket::{TimeBucket, TimeGranularity, TimeType},
+    };
+
+    #[test]
+    fn metadata_diff() {
+        let parse = |query: &str| QueryMetadata::parse(query, None).unwrap();
+        let approved = parse("SELECT SUM(a) AS total FROM db.s.t WHERE b > 10");
+        //written differently, but the same query
+        let same = parse("select sum(T.A) as total from DB.S.T as t where 10 < t.b");
+        assert!(diff(&approved, &same).is_empty());
+
+        let changed = parse("SELECT AVG(a) FROM db.s.t WHERE b >= '10'");
+        let changes = diff(&approved, &changed);
+        let change = |field, before: Option<&str>, after: Option<&str>| FieldChange {
+            field,
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+        };
+        assert_eq!(
+            changes.changes,
+            vec![
+                change(MetadataField::Function, Some("SUM"), Some("AVG")),
+                change(MetadataField::Alias, Some("total"), None),
+                change(MetadataField::FilterOperator, Some(">"), Some(">=")),
+                change(
+                    MetadataField::FilterValueType,
+                    Some("Number"),
+                    Some("String")
+                ),
+            ]
+        );
+        assert!(changes.change(MetadataField::Table).is_none());
+
+        let bucket = TimeBucket {
+            column: "ts".into(),
+            granularity: TimeGranularity::Hour,
+            time_type: TimeType::Timestamp,
+        };
+        let hourly = approved
+            .clone()
+            .with_time_bucket(Some(bucket), &ParseOptions::new());
+        assert_eq!(
+            diff(&approved, &hourly).changes,
+            vec![change(
+                MetadataField::TimeBucket,
+                None,
+                Some("DATE_TRUNC('hour', ts)")
+            )]
+        );
+
+        let running = Aggregation {
+            window: Some(WindowSpec {
+                order_by: "ts".into(),
+                descending: false,
+            }),
+            ..approved.aggregation.clone()
+        };
+        let running = approved
+            .clone()
+            .with_aggregation(running, &ParseOptions::new());
+        assert_eq!(
+            diff(&approved, &running).changes,
+            vec![change(
+                MetadataField::Window,
+                None,
+                Some("ORDER BY ts ROWS UNBOUNDED PRECEDING")
+            )]
+        );
+
+        let unfiltered = parse("SELECT SUM(a) AS total FROM db.s.u");
+        let changes = diff(&approved, &unfiltered);
+        assert_eq!(
+            changes.change(MetadataField::Table),
+            Some(&change(
+                MetadataField::Table,
+                Some("db.s.t"),
+                Some("db.s.u")
+            ))
+        );
+        assert_eq!(
+            changes.change(MetadataField::Filter),
+            Some(&change(MetadataField::Filter, Some("b > 10"), None))
+        );
+        assert!(changes.change(MetadataField::FilterOperator).is_none());
+
+        let element = parse("SELECT SUM(a) AS total FROM db.s.t WHERE tags[2] = 'x'");
+        let other = parse("SELECT SUM(a) AS total FROM db.s.t WHERE tags[3] = 'x'");
+        assert_eq!(
+            diff(&element, &other).changes,
+            vec![change(
+                MetadataField::FilterOperator,
+                Some("[2] ="),
+                Some("[3] =")
+            )]
+        );
+    }
+}
@@ -1,45 +1,590 @@
+use std::{
+    any::Any,
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use sqlparser::parser::ParserError;
 use thiserror::Error;
 
+use crate::span::{snippet, Span};
+
 /// Koron errors.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ParseError {
     #[error("malformed query: {message}")]
-    MalformedQuery { message: String },
+    MalformedQuery {
+        code: ErrorCode,
+        message: String,
+        span: Option<Span>,
+    },
     #[error("statement not supported: {message}")]
-    Unsupported { message: String },
+    Unsupported {
+        feature: UnsupportedFeature,
+        message: String,
+        span: Option<Span>,
+    },
     #[error("internal: {message}")]
-    Internal { message: String },
+    Internal { code: ErrorCode, message: String },
+    #[error("forbidden: {message}")]
+    Forbidden { code: ErrorCode, message: String },
+    #[error("limit exceeded: {message}")]
+    LimitExceeded { code: ErrorCode, message: String },
+}
+
+/// Stable, machine-readable codes of the errors, that don't change with the wording of the
+/// messages (e.g. `E_UNSUPPORTED_ORDER_BY`).
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// The query isn't valid SQL.
+    Syntax,
+    /// The query isn't a single SELECT statement.
+    MultiStatement,
+    /// The query has no statement (e.g. only comments).
+    EmptyQuery,
+    /// A function that's not a supported aggregation.
+    UnknownFunction,
+    /// A function with the wrong number of arguments.
+    WrongArgumentCount,
+    /// A column qualified with a table that's not the one in the FROM clause.
+    ColumnNotInTable,
+    /// A table that doesn't exist in the catalog.
+    UnknownTable,
+    /// A column that doesn't exist in the table.
+    UnknownColumn,
+    /// The alias of the aggregation used as a column in the WHERE clause.
+    AliasInWhere,
+    /// A column whose type doesn't fit the aggregation or the filter.
+    TypeMismatch,
+    /// A value that can't be embedded in SQL, e.g. a string with a NUL character.
+    InvalidLiteral,
+    /// A name that breaks the identifier rules of the options.
+    InvalidIdentifier,
+    /// A table that can't be queried.
+    ForbiddenTable,
+    /// A column that can't be queried.
+    ForbiddenColumn,
+    /// A table of another database than the session one, which can't be queried.
+    CrossDatabase,
+    /// A query longer than the limit.
+    QueryTooLong,
+    /// A query nested deeper than the limit.
+    ExpressionTooDeep,
+    /// A list with more items than the limit.
+    ListTooLong,
+    /// A bug in the parser.
+    Internal,
+    /// A WITH clause (CTEs).
+    UnsupportedCte,
+    /// An ORDER BY clause.
+    UnsupportedOrderBy,
+    /// A LIMIT clause.
+    UnsupportedLimit,
+    /// An OFFSET clause.
+    UnsupportedOffset,
+    /// A FETCH clause.
+    UnsupportedFetch,
+    /// A locking clause (e.g. FOR UPDATE).
+    UnsupportedLocking,
+    /// A LIMIT BY clause.
+    UnsupportedLimitBy,
+    /// A FOR clause (e.g. FOR XML).
+    UnsupportedFor,
+    /// A set operation (e.g. UNION).
+    UnsupportedSetOperation,
+    /// A VALUES list.
+    UnsupportedValues,
+    /// A TABLE statement.
+    UnsupportedTableStatement,
+    /// DISTINCT, in the SELECT clause or in the aggregation.
+    UnsupportedDistinct,
+    /// A TOP clause.
+    UnsupportedTop,
+    /// A SELECT INTO statement.
+    UnsupportedSelectInto,
+    /// A LATERAL VIEW clause.
+    UnsupportedLateralView,
+    /// A GROUP BY clause.
+    UnsupportedGroupBy,
+    /// A CLUSTER BY clause.
+    UnsupportedClusterBy,
+    /// A DISTRIBUTE BY clause.
+    UnsupportedDistributeBy,
+    /// A SORT BY clause.
+    UnsupportedSortBy,
+    /// A HAVING clause.
+    UnsupportedHaving,
+    /// A QUALIFY clause.
+    UnsupportedQualify,
+    /// A named window (WINDOW clause).
+    UnsupportedNamedWindow,
+    /// A window function (OVER).
+    UnsupportedWindowFunction,
+    /// An ORDER BY in the aggregation.
+    UnsupportedAggregationOrderBy,
+    /// A FILTER clause in the aggregation.
+    UnsupportedAggregationFilter,
+    /// IGNORE / RESPECT NULLS in the aggregation.
+    UnsupportedNullTreatment,
+    /// A SELECT clause that's not a single aggregation.
+    UnsupportedProjection,
+    /// A named function argument.
+    UnsupportedNamedArgument,
+    /// A function argument that's not a column.
+    UnsupportedArgument,
+    /// An operator that's not a supported comparison.
+    UnsupportedOperator,
+    /// A comparison that's not between a column and a constant.
+    UnsupportedComparison,
+    /// An expression in the WHERE clause that's not a comparison.
+    UnsupportedExpression,
+    /// A constant that's not a supported value.
+    UnsupportedValue,
+    /// A FROM clause that's not a single table.
+    UnsupportedFrom,
+    /// Table hints (WITH in the FROM clause).
+    UnsupportedTableHints,
+    /// A version qualifier of the table.
+    UnsupportedTableVersion,
+    /// Table partitions.
+    UnsupportedPartitions,
+    /// A table alias with columns.
+    UnsupportedAliasColumns,
+}
+
+impl ErrorCode {
+    /// The code as a string, e.g. `E_UNSUPPORTED_ORDER_BY`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Syntax => "E_SYNTAX",
+            Self::MultiStatement => "E_MULTI_STATEMENT",
+            Self::EmptyQuery => "E_EMPTY_QUERY",
+            Self::UnknownFunction => "E_UNKNOWN_FUNCTION",
+            Self::WrongArgumentCount => "E_WRONG_ARGUMENT_COUNT",
+            Self::ColumnNotInTable => "E_COLUMN_NOT_IN_TABLE",
+            Self::UnknownTable => "E_UNKNOWN_TABLE",
+            Self::UnknownColumn => "E_UNKNOWN_COLUMN",
+            Self::AliasInWhere => "E_ALIAS_IN_WHERE",
+            Self::TypeMismatch => "E_TYPE_MISMATCH",
+            Self::InvalidLiteral => "E_INVALID_LITERAL",
+            Self::InvalidIdentifier => "E_INVALID_IDENTIFIER",
+            Self::ForbiddenTable => "E_FORBIDDEN_TABLE",
+            Self::ForbiddenColumn => "E_FORBIDDEN_COLUMN",
+            Self::CrossDatabase => "E_CROSS_DATABASE",
+            Self::QueryTooLong => "E_QUERY_TOO_LONG",
+            Self::ExpressionTooDeep => "E_EXPRESSION_TOO_DEEP",
+            Self::ListTooLong => "E_LIST_TOO_LONG",
+            Self::Internal => "E_INTERNAL",
+            Self::UnsupportedCte => "E_UNSUPPORTED_CTE",
+            Self::UnsupportedOrderBy => "E_UNSUPPORTED_ORDER_BY",
+            Self::UnsupportedLimit => "E_UNSUPPORTED_LIMIT",
+            Self::UnsupportedOffset => "E_UNSUPPORTED_OFFSET",
+            Self::UnsupportedFetch => "E_UNSUPPORTED_FETCH",
+            Self::UnsupportedLocking => "E_UNSUPPORTED_LOCKING",
+            Self::UnsupportedLimitBy => "E_UNSUPPORTED_LIMIT_BY",
+            Self::UnsupportedFor => "E_UNSUPPORTED_FOR",
+            Self::UnsupportedSetOperation => "E_UNSUPPORTED_SET_OPERATION",
+            Self::UnsupportedValues => "E_UNSUPPORTED_VALUES",
+            Self::UnsupportedTableStatement => "E_UNSUPPORTED_TABLE_STATEMENT",
+            Self::UnsupportedDistinct => "E_UNSUPPORTED_DISTINCT",
+            Self::UnsupportedTop => "E_UNSUPPORTED_TOP",
+            Self::UnsupportedSelectInto => "E_UNSUPPORTED_SELECT_INTO",
+            Self::UnsupportedLateralView => "E_UNSUPPORTED_LATERAL_VIEW",
+            Self::UnsupportedGroupBy => "E_UNSUPPORTED_GROUP_BY",
+            Self::UnsupportedClusterBy => "E_UNSUPPORTED_CLUSTER_BY",
+            Self::UnsupportedDistributeBy => "E_UNSUPPORTED_DISTRIBUTE_BY",
+            Self::UnsupportedSortBy => "E_UNSUPPORTED_SORT_BY",
+            Self::UnsupportedHaving => "E_UNSUPPORTED_HAVING",
+            Self::UnsupportedQualify => "E_UNSUPPORTED_QUALIFY",
+            Self::UnsupportedNamedWindow => "E_UNSUPPORTED_NAMED_WINDOW",
+            Self::UnsupportedWindowFunction => "E_UNSUPPORTED_WINDOW_FUNCTION",
+            Self::UnsupportedAggregationOrderBy => "E_UNSUPPORTED_AGGREGATION_ORDER_BY",
+            Self::UnsupportedAggregationFilter => "E_UNSUPPORTED_AGGREGATION_FILTER",
+            Self::UnsupportedNullTreatment => "E_UNSUPPORTED_NULL_TREATMENT",
+            Self::UnsupportedProjection => "E_UNSUPPORTED_PROJECTION",
+            Self::UnsupportedNamedArgument => "E_UNSUPPORTED_NAMED_ARGUMENT",
+            Self::UnsupportedArgument => "E_UNSUPPORTED_ARGUMENT",
+            Self::UnsupportedOperator => "E_UNSUPPORTED_OPERATOR",
+            Self::UnsupportedComparison => "E_UNSUPPORTED_COMPARISON",
+            Self::UnsupportedExpression => "E_UNSUPPORTED_EXPRESSION",
+            Self::UnsupportedValue => "E_UNSUPPORTED_VALUE",
+            Self::UnsupportedFrom => "E_UNSUPPORTED_FROM",
+            Self::UnsupportedTableHints => "E_UNSUPPORTED_TABLE_HINTS",
+            Self::UnsupportedTableVersion => "E_UNSUPPORTED_TABLE_VERSION",
+            Self::UnsupportedPartitions => "E_UNSUPPORTED_PARTITIONS",
+            Self::UnsupportedAliasColumns => "E_UNSUPPORTED_ALIAS_COLUMNS",
+        }
+    }
 }
 
-macro_rules! impl_malformed_from {
-    ($err:ty) => {
-        impl From<$err> for ParseError {
-            fn from(e: $err) -> Self {
-                Self::MalformedQuery {
-                    message: e.to_string(),
-                }
+/// The constructs of the queries that aren't supported, more precise than their [`ErrorCode`]
+/// (e.g. the name of an unknown function).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnsupportedFeature {
+    /// A statement that's not a single SELECT statement.
+    Statement,
+    /// A function that's not a supported aggregation, with its name.
+    UnknownFunction(String),
+    /// A WITH clause (CTEs).
+    Cte,
+    /// An ORDER BY clause.
+    OrderBy,
+    /// A LIMIT clause.
+    Limit,
+    /// An OFFSET clause.
+    Offset,
+    /// A FETCH clause.
+    Fetch,
+    /// A locking clause (e.g. FOR UPDATE).
+    Locking,
+    /// A LIMIT BY clause.
+    LimitBy,
+    /// A FOR clause (e.g. FOR XML).
+    For,
+    /// A set operation, with its operator (e.g. `UNION`).
+    SetOperation(String),
+    /// A VALUES list.
+    Values,
+    /// A TABLE statement.
+    TableStatement,
+    /// DISTINCT, in the SELECT clause or in the aggregation.
+    Distinct,
+    /// A TOP clause.
+    Top,
+    /// A SELECT INTO statement.
+    SelectInto,
+    /// A LATERAL VIEW clause.
+    LateralView,
+    /// A GROUP BY clause.
+    GroupBy,
+    /// A CLUSTER BY clause.
+    ClusterBy,
+    /// A DISTRIBUTE BY clause.
+    DistributeBy,
+    /// A SORT BY clause.
+    SortBy,
+    /// A HAVING clause.
+    Having,
+    /// A QUALIFY clause.
+    Qualify,
+    /// A named window (WINDOW clause).
+    NamedWindow,
+    /// A window function (OVER).
+    WindowFunction,
+    /// An ORDER BY in the aggregation.
+    AggregationOrderBy,
+    /// A FILTER clause in the aggregation.
+    AggregationFilter,
+    /// IGNORE / RESPECT NULLS in the aggregation.
+    NullTreatment,
+    /// A SELECT clause that's not a single aggregation.
+    Projection,
+    /// A named function argument.
+    NamedArgument,
+    /// A function argument that's not a column.
+    Argument,
+    /// An operator that's not a supported comparison.
+    Operator,
+    /// A comparison that's not between a column and a constant.
+    Comparison,
+    /// An expression in the WHERE clause that's not a comparison.
+    Expression,
+    /// A constant that's not a supported value.
+    Value,
+    /// A FROM clause with more than one table, or with JOINs.
+    Join,
+    /// A subquery in the FROM clause.
+    Subquery,
+    /// A table function (or a table with arguments) in the FROM clause.
+    TableFunction,
+    /// A PIVOT or UNPIVOT in the FROM clause.
+    Pivot,
+    /// Table hints (WITH in the FROM clause).
+    TableHints,
+    /// A version qualifier of the table.
+    TableVersion,
+    /// Table partitions.
+    Partitions,
+    /// A table alias with columns.
+    AliasColumns,
+}
+
+impl UnsupportedFeature {
+    /// The code of the errors reporting the construct.
+    #[must_use]
+    pub const fn code(&self) -> ErrorCode {
+        match self {
+            Self::Statement => ErrorCode::MultiStatement,
+            Self::UnknownFunction(_) => ErrorCode::UnknownFunction,
+            Self::Cte => ErrorCode::UnsupportedCte,
+            Self::OrderBy => ErrorCode::UnsupportedOrderBy,
+            Self::Limit => ErrorCode::UnsupportedLimit,
+            Self::Offset => ErrorCode::UnsupportedOffset,
+            Self::Fetch => ErrorCode::UnsupportedFetch,
+            Self::Locking => ErrorCode::UnsupportedLocking,
+            Self::LimitBy => ErrorCode::UnsupportedLimitBy,
+            Self::For => ErrorCode::UnsupportedFor,
+            Self::SetOperation(_) => ErrorCode::UnsupportedSetOperation,
+            Self::Values => ErrorCode::UnsupportedValues,
+            Self::TableStatement => ErrorCode::UnsupportedTableStatement,
+            Self::Distinct => ErrorCode::UnsupportedDistinct,
+            Self::Top => ErrorCode::UnsupportedTop,
+            Self::SelectInto => ErrorCode::UnsupportedSelectInto,
+            Self::LateralView => ErrorCode::UnsupportedLateralView,
+            Self::GroupBy => ErrorCode::UnsupportedGroupBy,
+            Self::ClusterBy => ErrorCode::UnsupportedClusterBy,
+            Self::DistributeBy => ErrorCode::UnsupportedDistributeBy,
+            Self::SortBy => ErrorCode::UnsupportedSortBy,
+            Self::Having => ErrorCode::UnsupportedHaving,
+            Self::Qualify => ErrorCode::UnsupportedQualify,
+            Self::NamedWindow => ErrorCode::UnsupportedNamedWindow,
+            Self::WindowFunction => ErrorCode::UnsupportedWindowFunction,
+            Self::AggregationOrderBy => ErrorCode::UnsupportedAggregationOrderBy,
+            Self::AggregationFilter => ErrorCode::UnsupportedAggregationFilter,
+            Self::NullTreatment => ErrorCode::UnsupportedNullTreatment,
+            Self::Projection => ErrorCode::UnsupportedProjection,
+            Self::NamedArgument => ErrorCode::UnsupportedNamedArgument,
+            Self::Argument => ErrorCode::UnsupportedArgument,
+            Self::Operator => ErrorCode::UnsupportedOperator,
+            Self::Comparison => ErrorCode::UnsupportedComparison,
+            Self::Expression => ErrorCode::UnsupportedExpression,
+            Self::Value => ErrorCode::UnsupportedValue,
+            Self::Join | Self::Subquery | Self::TableFunction | Self::Pivot => {
+                ErrorCode::UnsupportedFrom
             }
+            Self::TableHints => ErrorCode::UnsupportedTableHints,
+            Self::TableVersion => ErrorCode::UnsupportedTableVersion,
+            Self::Partitions => ErrorCode::UnsupportedPartitions,
+            Self::AliasColumns => ErrorCode::UnsupportedAliasColumns,
         }
-    };
+    }
+}
+
+/// Called each time a query is rejected for an unsupported construct.
+///
+/// It receives the construct and the message of the error, e.g. to count which constructs the
+/// users need the most (see [`crate::config::ParseOptions::with_on_reject`]). It's shared between
+/// the clones of the options, and may hold state such as a counter or a metrics client.
+pub type RejectionHook = Arc<dyn Fn(&UnsupportedFeature, &str) + Send + Sync>;
+
+impl ErrorCode {
+    /// A hint on how to fix the query, if there's one beyond the message of the error.
+    #[must_use]
+    pub const fn help(self) -> Option<&'static str> {
+        let help = match self {
+            Self::Syntax => "check the query against the SQL dialect in the options.",
+            Self::MultiStatement | Self::EmptyQuery => "send a single SELECT statement.",
+            Self::UnknownFunction => {
+                "use one of SUM, COUNT, AVG, MEDIAN, VARIANCE, STDDEV, MIN or MAX, unquoted."
+            }
+            Self::WrongArgumentCount => "pass a single column to the aggregation.",
+            Self::ColumnNotInTable => {
+                "qualify the column with the table of the FROM clause (or its alias), \
+                 or leave it unqualified."
+            }
+            Self::UnknownTable | Self::UnknownColumn => "check the spelling and the case folding.",
+            Self::TypeMismatch => "aggregate or compare a column of the matching type.",
+            Self::InvalidLiteral => "remove the control characters (e.g. NUL) from the value.",
+            Self::InvalidIdentifier => "rename the table, the columns or the aliases.",
+            Self::AliasInWhere => {
+                "filter on a column of the table: the WHERE clause applies before the aggregation."
+            }
+            Self::ForbiddenTable | Self::ForbiddenColumn | Self::Internal => return None,
+            Self::CrossDatabase => {
+                "query a table of the session database, or leave out the database."
+            }
+            Self::QueryTooLong | Self::ExpressionTooDeep | Self::ListTooLong => {
+                "simplify the query, or split it into several ones."
+            }
+            Self::UnsupportedProjection => "select a single aggregation, e.g. SUM(column).",
+            Self::UnsupportedArgument | Self::UnsupportedNamedArgument => {
+                "pass a column name as the argument of the aggregation."
+            }
+            Self::UnsupportedOperator | Self::UnsupportedComparison => {
+                "compare a column with a constant using =, <>, <, <=, >, >=, or IS [NOT] NULL / \
+                 TRUE / FALSE."
+            }
+            Self::UnsupportedExpression => {
+                "filter with a single comparison between a column and a constant."
+            }
+            Self::UnsupportedValue => "compare the column with a number, a string or a boolean.",
+            Self::UnsupportedFrom => "query a single table, without JOINs or subqueries.",
+            Self::UnsupportedCte
+            | Self::UnsupportedOrderBy
+            | Self::UnsupportedLimit
+            | Self::UnsupportedOffset
+            | Self::UnsupportedFetch
+            | Self::UnsupportedLocking
+            | Self::UnsupportedLimitBy
+            | Self::UnsupportedFor
+            | Self::UnsupportedSetOperation
+            | Self::UnsupportedValues
+            | Self::UnsupportedTableStatement
+            | Self::UnsupportedDistinct
+            | Self::UnsupportedTop
+            | Self::UnsupportedSelectInto
+            | Self::UnsupportedLateralView
+            | Self::UnsupportedGroupBy
+            | Self::UnsupportedClusterBy
+            | Self::UnsupportedDistributeBy
+            | Self::UnsupportedSortBy
+            | Self::UnsupportedHaving
+            | Self::UnsupportedQualify
+            | Self::UnsupportedNamedWindow
+            | Self::UnsupportedWindowFunction
+            | Self::UnsupportedAggregationOrderBy
+            | Self::UnsupportedAggregationFilter
+            | Self::UnsupportedNullTreatment
+            | Self::UnsupportedTableHints
+            | Self::UnsupportedTableVersion
+            | Self::UnsupportedPartitions
+            | Self::UnsupportedAliasColumns => "remove it from the query.",
+        };
+        Some(help)
+    }
 }
 
-impl_malformed_from!(sqlparser::parser::ParserError);
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ParseError {
+    /// The machine-readable code of the error.
+    #[must_use]
+    pub const fn code(&self) -> ErrorCode {
+        match self {
+            Self::MalformedQuery { code, .. }
+            | Self::Internal { code, .. }
+            | Self::Forbidden { code, .. }
+            | Self::LimitExceeded { code, .. } => *code,
+            Self::Unsupported { feature, .. } => feature.code(),
+        }
+    }
+
+    /// The unsupported construct, if the error reports one.
+    #[must_use]
+    pub const fn unsupported_feature(&self) -> Option<&UnsupportedFeature> {
+        match self {
+            Self::Unsupported { feature, .. } => Some(feature),
+            Self::MalformedQuery { .. }
+            | Self::Internal { .. }
+            | Self::Forbidden { .. }
+            | Self::LimitExceeded { .. } => None,
+        }
+    }
+
+    /// The part of the query the error is about, if it was located (see
+    /// [`crate::config::ParseOptions::with_error_spans`]).
+    #[must_use]
+    pub const fn span(&self) -> Option<Span> {
+        match self {
+            Self::MalformedQuery { span, .. } | Self::Unsupported { span, .. } => *span,
+            Self::Internal { .. } | Self::Forbidden { .. } | Self::LimitExceeded { .. } => None,
+        }
+    }
+
+    /// A hint on how to fix the query, if any (see [`ErrorCode::help`]).
+    #[must_use]
+    pub const fn help(&self) -> Option<&'static str> {
+        self.code().help()
+    }
+
+    /// Renders the error for humans: its code and message, the line of `sql_query` it's about
+    /// with the span underlined (if the error was located), and the help text, e.g.
+    ///
+    /// ```text
+    /// error[E_UNSUPPORTED_LIMIT]: statement not supported: LIMIT.
+    ///   |
+    /// 1 | SELECT SUM(a) FROM t LIMIT 5
+    ///   |                      ^^^^^
+    ///   = help: remove it from the query.
+    /// ```
+    #[must_use]
+    pub fn report(&self, sql_query: &str) -> String {
+        let mut report = format!("error[{}]: {self}", self.code());
+        if let Some(span) = self.span() {
+            report.push_str(&snippet(sql_query, span));
+        }
+        if let Some(help) = self.help() {
+            report.push_str("\n  = help: ");
+            report.push_str(help);
+        }
+        report
+    }
+
+    /// Attaches `span` to the error, if it's a malformed query or an unsupported construct.
+    #[must_use]
+    pub const fn with_span(mut self, span: Option<Span>) -> Self {
+        if let Self::MalformedQuery { span: s, .. } | Self::Unsupported { span: s, .. } = &mut self
+        {
+            *s = span;
+        }
+        self
+    }
+
+    // the syntax error reported by the SQL parser
+    pub(crate) fn syntax(error: &ParserError) -> Self {
+        Self::MalformedQuery {
+            code: ErrorCode::Syntax,
+            message: error.to_string(),
+            span: None,
+        }
+    }
+
+    // the error reporting a panic caught while parsing, with its message if it has one
+    pub(crate) fn from_panic(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown cause".to_string());
+        Self::Internal {
+            code: ErrorCode::Internal,
+            message: format!("the parser panicked: {message}."),
+        }
+    }
+}
+
+#[cfg(feature = "raw-ast")]
+impl From<ParserError> for ParseError {
+    fn from(e: ParserError) -> Self {
+        Self::syntax(&e)
+    }
+}
 
 impl From<String> for ParseError {
     fn from(e: String) -> Self {
-        Self::Internal { message: e }
+        Self::Internal {
+            code: ErrorCode::Internal,
+            message: e,
+        }
     }
 }
 
-/// Constructs a `ParseError::Unsupported{message: $msg}`.
+/// Constructs a `ParseError::Unsupported{feature: $feature, message: $msg}`, without a span.
 #[macro_export]
 macro_rules! unsupported {
-    ($msg:literal) => {{
-        ParseError::Unsupported { message: $msg }
+    ($feature:expr, $msg:literal) => {{
+        ParseError::Unsupported {
+            feature: $feature,
+            message: $msg,
+            span: None,
+        }
     }};
-    ($msg:expr) => {{
-        ParseError::Unsupported { message: $msg }
+    ($feature:expr, $msg:expr) => {{
+        ParseError::Unsupported {
+            feature: $feature,
+            message: $msg,
+            span: None,
+        }
     }};
 }
 
@@ -47,40 +592,126 @@ macro_rules! unsupported {
 #[macro_export]
 macro_rules! internal {
     ($msg:literal) => {{
-        ParseError::Internal { message: $msg }
+        ParseError::Internal {
+            code: $crate::error::ErrorCode::Internal,
+            message: $msg,
+        }
     }};
     ($msg:expr) => {{
-        ParseError::Internal { message: $msg }
+        ParseError::Internal {
+            code: $crate::error::ErrorCode::Internal,
+            message: $msg,
+        }
     }};
 }
 
-/// Constructs a `ParseError::MalformedQuery{message: $msg}`.
+/// Constructs a `ParseError::MalformedQuery{code: $code, message: $msg}`, without a span.
 #[macro_export]
 macro_rules! malformed_query {
-    ($msg:literal) => {{
-        ParseError::MalformedQuery { message: $msg }
+    ($code:expr, $msg:literal) => {{
+        ParseError::MalformedQuery {
+            code: $code,
+            message: $msg,
+            span: None,
+        }
     }};
-    ($msg:expr) => {{
-        ParseError::MalformedQuery { message: $msg }
+    ($code:expr, $msg:expr) => {{
+        ParseError::MalformedQuery {
+            code: $code,
+            message: $msg,
+            span: None,
+        }
+    }};
+}
+
+/// Constructs a `ParseError::Forbidden{code: $code, message: $msg}`.
+#[macro_export]
+macro_rules! forbidden {
+    ($code:expr, $msg:literal) => {{
+        ParseError::Forbidden {
+            code: $code,
+            message: $msg,
+        }
+    }};
+    ($code:expr, $msg:expr) => {{
+        ParseError::Forbidden {
+            code: $code,
+            message: $msg,
+        }
+    }};
+}
+
+/// Constructs a `ParseError::LimitExceeded{code: $code, message: $msg}`.
+#[macro_export]
+macro_rules! limit_exceeded {
+    ($code:expr, $msg:literal) => {{
+        ParseError::LimitExceeded {
+            code: $code,
+            message: $msg,
+        }
+    }};
+    ($code:expr, $msg:expr) => {{
+        ParseError::LimitExceeded {
+            code: $code,
+            message: $msg,
+        }
     }};
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ParseError;
+    use super::{ErrorCode, ParseError, UnsupportedFeature};
 
     #[test]
     fn to_string() {
         let mut error = internal!("test.".to_string());
         assert_eq!(error.to_string(), "internal: test.".to_string());
 
-        error = malformed_query!("test.".to_string());
+        error = malformed_query!(ErrorCode::Syntax, "test.".to_string());
         assert_eq!(error.to_string(), "malformed query: test.".to_string());
 
-        error = unsupported!("test.".to_string());
+        error = unsupported!(UnsupportedFeature::Limit, "test.".to_string());
         assert_eq!(
             error.to_string(),
             "statement not supported: test.".to_string()
         );
+
+        error = forbidden!(ErrorCode::ForbiddenTable, "test.".to_string());
+        assert_eq!(error.to_string(), "forbidden: test.".to_string());
+
+        error = limit_exceeded!(ErrorCode::QueryTooLong, "test.".to_string());
+        assert_eq!(error.to_string(), "limit exceeded: test.".to_string());
+    }
+
+    #[test]
+    fn code() {
+        let error = unsupported!(UnsupportedFeature::OrderBy, "ORDER BY.".to_string());
+        assert_eq!(error.code(), ErrorCode::UnsupportedOrderBy);
+        assert_eq!(error.code().to_string(), "E_UNSUPPORTED_ORDER_BY");
+        assert_eq!(
+            error.unsupported_feature(),
+            Some(&UnsupportedFeature::OrderBy)
+        );
+
+        let error = unsupported!(
+            UnsupportedFeature::UnknownFunction("foo".to_string()),
+            "unrecognized or unsupported function: foo.".to_string()
+        );
+        assert_eq!(error.code(), ErrorCode::UnknownFunction);
+        assert_eq!(internal!("test.".to_string()).code(), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn from_panic() {
+        let panic = std::panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+        assert_eq!(
+            ParseError::from_panic(panic.as_ref()).to_string(),
+            "internal: the parser panicked: index out of bounds."
+        );
+        let panic = std::panic::catch_unwind(|| panic!("{} is out of bounds", 3)).unwrap_err();
+        assert_eq!(
+            ParseError::from_panic(panic.as_ref()),
+            internal!("the parser panicked: 3 is out of bounds.".to_string())
+        );
     }
 }
@@ -0,0 +1,328 @@
+use std::fmt::{self, Display};
+
+use sqlparser::ast;
+
+use crate::{
+    comparison::{SystemTime, ValueType},
+    dialect::Dialect,
+    identifier::Identifier,
+    support::quote_literal,
+};
+
+/// A literal of the SQL subset the metadata represents (see [`Expression`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Value {
+    /// A decimal number, e.g. `-2.5`.
+    Number(String),
+    /// A string, unescaped.
+    String(String),
+    /// `true` or `false`.
+    Boolean(bool),
+    /// `NULL`.
+    Null,
+    /// A date, e.g. `DATE '2021-04-02'`.
+    Date(String),
+    /// A time, e.g. `TIME '12:30:00'`.
+    Time(String),
+    /// A timestamp, e.g. `TIMESTAMP '2021-04-02 12:30:00'`.
+    Timestamp(String),
+    /// The current date or time, e.g. `CURRENT_DATE`.
+    SystemTime(SystemTime),
+}
+
+impl Value {
+    /// The literal of `value` written as `value_type`, as the values of the filters are stored
+    /// (see [`crate::filter::Filter::value_type`]): a symbolic value that isn't a known
+    /// [`SystemTime`] is a string.
+    #[must_use]
+    pub fn new(value: &str, value_type: ValueType) -> Self {
+        match value_type {
+            ValueType::Number => Self::Number(value.to_string()),
+            ValueType::String => Self::String(value.to_string()),
+            ValueType::Boolean => Self::Boolean(value.eq_ignore_ascii_case("true")),
+            ValueType::Null => Self::Null,
+            ValueType::Date => Self::Date(value.to_string()),
+            ValueType::Time => Self::Time(value.to_string()),
+            ValueType::Timestamp => Self::Timestamp(value.to_string()),
+            ValueType::Symbolic => SystemTime::from_name(value)
+                .map_or_else(|| Self::String(value.to_string()), Self::SystemTime),
+        }
+    }
+
+    // the literal as an expression of the SQL parser, with the strings escaped for `dialect`
+    pub(crate) fn to_ast(&self, dialect: Dialect) -> ast::Expr {
+        //the quoted literals are already escaped, they're rendered as is
+        let typed = |data_type, value| {
+            ast::Value::UnQuotedString(format!("{data_type} {}", quote_literal(value, dialect)))
+        };
+        let value = match self {
+            Self::Number(value) => ast::Value::Number(value.clone(), false),
+            Self::String(value) => ast::Value::UnQuotedString(quote_literal(value, dialect)),
+            Self::Boolean(value) => ast::Value::Boolean(*value),
+            Self::Null => ast::Value::Null,
+            Self::Date(value) => typed("DATE", value),
+            Self::Time(value) => typed("TIME", value),
+            Self::Timestamp(value) => typed("TIMESTAMP", value),
+            Self::SystemTime(time) => ast::Value::UnQuotedString(time.sql_name().to_string()),
+        };
+        ast::Expr::Value(value)
+    }
+}
+
+/// A comparison operator of an [`Expression`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    /// `=`.
+    Eq,
+    /// `<>`.
+    NotEq,
+    /// `<`.
+    Lt,
+    /// `<=`.
+    LtEq,
+    /// `>`.
+    Gt,
+    /// `>=`.
+    GtEq,
+}
+
+impl Operator {
+    const fn to_ast(self) -> ast::BinaryOperator {
+        match self {
+            Self::Eq => ast::BinaryOperator::Eq,
+            Self::NotEq => ast::BinaryOperator::NotEq,
+            Self::Lt => ast::BinaryOperator::Lt,
+            Self::LtEq => ast::BinaryOperator::LtEq,
+            Self::Gt => ast::BinaryOperator::Gt,
+            Self::GtEq => ast::BinaryOperator::GtEq,
+        }
+    }
+}
+
+/// An expression of the SQL subset the metadata represents, e.g. the condition of a filter
+/// (see [`crate::filter::Filter::expression`]).
+///
+/// Unlike the AST of the SQL parser, it doesn't change when the parser is upgraded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Expression {
+    /// A column.
+    Column(Identifier),
+    /// A literal.
+    Value(Value),
+    /// The field `key` of a JSON object, as JSON (`object -> 'key'`) or as text
+    /// (`object ->> 'key'`).
+    JsonField {
+        /// The JSON object.
+        object: Box<Self>,
+        /// The key of the field.
+        key: String,
+        /// Whether the field is accessed as text.
+        as_text: bool,
+    },
+    /// The element of an array at `index`, e.g. `tags[1]`.
+    Element {
+        /// The array.
+        array: Box<Self>,
+        /// The index of the element.
+        index: i64,
+    },
+    /// An expression with a collation, e.g. `name COLLATE "de_DE"`.
+    Collate {
+        /// The collated expression.
+        expr: Box<Self>,
+        /// The name of the collation, as stored (see [`crate::filter::Filter::collation`]).
+        collation: String,
+    },
+    /// A comparison, e.g. `a >= 10`.
+    Compare {
+        /// The left operand.
+        left: Box<Self>,
+        /// The operator.
+        op: Operator,
+        /// The right operand.
+        right: Box<Self>,
+    },
+    /// A comparison with any element of an array (`left = ANY(array)`) or every one of them
+    /// (`left <> ALL(array)`).
+    Quantified {
+        /// The compared operand.
+        left: Box<Self>,
+        /// The operator.
+        op: Operator,
+        /// Whether any element is compared, rather than every one of them.
+        any: bool,
+        /// The array.
+        array: Box<Self>,
+    },
+    /// A check of an expression against `NULL`, `TRUE` or `FALSE`, e.g. `a IS NOT NULL`.
+    Is {
+        /// The checked expression.
+        expr: Box<Self>,
+        /// The value checked, i.e. [`Value::Null`] or a [`Value::Boolean`].
+        value: Value,
+        /// Whether the check is negated (`IS NOT`).
+        negated: bool,
+    },
+}
+
+impl Expression {
+    /// Renders the expression as SQL of `dialect`, with the identifiers quoted only when they
+    /// wouldn't read back the same otherwise and the strings escaped.
+    #[must_use]
+    pub fn to_sql(&self, dialect: Dialect) -> String {
+        self.to_ast(dialect, &|column| column.render(dialect, None))
+            .to_string()
+    }
+
+    // the expression as an expression of the SQL parser, with its columns rendered by `column`;
+    // the recursion is bounded by the depth of the built expressions
+    pub(crate) fn to_ast(
+        &self,
+        dialect: Dialect,
+        column: &dyn Fn(&Identifier) -> ast::Ident,
+    ) -> ast::Expr {
+        let to_ast = |expr: &Self| Box::new(expr.to_ast(dialect, column));
+        match self {
+            Self::Column(name) => ast::Expr::Identifier(column(name)),
+            Self::Value(value) => value.to_ast(dialect),
+            Self::JsonField {
+                object,
+                key,
+                as_text,
+            } => ast::Expr::JsonAccess {
+                left: to_ast(object),
+                operator: if *as_text {
+                    ast::JsonOperator::LongArrow
+                } else {
+                    ast::JsonOperator::Arrow
+                },
+                right: Box::new(ast::Expr::Value(ast::Value::SingleQuotedString(
+                    key.clone(),
+                ))),
+            },
+            Self::Element { array, index } => ast::Expr::ArrayIndex {
+                obj: to_ast(array),
+                indexes: vec![ast::Expr::Value(ast::Value::Number(
+                    index.to_string(),
+                    false,
+                ))],
+            },
+            Self::Collate { expr, collation } => {
+                let parts = collation
+                    .split('.')
+                    .map(|part| Identifier::new(part).to_ident(dialect, None));
+                ast::Expr::Collate {
+                    expr: to_ast(expr),
+                    collation: ast::ObjectName(parts.collect()),
+                }
+            }
+            Self::Compare { left, op, right } => ast::Expr::BinaryOp {
+                left: to_ast(left),
+                op: op.to_ast(),
+                right: to_ast(right),
+            },
+            Self::Quantified {
+                left,
+                op,
+                any: true,
+                array,
+            } => ast::Expr::AnyOp {
+                left: to_ast(left),
+                compare_op: op.to_ast(),
+                right: to_ast(array),
+            },
+            Self::Quantified {
+                left,
+                op,
+                any: false,
+                array,
+            } => ast::Expr::AllOp {
+                left: to_ast(left),
+                compare_op: op.to_ast(),
+                right: to_ast(array),
+            },
+            Self::Is {
+                expr,
+                value,
+                negated,
+            } => {
+                let expr = to_ast(expr);
+                match (value, negated) {
+                    (Value::Boolean(true), false) => ast::Expr::IsTrue(expr),
+                    (Value::Boolean(true), true) => ast::Expr::IsNotTrue(expr),
+                    (Value::Boolean(false), false) => ast::Expr::IsFalse(expr),
+                    (Value::Boolean(false), true) => ast::Expr::IsNotFalse(expr),
+                    (_, false) => ast::Expr::IsNull(expr),
+                    (_, true) => ast::Expr::IsNotNull(expr),
+                }
+            }
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_sql(Dialect::Generic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expression, Operator, Value};
+    use crate::{
+        comparison::{CompareOp, SystemTime, ValueType},
+        dialect::Dialect,
+        filter::Filter,
+    };
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            Value::new("-2.5", ValueType::Number),
+            Value::Number("-2.5".to_string())
+        );
+        assert_eq!(Value::new("TRUE", ValueType::Boolean), Value::Boolean(true));
+        assert_eq!(
+            Value::new("now", ValueType::Symbolic),
+            Value::SystemTime(SystemTime::CurrentTimestamp)
+        );
+        //an unknown point in time stays a string
+        assert_eq!(
+            Value::new("tomorrow", ValueType::Symbolic),
+            Value::String("tomorrow".to_string())
+        );
+    }
+
+    #[test]
+    fn filter_expression() {
+        let filter = Filter {
+            column: "payload".into(),
+            comparison: CompareOp::NotEq {
+                value: "it's".to_string(),
+            },
+            value_type: Some(ValueType::String),
+            json_path: Some(vec!["a".to_string(), "b".to_string()]),
+            collation: Some("C".to_string()),
+        };
+        let expression = filter.expression();
+        let Expression::Compare { left, op, right } = &expression else {
+            panic!("not a comparison: {expression:?}");
+        };
+        assert_eq!(*op, Operator::NotEq);
+        assert_eq!(
+            **right,
+            Expression::Value(Value::String("it's".to_string()))
+        );
+        assert!(matches!(**left, Expression::Collate { .. }));
+        assert_eq!(
+            expression.to_sql(Dialect::PostgreSql),
+            "payload -> 'a' ->> 'b' COLLATE \"C\" <> 'it''s'"
+        );
+        assert_eq!(
+            expression.to_string(),
+            filter.to_sql(Dialect::Generic, None)
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{
     comparison::{
-        self, is_binary_operator_supported, is_expression_supported, CompareOp, ComparisonOperand,
+        self, compare_constants, is_binary_operator_supported, is_expression_supported,
+        normalize_number, rebalance_json_access, CompareOp, ComparisonOperand, SystemTime,
+        ValueType,
     },
-    error::ParseError,
+    dialect::{CaseFolding, Dialect},
+    error::{ParseError, UnsupportedFeature},
+    expression::Expression,
+    identifier::Identifier,
     query_metadata::FromClauseIdentifier,
-    support::remove_outer_parens,
+    support::{check_literal, remove_outer_parens},
 };
 
 use serde::{Deserialize, Serialize};
@@ -15,30 +20,108 @@ use crate::unsupported;
 
 pub(crate) struct FilterExtractor<'a> {
     from_clause_identifier: FromClauseIdentifier<'a>,
+    case_folding: CaseFolding,
 }
 
 impl<'a> FilterExtractor<'a> {
-    pub(crate) const fn new(from_clause_identifier: FromClauseIdentifier<'a>) -> Self {
+    pub(crate) const fn new(
+        from_clause_identifier: FromClauseIdentifier<'a>,
+        case_folding: CaseFolding,
+    ) -> Self {
         Self {
             from_clause_identifier,
+            case_folding,
         }
     }
 
-    pub(crate) fn extract(&self, selection: &ast::Expr) -> Result<Filter, ParseError> {
+    // extracts the filter of the WHERE clause, where the predicates of constants (e.g. `1 = 1`)
+    // are evaluated, and dropped if they're combined with the filter, e.g. by a query generator
+    // writing `1 = 1 AND a > 0`; the filters combined with AND or OR are only accepted if they
+    // reduce to one of them, e.g. `a > 1 AND a > 2` to `a > 2`; the filters dropped along with a
+    // constant (e.g. `b > 1` in `1 = 0 AND b > 1`) are pushed to `dropped`, so that their columns
+    // are still checked; the recursion is bounded by the recursion limit of the SQL parser
+    pub(crate) fn extract(
+        &self,
+        selection: &ast::Expr,
+        dropped: &mut Vec<Filter>,
+    ) -> Result<Predicate, ParseError> {
         let selection = remove_outer_parens(selection);
         match selection {
+            ast::Expr::BinaryOp {
+                left,
+                op: op @ (ast::BinaryOperator::And | ast::BinaryOperator::Or),
+                right,
+            } => {
+                let and = *op == ast::BinaryOperator::And;
+                match (self.extract(left, dropped), self.extract(right, dropped)) {
+                    //TRUE AND p and FALSE OR p are p, FALSE AND p and TRUE OR p are constants
+                    (Ok(Predicate::Constant(holds)), other)
+                    | (other, Ok(Predicate::Constant(holds))) => {
+                        if holds == and {
+                            other
+                        } else {
+                            other.map(|predicate| {
+                                if let Predicate::Filter(filter) = predicate {
+                                    dropped.push(filter);
+                                }
+                                Predicate::Constant(holds)
+                            })
+                        }
+                    }
+                    //the narrower filter of an AND, the wider one of an OR
+                    (Ok(Predicate::Filter(left)), Ok(Predicate::Filter(right)))
+                        if left.implies(&right) || right.implies(&left) =>
+                    {
+                        let (narrower, wider) = if left.implies(&right) {
+                            (left, right)
+                        } else {
+                            (right, left)
+                        };
+                        Ok(Predicate::Filter(if and { narrower } else { wider }))
+                    }
+                    _ => Err(unsupported!(
+                        UnsupportedFeature::Operator,
+                        format!("the {op} operator.")
+                    )),
+                }
+            }
+            ast::Expr::Value(ast::Value::Boolean(holds)) => Ok(Predicate::Constant(*holds)),
+            ast::Expr::JsonAccess { .. } => match rebalance_json_access(selection.clone()) {
+                ast::Expr::JsonAccess { .. } => Err(unsupported!(
+                    UnsupportedFeature::Expression,
+                    format!("unsupported expression in the WHERE clause: {selection}.")
+                )),
+                rebalanced => self.extract(&rebalanced, dropped),
+            },
             ast::Expr::BinaryOp { left, op, right } => {
                 self.extract_binary_comparison(selection, left, op, right)
             }
+            ast::Expr::AnyOp {
+                left,
+                compare_op,
+                right,
+            } => self
+                .extract_array_comparison(selection, left, compare_op, right, true)
+                .map(Predicate::Filter),
+            ast::Expr::AllOp {
+                left,
+                compare_op,
+                right,
+            } => self
+                .extract_array_comparison(selection, left, compare_op, right, false)
+                .map(Predicate::Filter),
             ast::Expr::IsNull(op)
             | ast::Expr::IsNotNull(op)
             | ast::Expr::IsTrue(op)
             | ast::Expr::IsNotTrue(op)
             | ast::Expr::IsFalse(op)
-            | ast::Expr::IsNotFalse(op) => self.extract_unary_comparison(selection, op),
-            _ => Err(unsupported!(format!(
-                "unsupported expression in the WHERE clause: {selection}."
-            ))),
+            | ast::Expr::IsNotFalse(op) => self
+                .extract_unary_comparison(selection, op)
+                .map(Predicate::Filter),
+            _ => Err(unsupported!(
+                UnsupportedFeature::Expression,
+                format!("unsupported expression in the WHERE clause: {selection}.")
+            )),
         }
     }
 
@@ -53,22 +136,178 @@ impl<'a> FilterExtractor<'a> {
         left: &ast::Expr,
         op: &ast::BinaryOperator,
         right: &ast::Expr,
-    ) -> Result<Filter, ParseError> {
+    ) -> Result<Predicate, ParseError> {
         if !is_binary_operator_supported(op) {
-            return Err(unsupported!(format!("the {op} operator.")));
+            return Err(unsupported!(
+                UnsupportedFeature::Operator,
+                format!("the {op} operator.")
+            ));
+        }
+        if let Some(filter) = self.extract_element_comparison(binary_expr, left, op, right)? {
+            return Ok(Predicate::Filter(filter));
         }
         //extract left operand and identify if it is a column or other
-        let left = ComparisonOperand::from_expression(self.from_clause_identifier, left)?;
+        let (left, left_collation) = ComparisonOperand::from_expression(
+            self.from_clause_identifier,
+            left,
+            self.case_folding,
+        )?;
         //extract right operand and identify if it is a column or other
-        let right = ComparisonOperand::from_expression(self.from_clause_identifier, right)?;
+        let (right, right_collation) = ComparisonOperand::from_expression(
+            self.from_clause_identifier,
+            right,
+            self.case_folding,
+        )?;
+        //the collation applies to the comparison, whichever operand it's given to
+        let collation = match (left_collation, right_collation) {
+            (Some(left), Some(right)) if left != right => {
+                return Err(unsupported!(
+                    UnsupportedFeature::Comparison,
+                    format!("{binary_expr}. The operands have different collations.")
+                ));
+            }
+            (left, right) => left.or(right),
+        };
+        //the constants are compared without collation
+        if let (ComparisonOperand::Other(left), ComparisonOperand::Other(right), None) =
+            (&left, &right, &collation)
+        {
+            let left = Self::extract_checked_value(left);
+            let right = Self::extract_checked_value(right);
+            if let (Ok((left, left_type)), Ok((right, right_type))) = (left, right) {
+                let holds = compare_constants(op, (&left, left_type), (&right, right_type));
+                if let Some(holds) = holds {
+                    return Ok(Predicate::Constant(holds));
+                }
+            }
+        }
         //analyze extracted operand and eventually reverse them
-        let (column, value, reverse) =
+        let (column, json_path, value, reverse) =
             comparison::analyze_comparison_operands(binary_expr, left, right)?;
+        let json_path = json_path.map(Self::checked_json_path).transpose()?;
 
-        let comparison =
-            CompareOp::from_binary_operator(op, Self::extract_constant_value(value)?, reverse)?;
+        let (value, value_type) = Self::extract_checked_value(value)?;
+        let comparison = CompareOp::from_binary_operator(op, value, reverse)?;
 
-        Ok(Filter { column, comparison })
+        Ok(Predicate::Filter(Filter {
+            column,
+            comparison,
+            value_type: Some(value_type),
+            json_path,
+            collation,
+        }))
+    }
+
+    // analyze and extract `column[index] OP value`, where OP is = or <>, with the operands in any
+    // order; none if neither operand is an array element
+    fn extract_element_comparison(
+        &self,
+        binary_expr: &ast::Expr,
+        left: &ast::Expr,
+        op: &ast::BinaryOperator,
+        right: &ast::Expr,
+    ) -> Result<Option<Filter>, ParseError> {
+        let ((array, index), value) = match (array_element(left), array_element(right)) {
+            (Some(element), None) => (element, right),
+            (None, Some(element)) => (element, left),
+            _ => return Ok(None),
+        };
+        let column = self.array_column(binary_expr, array)?;
+        let index = match Self::extract_checked_value(index)? {
+            (index, ValueType::Number) => index.parse().ok(),
+            _ => None,
+        };
+        let Some(index) = index else {
+            return Err(unsupported!(
+                UnsupportedFeature::Value,
+                format!("{binary_expr}. The array index must be an integer.")
+            ));
+        };
+        let (value, value_type) = Self::extract_checked_value(value)?;
+        let comparison = match op {
+            ast::BinaryOperator::Eq => CompareOp::ElementEq { index, value },
+            ast::BinaryOperator::NotEq => CompareOp::ElementNotEq { index, value },
+            _ => {
+                return Err(unsupported!(
+                    UnsupportedFeature::Comparison,
+                    format!("{binary_expr}. Array elements can only be compared with = or <>.")
+                ))
+            }
+        };
+        Ok(Some(Filter {
+            column,
+            comparison,
+            value_type: Some(value_type),
+            json_path: None,
+            collation: None,
+        }))
+    }
+
+    // analyze and extract `value = ANY(column)` or `value <> ALL(column)`
+    fn extract_array_comparison(
+        &self,
+        array_expr: &ast::Expr,
+        value: &ast::Expr,
+        op: &ast::BinaryOperator,
+        array: &ast::Expr,
+        any: bool,
+    ) -> Result<Filter, ParseError> {
+        let column = self.array_column(array_expr, array)?;
+        let (value, value_type) = Self::extract_checked_value(value)?;
+        let comparison = match (op, any) {
+            (ast::BinaryOperator::Eq, true) => CompareOp::AnyEq { value },
+            (ast::BinaryOperator::NotEq, false) => CompareOp::AllNotEq { value },
+            _ => {
+                return Err(unsupported!(
+                    UnsupportedFeature::Operator,
+                    format!("the {op} {} operator.", if any { "ANY" } else { "ALL" })
+                ))
+            }
+        };
+        Ok(Filter {
+            column,
+            comparison,
+            value_type: Some(value_type),
+            json_path: None,
+            collation: None,
+        })
+    }
+
+    // the array column of an element access or of ANY/ALL
+    fn array_column(
+        &self,
+        array_expr: &ast::Expr,
+        array: &ast::Expr,
+    ) -> Result<Identifier, ParseError> {
+        match ComparisonOperand::from_expression(
+            self.from_clause_identifier,
+            array,
+            self.case_folding,
+        )? {
+            (ComparisonOperand::Column(column, None), None) => Ok(column),
+            _ => Err(unsupported!(
+                UnsupportedFeature::Comparison,
+                format!("{array_expr}. Only the arrays of a column can be compared.")
+            )),
+        }
+    }
+
+    // evaluates a constant expression, with its number normalized
+    fn extract_checked_value(expr: &ast::Expr) -> Result<(String, ValueType), ParseError> {
+        let (mut value, value_type) = Self::extract_constant_value(expr)?;
+        //the value may be embedded back in the generated queries, or bound as a parameter
+        match value_type {
+            ValueType::String => check_literal(&value)?,
+            ValueType::Number => value = normalize_number(&value)?,
+            _ => (),
+        }
+        Ok((value, value_type))
+    }
+
+    // checks the keys of a JSON path, which are embedded back in the generated queries
+    fn checked_json_path(path: Vec<String>) -> Result<Vec<String>, ParseError> {
+        path.iter().try_for_each(|key| check_literal(key))?;
+        Ok(path)
     }
 
     // analyze and extract IS_NULL or IS_NOT_NULL
@@ -78,46 +317,95 @@ impl<'a> FilterExtractor<'a> {
         applied_on: &ast::Expr,
     ) -> Result<Filter, ParseError> {
         if !is_expression_supported(single_filter_expr) {
-            return Err(unsupported!(format!("the {single_filter_expr} operator.")));
+            return Err(unsupported!(
+                UnsupportedFeature::Operator,
+                format!("the {single_filter_expr} operator.")
+            ));
         }
 
-        let column: ComparisonOperand<'_> =
-            ComparisonOperand::from_expression(self.from_clause_identifier, applied_on)?;
+        let (column, collation) = ComparisonOperand::from_expression(
+            self.from_clause_identifier,
+            applied_on,
+            self.case_folding,
+        )?;
 
-        let ComparisonOperand::Column(column) = column else {
-            return Err(unsupported!(format!(
-                "{single_filter_expr}. Column must be specified.",
-            )));
+        let ComparisonOperand::Column(column, json_path) = column else {
+            return Err(unsupported!(
+                UnsupportedFeature::Comparison,
+                format!("{single_filter_expr}. Column must be specified.",)
+            ));
         };
 
         let comparison = CompareOp::from_expr(single_filter_expr)?;
+        let json_path = json_path.map(Self::checked_json_path).transpose()?;
 
-        Ok(Filter { column, comparison })
+        Ok(Filter {
+            column,
+            comparison,
+            value_type: None,
+            json_path,
+            collation,
+        })
     }
 
-    fn extract_constant_value(expr: &ast::Expr) -> Result<String, ParseError> {
+    // evaluates a constant expression: a literal, possibly in parentheses, with signs over
+    // numbers, or the concatenation of strings; the recursion is bounded by the recursion limit
+    // of the SQL parser
+    fn extract_constant_value(expr: &ast::Expr) -> Result<(String, ValueType), ParseError> {
+        let not_a_value = || {
+            Err(unsupported!(
+                UnsupportedFeature::Value,
+                format!("Expected a value, got {expr}")
+            ))
+        };
         let value = match expr {
+            ast::Expr::Nested(nested) => return Self::extract_constant_value(nested),
             ast::Expr::UnaryOp {
-                op,
-                expr: unary_op_expr,
+                op: op @ (ast::UnaryOperator::Plus | ast::UnaryOperator::Minus),
+                expr: operand,
             } => {
-                let sign = match op {
-                    ast::UnaryOperator::Plus => None,
-                    ast::UnaryOperator::Minus => Some("-"),
-                    _ => return Err(unsupported!(format!("Expected a value, got {expr}"))),
+                let (value, value_type) = Self::extract_constant_value(operand)?;
+                if value_type != ValueType::Number {
+                    return not_a_value();
+                }
+                let value = match (op, value.strip_prefix('-')) {
+                    (ast::UnaryOperator::Minus, Some(positive)) => positive.to_string(),
+                    (ast::UnaryOperator::Minus, None) => format!("-{value}"),
+                    _ => value,
                 };
-                let ast::Expr::Value(ast::Value::Number(val, _)) = unary_op_expr.as_ref() else {
-                    return Err(unsupported!(format!("Expected a value, got {expr}")));
+                return Ok((value, ValueType::Number));
+            }
+            ast::Expr::BinaryOp {
+                left,
+                op: ast::BinaryOperator::StringConcat,
+                right,
+            } => {
+                let (left, left_type) = Self::extract_constant_value(left)?;
+                let (right, right_type) = Self::extract_constant_value(right)?;
+                if left_type != ValueType::String || right_type != ValueType::String {
+                    return not_a_value();
+                }
+                return Ok((left + &right, ValueType::String));
+            }
+            ast::Expr::TypedString { data_type, value } => {
+                let Some(value_type) = ValueType::of_typed_string(data_type) else {
+                    return not_a_value();
                 };
-                return Ok(format!("{}{val}", sign.unwrap_or_default()));
+                value_type.check(value)?;
+                return Ok((value.clone(), value_type));
+            }
+            ast::Expr::Function(function) => {
+                return SystemTime::from_function(function).map_or_else(not_a_value, |time| {
+                    Ok((time.sql_name().to_string(), ValueType::Symbolic))
+                });
             }
             ast::Expr::Value(val) => val,
-            _ => return Err(unsupported!(format!("Expected a value, got {expr}"))),
+            _ => return not_a_value(),
         };
 
         match value {
-            ast::Value::Number(val, _)
-            | ast::Value::SingleQuotedString(val)
+            ast::Value::Number(val, _) => Ok((val.clone(), ValueType::Number)),
+            ast::Value::SingleQuotedString(val)
             | ast::Value::EscapedStringLiteral(val)
             | ast::Value::SingleQuotedByteStringLiteral(val)
             | ast::Value::DoubleQuotedByteStringLiteral(val)
@@ -125,22 +413,160 @@ impl<'a> FilterExtractor<'a> {
             | ast::Value::NationalStringLiteral(val)
             | ast::Value::HexStringLiteral(val)
             | ast::Value::DoubleQuotedString(val)
-            | ast::Value::UnQuotedString(val) => Ok(val.clone()),
-            ast::Value::Boolean(val) => Ok(val.to_string()),
-            ast::Value::Null => Ok("Null".to_string()),
-            ast::Value::Placeholder(val) => {
-                Err(unsupported!(format!("Expected a value, got {val}")))
-            }
-            ast::Value::DollarQuotedString(val) => Ok(val.value.clone()),
+            | ast::Value::UnQuotedString(val) => Ok((val.clone(), ValueType::String)),
+            ast::Value::Boolean(val) => Ok((val.to_string(), ValueType::Boolean)),
+            ast::Value::Null => Ok(("Null".to_string(), ValueType::Null)),
+            ast::Value::Placeholder(val) => Err(unsupported!(
+                UnsupportedFeature::Value,
+                format!("Expected a value, got {val}")
+            )),
+            ast::Value::DollarQuotedString(val) => Ok((val.value.clone(), ValueType::String)),
+        }
+    }
+}
+
+// a predicate of the WHERE clause: a filter, or a constant once evaluated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Predicate {
+    Filter(Filter),
+    Constant(bool),
+}
+
+impl Predicate {
+    // the filter, if any, and whether the predicate never holds
+    pub(crate) fn into_filter(self) -> (Option<Filter>, bool) {
+        match self {
+            Self::Filter(filter) => (Some(filter), false),
+            Self::Constant(holds) => (None, !holds),
         }
     }
 }
 
+// splits the access to an element of an array, e.g. `tags[1]`, into the array and the index
+fn array_element(expr: &ast::Expr) -> Option<(&ast::Expr, &ast::Expr)> {
+    match remove_outer_parens(expr) {
+        ast::Expr::ArrayIndex { obj, indexes } => match &indexes[..] {
+            [index] => Some((obj, index)),
+            _ => None,
+        },
+        //some dialects parse the subscripts as map accesses
+        ast::Expr::MapAccess { column, keys } => match &keys[..] {
+            [index] => Some((column, index)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 /// Contains information related to the filter applied in the query parsed.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema, IntoParams)]
+///
+/// It's serialized as an object whose comparison is tagged with its `type`, e.g.
+///
+/// ```json
+/// {
+///   "column": "test_column_2",
+///   "comparison": { "type": "GtEq", "value": "10" },
+///   "value_type": "Number"
+/// }
+/// ```
+///
+/// With the `camel-case` feature, the fields, the comparisons and the value types are renamed
+/// to camelCase instead (i.e. `valueType`, `gtEq` and `number`).
+///
+/// A filter on a field of a JSON column, e.g. `payload->'a'->>'b' = 'ok'` in PostgreSQL, has
+/// the column `payload` and the `json_path` `["a", "b"]`: the field is compared as text.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema, IntoParams,
+)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
 pub struct Filter {
     /// Column on which the filter is applied.
-    pub column: String,
+    #[schema(value_type = String)]
+    #[param(value_type = String)]
+    pub column: Identifier,
     /// Operation applied to the column.
     pub comparison: CompareOp,
+    /// Kind of literal the compared value was written as (`None` for unary checks such as
+    /// `IS NULL`).
+    #[serde(default)]
+    pub value_type: Option<ValueType>,
+    /// Path of the JSON field of the column the filter applies to, if any (e.g. `["status"]`
+    /// for `payload->>'status'`), left out of the serialized filter if there's none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_path: Option<Vec<String>>,
+    /// Collation the column is compared with, if any (e.g. `de_DE` for
+    /// `name = 'x' COLLATE "de_DE"`), as stored: case folded, with the parts of a qualified
+    /// name joined by dots. It's left out of the serialized filter if there's none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<String>,
+}
+
+impl Filter {
+    /// Renders the filter as a SQL condition, e.g. `"Column_2" >= 10`: the column is quoted
+    /// with `quote_style` if given, or only when `dialect` requires it, and string values are
+    /// escaped.
+    #[must_use]
+    pub fn to_sql(&self, dialect: Dialect, quote_style: Option<char>) -> String {
+        self.to_expr(dialect, quote_style).to_string()
+    }
+
+    /// The current date or time the column is compared to, if any (e.g. for
+    /// `created_at >= CURRENT_DATE`), to substitute when the query is run.
+    #[must_use]
+    pub fn system_time(&self) -> Option<SystemTime> {
+        self.value_type
+            .filter(|value_type| *value_type == ValueType::Symbolic)
+            .and_then(|_| self.comparison.value())
+            .and_then(SystemTime::from_name)
+    }
+
+    /// Returns `true` if every row matching the filter also matches `other`, e.g. `a > 2`
+    /// implies `a > 1` (see [`CompareOp::implies`]).
+    ///
+    /// The filters must apply to the same column (and JSON field), with the same collation, and
+    /// compare it to values of the same type; only the numbers are ordered.
+    #[must_use]
+    pub fn implies(&self, other: &Self) -> bool {
+        let values = self.comparison.value().zip(other.comparison.value());
+        if self.column != other.column
+            || self.json_path != other.json_path
+            || self.collation != other.collation
+            || values.is_some() && self.value_type != other.value_type
+        {
+            return false;
+        }
+        let numbers = values.is_some() && self.value_type == Some(ValueType::Number);
+        self.comparison.implies(&other.comparison, numbers)
+    }
+
+    /// The condition of the filter as an expression, e.g. to inspect or render it without
+    /// depending on the AST of the SQL parser.
+    #[must_use]
+    pub fn expression(&self) -> Expression {
+        let column = Expression::Column(self.column.clone());
+        //the JSON field is accessed as JSON up to its last key, as text at the last one
+        let keys = self.json_path.iter().flatten();
+        let last = self.json_path.as_ref().map_or(0, Vec::len);
+        let operand = keys
+            .enumerate()
+            .fold(column, |object, (i, key)| Expression::JsonField {
+                object: Box::new(object),
+                key: key.clone(),
+                as_text: i + 1 == last,
+            });
+        let operand = match &self.collation {
+            Some(collation) => Expression::Collate {
+                expr: Box::new(operand),
+                collation: collation.clone(),
+            },
+            None => operand,
+        };
+        self.comparison.expression(&operand, self.value_type)
+    }
+
+    // rebuilds the filter as a WHERE clause expression
+    pub(crate) fn to_expr(&self, dialect: Dialect, quote_style: Option<char>) -> ast::Expr {
+        self.expression()
+            .to_ast(dialect, &|column| column.render(dialect, quote_style))
+    }
 }
@@ -0,0 +1,396 @@
+use std::{
+    borrow::Borrow,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlparser::ast;
+
+use crate::{
+    dialect::{CaseFolding, Dialect},
+    error::{ErrorCode, ParseError},
+    malformed_query,
+    support::{needs_quotes, quote_identifier, render_identifier},
+};
+
+/// A name of a query (e.g. of a column or a table), both as written and as case folded.
+///
+/// The folded name identifies it: two identifiers are equal if their folded names are, and the
+/// identifier is displayed and serialized as its folded name, a plain string. The original
+/// spelling is kept for the error messages and the regenerated SQL.
+#[derive(Clone, Debug, Default)]
+pub struct Identifier {
+    /// The name as written in the query, without the quotes.
+    pub raw: String,
+    /// The name once case folded, as it's stored (see [`CaseFolding`]).
+    pub folded: String,
+    /// The character the name was quoted with, if any.
+    pub quoted: Option<char>,
+}
+
+impl Identifier {
+    /// Creates an unquoted identifier, already spelled as it's stored.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            raw: name.clone(),
+            folded: name,
+            quoted: None,
+        }
+    }
+
+    // folds the identifier of a query: unquoted identifiers are folded according to the dialect,
+    // quoted ones are kept as they're written
+    pub(crate) fn fold(ident: &ast::Ident, case_folding: CaseFolding) -> Self {
+        let ast::Ident { value, quote_style } = ident;
+        Self {
+            raw: value.clone(),
+            folded: match quote_style {
+                None => case_folding.fold(value),
+                Some(_) => value.clone(),
+            },
+            quoted: *quote_style,
+        }
+    }
+
+    /// The folded name.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.folded
+    }
+
+    /// The name as written in the query, with its quotes if it was quoted, e.g. for error
+    /// messages.
+    #[must_use]
+    pub fn original(&self) -> String {
+        ast::Ident {
+            value: self.raw.clone(),
+            quote_style: self.quoted,
+        }
+        .to_string()
+    }
+
+    // the stored name, quoted with `quote_style` if given, otherwise as it was in the query,
+    // except that a name that wouldn't read back the same unquoted in `dialect` is always quoted
+    // (e.g. a column `order`, `Mixed` or `say "hi"` that wasn't written in a query)
+    pub(crate) fn to_ident(&self, dialect: Dialect, quote_style: Option<char>) -> ast::Ident {
+        let quote_style = quote_style.or(self.quoted).or_else(|| {
+            let name = &self.folded;
+            (needs_quotes(name)
+                || !dialect.case_folding().is_folded(name)
+                || dialect.is_reserved_keyword(name))
+            .then_some(dialect.identifier_quote())
+        });
+        quote_identifier(&self.folded, quote_style)
+    }
+
+    // renders the identifier so that it reads back as the folded name in `dialect`, keeping its
+    // original spelling when it's unquoted and reads back the same (e.g. `Amount` rather than
+    // `amount` in a dialect that folds to lowercase)
+    pub(crate) fn render(&self, dialect: Dialect, quote_style: Option<char>) -> ast::Ident {
+        let ident = render_identifier(&self.folded, dialect, quote_style);
+        if ident.quote_style.is_none()
+            && self.quoted.is_none()
+            && dialect.case_folding().fold(&self.raw) == self.folded
+        {
+            ast::Ident::new(&self.raw)
+        } else {
+            ident
+        }
+    }
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded == other.folded
+    }
+}
+
+impl Eq for Identifier {}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.folded.hash(state);
+    }
+}
+
+impl PartialEq<str> for Identifier {
+    fn eq(&self, other: &str) -> bool {
+        self.folded == other
+    }
+}
+
+impl PartialEq<&str> for Identifier {
+    fn eq(&self, other: &&str) -> bool {
+        self.folded == *other
+    }
+}
+
+impl PartialEq<String> for Identifier {
+    fn eq(&self, other: &String) -> bool {
+        &self.folded == other
+    }
+}
+
+impl PartialEq<Identifier> for String {
+    fn eq(&self, other: &Identifier) -> bool {
+        *self == other.folded
+    }
+}
+
+impl Borrow<str> for Identifier {
+    fn borrow(&self) -> &str {
+        &self.folded
+    }
+}
+
+impl AsRef<str> for Identifier {
+    fn as_ref(&self) -> &str {
+        &self.folded
+    }
+}
+
+impl From<&str> for Identifier {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Identifier {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.folded)
+    }
+}
+
+impl Serialize for Identifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.folded)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Rules the names of a query must follow, for downstream systems stricter than SQL (see
+/// [`crate::config::ParseOptions::with_identifier_rules`]).
+///
+/// The names are checked as they're stored (i.e. case folded), and the ones breaking a rule are
+/// rejected with [`ParseError::MalformedQuery`]. Names with control characters are always
+/// rejected, the other rules are off by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct IdentifierRules {
+    max_length: Option<usize>,
+    plain_names: bool,
+}
+
+impl IdentifierRules {
+    /// Creates the default rules: any name without control characters.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum length of the names, in bytes (e.g. 63 for PostgreSQL).
+    #[must_use]
+    pub const fn with_max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Sets whether the names must be plain, i.e. match `[A-Za-z_][A-Za-z0-9_$]*`.
+    #[must_use]
+    pub const fn with_plain_names(mut self, plain_names: bool) -> Self {
+        self.plain_names = plain_names;
+        self
+    }
+
+    /// The maximum length of the names, in bytes.
+    #[must_use]
+    pub const fn max_length(&self) -> Option<usize> {
+        self.max_length
+    }
+
+    /// Whether the names must match `[A-Za-z_][A-Za-z0-9_$]*`.
+    #[must_use]
+    pub const fn plain_names(&self) -> bool {
+        self.plain_names
+    }
+
+    // checks each of `names` against the rules
+    pub(crate) fn check<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), ParseError> {
+        names.into_iter().try_for_each(|name| self.check_name(name))
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), ParseError> {
+        let invalid = |reason: String| {
+            Err(malformed_query!(
+                ErrorCode::InvalidIdentifier,
+                format!("the name {name:?} {reason}.")
+            ))
+        };
+        if let Some(c) = name.chars().find(|c| c.is_control()) {
+            return invalid(format!(
+                "contains the control character U+{:04X}",
+                u32::from(c)
+            ));
+        }
+        if let Some(max_length) = self.max_length {
+            if name.len() > max_length {
+                return invalid(format!("is longer than {max_length} bytes"));
+            }
+        }
+        let mut chars = name.chars();
+        let plain = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+        if self.plain_names && !plain {
+            return invalid("isn't made of ASCII letters, digits, `_` and `$`".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlparser::ast;
+
+    use super::{Identifier, IdentifierRules};
+    use crate::{
+        dialect::{CaseFolding, Dialect},
+        error::ErrorCode,
+        table::TabIdent,
+    };
+
+    #[test]
+    fn fold_and_render() {
+        let unquoted = Identifier::fold(&ast::Ident::new("Amount"), CaseFolding::Lowercase);
+        assert_eq!(unquoted.raw, "Amount");
+        assert_eq!(unquoted.folded, "amount");
+        assert_eq!(unquoted, Identifier::new("amount"));
+        assert_eq!(unquoted.original(), "Amount");
+        assert_eq!(
+            unquoted.render(Dialect::Generic, None).to_string(),
+            "Amount"
+        );
+        //the original spelling would be folded differently
+        assert_eq!(
+            unquoted.render(Dialect::Snowflake, None).to_string(),
+            "\"amount\""
+        );
+        assert_eq!(
+            unquoted.render(Dialect::Generic, Some('"')).to_string(),
+            "\"amount\""
+        );
+
+        let quoted = Identifier::fold(
+            &ast::Ident::with_quote('"', "Amount"),
+            CaseFolding::Lowercase,
+        );
+        assert_eq!(quoted.folded, "Amount");
+        assert_eq!(quoted.original(), "\"Amount\"");
+        assert_eq!(quoted.render(Dialect::MySql, None).to_string(), "`Amount`");
+        assert_ne!(quoted, unquoted);
+
+        assert_eq!(serde_json::to_string(&unquoted).unwrap(), "\"amount\"");
+        assert_eq!(
+            serde_json::from_str::<Identifier>("\"amount\"").unwrap(),
+            unquoted
+        );
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let names = [
+            "amount",
+            "Mixed",
+            "order",
+            "key",
+            "with space",
+            "say \"hi\"",
+            "back`tick",
+            "it's",
+            "a]b",
+            "café",
+            "1st",
+            "$x",
+        ];
+        let dialects = [
+            Dialect::Generic,
+            Dialect::PostgreSql,
+            Dialect::MySql,
+            Dialect::Snowflake,
+            Dialect::BigQuery,
+        ];
+        for dialect in dialects {
+            for quote_style in [None, Some(dialect.identifier_quote())] {
+                for name in names {
+                    let stored = Identifier::new(name);
+                    for ident in [
+                        stored.to_ident(dialect, quote_style),
+                        stored.render(dialect, quote_style),
+                    ] {
+                        //parsed back as a table name, the same way as in the queries
+                        let parsed = TabIdent::parse(&ident.to_string(), dialect);
+                        assert_eq!(
+                            parsed.map(|table| table.table.folded),
+                            Ok(name.to_string()),
+                            "{name:?} rendered as {ident} in {dialect:?}"
+                        );
+                    }
+                }
+            }
+        }
+        //the closing bracket is doubled, as in T-SQL
+        let ident = Identifier::new("a]b").to_ident(Dialect::Generic, Some('['));
+        assert_eq!(ident.to_string(), "[a]]b]");
+    }
+
+    #[test]
+    fn identifier_rules() {
+        let code = |rules: IdentifierRules, name: &str| rules.check([name]).err().map(|e| e.code());
+        let default = IdentifierRules::new();
+        assert_eq!(code(default, "with space"), None);
+        assert_eq!(code(default, &"x".repeat(100)), None);
+        assert_eq!(code(default, "a\0b"), Some(ErrorCode::InvalidIdentifier));
+        assert_eq!(code(default, "a\nb"), Some(ErrorCode::InvalidIdentifier));
+
+        let postgres = default.with_max_length(Some(63));
+        assert_eq!(code(postgres, &"x".repeat(63)), None);
+        assert_eq!(
+            code(postgres, &"x".repeat(64)),
+            Some(ErrorCode::InvalidIdentifier)
+        );
+        //the length is in bytes
+        assert_eq!(
+            code(postgres, &"é".repeat(32)),
+            Some(ErrorCode::InvalidIdentifier)
+        );
+
+        let plain = default.with_plain_names(true);
+        for name in ["a", "_", "A_1", "col$2", "_x$"] {
+            assert_eq!(code(plain, name), None, "{name}");
+        }
+        for name in ["", "1a", "$a", "with space", "é", "a-b", "a.b"] {
+            assert_eq!(
+                code(plain, name),
+                Some(ErrorCode::InvalidIdentifier),
+                "{name}"
+            );
+        }
+    }
+}
@@ -1,38 +1,118 @@
 #![allow(clippy::missing_errors_doc, clippy::doc_markdown)]
+
+// declares an item exposing the AST of the SQL parser: it's public with the `raw-ast` feature,
+// and crate-internal otherwise, so that upgrading the parser doesn't break the public API
+macro_rules! raw_ast {
+    ($(#[$attr:meta])* pub $($item:tt)*) => {
+        $(#[$attr])*
+        #[cfg(feature = "raw-ast")]
+        pub $($item)*
+
+        $(#[$attr])*
+        #[cfg(not(feature = "raw-ast"))]
+        #[allow(dead_code)]
+        pub(crate) $($item)*
+    };
+}
+
 pub mod aggregation;
+pub mod annotation;
+pub mod cache;
+pub mod capabilities;
 pub mod comparison;
+pub mod config;
 pub mod destructured_query;
+pub mod dialect;
+pub mod diff;
 pub mod error;
+pub mod expression;
 pub mod filter;
+pub mod identifier;
+pub mod limits;
+pub mod plan;
+pub mod policy;
 pub mod query_metadata;
+pub mod schema;
+pub mod span;
+pub mod stats;
 pub mod support;
 pub mod table;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time_bucket;
+pub mod visitor;
+
+// the options are shared across threads, and the results sent back from them
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<config::ParseOptions>();
+    assert_send_sync::<query_metadata::QueryMetadata>();
+    assert_send_sync::<error::ParseError>();
+};
 
 #[cfg(test)]
 mod tests {
 
-    use crate::query_metadata::QueryMetadata;
-    use crate::table::TabIdent;
-    use crate::{internal, malformed_query, unsupported};
+    use crate::capabilities::supported_features;
+    use crate::config::ParseOptions;
+    use crate::destructured_query::{validate, DestructuredQuery};
+    use crate::dialect::CaseFolding;
+    use crate::dialect::{Dialect, TableQualifier};
+    use crate::identifier::IdentifierRules;
+    use crate::limits::Limits;
+    use crate::policy::{
+        CrossDatabase, FeaturePolicy, Qualification, SensitiveColumns, TablePattern,
+    };
+    use crate::query_metadata::{
+        Aliases, Analysis, PartialQueryMetadata, QueryMetadata, QueryMetadataRef, RenderedQueries,
+        Sample,
+    };
+    use crate::schema::{Catalog, ColumnDef, ColumnType, TableSchema};
+    use crate::span::{Location, Span};
+    use crate::stats::QueryStats;
+    use crate::support::{cast_to_text, format_query, parse_statements, select_query};
+    use crate::table::{TabIdent, TableIdentWithAlias, TableVersion};
+    use crate::time_bucket::{TimeBucket, TimeGranularity, TimeType};
+    use crate::visitor::MetadataVisitor;
+    use crate::{forbidden, internal, malformed_query, unsupported};
+    use sqlparser::{ast, dialect::GenericDialect, parser::Parser};
+    use std::collections::{hash_map::DefaultHasher, HashSet};
+    use std::hash::{Hash, Hasher};
+    use std::sync::{Arc, Mutex};
 
-    use super::aggregation::{Aggregation, KoronFunction};
-    use super::comparison::CompareOp;
-    use super::error::ParseError;
+    use super::aggregation::{Aggregation, KoronFunction, WindowSpec};
+    use super::annotation::Annotation;
+    use super::comparison::{CompareOp, SystemTime, ValueType};
+    use super::error::{ErrorCode, ParseError, UnsupportedFeature};
     use super::filter::Filter;
 
     fn sample_sum() -> Aggregation {
         Aggregation {
             function: KoronFunction::Sum,
-            column: "test_column_2".to_string(),
+            column: "test_column_2".into(),
             alias: None,
+            window: None,
         }
     }
 
     fn sample_tab_ident() -> TabIdent {
         TabIdent {
-            db: Some("test_db".to_string()),
-            schema: Some("test_schema".to_string()),
-            table: "test_table_1".to_string(),
+            db: Some("test_db".into()),
+            schema: Some("test_schema".into()),
+            table: "test_table_1".into(),
+        }
+    }
+
+    fn aliases(tables: &[(&str, &str)], results: &[(&str, &str)]) -> Aliases {
+        let map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(alias, name)| ((*alias).to_string(), (*name).to_string()))
+                .collect()
+        };
+        Aliases {
+            tables: map(tables),
+            results: map(results),
         }
     }
 
@@ -60,18 +140,36 @@ mod tests {
                 ))
             };
 
+            let data_count_query = matches!(
+                function,
+                KoronFunction::Average | KoronFunction::Variance | KoronFunction::StandardDeviation
+            )
+            .then(|| {
+                "SELECT CAST(COUNT(test_column_2) AS TEXT) FROM test_db.test_schema.test_table_1"
+                    .to_string()
+            });
+
             let expected = Ok(QueryMetadata {
                 table: sample_tab_ident(),
                 aggregation: Aggregation {
                     function,
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     alias: None,
+                    window: None,
                 },
                 filter: None,
+                time_bucket: None,
                 data_extraction_query: String::from(
                     "SELECT test_column_2 FROM test_db.test_schema.test_table_1",
                 ),
                 data_aggregation_query,
+                data_count_query,
+                aliases: Aliases::default(),
+                table_version: None,
+                table_hints: Vec::new(),
+                annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
             });
             assert_eq!(
                 QueryMetadata::parse(query, None),
@@ -88,12 +186,20 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: sample_sum(),
             filter: None,
+            time_bucket: None,
             data_extraction_query: String::from(
                 "SELECT test_column_2 FROM test_db.test_schema.test_table_1",
             ),
             data_aggregation_query: Some(String::from(
                 "SELECT CAST(SUM(test_column_2) AS TEXT) FROM test_db.test_schema.test_table_1",
             )),
+            data_count_query: None,
+            aliases: Aliases::default(),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+            empty_result: false,
+            schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -105,8 +211,16 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: sample_sum(),
             filter: None,
+            time_bucket: None,
             data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
             data_aggregation_query: Some(String::from("SELECT CAST((((SUM(test_column_2)))) AS TEXT) FROM test_db.test_schema.test_table_1")),
+            data_count_query: None,
+            aliases: Aliases::default(),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -118,8 +232,16 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: sample_sum(),
             filter: None,
+            time_bucket: None,
             data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
             data_aggregation_query: Some(String::from("SELECT CAST(SUM((((test_column_2)))) AS TEXT) FROM test_db.test_schema.test_table_1")),
+            data_count_query: None,
+            aliases: Aliases::default(),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -131,12 +253,21 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: Aggregation {
                 function: KoronFunction::Sum,
-                column: "test_column_2".to_string(),
+                column: "test_column_2".into(),
                 alias: Some("s".to_string()),
+                window: None,
             },
             filter: None,
+            time_bucket: None,
             data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
             data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) AS s FROM test_db.test_schema.test_table_1")),
+            data_count_query: None,
+            aliases: aliases(&[], &[("s", "SUM(test_column_2)")]),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -148,8 +279,16 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: sample_sum(),
             filter: None,
+            time_bucket: None,
             data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
             data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) FROM test_db.test_schema.test_table_1 AS t")),
+            data_count_query: None,
+            aliases: aliases(&[("t", "test_db.test_schema.test_table_1")], &[]),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -161,12 +300,20 @@ mod tests {
             table: sample_tab_ident(),
             aggregation: sample_sum(),
             filter: None,
+            time_bucket: None,
             data_extraction_query: String::from(
                 "SELECT test_column_2 FROM test_db.test_schema.test_table_1",
             ),
             data_aggregation_query: Some(String::from(
                 "SELECT CAST(sum(test_column_2) AS TEXT) FROM test_db.test_schema.test_table_1",
             )),
+            data_count_query: None,
+            aliases: Aliases::default(),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+            empty_result: false,
+            schema_version: QueryMetadata::SCHEMA_VERSION,
         });
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
@@ -175,97 +322,3722 @@ mod tests {
     fn quoted_function_case_sensitive() {
         let query = "SELECT \"SUM\"(test_column_2) FROM test_db.test_schema.test_table_1";
         let expected = Err(unsupported!(
+            UnsupportedFeature::UnknownFunction("\"SUM\"".to_string()),
             "unrecognized or unsupported function: \"SUM\".".to_string()
         ));
         assert_eq!(QueryMetadata::parse(query, None), expected);
     }
 
     #[test]
-    fn unquoted_result_alias_case_insensitive() {
-        let query = "SELECT SUM(test_column_2) AS S FROM test_db.test_schema.test_table_1";
-        let expected = Ok(QueryMetadata {
-            table: sample_tab_ident(),
-            aggregation: Aggregation {
-                function: KoronFunction::Sum,
-                column: "test_column_2".to_string(),
-                alias: Some("s".to_string()),
-            },
-            filter: None,
-            data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
-            data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) AS S FROM test_db.test_schema.test_table_1")),
-        });
-        assert_eq!(QueryMetadata::parse(query, None), expected);
+    fn quoted_function_case_insensitive() {
+        let options = ParseOptions::new().with_case_insensitive_functions(true);
+        let query = "SELECT \"SUM\"(test_column_2) FROM test_db.test_schema.test_table_1";
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(metadata.aggregation.function, KoronFunction::Sum);
+
+        let snowflake = options.with_dialect(Dialect::Snowflake);
+        let query = "SELECT \"sum\"(test_column_2) FROM test_table_1";
+        let metadata = QueryMetadata::parse_with(query, &snowflake).unwrap();
+        assert_eq!(metadata.aggregation.function, KoronFunction::Sum);
+
+        //the quoted columns are still case-sensitive
+        assert_eq!(metadata.aggregation.column, "TEST_COLUMN_2");
+        let query = "SELECT \"Sum\"(\"Test_Column_2\") FROM test_table_1";
+        let metadata = QueryMetadata::parse_with(query, &snowflake).unwrap();
+        assert_eq!(metadata.aggregation.column, "Test_Column_2");
     }
 
     #[test]
-    fn quoted_result_alias_case_sensitive() {
-        let query = "SELECT SUM(test_column_2) AS \"S\" FROM test_db.test_schema.test_table_1";
+    fn snowflake_uppercase_folding() {
+        let snowflake = ParseOptions::new().with_dialect(Dialect::Snowflake);
+        let query = "SELECT sum(t.Test_Column_2) AS s FROM test_db.test_schema.test_table_1 AS t \
+                     WHERE test_column_3 > 1";
         let expected = Ok(QueryMetadata {
-            table: sample_tab_ident(),
+            table: TabIdent {
+                db: Some("TEST_DB".into()),
+                schema: Some("TEST_SCHEMA".into()),
+                table: "TEST_TABLE_1".into(),
+            },
             aggregation: Aggregation {
                 function: KoronFunction::Sum,
-                column: "test_column_2".to_string(),
+                column: "TEST_COLUMN_2".into(),
                 alias: Some("S".to_string()),
+                window: None,
             },
-            filter: None,
-            data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
-            data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) AS \"S\" FROM test_db.test_schema.test_table_1")),
+            filter: Some(Filter {
+                column: "TEST_COLUMN_3".into(),
+                comparison: CompareOp::Gt {
+                    value: "1".to_string(),
+                },
+                value_type: Some(ValueType::Number),
+                json_path: None,
+                collation: None,
+            }),
+            time_bucket: None,
+            data_extraction_query: String::from(
+                "SELECT TEST_COLUMN_2, TEST_COLUMN_3 FROM TEST_DB.TEST_SCHEMA.TEST_TABLE_1",
+            ),
+            data_aggregation_query: Some(String::from(
+                "SELECT CAST(sum(t.Test_Column_2) AS TEXT) AS s \
+                 FROM test_db.test_schema.test_table_1 AS t WHERE test_column_3 > 1",
+            )),
+            data_count_query: None,
+            aliases: aliases(
+                &[("T", "TEST_DB.TEST_SCHEMA.TEST_TABLE_1")],
+                &[("S", "SUM(TEST_COLUMN_2)")],
+            ),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+            empty_result: false,
+            schema_version: QueryMetadata::SCHEMA_VERSION,
         });
-        assert_eq!(QueryMetadata::parse(query, None), expected);
+        assert_eq!(QueryMetadata::parse_with(query, &snowflake), expected);
     }
 
     #[test]
-    fn quoted_table_alias_case_sensitive() {
-        for (column, alias, extracted_alias) in [
-            ("t.test_column_2", "\"T\"", "T"),
-            ("\"T\".test_column_2", "\"t\"", "t"),
-        ] {
-            let query =
-                &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1 AS {alias}");
+    fn snowflake_quoted_function_case_sensitive() {
+        let snowflake = ParseOptions::new().with_dialect(Dialect::Snowflake);
+        let query = "SELECT \"SUM\"(test_column_2) FROM test_table_1";
+        let result = QueryMetadata::parse_with(query, &snowflake).unwrap();
+        assert_eq!(result.aggregation.function, KoronFunction::Sum);
 
-            let expected = Err(malformed_query!(format!(
-                "the {column} column is not part of \
-                     the table that's listed in the FROM clause ({extracted_alias}).",
-            )));
+        let query = "SELECT \"sum\"(test_column_2) FROM test_table_1";
+        let expected = Err(unsupported!(
+            UnsupportedFeature::UnknownFunction("\"sum\"".to_string()),
+            "unrecognized or unsupported function: \"sum\".".to_string()
+        ));
+        assert_eq!(QueryMetadata::parse_with(query, &snowflake), expected);
+    }
+
+    #[test]
+    fn snowflake_qualify_rejected() {
+        let snowflake = ParseOptions::new().with_dialect(Dialect::Snowflake);
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 \
+                     QUALIFY ROW_NUMBER() OVER (ORDER BY test_column_2) = 1";
+        let expected = Err(unsupported!(
+            UnsupportedFeature::Qualify,
+            "QUALIFY.".to_string()
+        ));
+        assert_eq!(QueryMetadata::parse_with(query, &snowflake), expected);
+    }
+
+    #[test]
+    fn to_sql_round_trip() {
+        let cases = [
+            (
+                "SELECT sum((test_column_2)) FROM test_db.test_schema.test_table_1",
+                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+            ),
+            (
+                "SELECT AVG(t.test_column_2) AS \"Avg\" FROM test_schema.\"Test_Table\" AS t \
+                 WHERE 'it''s' <> t.test_column_3",
+                "SELECT AVG(test_column_2) AS \"Avg\" FROM test_schema.\"Test_Table\" \
+                 WHERE test_column_3 <> 'it''s'",
+            ),
+            (
+                "SELECT COUNT(test_column_2) FROM test_table_1 WHERE test_column_3 = NULL",
+                "SELECT COUNT(test_column_2) FROM test_table_1 WHERE test_column_3 = NULL",
+            ),
+            (
+                "SELECT MAX(test_column_2) FROM test_table_1 WHERE -1 >= test_column_4",
+                "SELECT MAX(test_column_2) FROM test_table_1 WHERE test_column_4 <= -1",
+            ),
+            (
+                "SELECT MIN(test_column_2) FROM test_table_1 WHERE test_column_5 IS NOT TRUE",
+                "SELECT MIN(test_column_2) FROM test_table_1 WHERE test_column_5 IS NOT TRUE",
+            ),
+        ];
+
+        for (query, expected) in cases {
+            let metadata = QueryMetadata::parse(query, None).unwrap();
+            let sql = metadata.to_sql(Dialect::Generic);
+            assert_eq!(sql, expected, "\nfailed for query {query:?}");
+
+            let reparsed = QueryMetadata::parse(&sql, None).unwrap();
+            assert_eq!(reparsed.aggregation, metadata.aggregation);
+            assert_eq!(reparsed.table, metadata.table);
+            assert_eq!(reparsed.filter, metadata.filter);
+        }
+    }
+
+    #[test]
+    fn generated_queries_as_ast() {
+        let query = "SELECT SUM(t.test_column_2) AS s FROM test_db.test_schema.test_table_1 AS t \
+                     WHERE t.test_column_3 > 1";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+
+        let mut extraction = metadata.data_extraction_ast(Dialect::Generic, None);
+        assert_eq!(extraction.to_string(), metadata.data_extraction_query);
+        extraction.limit = Some(ast::Expr::Value(ast::Value::Number(
+            "10".to_string(),
+            false,
+        )));
+        assert_eq!(
+            extraction.to_string(),
+            "SELECT test_column_2, test_column_3 FROM test_db.test_schema.test_table_1 LIMIT 10"
+        );
+
+        let expected = RenderedQueries {
+            data_extraction_query: String::from(
+                "SELECT \"test_column_2\", \"test_column_3\" \
+                 FROM \"test_db\".\"test_schema\".\"test_table_1\"",
+            ),
+            data_aggregation_query: Some(String::from(
+                "SELECT CAST(SUM(\"test_column_2\") AS TEXT) AS \"s\" \
+                 FROM \"test_db\".\"test_schema\".\"test_table_1\" WHERE \"test_column_3\" > 1",
+            )),
+        };
+        assert_eq!(metadata.render(Dialect::Generic, Some('"')), expected);
+
+        let query = "SELECT MEDIAN(test_column_2) FROM test_db.test_schema.test_table_1";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+        assert_eq!(metadata.data_aggregation_ast(Dialect::Generic, None), None);
+    }
+
+    #[test]
+    fn aggregation_without_cast() {
+        let query = "SELECT SUM(test_column_2) AS s FROM test_db.test_schema.test_table_1 \
+                     WHERE test_column_2 > 1";
+        let options = ParseOptions::new().with_cast_to_text(false);
+        let result = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            result.data_aggregation_query,
+            Some(String::from(
+                "SELECT SUM(test_column_2) AS s FROM test_db.test_schema.test_table_1 \
+                 WHERE test_column_2 > 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn pretty_printed_queries() {
+        let query = "SELECT SUM(test_column_2) AS s FROM test_db.test_schema.test_table_1 \
+                     WHERE test_column_3 > 1";
+        let options = ParseOptions::new().with_pretty_print(true);
+        let result = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            result.data_extraction_query,
+            "SELECT\n    test_column_2,\n    test_column_3\n\
+             FROM\n    test_db.test_schema.test_table_1"
+        );
+        assert_eq!(
+            result.data_aggregation_query,
+            Some(String::from(
+                "SELECT\n    CAST(SUM(test_column_2) AS TEXT) AS s\n\
+                 FROM\n    test_db.test_schema.test_table_1\n\
+                 WHERE\n    test_column_3 > 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn always_quote_identifiers() {
+        let cases = [
+            (
+                Dialect::Generic,
+                "SELECT SUM(\"order\") FROM test_schema.\"select\" WHERE \"Group\" > 1",
+                "SELECT \"order\", \"Group\" FROM \"test_schema\".\"select\"",
+            ),
+            (
+                Dialect::MySql,
+                "SELECT SUM(`order`) FROM test_schema.`select` WHERE `Group` > 1",
+                "SELECT `order`, `Group` FROM `test_schema`.`select`",
+            ),
+        ];
+
+        for (dialect, query, expected) in cases {
+            let options = ParseOptions::new()
+                .with_dialect(dialect)
+                .with_always_quote(true);
+            let result = QueryMetadata::parse_with(query, &options).unwrap();
             assert_eq!(
-                QueryMetadata::parse(query, None),
-                expected,
-                "\nfailed for query {query:?}",
+                result.data_extraction_query, expected,
+                "\nfailed for dialect {dialect:?}"
             );
         }
     }
 
     #[test]
-    fn qualified_column_from_different_table() {
-        for column in [
-            "\"test_table_2\".test_column_2",
-            "\"test_schema\".test_table_2.test_column_2",
-        ] {
-            let query = &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1");
+    fn filter_pushdown() {
+        let cases = [
+            (
+                "test_column_3 = 'it''s'",
+                "SELECT test_column_2, test_column_3 FROM test_db.test_schema.test_table_1 \
+                 WHERE test_column_3 = 'it''s'",
+            ),
+            (
+                "1 < test_column_2",
+                "SELECT test_column_2 FROM test_db.test_schema.test_table_1 \
+                 WHERE test_column_2 > 1",
+            ),
+            (
+                "test_column_5 IS NOT FALSE",
+                "SELECT test_column_2, test_column_5 FROM test_db.test_schema.test_table_1 \
+                 WHERE test_column_5 IS NOT FALSE",
+            ),
+        ];
 
-            let expected = Err(malformed_query!(format!(
-                    "the {column} column is not part of \
-                     the table that's listed in the FROM clause (test_db.test_schema.test_table_1).",
-                )));
+        let options = ParseOptions::new().with_filter_pushdown(true);
+        for (selection, expected) in cases {
+            let query = &format!(
+                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WHERE {selection}"
+            );
+            let result = QueryMetadata::parse_with(query, &options).unwrap();
             assert_eq!(
-                QueryMetadata::parse(query, None),
+                result.data_extraction_query, expected,
+                "\nfailed for selection {selection:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn paged_extraction_query() {
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 \
+                     WHERE test_column_3 > 1";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+
+        assert_eq!(
+            metadata
+                .paged_extraction_query(Dialect::Generic, "id", 1000, None)
+                .unwrap(),
+            "SELECT test_column_2, test_column_3, id FROM test_db.test_schema.test_table_1 \
+             ORDER BY id ASC LIMIT 1000"
+        );
+        assert_eq!(
+            metadata
+                .paged_extraction_query(
+                    Dialect::MySql,
+                    "test_column_3",
+                    1000,
+                    Some(("it's", ValueType::String))
+                )
+                .unwrap(),
+            "SELECT test_column_2, test_column_3 FROM test_db.test_schema.test_table_1 \
+             WHERE test_column_3 > 'it''s' ORDER BY test_column_3 ASC LIMIT 1000"
+        );
+        assert_eq!(
+            metadata.paged_extraction_query(
+                Dialect::Snowflake,
+                "ID",
+                10,
+                Some(("42", ValueType::Number))
+            ).unwrap(),
+            "SELECT \"test_column_2\", \"test_column_3\", ID \
+             FROM \"test_db\".\"test_schema\".\"test_table_1\" WHERE ID > 42 ORDER BY ID ASC LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn sampled_extraction_query() {
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+
+        let cases = [
+            (
+                Dialect::PostgreSql,
+                Sample::Fraction(0.07),
+                "SELECT test_column_2 FROM test_db.test_schema.test_table_1 \
+                 TABLESAMPLE BERNOULLI (7)",
+            ),
+            (
+                Dialect::Snowflake,
+                Sample::Rows(100),
+                "SELECT \"test_column_2\" FROM \"test_db\".\"test_schema\".\"test_table_1\" \
+                 TABLESAMPLE (100 ROWS)",
+            ),
+            (
+                Dialect::PostgreSql,
+                Sample::Rows(100),
+                "SELECT test_column_2 FROM test_db.test_schema.test_table_1 \
+                 ORDER BY RANDOM() LIMIT 100",
+            ),
+            (
+                Dialect::MySql,
+                Sample::Fraction(0.25),
+                "SELECT test_column_2 FROM test_db.test_schema.test_table_1 WHERE RAND() < 0.25",
+            ),
+            (
+                Dialect::Generic,
+                Sample::Rows(5),
+                "SELECT test_column_2 FROM test_db.test_schema.test_table_1 \
+                 ORDER BY RANDOM() LIMIT 5",
+            ),
+        ];
+
+        for (dialect, sample, expected) in cases {
+            assert_eq!(
+                metadata
+                    .sampled_extraction_query(dialect, sample)
+                    .as_deref(),
+                Ok(expected),
+                "\nfailed for {dialect:?} {sample:?}"
+            );
+        }
+
+        //the fractions that aren't between 0 and 1 are rejected, rather than clamped
+        for fraction in [f64::NAN, f64::INFINITY, 1.5, -0.2] {
+            assert_eq!(
+                metadata
+                    .sampled_extraction_query(Dialect::PostgreSql, Sample::Fraction(fraction))
+                    .map_err(|error| error.code()),
+                Err(ErrorCode::InvalidLiteral),
+                "{fraction}"
+            );
+        }
+    }
+
+    #[test]
+    fn dialect_specific_median() {
+        let query = "SELECT MEDIAN(t.test_column_2) AS m FROM test_schema.test_table_1 AS t \
+                     WHERE test_column_3 > 1";
+        let cases = [
+            (Dialect::Generic, None),
+            (Dialect::MySql, None),
+            (
+                Dialect::PostgreSql,
+                Some(
+                    "SELECT CAST(PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY test_column_2) AS TEXT) \
+                     AS m FROM test_schema.test_table_1 AS t WHERE test_column_3 > 1",
+                ),
+            ),
+            (
+                Dialect::Snowflake,
+                Some(
+                    "SELECT CAST(MEDIAN(test_column_2) AS TEXT) AS m \
+                     FROM test_schema.test_table_1 AS t WHERE test_column_3 > 1",
+                ),
+            ),
+            (
+                Dialect::BigQuery,
+                Some(
+                    "SELECT CAST(APPROX_QUANTILES(test_column_2, 2)[OFFSET(1)] AS TEXT) AS m \
+                     FROM test_schema.test_table_1 AS t WHERE test_column_3 > 1",
+                ),
+            ),
+        ];
+
+        for (dialect, expected) in cases {
+            let options = ParseOptions::new().with_dialect(dialect);
+            let result = QueryMetadata::parse_with(query, &options).unwrap();
+            assert_eq!(
+                result.data_aggregation_query.as_deref(),
                 expected,
-                "\nfailed for query {query:?}",
+                "\nfailed for dialect {dialect:?}"
             );
         }
     }
 
     #[test]
-    fn qualified_column_not_from_table_alias() {
-        for column in [
-            "test_table_1.test_column_2",
-            "test_schema.test_table_1.test_column_2",
+    fn count_query() {
+        let query = "SELECT SUM(test_column_1) FROM \"Test_Schema\".test_table_1 \
+                     WHERE test_column_2 = 'x'";
+        let result = QueryMetadata::parse(query, None).unwrap();
+        assert_eq!(
+            result.count_query(Dialect::Generic, None),
+            "SELECT COUNT(*) FROM \"Test_Schema\".test_table_1 WHERE test_column_2 = 'x'"
+        );
+        assert_eq!(
+            result.count_query(Dialect::MySql, Some('`')),
+            "SELECT COUNT(*) FROM `Test_Schema`.`test_table_1` WHERE `test_column_2` = 'x'"
+        );
+
+        let query = "SELECT AVG(test_column_1) FROM test_table_1";
+        let result = QueryMetadata::parse(query, None).unwrap();
+        assert_eq!(
+            result.count_query(Dialect::Generic, None),
+            "SELECT COUNT(*) FROM test_table_1"
+        );
+    }
+
+    #[test]
+    fn query_fingerprint() {
+        let fingerprint = |query: &str, with_literals: bool, with_alias: bool| {
+            QueryMetadata::parse(query, None)
+                .unwrap()
+                .fingerprint(with_literals, with_alias)
+        };
+        let query = "SELECT SUM(t.test_column_1) AS total FROM test_table_1 AS t \
+                     WHERE t.test_column_2 > 10";
+
+        //the way the query is written doesn't matter
+        assert_eq!(
+            fingerprint(query, true, true),
+            fingerprint(
+                "select  sum(TEST_COLUMN_1) as total\nfrom TEST_TABLE_1 where 10 < test_column_2",
+                true,
+                true
+            )
+        );
+        //literals and aliases only matter if asked to
+        let other = "SELECT SUM(test_column_1) AS s FROM test_table_1 WHERE test_column_2 > 20";
+        assert_eq!(
+            fingerprint(query, false, false),
+            fingerprint(other, false, false)
+        );
+        assert_ne!(
+            fingerprint(query, true, false),
+            fingerprint(other, true, false)
+        );
+        assert_ne!(
+            fingerprint(query, false, true),
+            fingerprint(other, false, true)
+        );
+        //the shape always matters
+        let other = "SELECT SUM(test_column_1) FROM test_table_1 WHERE test_column_2 < 10";
+        assert_ne!(
+            fingerprint(query, false, false),
+            fingerprint(other, false, false)
+        );
+        let other = "SELECT SUM(test_column_1) FROM test_table_1";
+        assert_ne!(
+            fingerprint(query, false, false),
+            fingerprint(other, false, false)
+        );
+        assert_ne!(
+            fingerprint(other, true, true),
+            fingerprint(
+                "SELECT SUM(test_column_1) FROM test_table_1 WHERE 1 = 0",
+                true,
+                true
+            )
+        );
+        //the fingerprint is stable
+        assert_eq!(
+            fingerprint("SELECT COUNT(c) FROM t", true, true),
+            0xeaeb_31ef_065e_3b98
+        );
+        assert_eq!(
+            fingerprint(
+                "SELECT COUNT(c) FROM t WHERE d = DATE '2024-01-01'",
+                true,
+                true
+            ),
+            0xed89_71eb_593d_d67a
+        );
+    }
+
+    #[test]
+    fn canonical_form() {
+        let canonicalize = |query: &str| QueryMetadata::parse(query, None).unwrap().canonicalize();
+        let expected = "select sum(test_column_1) as total from test_schema.\"Test_Table\" \
+                        where test_column_2 >= 'it''s'";
+
+        assert_eq!(
+            canonicalize(
+                "SELECT SUM(t.test_column_1) AS total FROM test_schema.\"Test_Table\" AS t \
+                 WHERE t.test_column_2 >= 'it''s'"
+            ),
+            expected
+        );
+        assert_eq!(
+            canonicalize(
+                "select  Sum(((Test_Column_1))) total\nfrom TEST_SCHEMA.\"Test_Table\" \
+                 where ('it''s' <= test_column_2)"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn validate_against_schema() {
+        let schema = TableSchema {
+            columns: vec![
+                ColumnDef {
+                    name: "amount".to_string(),
+                    data_type: ColumnType::Number,
+                },
+                ColumnDef {
+                    name: "name".to_string(),
+                    data_type: ColumnType::Text,
+                },
+                ColumnDef {
+                    name: "active".to_string(),
+                    data_type: ColumnType::Boolean,
+                },
+            ],
+        };
+        let validate = |query: &str| {
+            QueryMetadata::parse(query, None)
+                .unwrap()
+                .validate_against(&schema)
+        };
+
+        assert_eq!(
+            validate("SELECT SUM(amount) FROM t WHERE name = 'x'"),
+            Ok(())
+        );
+        assert_eq!(
+            validate("SELECT COUNT(name) FROM t WHERE active IS TRUE"),
+            Ok(())
+        );
+        assert_eq!(
+            validate("SELECT MAX(name) FROM t WHERE amount > 10"),
+            Ok(())
+        );
+        //a column named like the alias is still the column
+        assert_eq!(
+            validate("SELECT SUM(amount) AS name FROM t WHERE name = 'x'"),
+            Ok(())
+        );
+
+        let cases = [
+            (
+                "SELECT SUM(price) FROM t",
+                ErrorCode::UnknownColumn,
+                "the column `price` doesn't exist in the table.",
+            ),
+            (
+                "SELECT SUM(amount) FROM t WHERE price > 1",
+                ErrorCode::UnknownColumn,
+                "the column `price` doesn't exist in the table.",
+            ),
+            (
+                "SELECT SUM(name) FROM t",
+                ErrorCode::TypeMismatch,
+                "SUM only applies to numeric columns, but `name` is of type text.",
+            ),
+            (
+                "SELECT COUNT(amount) FROM t WHERE name > 1",
+                ErrorCode::TypeMismatch,
+                "the column `name` of type text can't be compared to a number value.",
+            ),
+            (
+                "SELECT COUNT(amount) FROM t WHERE amount IS NOT FALSE",
+                ErrorCode::TypeMismatch,
+                "the column `amount` of type number can't be compared to a boolean value.",
+            ),
+            (
+                "SELECT SUM(amount) AS Total FROM t WHERE Total > 10",
+                ErrorCode::AliasInWhere,
+                "the alias `Total` of SUM(amount) can't be used in the WHERE clause.",
+            ),
+        ];
+        for (query, code, message) in cases {
+            assert_eq!(
+                validate(query),
+                Err(malformed_query!(code, message.to_string())),
+                "\nfailed for query {query}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_with_catalog() {
+        struct TestCatalog;
+
+        impl Catalog for TestCatalog {
+            fn resolve_table(&self, table: &TabIdent) -> Option<TableSchema> {
+                (table == &sample_tab_ident()).then(|| TableSchema {
+                    columns: vec![ColumnDef {
+                        name: "test_column_2".to_string(),
+                        data_type: ColumnType::Number,
+                    }],
+                })
+            }
+
+            fn qualify(&self, table: &TabIdent) -> TabIdent {
+                TabIdent {
+                    db: table.db.clone().or_else(|| Some("test_db".into())),
+                    schema: table.schema.clone().or_else(|| Some("test_schema".into())),
+                    table: table.table.clone(),
+                }
+            }
+        }
+
+        let options = ParseOptions::new();
+        let result = QueryMetadata::parse_with_catalog(
+            "SELECT SUM(t.test_column_2) FROM test_table_1 AS t WHERE test_column_2 > 1",
+            &options,
+            &TestCatalog,
+        )
+        .unwrap();
+        assert_eq!(result.table, sample_tab_ident());
+        assert_eq!(
+            result.data_extraction_query,
+            "SELECT test_column_2 FROM test_db.test_schema.test_table_1"
+        );
+
+        let cases = [
+            (
+                "SELECT SUM(test_column_2) FROM test_table_2",
+                ErrorCode::UnknownTable,
+                "the table `test_db.test_schema.test_table_2` doesn't exist.",
+            ),
+            (
+                "SELECT SUM(test_column_1) FROM test_table_1",
+                ErrorCode::UnknownColumn,
+                "the column `test_column_1` doesn't exist in the table.",
+            ),
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1 WHERE 1 = 0 AND test_column_1 > 2",
+                ErrorCode::UnknownColumn,
+                "the column `test_column_1` doesn't exist in the table.",
+            ),
+        ];
+        for (query, code, message) in cases {
+            assert_eq!(
+                QueryMetadata::parse_with_catalog(query, &options, &TestCatalog),
+                Err(malformed_query!(code, message.to_string())),
+                "\nfailed for query {query}"
+            );
+        }
+    }
+
+    #[test]
+    fn default_db_and_schema() {
+        let options = ParseOptions::new()
+            .with_default_db("test_db")
+            .with_default_schema("test_schema");
+        let result = QueryMetadata::parse_with(
+            "SELECT SUM(test_table_1.test_column_2) FROM test_table_1",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result.table, sample_tab_ident());
+        assert_eq!(
+            result.data_extraction_query,
+            "SELECT test_column_2 FROM test_db.test_schema.test_table_1"
+        );
+
+        //the names written in the query take precedence
+        let result = QueryMetadata::parse_with(
+            "SELECT SUM(test_column_2) FROM other_schema.test_table_1",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            result.table,
+            TabIdent {
+                db: Some("test_db".into()),
+                schema: Some("other_schema".into()),
+                table: "test_table_1".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn table_access_lists() {
+        let pattern = |schema: Option<&str>, table: &str| TablePattern {
+            db: None,
+            schema: schema.map(str::to_string),
+            table: table.to_string(),
+        };
+        let options = ParseOptions::new()
+            .with_allowed_tables(vec![
+                pattern(Some("test_schema"), "test_table_1"),
+                pattern(None, "test_table_2"),
+            ])
+            .with_denied_tables(vec![pattern(Some("private"), "test_table_2")]);
+
+        for query in [
+            "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+            "SELECT SUM(test_column_2) FROM test_table_2",
+            "SELECT SUM(test_column_2) FROM other_db.public.test_table_2",
         ] {
-            let query = &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1 AS t");
-            let expected = Err(malformed_query!(format!(
-                "the {column} column is not part of \
+            assert!(
+                QueryMetadata::parse_with(query, &options).is_ok(),
+                "\nfailed for query {query}"
+            );
+        }
+
+        let cases = [
+            ("test_table_1", "test_table_1"),
+            ("other_schema.test_table_1", "other_schema.test_table_1"),
+            (
+                "test_db.private.test_table_2",
+                "test_db.private.test_table_2",
+            ),
+            ("test_table_3", "test_table_3"),
+        ];
+        for (table, name) in cases {
+            let query = format!("SELECT SUM(test_column_2) FROM {table}");
+            assert_eq!(
+                QueryMetadata::parse_with(&query, &options),
+                Err(forbidden!(
+                    ErrorCode::ForbiddenTable,
+                    format!("the table `{name}` can't be queried.")
+                )),
+                "\nfailed for query {query}"
+            );
+        }
+    }
+
+    #[test]
+    fn sensitive_columns() {
+        let options = ParseOptions::new().with_sensitive_columns(vec![SensitiveColumns {
+            table: TablePattern {
+                db: None,
+                schema: None,
+                table: "users".to_string(),
+            },
+            columns: vec!["ssn".to_string(), "email".to_string()],
+        }]);
+
+        for query in [
+            "SELECT COUNT(id) FROM users WHERE age > 18",
+            "SELECT COUNT(ssn) FROM orders WHERE email = 'x'",
+        ] {
+            assert!(
+                QueryMetadata::parse_with(query, &options).is_ok(),
+                "\nfailed for query {query}"
+            );
+        }
+
+        let cases = [
+            (
+                "SELECT COUNT(SSN) FROM users",
+                "the column `ssn` of the table `users` can't be queried.",
+            ),
+            (
+                "SELECT COUNT(id) FROM public.users WHERE email IS NULL",
+                "the column `email` of the table `public.users` can't be queried.",
+            ),
+            //the predicates dropped along with a constant are checked too
+            (
+                "SELECT COUNT(id) FROM users WHERE ssn > 2 OR 1 = 1",
+                "the column `ssn` of the table `users` can't be queried.",
+            ),
+            (
+                "SELECT COUNT(id) FROM users WHERE 1 = 0 AND (email = 'x' OR FALSE)",
+                "the column `email` of the table `users` can't be queried.",
+            ),
+        ];
+        for (query, message) in cases {
+            assert_eq!(
+                QueryMetadata::parse_with(query, &options),
+                Err(forbidden!(ErrorCode::ForbiddenColumn, message.to_string())),
+                "\nfailed for query {query}"
+            );
+        }
+        let partial = QueryMetadata::parse_lenient(
+            "SELECT COUNT(id) FROM users WHERE 1 = 0 AND ssn > 2",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(partial.errors.len(), 1);
+        assert_eq!(partial.errors[0].code(), ErrorCode::ForbiddenColumn);
+    }
+
+    #[test]
+    fn function_column_type_compatibility() {
+        struct TestCatalog;
+
+        impl Catalog for TestCatalog {
+            fn resolve_table(&self, _table: &TabIdent) -> Option<TableSchema> {
+                let column = |name: &str, data_type| ColumnDef {
+                    name: name.to_string(),
+                    data_type,
+                };
+                Some(TableSchema {
+                    columns: vec![
+                        column("amount", ColumnType::Number),
+                        column("name", ColumnType::Text),
+                        column("created_at", ColumnType::Temporal),
+                        column("payload", ColumnType::Other),
+                    ],
+                })
+            }
+        }
+
+        let parse = |query: &str| {
+            QueryMetadata::parse_with_catalog(query, &ParseOptions::new(), &TestCatalog)
+        };
+        for function in ["SUM", "AVG", "MEDIAN", "VARIANCE", "STDDEV"] {
+            assert!(parse(&format!("SELECT {function}(amount) FROM t")).is_ok());
+            assert!(parse(&format!("SELECT {function}(payload) FROM t")).is_ok());
+            for (column, data_type) in [("name", "text"), ("created_at", "temporal")] {
+                assert_eq!(
+                    parse(&format!("SELECT {function}({column}) FROM t")),
+                    Err(malformed_query!(
+                        ErrorCode::TypeMismatch,
+                        format!(
+                        "{function} only applies to numeric columns, but `{column}` is of type \
+                         {data_type}."
+                    )
+                    ))
+                );
+            }
+        }
+        for function in ["COUNT", "MIN", "MAX"] {
+            for column in ["amount", "name", "created_at", "payload"] {
+                assert!(parse(&format!("SELECT {function}({column}) FROM t")).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_parse() {
+        let options = ParseOptions::new();
+        let query =
+            "SELECT DISTINCT SUM(t.test_column_2) FROM test_db.test_schema.test_table_1 AS t \
+                     WHERE test_column_3 > 1 ORDER BY test_column_3 LIMIT 5";
+        let expected = PartialQueryMetadata {
+            aggregation: Some(sample_sum()),
+            table: Some(sample_tab_ident()),
+            filter: Some(Filter {
+                column: "test_column_3".into(),
+                comparison: CompareOp::Gt {
+                    value: "1".to_string(),
+                },
+                value_type: Some(ValueType::Number),
+                json_path: None,
+                collation: None,
+            }),
+            time_bucket: None,
+            empty_result: false,
+            table_version: None,
+            table_hints: Vec::new(),
+            errors: vec![
+                unsupported!(UnsupportedFeature::OrderBy, "ORDER BY.".to_string()),
+                unsupported!(UnsupportedFeature::Limit, "LIMIT.".to_string()),
+                unsupported!(UnsupportedFeature::Distinct, "DISTINCT.".to_string()),
+            ],
+        };
+        assert_eq!(QueryMetadata::parse_lenient(query, &options), Ok(expected));
+
+        let query = "SELECT SUM(test_column_2), COUNT(test_column_2) \
+                     FROM test_db.test_schema.test_table_1 WHERE test_column_3 IS NULL \
+                     GROUP BY test_column_3";
+        let expected = PartialQueryMetadata {
+            aggregation: None,
+            table: Some(sample_tab_ident()),
+            filter: Some(Filter {
+                column: "test_column_3".into(),
+                comparison: CompareOp::IsNull,
+                value_type: None,
+                json_path: None,
+                collation: None,
+            }),
+            time_bucket: None,
+            empty_result: false,
+            table_version: None,
+            table_hints: Vec::new(),
+            errors: vec![
+                unsupported!(UnsupportedFeature::GroupBy, "GROUP BY.".to_string()),
+                unsupported!(UnsupportedFeature::Projection, "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.".to_string()),
+            ],
+        };
+        assert_eq!(QueryMetadata::parse_lenient(query, &options), Ok(expected));
+
+        //a query that's not a SELECT can't be parsed at all
+        assert_eq!(
+            QueryMetadata::parse_lenient("SELECT 1 UNION SELECT 2", &options),
+            Err(unsupported!(
+                UnsupportedFeature::SetOperation("UNION".to_string()),
+                "set operations (i.e., UNION).".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn error_spans() {
+        let span = |line, start, end| {
+            Some(Span {
+                start: Location {
+                    line,
+                    column: start,
+                },
+                end: Location { line, column: end },
+            })
+        };
+        let options = ParseOptions::new().with_error_spans(true);
+        let cases = [
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1\nORDER  BY test_column_2",
+                span(2, 1, 10),
+            ),
+            (
+                "SELECT DISTINCT SUM(test_column_2) FROM test_table_1",
+                span(1, 8, 16),
+            ),
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1 UNION SELECT 1",
+                span(1, 45, 50),
+            ),
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1 x y",
+                span(1, 47, 48),
+            ),
+            //errors that can't be located
+            ("SELECT SUM(test_column_2) FROM", None),
+            ("SELECT SUM(test_column_2), 1 FROM test_table_1", None),
+        ];
+        for (query, expected) in cases {
+            let error = QueryMetadata::parse_with(query, &options).unwrap_err();
+            assert_eq!(error.span(), expected, "\nfailed for query {query}");
+        }
+
+        //errors aren't located unless asked to
+        let query = "SELECT DISTINCT SUM(test_column_2) FROM test_table_1";
+        let error = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap_err();
+        assert_eq!(error.span(), None);
+        //the lenient parse locates every error
+        let query = "SELECT DISTINCT SUM(test_column_2) FROM test_table_1 LIMIT 1";
+        let errors = QueryMetadata::parse_lenient(query, &options)
+            .unwrap()
+            .errors;
+        let spans = errors.iter().map(ParseError::span).collect::<Vec<_>>();
+        assert_eq!(spans, vec![span(1, 54, 59), span(1, 8, 16)]);
+    }
+
+    #[test]
+    fn all_errors() {
+        let options = ParseOptions::new();
+        let query =
+            "SELECT DISTINCT SUM(other.test_column_2) FROM test_db.test_schema.test_table_1 \
+                     ORDER BY test_column_2";
+        let expected = Err(vec![
+            unsupported!(UnsupportedFeature::OrderBy, "ORDER BY.".to_string()),
+            unsupported!(UnsupportedFeature::Distinct, "DISTINCT.".to_string()),
+            malformed_query!(
+                ErrorCode::ColumnNotInTable,
+                "the other.test_column_2 column is not part of the table that's listed in the FROM clause (test_db.test_schema.test_table_1).".to_string()
+            ),
+        ]);
+        assert_eq!(
+            QueryMetadata::parse_with_all_errors(query, &options),
+            expected
+        );
+
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1";
+        assert!(QueryMetadata::parse_with_all_errors(query, &options).is_ok());
+
+        let query = "SELECT SUM(test_column_2) FROM";
+        assert_eq!(
+            QueryMetadata::parse_with_all_errors(query, &options)
+                .unwrap_err()
+                .iter()
+                .map(ParseError::code)
+                .collect::<Vec<_>>(),
+            vec![ErrorCode::Syntax]
+        );
+    }
+
+    #[test]
+    fn error_report() {
+        let query = "SELECT SUM(test_column_2) FROM test_table_1\nLIMIT 5";
+        let options = ParseOptions::new().with_error_spans(true);
+        let error = QueryMetadata::parse_with(query, &options).unwrap_err();
+        assert_eq!(error.help(), Some("remove it from the query."));
+        assert_eq!(
+            error.report(query),
+            "error[E_UNSUPPORTED_LIMIT]: statement not supported: LIMIT.\n  \
+             |\n2 | LIMIT 5\n  | ^^^^^\n  = help: remove it from the query."
+        );
+
+        //without a span, only the message and the help are reported
+        let error = QueryMetadata::parse(query, None).unwrap_err();
+        assert_eq!(
+            error.report(query),
+            "error[E_UNSUPPORTED_LIMIT]: statement not supported: LIMIT.\n  \
+             = help: remove it from the query."
+        );
+        let error = internal!("test.".to_string());
+        assert_eq!(error.report(query), "error[E_INTERNAL]: internal: test.");
+    }
+
+    #[test]
+    fn filter_to_sql() {
+        let filter = |column: &str, comparison, value_type| Filter {
+            column: column.into(),
+            comparison,
+            value_type,
+            json_path: None,
+            collation: None,
+        };
+        let cases = [
+            (
+                filter(
+                    "test_column_2",
+                    CompareOp::GtEq {
+                        value: "10".to_string(),
+                    },
+                    Some(ValueType::Number),
+                ),
+                Dialect::Generic,
+                None,
+                "test_column_2 >= 10",
+            ),
+            (
+                filter(
+                    "test_column_2",
+                    CompareOp::GtEq {
+                        value: "10".to_string(),
+                    },
+                    Some(ValueType::Number),
+                ),
+                Dialect::Generic,
+                Some('"'),
+                "\"test_column_2\" >= 10",
+            ),
+            (
+                filter(
+                    "Test Column",
+                    CompareOp::Eq {
+                        value: "O'Brien".to_string(),
+                    },
+                    Some(ValueType::String),
+                ),
+                Dialect::MySql,
+                None,
+                "`Test Column` = 'O''Brien'",
+            ),
+            (
+                filter("test_column_3", CompareOp::IsNotNull, None),
+                Dialect::Snowflake,
+                None,
+                "\"test_column_3\" IS NOT NULL",
+            ),
+            (
+                filter(
+                    "flag",
+                    CompareOp::NotEq {
+                        value: "true".to_string(),
+                    },
+                    Some(ValueType::Boolean),
+                ),
+                Dialect::Generic,
+                None,
+                "flag <> true",
+            ),
+        ];
+        for (filter, dialect, quote_style, expected) in cases {
+            assert_eq!(filter.to_sql(dialect, quote_style), expected);
+        }
+
+        //the filters extracted from a query are rendered back as written
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_3 < 'a''b'";
+        let filter = QueryMetadata::parse(query, None).unwrap().filter.unwrap();
+        assert_eq!(
+            filter.to_sql(Dialect::Generic, None),
+            "test_column_3 < 'a''b'"
+        );
+    }
+
+    #[test]
+    fn filter_json() {
+        let filter = Filter {
+            column: "test_column_2".into(),
+            comparison: CompareOp::GtEq {
+                value: "10".to_string(),
+            },
+            value_type: Some(ValueType::Number),
+            json_path: None,
+            collation: None,
+        };
+        let json = if cfg!(feature = "camel-case") {
+            r#"{"column":"test_column_2","comparison":{"type":"gtEq","value":"10"},"valueType":"number"}"#
+        } else {
+            r#"{"column":"test_column_2","comparison":{"type":"GtEq","value":"10"},"value_type":"Number"}"#
+        };
+        assert_eq!(serde_json::to_string(&filter).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Filter>(json).unwrap(), filter);
+
+        let filter = Filter {
+            column: "test_column_2".into(),
+            comparison: CompareOp::IsNull,
+            value_type: None,
+            json_path: None,
+            collation: None,
+        };
+        let json = if cfg!(feature = "camel-case") {
+            r#"{"column":"test_column_2","comparison":{"type":"isNull"},"valueType":null}"#
+        } else {
+            r#"{"column":"test_column_2","comparison":{"type":"IsNull"},"value_type":null}"#
+        };
+        assert_eq!(serde_json::to_string(&filter).unwrap(), json);
+        //the value type may be omitted
+        let json = if cfg!(feature = "camel-case") {
+            r#"{"column":"test_column_2","comparison":{"type":"isNull"}}"#
+        } else {
+            r#"{"column":"test_column_2","comparison":{"type":"IsNull"}}"#
+        };
+        assert_eq!(serde_json::from_str::<Filter>(json).unwrap(), filter);
+    }
+
+    #[test]
+    fn query_metadata_json() {
+        let query = "SELECT STDDEV(t.a) AS s FROM db.sc.tbl AS t WHERE t.b IS NOT NULL";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+        let json = serde_json::to_value(&metadata).unwrap();
+        let (keys, function) = if cfg!(feature = "camel-case") {
+            (
+                [
+                    "aggregation",
+                    "aliases",
+                    "annotations",
+                    "dataAggregationQuery",
+                    "dataCountQuery",
+                    "dataExtractionQuery",
+                    "emptyResult",
+                    "filter",
+                    "schemaVersion",
+                    "table",
+                    "tableHints",
+                    "tableVersion",
+                ],
+                "standardDeviation",
+            )
+        } else {
+            (
+                [
+                    "aggregation",
+                    "aliases",
+                    "annotations",
+                    "data_aggregation_query",
+                    "data_count_query",
+                    "data_extraction_query",
+                    "empty_result",
+                    "filter",
+                    "schema_version",
+                    "table",
+                    "table_hints",
+                    "table_version",
+                ],
+                "StandardDeviation",
+            )
+        };
+        let fields = json.as_object().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(fields, keys);
+        assert_eq!(json["aggregation"]["function"], function);
+        assert_eq!(json["aliases"]["tables"]["t"], "db.sc.tbl");
+        let comparison = if cfg!(feature = "camel-case") {
+            "isNotNull"
+        } else {
+            "IsNotNull"
+        };
+        assert_eq!(json["filter"]["comparison"]["type"], comparison);
+        //the metadata reads back the same
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(
+            serde_json::from_str::<QueryMetadata>(&json).unwrap(),
+            metadata
+        );
+        let versioned = QueryMetadata {
+            table_version: Some(TableVersion {
+                as_of: "2024-01-01 00:00:00".to_string(),
+            }),
+            ..metadata
+        };
+        let json = serde_json::to_string(&versioned).unwrap();
+        let as_of = if cfg!(feature = "camel-case") {
+            r#""tableVersion":{"asOf":"2024-01-01 00:00:00"}"#
+        } else {
+            r#""table_version":{"as_of":"2024-01-01 00:00:00"}"#
+        };
+        assert!(json.contains(as_of), "{json}");
+        assert_eq!(
+            serde_json::from_str::<QueryMetadata>(&json).unwrap(),
+            versioned
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_any_version() {
+        use crate::query_metadata::MIGRATIONS;
+
+        let key = |snake_case, camel_case| {
+            if cfg!(feature = "camel-case") {
+                camel_case
+            } else {
+                snake_case
+            }
+        };
+        assert_eq!(MIGRATIONS.len(), QueryMetadata::SCHEMA_VERSION as usize);
+        let query = "SELECT SUM(a) FROM t WHERE b = 'x''y'";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+        assert_eq!(metadata.schema_version, QueryMetadata::SCHEMA_VERSION);
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(
+            QueryMetadata::from_json_any_version(&json).unwrap(),
+            metadata
+        );
+
+        //the metadata serialized before it was versioned
+        let unversioned = |metadata: &QueryMetadata| {
+            let mut document = serde_json::to_value(metadata).unwrap();
+            let fields = document.as_object_mut().unwrap();
+            fields.retain(|field, _| {
+                [
+                    "aggregation",
+                    "table",
+                    "filter",
+                    key("data_extraction_query", "dataExtractionQuery"),
+                    key("data_aggregation_query", "dataAggregationQuery"),
+                ]
+                .contains(&field.as_str())
+            });
+            let filter = fields["filter"].as_object_mut().unwrap();
+            filter.remove(key("value_type", "valueType"));
+            document.to_string()
+        };
+        let migrated = QueryMetadata::from_json_any_version(&unversioned(&metadata)).unwrap();
+        assert_eq!(migrated, metadata);
+        //the string is still escaped once the metadata is migrated
+        assert_eq!(
+            migrated.to_sql(Dialect::Generic),
+            metadata.to_sql(Dialect::Generic)
+        );
+        let metadata = QueryMetadata::parse("SELECT SUM(a) FROM t WHERE b > 1.5", None).unwrap();
+        let migrated = QueryMetadata::from_json_any_version(&unversioned(&metadata)).unwrap();
+        assert_eq!(migrated.filter, metadata.filter);
+
+        let newer = json.replace(
+            &format!(
+                "\"{}\":{}",
+                key("schema_version", "schemaVersion"),
+                QueryMetadata::SCHEMA_VERSION
+            ),
+            &format!(
+                "\"{}\":{}",
+                key("schema_version", "schemaVersion"),
+                QueryMetadata::SCHEMA_VERSION + 1
+            ),
+        );
+        assert_ne!(newer, json);
+        assert!(QueryMetadata::from_json_any_version(&newer).is_err());
+        assert!(QueryMetadata::from_json_any_version("{}").is_err());
+    }
+
+    #[test]
+    fn parse_many() {
+        let options = ParseOptions::new();
+        let script = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1;\n\
+                      SELECT COUNT(test_column_2) FROM test_table_1 WHERE test_column_3 IS NULL;";
+        let expected = vec![
+            QueryMetadata::parse_with(
+                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+                &options,
+            )
+            .unwrap(),
+            QueryMetadata::parse_with(
+                "SELECT COUNT(test_column_2) FROM test_table_1 WHERE test_column_3 IS NULL",
+                &options,
+            )
+            .unwrap(),
+        ];
+        assert_eq!(QueryMetadata::parse_many(script, &options), Ok(expected));
+        assert_eq!(QueryMetadata::parse_many("", &options), Ok(vec![]));
+
+        let script = "SELECT SUM(test_column_2) FROM test_table_1; \
+                      DELETE FROM test_table_1; \
+                      SELECT SUM(test_column_2) FROM test_table_1 LIMIT 1";
+        assert_eq!(
+            QueryMetadata::parse_many(script, &options),
+            Err(unsupported!(
+                UnsupportedFeature::Statement,
+                "statements different from single SELECT statement.".to_string()
+            ))
+        );
+        let script = "SELECT SUM(test_column_2) FROM test_table_1; \
+                      SELECT SUM(test_column_2) FROM test_table_1 LIMIT 1";
+        assert_eq!(
+            QueryMetadata::parse_many(script, &options),
+            Err(unsupported!(
+                UnsupportedFeature::Limit,
+                "LIMIT.".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn cosmetic_content() {
+        let expected = QueryMetadata::parse(
+            "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+            None,
+        );
+        let queries = [
+            "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1;",
+            "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1; -- comment",
+            "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1;;\r\n/* comment */\r\n",
+            "-- comment\n/* comment */ SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+            "\u{feff}SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+        ];
+        for query in queries {
+            //the leading comments are kept as annotations
+            let parsed = QueryMetadata::parse(query, None).map(|metadata| QueryMetadata {
+                annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
+                ..metadata
+            });
+            assert_eq!(parsed, expected, "\nfailed for {query:?}");
+        }
+
+        //`#` starts a comment in MySQL and BigQuery, unless it's quoted
+        for dialect in [Dialect::MySql, Dialect::BigQuery] {
+            let options = ParseOptions::new().with_dialect(dialect);
+            let query = "SELECT SUM(test_column_2) FROM test_table_1 \
+                         WHERE test_column_3 = '#1' # comment ' with a quote\n;# comment";
+            let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+            assert_eq!(
+                metadata.filter.map(|filter| filter.comparison),
+                Some(CompareOp::Eq {
+                    value: "#1".to_string()
+                })
+            );
+        }
+        assert_eq!(
+            QueryMetadata::parse(
+                "SELECT SUM(test_column_2) FROM test_table_1 # comment",
+                None
+            )
+            .map_err(|error| error.code()),
+            Err(ErrorCode::Syntax)
+        );
+
+        //without any statement, the query is empty
+        for query in ["", ";", "-- comment"] {
+            assert_eq!(
+                QueryMetadata::parse(query, None),
+                Err(malformed_query!(
+                    ErrorCode::EmptyQuery,
+                    "the query has no statement.".to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn metadata_visitor() {
+        //collects the columns of the table that are read by the query
+        #[derive(Default)]
+        struct Columns(Vec<String>);
+
+        impl MetadataVisitor for Columns {
+            fn visit_table(&mut self, table: &TabIdent) {
+                self.0.push(table.to_string());
+            }
+
+            fn visit_aggregation(&mut self, aggregation: &Aggregation) {
+                self.0.push(aggregation.column.to_string());
+            }
+
+            fn visit_filter(&mut self, filter: &Filter) {
+                self.0.push(filter.column.to_string());
+            }
+
+            fn visit_window(&mut self, window: &WindowSpec) {
+                self.0.push(window.order_by.to_string());
+            }
+
+            fn visit_time_bucket(&mut self, time_bucket: &TimeBucket) {
+                self.0.push(time_bucket.column.to_string());
+            }
+        }
+
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 \
+                     WHERE test_column_3 > 1";
+        let mut columns = Columns::default();
+        QueryMetadata::parse(query, None)
+            .unwrap()
+            .walk(&mut columns);
+        assert_eq!(
+            columns.0,
+            [
+                "test_db.test_schema.test_table_1",
+                "test_column_2",
+                "test_column_3"
+            ]
+        );
+
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1";
+        let mut columns = Columns::default();
+        QueryMetadata::parse(query, None)
+            .unwrap()
+            .walk(&mut columns);
+        assert_eq!(
+            columns.0,
+            ["test_db.test_schema.test_table_1", "test_column_2"]
+        );
+
+        let query = "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 \
+                     WHERE test_column_3 > 1 GROUP BY DATE(test_column_4)";
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_time_buckets: true,
+            ..FeaturePolicy::default()
+        });
+        let mut columns = Columns::default();
+        QueryMetadata::parse_with(query, &options)
+            .unwrap()
+            .walk(&mut columns);
+        assert_eq!(
+            columns.0,
+            [
+                "test_db.test_schema.test_table_1",
+                "test_column_2",
+                "test_column_3",
+                "test_column_4"
+            ]
+        );
+
+        let query = "SELECT SUM(test_column_2) OVER (ORDER BY test_column_4 ROWS UNBOUNDED \
+                     PRECEDING) FROM test_db.test_schema.test_table_1";
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_running_aggregates: true,
+            ..FeaturePolicy::default()
+        });
+        let mut columns = Columns::default();
+        QueryMetadata::parse_with(query, &options)
+            .unwrap()
+            .walk(&mut columns);
+        assert_eq!(
+            columns.0,
+            [
+                "test_db.test_schema.test_table_1",
+                "test_column_2",
+                "test_column_4"
+            ]
+        );
+    }
+
+    #[test]
+    fn standalone_validation() {
+        //only the clauses and the FROM clause are checked, not the aggregation nor the filter
+        let query = "SELECT custom(test_column_2) FROM test_table_1 WHERE 1 < 2";
+        assert_eq!(validate(query, Dialect::Generic), Ok(()));
+
+        let cases = [
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1 ORDER BY 1",
+                ErrorCode::UnsupportedOrderBy,
+            ),
+            (
+                "SELECT SUM(test_column_2) FROM test_table_1, test_table_2",
+                ErrorCode::UnsupportedFrom,
+            ),
+            ("DELETE FROM test_table_1", ErrorCode::MultiStatement),
+            ("SELECT SUM(", ErrorCode::Syntax),
+        ];
+        for (query, code) in cases {
+            assert_eq!(
+                validate(query, Dialect::Generic).map_err(|error| error.code()),
+                Err(code),
+                "\nfailed for query {query:?}"
+            );
+        }
+
+        let query = "SELECT SUM(t.test_column_2) FROM Test_Table_1 AS t WHERE t.test_column_3 > 1";
+        let statements = Parser::parse_sql(&GenericDialect {}, query).unwrap();
+        let [ast::Statement::Query(query)] = &statements[..] else {
+            panic!("not a query");
+        };
+        let DestructuredQuery {
+            projection,
+            from,
+            selection,
+            group_by,
+        } = DestructuredQuery::destructure(query).unwrap();
+        assert_eq!(projection.len(), 1);
+        assert!(selection.is_some());
+        assert!(group_by.is_empty());
+        let TableIdentWithAlias(table, alias) =
+            TableIdentWithAlias::extract(from, CaseFolding::Lowercase).unwrap();
+        assert_eq!(table.table, "test_table_1");
+        assert_eq!(alias.as_deref(), Some("t"));
+    }
+
+    #[test]
+    fn parse_keep_ast() {
+        let options = ParseOptions::new();
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_3 = 1.50";
+        let (metadata, statement) = QueryMetadata::parse_keep_ast(query, &options).unwrap();
+        assert_eq!(Ok(metadata), QueryMetadata::parse_with(query, &options));
+        //the literal is kept as written
+        assert_eq!(statement.to_string(), query);
+
+        assert_eq!(
+            QueryMetadata::parse_keep_ast("SELECT SUM(test_column_2) FROM t LIMIT 1", &options)
+                .map_err(|error| error.code()),
+            Err(ErrorCode::UnsupportedLimit)
+        );
+    }
+
+    #[test]
+    fn borrowed_metadata() {
+        let options = ParseOptions::new().with_dialect(Dialect::PostgreSql);
+        let query = "SELECT MEDIAN(t.test_column_2) AS m FROM test_table_1 AS t \
+                     WHERE test_column_3 > 1";
+        let statements = parse_statements(query, options.dialect()).unwrap();
+        let metadata = QueryMetadataRef::extract(&statements[0], &options).unwrap();
+        let expected = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(metadata.aggregation, expected.aggregation);
+        assert_eq!(metadata.table, expected.table);
+        assert_eq!(metadata.filter, expected.filter);
+        assert_eq!(metadata.aliases, expected.aliases);
+        assert_eq!(
+            metadata.data_extraction_query(),
+            expected.data_extraction_query
+        );
+        assert_eq!(
+            metadata.data_aggregation_query(),
+            Ok(expected.data_aggregation_query.clone())
+        );
+        assert_eq!(metadata.into_owned(), Ok(expected));
+
+        let statements = parse_statements("DELETE FROM test_table_1", Dialect::Generic).unwrap();
+        assert_eq!(
+            QueryMetadataRef::extract(&statements[0], &options).map(|_| ()),
+            Err(unsupported!(
+                UnsupportedFeature::Statement,
+                "statements different from single SELECT statement.".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn data_aggregation_query_rendering() {
+        //the query is rendered as the AST rebuilt from its clauses would be
+        let queries = [
+            "SELECT SUM(test_column_2) FROM test_table_1",
+            "SELECT COUNT(t.test_column_2) AS \"Total\" FROM test_db.test_schema.test_table_1 t \
+             WHERE ((t.test_column_3 >= 'a''b'))",
+            "SELECT AVG(test_column_2) total FROM test_table_1 WHERE test_column_3 IS NOT NULL",
+        ];
+        for query in queries {
+            let statements = parse_statements(query, Dialect::Generic).unwrap();
+            let [ast::Statement::Query(statement)] = &statements[..] else {
+                panic!("not a query");
+            };
+            let ast::SetExpr::Select(select) = statement.body.as_ref() else {
+                panic!("not a select");
+            };
+            for (cast, pretty) in [(false, false), (true, false), (false, true), (true, true)] {
+                let projection = match &select.projection[..] {
+                    [ast::SelectItem::UnnamedExpr(expr)] if cast => {
+                        vec![ast::SelectItem::UnnamedExpr(cast_to_text(expr.clone()))]
+                    }
+                    [ast::SelectItem::ExprWithAlias { expr, alias }] if cast => {
+                        vec![ast::SelectItem::ExprWithAlias {
+                            expr: cast_to_text(expr.clone()),
+                            alias: alias.clone(),
+                        }]
+                    }
+                    projection => projection.to_vec(),
+                };
+                let expected = format_query(
+                    &select_query(projection, select.from.clone(), select.selection.clone()),
+                    pretty,
+                );
+                let options = ParseOptions::new()
+                    .with_cast_to_text(cast)
+                    .with_pretty_print(pretty);
+                assert_eq!(
+                    QueryMetadata::parse_with(query, &options)
+                        .unwrap()
+                        .data_aggregation_query,
+                    Some(expected),
+                    "\nfailed for query {query:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_batch() {
+        let options = ParseOptions::new();
+        let queries = (0..100)
+            .map(|i| match i % 3 {
+                0 => {
+                    format!("SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_3 = {i}")
+                }
+                1 => format!("SELECT COUNT(test_column_{i}) FROM test_table_1"),
+                _ => format!("SELECT SUM(test_column_2) FROM test_table_1 LIMIT {i}"),
+            })
+            .collect::<Vec<_>>();
+        let queries = queries.iter().map(String::as_str).collect::<Vec<_>>();
+        let expected = queries
+            .iter()
+            .map(|query| QueryMetadata::parse_with(query, &options))
+            .collect::<Vec<_>>();
+        assert_eq!(QueryMetadata::parse_batch(&queries, &options), expected);
+        assert_eq!(QueryMetadata::parse_batch(&[], &options), vec![]);
+    }
+
+    #[test]
+    fn query_limits() {
+        let nested = |depth: usize| {
+            format!(
+                "SELECT SUM(test_column_2) FROM test_table_1 WHERE {}test_column_1 > 1{}",
+                "(".repeat(depth),
+                ")".repeat(depth)
+            )
+        };
+        let options = ParseOptions::new();
+        assert!(QueryMetadata::parse_with(&nested(8), &options).is_ok());
+        let error = QueryMetadata::parse_with(&nested(10_000), &options).unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::LimitExceeded {
+                code: ErrorCode::ExpressionTooDeep,
+                ..
+            }
+        ));
+        assert_eq!(error.code().to_string(), "E_EXPRESSION_TOO_DEEP");
+
+        //without limits, the SQL parser still stops before exhausting the stack
+        let unlimited = ParseOptions::new().with_limits(Limits::unlimited());
+        assert_eq!(
+            QueryMetadata::parse_with(&nested(10_000), &unlimited).map_err(|e| e.code()),
+            Err(ErrorCode::ExpressionTooDeep)
+        );
+
+        let options = ParseOptions::new().with_limits(
+            Limits::new()
+                .with_max_query_length(Some(100))
+                .with_max_list_size(Some(2)),
+        );
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_1 IN (1, 2, 3)";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options).map_err(|e| e.code()),
+            Err(ErrorCode::ListTooLong)
+        );
+        assert_eq!(
+            QueryMetadata::parse_with(&format!("{query}{}", " ".repeat(100)), &options)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::QueryTooLong)
+        );
+        assert_eq!(
+            validate(&nested(100), Dialect::Generic).map_err(|e| e.code()),
+            Err(ErrorCode::ExpressionTooDeep)
+        );
+    }
+
+    #[test]
+    fn nested_queries() {
+        let options = ParseOptions::new();
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_1 > 1";
+        let expected = QueryMetadata::parse_with(query, &options).unwrap();
+        let nested = format!("{}{query}{}", "(".repeat(12), ")".repeat(12));
+        assert_eq!(QueryMetadata::parse_with(&nested, &options), Ok(expected));
+
+        //the clauses of the nested queries are checked too
+        let nested = format!("(({query}) LIMIT 1)");
+        assert_eq!(
+            QueryMetadata::parse_with(&nested, &options).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedLimit)
+        );
+    }
+
+    #[test]
+    fn extraction_schema() {
+        let metadata = QueryMetadata::parse(
+            "SELECT AVG(test_column_2) FROM test_table_1 WHERE test_column_1 = 'a'",
+            None,
+        )
+        .unwrap();
+        let column = |name: &str, data_type| ColumnDef {
+            name: name.to_string(),
+            data_type,
+        };
+        assert_eq!(
+            metadata.extraction_schema(None),
+            vec![
+                column("test_column_2", ColumnType::Other),
+                column("test_column_1", ColumnType::Other)
+            ]
+        );
+        let schema = TableSchema {
+            columns: vec![
+                column("test_column_1", ColumnType::Text),
+                column("test_column_2", ColumnType::Number),
+            ],
+        };
+        assert_eq!(
+            metadata.extraction_schema(Some(&schema)),
+            vec![
+                column("test_column_2", ColumnType::Number),
+                column("test_column_1", ColumnType::Text)
+            ]
+        );
+    }
+
+    #[test]
+    fn feature_policy() {
+        let query = "SELECT DISTINCT SUM(test_column_2) FROM test_table_1 \
+                     WHERE test_column_1 > 1 ORDER BY 1 LIMIT 10";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &ParseOptions::new()).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedOrderBy)
+        );
+
+        let policy = FeaturePolicy {
+            allow_order_by: true,
+            allow_limit: true,
+            allow_distinct: true,
+            ..FeaturePolicy::default()
+        };
+        let options = ParseOptions::new().with_feature_policy(policy);
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        let expected = QueryMetadata::parse(
+            "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_1 > 1",
+            None,
+        )
+        .unwrap();
+        assert_eq!(metadata.filter, expected.filter);
+        assert_eq!(
+            metadata.data_extraction_query,
+            expected.data_extraction_query
+        );
+        //the allowed clauses are left out of the derived queries
+        assert_eq!(
+            metadata.data_aggregation_query,
+            expected.data_aggregation_query
+        );
+
+        //a LIMIT 0 drops the only row of the aggregation
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 LIMIT 0";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedLimit)
+        );
+        //DISTINCT in the aggregation changes its result
+        let query = "SELECT SUM(DISTINCT test_column_2) FROM test_table_1";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedDistinct)
+        );
+
+        let query = "SELECT SUM(test_column_2) FROM test_table_1 WITH (NOLOCK)";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedTableHints)
+        );
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_table_hints: true,
+            ..FeaturePolicy::default()
+        });
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(metadata.table_hints, vec!["NOLOCK".to_string()]);
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT test_column_2 FROM test_table_1"
+        );
+        //the aggregation query passes them through, with the FROM clause as written
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some("SELECT CAST(SUM(test_column_2) AS TEXT) FROM test_table_1 WITH (NOLOCK)")
+        );
+    }
+
+    #[test]
+    fn capability_report() {
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_order_by: true,
+            ..FeaturePolicy::default()
+        });
+        let report = supported_features(&options);
+        assert_eq!(report.functions.len(), KoronFunction::all().len());
+        assert!(report.policy.allow_order_by && !report.policy.allow_limit);
+
+        //every reported function and operator is accepted
+        for function in &report.functions {
+            let query = format!("SELECT {}(test_column_2) FROM test_table_1", function.name);
+            let metadata = QueryMetadata::parse_with(&query, &options).unwrap();
+            assert_eq!(metadata.aggregation.function, function.function);
+            assert_eq!(function.arity, 1);
+        }
+        for operator in &report.operators {
+            let value = if operator.takes_value { " 1" } else { "" };
+            let predicate = match operator.operator.split_once(' ') {
+                Some(("[]", op)) => format!("test_column_1[1] {op}{value}"),
+                Some((op, quantifier @ ("ANY" | "ALL"))) => {
+                    format!("1 {op} {quantifier}(test_column_1)")
+                }
+                _ => format!("test_column_1 {}{value}", operator.operator),
+            };
+            let query = format!("SELECT SUM(test_column_2) FROM test_table_1 WHERE {predicate}");
+            let filter = QueryMetadata::parse_with(&query, &options)
+                .unwrap()
+                .filter
+                .unwrap();
+            assert_eq!(filter.comparison.sql_symbol(), operator.operator);
+        }
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["functions"][2]["name"], "AVG");
+        assert_eq!(json["operators"][0]["operator"], "=");
+    }
+
+    #[test]
+    fn analysis() {
+        let options = ParseOptions::new();
+        let query = "SELECT SUM(test_column_2) FROM test_table_1";
+        let analysis = QueryMetadata::analyze(query, &options);
+        assert!(analysis.is_supported());
+        assert_eq!(
+            analysis,
+            Analysis::Supported(QueryMetadata::parse_with(query, &options).unwrap())
+        );
+
+        let query = "SELECT DISTINCT SUM(test_column_2) FROM test_table_1 ORDER BY 1 LIMIT 5";
+        let Analysis::Rejected(errors) = QueryMetadata::analyze(query, &options) else {
+            panic!("the query is supported.");
+        };
+        let blockers = errors
+            .iter()
+            .map(|error| (error.unsupported_feature().cloned(), error.span().is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blockers,
+            vec![
+                (Some(UnsupportedFeature::OrderBy), true),
+                (Some(UnsupportedFeature::Limit), true),
+                (Some(UnsupportedFeature::Distinct), true)
+            ]
+        );
+
+        let Analysis::Rejected(errors) = QueryMetadata::analyze("SELECT SUM(", &options) else {
+            panic!("the query is supported.");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), ErrorCode::Syntax);
+    }
+
+    #[test]
+    fn query_stats() {
+        let options = ParseOptions::new();
+        let query = "SELECT SUM(t.test_column_2) AS s FROM test_db.test_schema.test_table_1 AS t \
+                     WHERE ((test_column_1 > -5))";
+        let (metadata, stats) = QueryMetadata::parse_with_stats(query, &options).unwrap();
+        assert_eq!(
+            metadata,
+            QueryMetadata::parse_with(query, &options).unwrap()
+        );
+        assert_eq!(
+            stats,
+            QueryStats {
+                byte_length: query.len(),
+                //the two parentheses, the comparison, the minus and the number
+                expression_depth: 5,
+                filter_predicates: 1,
+                literal_count: 1,
+                //SUM, t.test_column_2, s, the three parts of the table, t and test_column_1
+                identifier_count: 9,
+            }
+        );
+
+        let query = "SELECT COUNT(test_column_2) FROM test_table_1";
+        let (_, stats) = QueryMetadata::parse_with_stats(query, &options).unwrap();
+        assert_eq!(stats.expression_depth, 2);
+        assert_eq!(stats.filter_predicates, 0);
+    }
+
+    #[test]
+    fn parse_catching() {
+        let options = ParseOptions::new().with_error_spans(true);
+        for query in [
+            "SELECT SUM(test_column_2) FROM test_table_1 WHERE test_column_1 > 5",
+            "SELECT SUM(test_column_2) FROM test_table_1 LIMIT 5",
+            "SELECT SUM(",
+            &format!(
+                "SELECT SUM(a) FROM t WHERE {}a > 1{}",
+                "(".repeat(40),
+                ")".repeat(40)
+            ),
+        ] {
+            assert_eq!(
+                QueryMetadata::parse_catching(query, &options),
+                QueryMetadata::parse_with(query, &options)
+            );
+        }
+    }
+
+    #[test]
+    fn original_spellings() {
+        let query = "SELECT SUM(Amount) FROM Sales.Orders WHERE \"Region\" = 'EU'";
+        let metadata = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap();
+        let column = &metadata.aggregation.column;
+        assert_eq!((&column.raw[..], &column.folded[..]), ("Amount", "amount"));
+        assert_eq!(column.quoted, None);
+        assert_eq!(metadata.table.schema, Some("sales".into()));
+        let column = &metadata.filter.as_ref().unwrap().column;
+        assert_eq!(column.quoted, Some('"'));
+        assert_eq!(column.original(), "\"Region\"");
+        //the regenerated query keeps the spellings, the derived ones use the stored names
+        assert_eq!(metadata.to_sql(Dialect::Generic), query);
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT amount, \"Region\" FROM sales.orders"
+        );
+        //the names are serialized as they're stored
+        let json = serde_json::to_value(&metadata.aggregation).unwrap();
+        assert_eq!(json["column"], "amount");
+        let schema = TableSchema {
+            columns: vec![ColumnDef {
+                name: "Region".to_string(),
+                data_type: ColumnType::Text,
+            }],
+        };
+        assert_eq!(
+            metadata.validate_against(&schema).unwrap_err().to_string(),
+            "malformed query: the column `Amount` doesn't exist in the table."
+        );
+    }
+
+    #[test]
+    fn mixed_quoting() {
+        let query = "SELECT SUM(\"My Column\") FROM test_db.\"My Schema\".Test_Table \
+                     WHERE \"Other Column\" > 1";
+        let metadata = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap();
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT \"My Column\", \"Other Column\" FROM test_db.\"My Schema\".test_table"
+        );
+        //an explicit quote style still applies to every part
+        let options = ParseOptions::new().with_quote_style(Some('`'));
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT `My Column`, `Other Column` FROM `test_db`.`My Schema`.`test_table`"
+        );
+    }
+
+    #[test]
+    fn table_version() {
+        let options = ParseOptions::new().with_dialect(Dialect::BigQuery);
+        let query =
+            "SELECT SUM(a) FROM d.t FOR SYSTEM_TIME AS OF '2024-01-01 00:00:00' WHERE b > 1";
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            metadata.table_version,
+            Some(TableVersion {
+                as_of: "2024-01-01 00:00:00".to_string()
+            })
+        );
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT a, b FROM d.t FOR SYSTEM_TIME AS OF TIMESTAMP '2024-01-01 00:00:00'"
+        );
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some(
+                "SELECT CAST(SUM(a) AS TEXT) FROM d.t \
+                 FOR SYSTEM_TIME AS OF '2024-01-01 00:00:00' WHERE b > 1"
+            )
+        );
+        let rendered = metadata.to_sql(Dialect::BigQuery);
+        let parsed = QueryMetadata::parse_with(&rendered, &options).unwrap();
+        assert_eq!(parsed.table_version, metadata.table_version);
+        assert_eq!(parsed.data_extraction_query, metadata.data_extraction_query);
+        //the dialects without temporal tables leave it out
+        assert_eq!(
+            metadata.to_sql(Dialect::PostgreSql),
+            "SELECT SUM(a) FROM d.t WHERE b > 1"
+        );
+        assert_ne!(
+            metadata.fingerprint(false, false),
+            QueryMetadata::parse_with("SELECT SUM(a) FROM d.t WHERE b > 1", &options)
+                .unwrap()
+                .fingerprint(false, false)
+        );
+
+        let query = "SELECT SUM(a) FROM t FOR SYSTEM_TIME AS OF CURRENT_TIMESTAMP()";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options).map_err(|error| error.code()),
+            Err(ErrorCode::UnsupportedTableVersion)
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let query = "-- nightly revenue\n\
+                     SELECT /*+ koron: max_rows=10000 */ SUM(a) FROM t WHERE b > 1";
+        let metadata = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap();
+        assert_eq!(
+            metadata.annotations,
+            vec![
+                Annotation::Comment {
+                    text: "nightly revenue".to_string()
+                },
+                Annotation::Hint {
+                    key: "max_rows".to_string(),
+                    value: "10000".to_string()
+                }
+            ]
+        );
+        //the comments aren't part of the derived queries
+        assert_eq!(metadata.data_extraction_query, "SELECT a, b FROM t");
+        let (with_stats, _) = QueryMetadata::parse_with_stats(query, &ParseOptions::new()).unwrap();
+        assert_eq!(with_stats.annotations, metadata.annotations);
+    }
+
+    #[test]
+    fn table_rewriter() {
+        fn tenant_table(table: &TabIdent) -> TabIdent {
+            TabIdent {
+                db: Some("tenant_42".into()),
+                ..table.clone()
+            }
+        }
+        let options = ParseOptions::new()
+            .with_table_rewriter(tenant_table)
+            .with_allowed_tables(vec![TablePattern {
+                db: None,
+                schema: Some("analytics".to_string()),
+                table: "events".to_string(),
+            }]);
+        let metadata = QueryMetadata::parse_with(
+            "SELECT SUM(e.amount) FROM analytics.events AS e WHERE e.amount > 0",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(metadata.table.to_string(), "tenant_42.analytics.events");
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT amount FROM tenant_42.analytics.events"
+        );
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some(
+                "SELECT CAST(SUM(e.amount) AS TEXT) FROM tenant_42.analytics.events AS e \
+                 WHERE e.amount > 0"
+            )
+        );
+        //the access is checked on the table as queried
+        assert_eq!(
+            QueryMetadata::parse_with("SELECT SUM(amount) FROM analytics.users", &options)
+                .map_err(|error| error.code()),
+            Err(ErrorCode::ForbiddenTable)
+        );
+    }
+
+    #[test]
+    fn literal_escaping() {
+        let mysql = ParseOptions::new().with_dialect(Dialect::MySql);
+        let query = r"SELECT SUM(a) FROM t WHERE b = 'x\\'' OR 1=1 --'";
+        let metadata = QueryMetadata::parse_with(query, &mysql).unwrap();
+        let filter = metadata.filter.as_ref().unwrap();
+        assert_eq!(filter.comparison.value(), Some(r"x\' OR 1=1 --"));
+        assert_eq!(
+            filter.to_sql(Dialect::MySql, None),
+            r"b = 'x\\'' OR 1=1 --'"
+        );
+        assert_eq!(
+            filter.to_sql(Dialect::PostgreSql, None),
+            r"b = 'x\'' OR 1=1 --'"
+        );
+        let rendered = metadata.to_sql(Dialect::MySql);
+        let parsed = QueryMetadata::parse_with(&rendered, &mysql).unwrap();
+        assert_eq!(parsed.filter, metadata.filter);
+
+        //the control characters are rejected, as they'd be embedded back in the queries
+        let query = r"SELECT SUM(a) FROM t WHERE b = 'x\0'";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &mysql).map_err(|error| error.code()),
+            Err(ErrorCode::InvalidLiteral)
+        );
+        for cursor in [("x\0", ValueType::String), ("1 OR 1=1", ValueType::Number)] {
+            assert_eq!(
+                metadata
+                    .paged_extraction_query(Dialect::MySql, "id", 10, Some(cursor))
+                    .map_err(|error| error.code()),
+                Err(ErrorCode::InvalidLiteral)
+            );
+        }
+    }
+
+    #[test]
+    fn identifier_rules() {
+        let options = ParseOptions::new().with_identifier_rules(
+            IdentifierRules::new()
+                .with_max_length(Some(63))
+                .with_plain_names(true),
+        );
+        let query = "SELECT SUM(t.amount$usd) AS total FROM sales._2024 AS t WHERE t.Region = 'x'";
+        assert!(QueryMetadata::parse_with(query, &options).is_ok());
+        let long = "x".repeat(64);
+        let queries = [
+            format!("SELECT SUM(a) FROM {long}"),
+            format!("SELECT SUM(a) AS {long} FROM t"),
+            "SELECT SUM(\"with space\") FROM t".to_string(),
+            "SELECT SUM(a) FROM t AS \"t-1\"".to_string(),
+            "SELECT SUM(a) FROM t WHERE \"é\" > 1".to_string(),
+        ];
+        for query in queries {
+            assert_eq!(
+                QueryMetadata::parse_with(&query, &options).map_err(|error| error.code()),
+                Err(ErrorCode::InvalidIdentifier),
+                "{query}"
+            );
+        }
+        //the control characters are rejected even without rules
+        let query = "SELECT SUM(\"a\u{1}\") FROM t";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &ParseOptions::new()).map_err(|error| error.code()),
+            Err(ErrorCode::InvalidIdentifier)
+        );
+        let partial = QueryMetadata::parse_lenient("SELECT SUM(\"a b\") FROM t", &options).unwrap();
+        assert_eq!(
+            partial
+                .errors
+                .iter()
+                .map(ParseError::code)
+                .collect::<Vec<_>>(),
+            vec![ErrorCode::InvalidIdentifier]
+        );
+    }
+
+    #[test]
+    fn unicode_and_national_strings() {
+        let options = ParseOptions::new().with_dialect(Dialect::PostgreSql);
+        let value = |query: &str| {
+            QueryMetadata::parse_with(query, &options)
+                .map(|metadata| {
+                    let filter = metadata.filter.unwrap();
+                    (
+                        filter.comparison.value().map(str::to_string),
+                        filter.value_type,
+                    )
+                })
+                .map_err(|error| error.code())
+        };
+        let expected = Ok((Some("data".to_string()), Some(ValueType::String)));
+        assert_eq!(
+            value(r"SELECT SUM(a) FROM t WHERE b = U&'d\0061t\+000061'"),
+            expected.clone()
+        );
+        assert_eq!(
+            value(r"SELECT SUM(a) FROM t WHERE u&'d\0061ta' < b"),
+            expected
+        );
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b = N'it''s'"),
+            Ok((Some("it's".to_string()), Some(ValueType::String)))
+        );
+        assert_eq!(
+            value(r"SELECT SUM(a) FROM t WHERE b = U&'\00'"),
+            Err(ErrorCode::InvalidLiteral)
+        );
+        //the unescaped value is embedded as a plain string
+        let metadata =
+            QueryMetadata::parse_with(r"SELECT SUM(a) FROM t WHERE b = U&'caf\00e9'", &options)
+                .unwrap();
+        assert_eq!(
+            metadata.to_sql(Dialect::PostgreSql),
+            "SELECT SUM(a) FROM t WHERE b = 'café'"
+        );
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some("SELECT CAST(SUM(a) AS TEXT) FROM t WHERE b = 'café'")
+        );
+        //a column `u` combined with a string isn't a Unicode string
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b = u & 'data'"),
+            Err(ErrorCode::UnsupportedValue)
+        );
+        assert_eq!(
+            value(r#"SELECT SUM(a) FROM t WHERE b = "u"&'data'"#),
+            Err(ErrorCode::UnsupportedValue)
+        );
+        //nor is a `U&'...'` within a string or a comment
+        assert_eq!(
+            value(r"SELECT SUM(a) FROM t WHERE b = 'U&''\0041''' -- U&'\00'"),
+            Ok((Some(r"U&'\0041'".to_string()), Some(ValueType::String)))
+        );
+    }
+
+    #[test]
+    fn typed_literals() {
+        let filter = |query: &str| {
+            QueryMetadata::parse(query, None)
+                .map(|metadata| metadata.filter.unwrap())
+                .map_err(|error| error.code())
+        };
+        let cases = [
+            (
+                "SELECT SUM(a) FROM t WHERE d = DATE '2024-02-29'",
+                CompareOp::Eq {
+                    value: "2024-02-29".to_string(),
+                },
+                ValueType::Date,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE TIME '12:30:00' <= d",
+                CompareOp::GtEq {
+                    value: "12:30:00".to_string(),
+                },
+                ValueType::Time,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE d > TIMESTAMP '2024-01-01 00:00:00'",
+                CompareOp::Gt {
+                    value: "2024-01-01 00:00:00".to_string(),
+                },
+                ValueType::Timestamp,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE d < NUMERIC '2.5'",
+                CompareOp::Lt {
+                    value: "2.5".to_string(),
+                },
+                ValueType::Number,
+            ),
+        ];
+        for (query, comparison, value_type) in cases {
+            let expected = Filter {
+                column: "d".into(),
+                comparison,
+                value_type: Some(value_type),
+                json_path: None,
+                collation: None,
+            };
+            assert_eq!(filter(query), Ok(expected.clone()), "{query}");
+            //the type is rendered back with the value
+            let metadata = QueryMetadata::parse(query, None).unwrap();
+            let parsed = QueryMetadata::parse(&metadata.to_sql(Dialect::Generic), None).unwrap();
+            assert_eq!(parsed.filter, Some(expected));
+        }
+        assert_eq!(
+            QueryMetadata::parse("SELECT SUM(a) FROM t WHERE d = DATE '2024-02-29'", None)
+                .unwrap()
+                .to_sql(Dialect::PostgreSql),
+            "SELECT SUM(a) FROM t WHERE d = DATE '2024-02-29'"
+        );
+
+        for query in [
+            "SELECT SUM(a) FROM t WHERE d = DATE '2023-02-29'",
+            "SELECT SUM(a) FROM t WHERE d = TIMESTAMP 'yesterday'",
+            "SELECT SUM(a) FROM t WHERE d = NUMERIC 'abc'",
+        ] {
+            assert_eq!(filter(query), Err(ErrorCode::InvalidLiteral), "{query}");
+        }
+        assert_eq!(
+            filter("SELECT SUM(a) FROM t WHERE d = JSON '{}'"),
+            Err(ErrorCode::UnsupportedValue)
+        );
+
+        let schema = TableSchema {
+            columns: vec![ColumnDef {
+                name: "d".to_string(),
+                data_type: ColumnType::Number,
+            }],
+        };
+        let metadata =
+            QueryMetadata::parse("SELECT COUNT(d) FROM t WHERE d = DATE '2024-01-01'", None)
+                .unwrap();
+        assert_eq!(
+            metadata
+                .validate_against(&schema)
+                .map_err(|error| error.code()),
+            Err(ErrorCode::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn normalized_numbers() {
+        let value = |query: &str, dialect| {
+            QueryMetadata::parse_with(query, &ParseOptions::new().with_dialect(dialect))
+                .map(|metadata| metadata.filter.unwrap().comparison)
+                .map_err(|error| error.code())
+        };
+        let gt = |value: &str| {
+            Ok(CompareOp::Gt {
+                value: value.to_string(),
+            })
+        };
+        let dialect = Dialect::Generic;
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b > 1e3", dialect),
+            gt("1000")
+        );
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b > +2.50", dialect),
+            gt("2.5")
+        );
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b > -1.5E-2", dialect),
+            gt("-0.015")
+        );
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b > NUMERIC '1e2'", dialect),
+            gt("100")
+        );
+        assert_eq!(
+            value("SELECT SUM(a) FROM t WHERE b > 1e9999", dialect),
+            Err(ErrorCode::InvalidLiteral)
+        );
+        //the normalized value is rendered back
+        let metadata = QueryMetadata::parse("SELECT SUM(a) FROM t WHERE b > 1e3", None).unwrap();
+        assert_eq!(
+            metadata.to_sql(Dialect::Generic),
+            "SELECT SUM(a) FROM t WHERE b > 1000"
+        );
+    }
+
+    #[test]
+    fn constant_expressions() {
+        let comparison = |query: &str| {
+            QueryMetadata::parse(query, None)
+                .map(|metadata| metadata.filter.unwrap())
+                .map(|filter| (filter.comparison, filter.value_type))
+                .map_err(|error| error.code())
+        };
+        let number = |value: &str| {
+            Ok((
+                CompareOp::Gt {
+                    value: value.to_string(),
+                },
+                Some(ValueType::Number),
+            ))
+        };
+        assert_eq!(
+            comparison("SELECT SUM(a) FROM t WHERE x > -(5)"),
+            number("-5")
+        );
+        assert_eq!(
+            comparison("SELECT SUM(a) FROM t WHERE x > -(-(5))"),
+            number("5")
+        );
+        assert_eq!(
+            comparison("SELECT SUM(a) FROM t WHERE x > +((-2.5))"),
+            number("-2.5")
+        );
+        assert_eq!(
+            comparison("SELECT SUM(a) FROM t WHERE x > - -1e2"),
+            number("100")
+        );
+        assert_eq!(
+            comparison("SELECT SUM(a) FROM t WHERE name = 'ab' || ('c' || 'd')"),
+            Ok((
+                CompareOp::Eq {
+                    value: "abcd".to_string()
+                },
+                Some(ValueType::String)
+            ))
+        );
+        for query in [
+            "SELECT SUM(a) FROM t WHERE x > -'a'",
+            "SELECT SUM(a) FROM t WHERE name = 'a' || 1",
+            "SELECT SUM(a) FROM t WHERE name = 'a' || b",
+            "SELECT SUM(a) FROM t WHERE x > NOT TRUE",
+        ] {
+            assert_eq!(
+                comparison(query),
+                Err(ErrorCode::UnsupportedValue),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn symbolic_time_values() {
+        let filter = |query: &str| {
+            QueryMetadata::parse(query, None)
+                .map(|metadata| metadata.filter.unwrap())
+                .map_err(|error| error.code())
+        };
+        let cases = [
+            (
+                "SELECT SUM(a) FROM t WHERE created_at >= CURRENT_DATE",
+                SystemTime::CurrentDate,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE current_time < created_at",
+                SystemTime::CurrentTime,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE created_at >= CURRENT_TIMESTAMP",
+                SystemTime::CurrentTimestamp,
+            ),
+            (
+                "SELECT SUM(a) FROM t WHERE created_at >= now()",
+                SystemTime::CurrentTimestamp,
+            ),
+        ];
+        for (query, time) in cases {
+            let parsed = filter(query).unwrap();
+            assert_eq!(parsed.value_type, Some(ValueType::Symbolic), "{query}");
+            assert_eq!(parsed.comparison.value(), Some(time.sql_name()), "{query}");
+            assert_eq!(parsed.system_time(), Some(time), "{query}");
+            //the current date or time is rendered back as SQL, not as a string
+            let metadata = QueryMetadata::parse(query, None).unwrap();
+            let sql = metadata.to_sql(Dialect::Generic);
+            assert!(sql.contains(time.sql_name()), "{sql}");
+            assert!(!sql.contains('\''), "{sql}");
+            let reparsed = QueryMetadata::parse(&sql, None).unwrap();
+            assert_eq!(reparsed.filter, Some(parsed));
+        }
+        for query in [
+            "SELECT SUM(a) FROM t WHERE created_at >= NOW(1)",
+            "SELECT SUM(a) FROM t WHERE created_at >= \"NOW\"()",
+            "SELECT SUM(a) FROM t WHERE created_at >= LOCALTIME()",
+        ] {
+            assert_eq!(filter(query), Err(ErrorCode::UnsupportedValue), "{query}");
+        }
+        assert_eq!(
+            filter("SELECT SUM(a) FROM t WHERE created_at = 1")
+                .unwrap()
+                .system_time(),
+            None
+        );
+    }
+
+    #[test]
+    fn constant_predicates() {
+        let parse = |selection: &str| {
+            QueryMetadata::parse(&format!("SELECT SUM(a) FROM t WHERE {selection}"), None)
+                .map(|metadata| (metadata.filter, metadata.empty_result))
+                .map_err(|error| error.code())
+        };
+        let filter = Filter {
+            column: "x".into(),
+            comparison: CompareOp::Gt {
+                value: "5".to_string(),
+            },
+            value_type: Some(ValueType::Number),
+            json_path: None,
+            collation: None,
+        };
+        for selection in [
+            "TRUE",
+            "1 = 1",
+            "(1 <> 2)",
+            "1.0 = 1e0",
+            "'a' = 'a'",
+            "1 = 1 OR x > 5",
+        ] {
+            assert_eq!(parse(selection), Ok((None, false)), "{selection}");
+        }
+        for selection in ["FALSE", "1 = 0", "2 < 1", "NULL = NULL", "1 = 0 AND x > 5"] {
+            assert_eq!(parse(selection), Ok((None, true)), "{selection}");
+        }
+        for selection in [
+            "1 = 1 AND x > 5",
+            "x > 5 AND 1 = 1",
+            "1 = 1 AND (TRUE AND x > 5)",
+            "1 = 0 OR x > 5",
+        ] {
+            assert_eq!(
+                parse(selection),
+                Ok((Some(filter.clone()), false)),
+                "{selection}"
+            );
+        }
+        //the filter is the only predicate left, which is extracted alone
+        let metadata =
+            QueryMetadata::parse("SELECT SUM(a) FROM t WHERE 1 = 1 AND x > 5", None).unwrap();
+        assert_eq!(metadata.data_extraction_query, "SELECT a, x FROM t");
+        //the other predicates are still checked
+        assert_eq!(
+            parse("1 = 0 AND x > y"),
+            Err(ErrorCode::UnsupportedComparison)
+        );
+        assert_eq!(
+            parse("x > 5 AND y < 2"),
+            Err(ErrorCode::UnsupportedOperator)
+        );
+        assert_eq!(parse("'b' < 'a'"), Err(ErrorCode::UnsupportedComparison));
+        //strings that differ may be equal in the collation of the column (e.g. in MySQL)
+        let options = ParseOptions::new().with_dialect(Dialect::MySql);
+        for selection in ["'a' = 'A'", "'a' <> 'a '"] {
+            let query = format!("SELECT SUM(a) FROM t WHERE {selection}");
+            let error = QueryMetadata::parse_with(&query, &options).unwrap_err();
+            assert_eq!(
+                error.code(),
+                ErrorCode::UnsupportedComparison,
+                "{selection}"
+            );
+        }
+    }
+
+    #[test]
+    fn simplified_filters() {
+        let comparison = |selection: &str| {
+            QueryMetadata::parse(&format!("SELECT SUM(a) FROM t WHERE {selection}"), None)
+                .map(|metadata| metadata.filter.map(|filter| filter.comparison))
+                .map_err(|error| error.code())
+        };
+        let value = |value: &str| value.to_string();
+        let cases = [
+            ("x > 1 AND x > 2", CompareOp::Gt { value: value("2") }),
+            ("x > 1 OR x > 2", CompareOp::Gt { value: value("1") }),
+            ("x >= 2 AND x > 2", CompareOp::Gt { value: value("2") }),
+            ("x < 10 AND 5.0 >= x", CompareOp::LtEq { value: value("5") }),
+            ("x = 3 AND x > 1", CompareOp::Eq { value: value("3") }),
+            (
+                "x > 1 AND x IS NOT NULL",
+                CompareOp::Gt { value: value("1") },
+            ),
+            ("x = 'a' AND x = 'a'", CompareOp::Eq { value: value("a") }),
+            ("x IS TRUE OR x IS NOT FALSE", CompareOp::IsNotFalse),
+            (
+                "1 = 1 AND (x > 1 AND (x > 2 AND x >= 3))",
+                CompareOp::GtEq { value: value("3") },
+            ),
+        ];
+        for (selection, expected) in cases {
+            assert_eq!(comparison(selection), Ok(Some(expected)), "{selection}");
+        }
+        //the filters that don't reduce to one of them are still rejected
+        for selection in [
+            "x > 1 AND x < 5",
+            "x > 1 AND y > 2",
+            "x = 'a' AND x = 'b'",
+            "x = 1 AND x = '1'",
+            "x > 'a' AND x > 'b'",
+        ] {
+            assert_eq!(
+                comparison(selection),
+                Err(ErrorCode::UnsupportedOperator),
+                "{selection}"
+            );
+        }
+    }
+
+    #[test]
+    fn explain() {
+        let explain = |query: &str| QueryMetadata::parse(query, None).unwrap().explain();
+        assert_eq!(
+            explain(
+                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 \
+                 WHERE test_column_3 > 5"
+            ),
+            "Sum of test_column_2 from test_db.test_schema.test_table_1 where test_column_3 > 5"
+        );
+        assert_eq!(
+            explain("SELECT STDDEV(t.a) AS s FROM tbl AS t"),
+            "Standard deviation of a from tbl"
+        );
+        assert_eq!(
+            explain("SELECT MAX(a) FROM tbl WHERE b = 'it''s'"),
+            "Maximum of a from tbl where b = 'it''s'"
+        );
+        assert_eq!(
+            explain("SELECT COUNT(a) FROM tbl WHERE \"B\" IS NOT NULL"),
+            "Count of a from tbl where \"B\" IS NOT NULL"
+        );
+        assert_eq!(
+            explain("SELECT AVG(a) FROM tbl WHERE 1 = 0"),
+            "Average of a from tbl, over no rows since the filter never holds"
+        );
+        let options = ParseOptions::new().with_dialect(Dialect::BigQuery);
+        let query = "SELECT SUM(a) FROM d.t FOR SYSTEM_TIME AS OF '2024-01-01 00:00:00'";
+        assert_eq!(
+            QueryMetadata::parse_with(query, &options)
+                .unwrap()
+                .explain(),
+            "Sum of a from d.t as of 2024-01-01 00:00:00"
+        );
+    }
+
+    #[test]
+    fn semantic_equality() {
+        let hash = |metadata: &QueryMetadata| {
+            let mut hasher = DefaultHasher::new();
+            metadata.hash(&mut hasher);
+            hasher.finish()
+        };
+        let parse = |query: &str| QueryMetadata::parse(query, None).unwrap();
+        let equivalent = [
+            "SELECT SUM(a) FROM db.t WHERE b >= 10",
+            "select sum(\"a\") as total from DB.T as x where 1e1 <= x.b",
+            "/* daily */ SELECT SUM(a) FROM db.t WHERE (b >= 10.0) AND 1 = 1",
+            "SELECT SUM(A) FROM \"db\".t WHERE b >= +10 OR 1 = 0",
+        ];
+        let metadata = parse("SELECT SUM(a) FROM db.t WHERE b >= 10");
+        for query in equivalent {
+            let other = parse(query);
+            assert!(metadata.semantically_eq(&other), "{query}");
+            assert_eq!(hash(&metadata), hash(&other), "{query}");
+        }
+        let different = [
+            "SELECT AVG(a) FROM db.t WHERE b >= 10",
+            "SELECT SUM(a) FROM t WHERE b >= 10",
+            "SELECT SUM(\"A\") FROM db.t WHERE b >= 10",
+            "SELECT SUM(a) FROM db.t WHERE b > 10",
+            "SELECT SUM(a) FROM db.t WHERE b >= '10'",
+            "SELECT SUM(a) FROM db.t",
+            "SELECT SUM(a) FROM db.t WHERE 1 = 0",
+        ];
+        for query in different {
+            assert!(!metadata.semantically_eq(&parse(query)), "{query}");
+        }
+        //deduplicating the equivalent queries
+        struct Semantic(QueryMetadata);
+        impl PartialEq for Semantic {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.semantically_eq(&other.0)
+            }
+        }
+        impl Eq for Semantic {}
+        impl Hash for Semantic {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+        let distinct = equivalent
+            .iter()
+            .chain(&different)
+            .map(|query| Semantic(parse(query)))
+            .collect::<HashSet<_>>();
+        assert_eq!(distinct.len(), 1 + different.len());
+    }
+
+    #[test]
+    fn with_filter_table_aggregation() {
+        let options = ParseOptions::new();
+        let parse = |query: &str| QueryMetadata::parse_with(query, &options).unwrap();
+        let queries = |metadata: &QueryMetadata| {
+            (
+                metadata.data_extraction_query.clone(),
+                metadata.data_aggregation_query.clone(),
+            )
+        };
+        let metadata = parse("SELECT SUM(a) AS s FROM db.t AS x WHERE x.b > 1");
+
+        let filter = parse("SELECT SUM(a) FROM t WHERE c <= 'it''s'").filter;
+        let edited = metadata.clone().with_filter(filter, &options);
+        let expected = parse("SELECT SUM(a) AS s FROM db.t WHERE c <= 'it''s'");
+        assert_eq!(queries(&edited), queries(&expected));
+        assert_eq!(edited.filter, expected.filter);
+
+        let edited = metadata.clone().with_filter(None, &options);
+        assert_eq!(
+            queries(&edited),
+            queries(&parse("SELECT SUM(a) AS s FROM db.t"))
+        );
+
+        let table = TabIdent::parse("other.\"T 2\"", Dialect::Generic).unwrap();
+        let edited = metadata.clone().with_table(table.clone(), &options);
+        let expected = parse("SELECT SUM(a) AS s FROM other.\"T 2\" WHERE b > 1");
+        assert_eq!(queries(&edited), queries(&expected));
+        assert_eq!(edited.table, table);
+        assert_eq!(edited.aliases.tables["x"], "other.T 2");
+
+        let aggregation = parse("SELECT AVG(d) AS mean FROM t").aggregation;
+        let edited = metadata.clone().with_aggregation(aggregation, &options);
+        let expected = parse("SELECT AVG(d) AS mean FROM db.t WHERE b > 1");
+        assert_eq!(queries(&edited), queries(&expected));
+        assert_eq!(edited.aliases.results["mean"], "AVG(d)");
+        assert!(!edited.aliases.results.contains_key("s"));
+
+        //the queries follow the options
+        let options = ParseOptions::new()
+            .with_cast_to_text(false)
+            .with_filter_pushdown(true);
+        let mut edited = metadata;
+        edited.recompute_queries(&options);
+        let expected =
+            QueryMetadata::parse_with("SELECT SUM(a) AS s FROM db.t WHERE b > 1", &options)
+                .unwrap();
+        assert_eq!(queries(&edited), queries(&expected));
+        //a WHERE clause that never holds is kept
+        let mut empty = parse("SELECT SUM(a) FROM t WHERE 1 = 0");
+        empty.recompute_queries(&ParseOptions::new());
+        assert_eq!(
+            empty.data_aggregation_query.as_deref(),
+            Some("SELECT CAST(SUM(a) AS TEXT) FROM t WHERE false")
+        );
+        //and pushed down, so that no row is extracted
+        let options = ParseOptions::new().with_filter_pushdown(true);
+        let parsed =
+            QueryMetadata::parse_with("SELECT SUM(a) FROM t WHERE 1 = 0", &options).unwrap();
+        assert_eq!(parsed.data_extraction_query, "SELECT a FROM t WHERE false");
+        empty.recompute_queries(&options);
+        assert_eq!(empty.data_extraction_query, parsed.data_extraction_query);
+    }
+
+    #[test]
+    fn qualification_modes() {
+        let code = |qualification: Qualification, query: &str| {
+            let options = ParseOptions::new().with_qualification(qualification);
+            QueryMetadata::parse_with(query, &options)
+                .err()
+                .map(|error| error.code())
+        };
+        let rejected = Some(ErrorCode::ColumnNotInTable);
+        //(query, strict, standard, permissive)
+        let cases = [
+            ("SELECT SUM(a) FROM db.s.t", None, None, None),
+            ("SELECT SUM(db.s.t.a) FROM db.s.t", None, None, None),
+            ("SELECT SUM(S.T.a) FROM db.s.t", rejected, None, None),
+            ("SELECT SUM(t.a) FROM db.s.t", rejected, None, None),
+            (
+                "SELECT SUM(t.a) FROM s.t WHERE s.t.b > 1",
+                rejected,
+                None,
+                None,
+            ),
+            ("SELECT SUM(s.t.a) FROM t", rejected, rejected, None),
+            ("SELECT SUM(db.s.t.a) FROM s.t", rejected, rejected, None),
+            (
+                "SELECT SUM(other.t.a) FROM s.t",
+                rejected,
+                rejected,
+                rejected,
+            ),
+            (
+                "SELECT SUM(db.other.t.a) FROM db.s.t",
+                rejected,
+                rejected,
+                rejected,
+            ),
+            ("SELECT SUM(u.a) FROM s.t", rejected, rejected, rejected),
+            ("SELECT SUM(x.a) FROM s.t AS x", None, None, None),
+            (
+                "SELECT SUM(s.x.a) FROM s.t AS x",
+                rejected,
+                rejected,
+                rejected,
+            ),
+        ];
+        for (query, strict, standard, permissive) in cases {
+            assert_eq!(code(Qualification::Strict, query), strict, "{query}");
+            assert_eq!(code(Qualification::Standard, query), standard, "{query}");
+            assert_eq!(
+                code(Qualification::Permissive, query),
+                permissive,
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn two_part_table_names() {
+        let table = |query: &str, options: &ParseOptions| {
+            QueryMetadata::parse_with(query, options).unwrap().table
+        };
+        let in_db = TabIdent {
+            db: Some("mydb".into()),
+            schema: None,
+            table: "events".into(),
+        };
+        let in_schema = TabIdent {
+            db: None,
+            schema: Some("mydb".into()),
+            table: "events".into(),
+        };
+        let query = "SELECT SUM(mydb.events.a) FROM mydb.events WHERE events.b > 1";
+        for dialect in [Dialect::MySql, Dialect::BigQuery] {
+            let options = ParseOptions::new().with_dialect(dialect);
+            assert_eq!(table(query, &options), in_db, "{dialect:?}");
+            let options = options.with_table_qualifier(TableQualifier::Schema);
+            assert_eq!(table(query, &options), in_schema, "{dialect:?}");
+        }
+        for dialect in [Dialect::Generic, Dialect::PostgreSql] {
+            let options = ParseOptions::new().with_dialect(dialect);
+            assert_eq!(table(query, &options), in_schema, "{dialect:?}");
+            let options = options.with_table_qualifier(TableQualifier::Database);
+            assert_eq!(table(query, &options), in_db, "{dialect:?}");
+        }
+        //the table names of three parts are always db.schema.table
+        let options = ParseOptions::new().with_dialect(Dialect::MySql);
+        assert_eq!(
+            table("SELECT SUM(a) FROM mydb.s.events", &options).schema,
+            Some("s".into())
+        );
+        //a column qualified with another database doesn't match
+        assert_eq!(
+            QueryMetadata::parse_with("SELECT SUM(other.events.a) FROM mydb.events", &options)
+                .map_err(|error| error.code()),
+            Err(ErrorCode::ColumnNotInTable)
+        );
+        //the database is rendered as written
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            metadata.to_sql(Dialect::MySql),
+            "SELECT SUM(a) FROM mydb.events WHERE b > 1"
+        );
+    }
+
+    #[test]
+    fn cross_database_references() {
+        let code = |options: &ParseOptions, table: &str| {
+            let query = format!("SELECT SUM(a) FROM {table}");
+            QueryMetadata::parse_with(&query, options)
+                .err()
+                .map(|error| error.code())
+        };
+        let rejected = Some(ErrorCode::CrossDatabase);
+        let options = ParseOptions::new().with_default_db("main");
+        assert_eq!(code(&options, "other.s.t"), None);
+
+        let options = options.with_cross_database(CrossDatabase::Deny);
+        assert_eq!(code(&options, "t"), None);
+        assert_eq!(code(&options, "main.s.t"), None);
+        assert_eq!(code(&options, "other.s.t"), rejected);
+        assert_eq!(
+            QueryMetadata::parse_with("SELECT SUM(a) FROM other.s.t", &options),
+            Err(forbidden!(
+                ErrorCode::CrossDatabase,
+                "the table `other.s.t` isn't in the session database.".to_string()
+            ))
+        );
+        //in MySQL, two-part names are database-qualified
+        let mysql = options.clone().with_dialect(Dialect::MySql);
+        assert_eq!(code(&mysql, "other.t"), rejected);
+        assert_eq!(code(&mysql, "main.t"), None);
+        let errors = QueryMetadata::parse_with_all_errors("SELECT SUM(a) FROM other.t", &mysql);
+        assert_eq!(
+            errors.map_err(|errors| errors.iter().map(ParseError::code).collect::<Vec<_>>()),
+            Err(vec![ErrorCode::CrossDatabase])
+        );
+
+        let options = options.with_cross_database(CrossDatabase::AllowList(vec!["shared".into()]));
+        assert_eq!(code(&options, "shared.s.t"), None);
+        assert_eq!(code(&options, "main.s.t"), None);
+        assert_eq!(code(&options, "other.s.t"), rejected);
+        //without a session database, any written database is another one
+        let options = ParseOptions::new().with_cross_database(CrossDatabase::Deny);
+        assert_eq!(code(&options, "s.t"), None);
+        assert_eq!(code(&options, "main.s.t"), rejected);
+    }
+
+    #[test]
+    fn reserved_keywords_quoted() {
+        let metadata =
+            QueryMetadata::parse_with("SELECT SUM(a) FROM t WHERE b > 1", &ParseOptions::new())
+                .unwrap();
+        let aggregation = Aggregation {
+            function: KoronFunction::Sum,
+            column: "order".into(),
+            alias: None,
+            window: None,
+        };
+        let table = TabIdent {
+            db: None,
+            schema: Some("select".into()),
+            table: "key".into(),
+        };
+        let extraction_query = |dialect: Dialect| {
+            let options = ParseOptions::new().with_dialect(dialect);
+            metadata
+                .clone()
+                .with_aggregation(aggregation.clone(), &options)
+                .with_table(table.clone(), &options)
+                .data_extraction_query
+        };
+        assert_eq!(
+            extraction_query(Dialect::MySql),
+            "SELECT `order`, b FROM `select`.`key`"
+        );
+        //KEY is only reserved in MySQL
+        assert_eq!(
+            extraction_query(Dialect::PostgreSql),
+            "SELECT \"order\", b FROM \"select\".key"
+        );
+        assert_eq!(
+            extraction_query(Dialect::Generic),
+            "SELECT \"order\", b FROM \"select\".\"key\""
+        );
+        //an explicit quote style still applies to every identifier
+        let options = ParseOptions::new().with_quote_style(Some('"'));
+        assert_eq!(
+            metadata
+                .clone()
+                .with_aggregation(aggregation, &options)
+                .data_extraction_query,
+            "SELECT \"order\", \"b\" FROM \"t\""
+        );
+    }
+
+    #[test]
+    fn json_field_filters() {
+        let options = ParseOptions::new()
+            .with_dialect(Dialect::PostgreSql)
+            .with_filter_pushdown(true);
+        let parse = |query: &str| QueryMetadata::parse_with(query, &options);
+        let json_filter = |path: &[&str], comparison, value_type| Filter {
+            column: "payload".into(),
+            comparison,
+            value_type,
+            json_path: Some(path.iter().map(ToString::to_string).collect()),
+            collation: None,
+        };
+        let ok = || CompareOp::Eq {
+            value: "ok".to_string(),
+        };
+
+        let metadata = parse("SELECT SUM(a) FROM t WHERE payload->>'status' = 'ok'").unwrap();
+        assert_eq!(
+            metadata.filter,
+            Some(json_filter(&["status"], ok(), Some(ValueType::String)))
+        );
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT a, payload FROM t WHERE payload ->> 'status' = 'ok'"
+        );
+        //the JSON operators bind tighter than the comparisons and the logical operators
+        for query in [
+            "SELECT SUM(a) FROM t WHERE t.payload -> 'event' ->> 'status' = 'ok'",
+            "SELECT SUM(a) FROM t WHERE 'ok' = (payload->'event')->>'status'",
+            "SELECT SUM(a) FROM t WHERE payload->'event'->>'status' = 'ok' AND TRUE",
+            "SELECT SUM(a) FROM t WHERE 1 = 1 AND payload->'event'->>'status' = 'ok'",
+        ] {
+            let filter = parse(query).unwrap().filter;
+            assert_eq!(
+                filter,
+                Some(json_filter(
+                    &["event", "status"],
+                    ok(),
+                    Some(ValueType::String)
+                )),
+                "{query}"
+            );
+            assert_eq!(
+                filter.unwrap().to_sql(Dialect::PostgreSql, None),
+                "payload -> 'event' ->> 'status' = 'ok'"
+            );
+        }
+        assert_eq!(
+            parse("SELECT SUM(a) FROM t WHERE payload->>'deleted_at' IS NULL")
+                .unwrap()
+                .filter,
+            Some(json_filter(&["deleted_at"], CompareOp::IsNull, None))
+        );
+        //the filters on different fields of a column aren't combined
+        let query = "SELECT SUM(a) FROM t WHERE payload->>'a' = 'ok' AND payload->>'a' = 'ok'";
+        assert_eq!(
+            parse(query).unwrap().filter,
+            Some(json_filter(&["a"], ok(), Some(ValueType::String)))
+        );
+        let query = "SELECT SUM(a) FROM t WHERE payload->>'a' = 'ok' AND payload->>'b' = 'ok'";
+        assert!(parse(query).is_err());
+
+        //only the fields accessed as text by key are supported
+        for query in [
+            "SELECT SUM(a) FROM t WHERE payload->'status' = 'ok'",
+            "SELECT SUM(a) FROM t WHERE payload->>0 = 'ok'",
+            "SELECT SUM(a) FROM t WHERE payload->>'status'",
+            "SELECT SUM(a) FROM t WHERE other.payload->>'status' = 'ok'",
+        ] {
+            assert!(parse(query).is_err(), "{query}");
+        }
+
+        let json = serde_json::to_string(&json_filter(&["status"], ok(), None)).unwrap();
+        assert!(json.contains("\"status\"]"), "{json}");
+        assert_eq!(
+            serde_json::from_str::<Filter>(&json).unwrap(),
+            json_filter(&["status"], ok(), None)
+        );
+    }
+
+    #[test]
+    fn array_comparisons() {
+        let options = ParseOptions::new()
+            .with_dialect(Dialect::PostgreSql)
+            .with_filter_pushdown(true);
+        let parse = |query: &str| QueryMetadata::parse_with(query, &options);
+        let x = || "x".to_string();
+        let cases = [
+            (
+                "tags[1] = 'x'",
+                CompareOp::ElementEq {
+                    index: 1,
+                    value: x(),
+                },
+            ),
+            (
+                "'x' = (t.tags)[1]",
+                CompareOp::ElementEq {
+                    index: 1,
+                    value: x(),
+                },
+            ),
+            (
+                "tags[-1] <> 'x'",
+                CompareOp::ElementNotEq {
+                    index: -1,
+                    value: x(),
+                },
+            ),
+            ("'x' = ANY(tags)", CompareOp::AnyEq { value: x() }),
+            ("'x' <> ALL(t.tags)", CompareOp::AllNotEq { value: x() }),
+        ];
+        for (predicate, comparison) in cases {
+            let query = format!("SELECT SUM(a) FROM t WHERE {predicate}");
+            let filter = parse(&query).unwrap().filter.unwrap();
+            assert_eq!(filter.column, "tags", "{query}");
+            assert_eq!(filter.comparison, comparison, "{query}");
+            assert_eq!(filter.value_type, Some(ValueType::String), "{query}");
+            //the rendered filter is parsed back the same
+            let rendered = filter.to_sql(Dialect::PostgreSql, None);
+            let query = format!("SELECT SUM(a) FROM t WHERE {rendered}");
+            assert_eq!(parse(&query).unwrap().filter, Some(filter), "{query}");
+        }
+        assert_eq!(
+            parse("SELECT SUM(a) FROM t WHERE 'x' = ANY(tags)")
+                .unwrap()
+                .data_extraction_query,
+            "SELECT a, tags FROM t WHERE 'x' = ANY(tags)"
+        );
+        //MySQL parses the subscripts as map accesses
+        let options = ParseOptions::new().with_dialect(Dialect::MySql);
+        assert_eq!(
+            QueryMetadata::parse_with("SELECT SUM(a) FROM t WHERE tags[2] = 3", &options)
+                .unwrap()
+                .filter
+                .map(|filter| filter.comparison),
+            Some(CompareOp::ElementEq {
+                index: 2,
+                value: "3".to_string()
+            })
+        );
+
+        for (predicate, code) in [
+            ("tags[1] > 'x'", ErrorCode::UnsupportedComparison),
+            ("tags['a'] = 'x'", ErrorCode::UnsupportedValue),
+            ("tags[1.5] = 'x'", ErrorCode::UnsupportedValue),
+            ("tags[1][2] = 'x'", ErrorCode::UnsupportedComparison),
+            ("'x' < ANY(tags)", ErrorCode::UnsupportedOperator),
+            ("'x' = ALL(tags)", ErrorCode::UnsupportedOperator),
+            ("'x' = ANY(other.tags)", ErrorCode::ColumnNotInTable),
+        ] {
+            let query = format!("SELECT SUM(a) FROM t WHERE {predicate}");
+            assert_eq!(
+                parse(&query).map_err(|error| error.code()),
+                Err(code),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn collated_filters() {
+        let options = ParseOptions::new()
+            .with_dialect(Dialect::PostgreSql)
+            .with_filter_pushdown(true);
+        let parse = |query: &str| QueryMetadata::parse_with(query, &options);
+        let filter = |comparison, value_type, collation: &str| Filter {
+            column: "name".into(),
+            comparison,
+            value_type,
+            json_path: None,
+            collation: Some(collation.to_string()),
+        };
+        let x = || CompareOp::Eq {
+            value: "x".to_string(),
+        };
+        let cases = [
+            (
+                "name = 'x' COLLATE \"de_DE\"",
+                filter(x(), Some(ValueType::String), "de_DE"),
+            ),
+            (
+                "name COLLATE \"de_DE\" = 'x'",
+                filter(x(), Some(ValueType::String), "de_DE"),
+            ),
+            (
+                "'x' = (t.name COLLATE pg_catalog.\"C\")",
+                filter(x(), Some(ValueType::String), "pg_catalog.C"),
+            ),
+            (
+                "name COLLATE \"C\" = 'x' COLLATE \"C\"",
+                filter(x(), Some(ValueType::String), "C"),
+            ),
+            //unquoted names are case folded
+            (
+                "name COLLATE Und IS NULL",
+                filter(CompareOp::IsNull, None, "und"),
+            ),
+        ];
+        for (predicate, expected) in cases {
+            let query = format!("SELECT SUM(a) FROM t WHERE {predicate}");
+            let filter = parse(&query).unwrap().filter.unwrap();
+            assert_eq!(filter, expected, "{query}");
+            //the rendered filter is parsed back the same
+            let rendered = filter.to_sql(Dialect::PostgreSql, None);
+            let query = format!("SELECT SUM(a) FROM t WHERE {rendered}");
+            assert_eq!(parse(&query).unwrap().filter, Some(filter), "{query}");
+        }
+        assert_eq!(
+            parse("SELECT SUM(a) FROM t WHERE name = 'x' COLLATE \"de_DE\"")
+                .unwrap()
+                .data_extraction_query,
+            "SELECT a, name FROM t WHERE name COLLATE \"de_DE\" = 'x'"
+        );
+        //the collation tells the filters apart
+        let collated = parse("SELECT SUM(a) FROM t WHERE name = 'x' COLLATE \"C\"").unwrap();
+        let plain = parse("SELECT SUM(a) FROM t WHERE name = 'x'").unwrap();
+        assert_ne!(
+            collated.fingerprint(false, false),
+            plain.fingerprint(false, false)
+        );
+        assert_eq!(
+            serde_json::to_value(plain.filter).unwrap().get("collation"),
+            None
+        );
+
+        for predicate in [
+            "name COLLATE \"C\" = 'x' COLLATE \"de_DE\"",
+            "'x' COLLATE \"C\" = 'x'",
+            "'x' = ANY(tags COLLATE \"C\")",
+        ] {
+            let query = format!("SELECT SUM(a) FROM t WHERE {predicate}");
+            assert_eq!(
+                parse(&query).map_err(|error| error.code()),
+                Err(ErrorCode::UnsupportedComparison),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn shared_options() {
+        let options = Arc::new(
+            ParseOptions::new()
+                .with_dialect(Dialect::PostgreSql)
+                .with_denied_tables(vec![TablePattern {
+                    table: "secrets".to_string(),
+                    ..TablePattern::default()
+                }]),
+        );
+        let results = std::thread::scope(|scope| {
+            let workers = ["t", "secrets"].map(|table| {
+                let options = Arc::clone(&options);
+                scope.spawn(move || {
+                    let query = format!("SELECT SUM(a) FROM {table}");
+                    QueryMetadata::parse_with(&query, &options)
+                })
+            });
+            workers.map(|worker| worker.join().unwrap())
+        });
+        let [allowed, denied] = results;
+        assert_eq!(allowed.unwrap().table.table, "t");
+        assert_eq!(
+            denied.map_err(|error| error.code()),
+            Err(ErrorCode::ForbiddenTable)
+        );
+    }
+
+    #[test]
+    fn rejection_hook() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let options = ParseOptions::new().with_on_reject({
+            let reported = Arc::clone(&reported);
+            Arc::new(move |feature: &UnsupportedFeature, message: &str| {
+                assert!(!message.is_empty());
+                reported.lock().unwrap().push(feature.clone());
+            })
+        });
+        let rejected = || std::mem::take(&mut *reported.lock().unwrap());
+
+        QueryMetadata::parse_with("SELECT SUM(a) FROM t", &options).unwrap();
+        assert_eq!(rejected(), vec![]);
+        QueryMetadata::parse_with("SELECT SUM(a) FROM t ORDER BY a", &options).unwrap_err();
+        assert_eq!(rejected(), vec![UnsupportedFeature::OrderBy]);
+        //the other errors aren't reported
+        QueryMetadata::parse_with("SELECT SUM(a) FROM", &options).unwrap_err();
+        assert_eq!(rejected(), vec![]);
+
+        let query = "SELECT DISTINCT SUM(a) FROM t ORDER BY a";
+        QueryMetadata::parse_lenient(query, &options).unwrap();
+        assert_eq!(
+            rejected(),
+            vec![UnsupportedFeature::OrderBy, UnsupportedFeature::Distinct]
+        );
+        //each error is reported once, although the query is parsed twice
+        let errors = QueryMetadata::parse_with_all_errors(query, &options).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            rejected(),
+            vec![UnsupportedFeature::OrderBy, UnsupportedFeature::Distinct]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn canonical_json() {
+        let query = "/* daily */ SELECT SUM(t.a) AS s FROM db.sc.tbl AS t WHERE t.b = 'x'";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+        let json = metadata.to_canonical_json().unwrap();
+        assert_eq!(json, metadata.clone().to_canonical_json().unwrap());
+        assert_eq!(
+            serde_json::from_str::<QueryMetadata>(&json).unwrap(),
+            metadata
+        );
+        //the keys are sorted at every level
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for object in [&document, &document["aggregation"], &document["filter"]] {
+            let keys = object.as_object().unwrap().keys().collect::<Vec<_>>();
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]), "{keys:?}");
+        }
+        assert!(
+            json.starts_with("{\"aggregation\":{\"alias\":\"s\""),
+            "{json}"
+        );
+        assert!(!json.contains("collation"), "{json}");
+    }
+
+    #[test]
+    fn shard_queries() {
+        let shard_query = |query: &str, dialect| {
+            let metadata = QueryMetadata::parse(query, None).unwrap();
+            metadata.shard_query(dialect)
+        };
+        assert_eq!(
+            shard_query(
+                "SELECT AVG(t.a) AS m FROM db.s.tbl AS t WHERE t.b > 1",
+                Dialect::Generic
+            )
+            .as_deref(),
+            Some("SELECT SUM(a), COUNT(a) FROM db.s.tbl WHERE b > 1")
+        );
+        assert_eq!(
+            shard_query("SELECT STDDEV(\"Order\") FROM t", Dialect::MySql).as_deref(),
+            Some("SELECT SUM(`Order`), SUM(`Order` * `Order`), COUNT(`Order`) FROM t")
+        );
+        assert_eq!(
+            shard_query("SELECT COUNT(a) FROM t WHERE 1 = 0", Dialect::Generic).as_deref(),
+            Some("SELECT COUNT(a) FROM t WHERE false")
+        );
+        assert_eq!(
+            shard_query("SELECT MEDIAN(a) FROM t", Dialect::Snowflake),
+            None
+        );
+    }
+
+    #[test]
+    fn partial_plans() {
+        use crate::plan::MergeFunction;
+
+        let metadata = QueryMetadata::parse("SELECT VARIANCE(a) FROM t WHERE b > 1", None).unwrap();
+        let plan = metadata.partial_plan(Dialect::Generic).unwrap();
+        assert_eq!(
+            Some(plan.source_query),
+            metadata.shard_query(Dialect::Generic)
+        );
+        assert_eq!(
+            plan.merge,
+            MergeFunction::Combine {
+                decomposition: metadata.aggregation.decompose().unwrap()
+            }
+        );
+        assert!(plan.merge.to_string().contains("(count - 1)"));
+
+        let metadata =
+            QueryMetadata::parse("SELECT MEDIAN(t.a) FROM s.tbl AS t WHERE t.b = 'x'", None)
+                .unwrap();
+        let plan = metadata.partial_plan(Dialect::PostgreSql).unwrap();
+        assert_eq!(
+            plan.source_query,
+            "SELECT a, COUNT(a) FROM s.tbl WHERE b = 'x' GROUP BY a"
+        );
+        assert_eq!(plan.merge, MergeFunction::Histogram);
+    }
+
+    #[test]
+    fn data_count_queries() {
+        let query = "SELECT AVG(t.a) AS m FROM db.sc.tbl AS t WHERE t.b > 1";
+        let metadata = QueryMetadata::parse(query, None).unwrap();
+        //the clauses are kept as written, and the alias is left out
+        assert_eq!(
+            metadata.data_count_query.as_deref(),
+            Some("SELECT CAST(COUNT(t.a) AS TEXT) FROM db.sc.tbl AS t WHERE t.b > 1")
+        );
+        let options = ParseOptions::new().with_cast_to_text(false);
+        let metadata = metadata.with_filter(None, &options);
+        assert_eq!(
+            metadata.data_count_query.as_deref(),
+            Some("SELECT COUNT(a) FROM db.sc.tbl")
+        );
+        let metadata = QueryMetadata::parse("SELECT SUM(a) FROM t", None).unwrap();
+        assert_eq!(metadata.data_count_query, None);
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(!json.to_lowercase().contains("count"), "{json}");
+    }
+
+    #[test]
+    fn time_buckets() {
+        let query = "SELECT DATE_TRUNC('day', t.ts) AS day, SUM(t.a) AS total FROM db.s.tbl AS t \
+                     WHERE t.b > 1 GROUP BY DATE_TRUNC('day', t.ts)";
+        let error = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::UnsupportedGroupBy);
+
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_time_buckets: true,
+            ..FeaturePolicy::default()
+        });
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        let day = TimeBucket {
+            column: "ts".into(),
+            granularity: TimeGranularity::Day,
+            time_type: TimeType::Timestamp,
+        };
+        assert_eq!(metadata.time_bucket, Some(day.clone()));
+        assert_eq!(metadata.aggregation.alias.as_deref(), Some("total"));
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT a, b, ts FROM db.s.tbl"
+        );
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some(
+                "SELECT DATE_TRUNC('day', t.ts) AS day, CAST(SUM(t.a) AS TEXT) AS total \
+                 FROM db.s.tbl AS t WHERE t.b > 1 GROUP BY DATE_TRUNC('day', t.ts)"
+            )
+        );
+        let sql = metadata.to_sql(Dialect::Generic);
+        assert_eq!(
+            sql,
+            "SELECT DATE_TRUNC('day', ts), SUM(a) AS total FROM db.s.tbl WHERE b > 1 \
+             GROUP BY DATE_TRUNC('day', ts)"
+        );
+        let parsed = QueryMetadata::parse_with(&sql, &options).unwrap();
+        assert!(parsed.semantically_eq(&metadata));
+        assert!(metadata.explain().contains(" per day of ts where "));
+        //the same query over the whole table is another query
+        let ungrouped = metadata.clone().with_time_bucket(None, &options);
+        assert!(!ungrouped.semantically_eq(&metadata));
+        assert_ne!(
+            ungrouped.fingerprint(true, true),
+            metadata.fingerprint(true, true)
+        );
+        assert_eq!(
+            ungrouped.data_aggregation_query.as_deref(),
+            Some("SELECT CAST(SUM(a) AS TEXT) AS total FROM db.s.tbl WHERE b > 1")
+        );
+
+        //the other spellings, selected or not
+        let cases = [
+            (
+                Dialect::Generic,
+                "SELECT AVG(a) FROM t GROUP BY toStartOfHour(ts)",
+                TimeGranularity::Hour,
+            ),
+            (
+                Dialect::MySql,
+                "SELECT MAX(a), DATE(ts) FROM t GROUP BY DATE(ts)",
+                TimeGranularity::Day,
+            ),
+            (
+                Dialect::BigQuery,
+                "SELECT TIMESTAMP_TRUNC(ts, ISOWEEK), MIN(a) FROM d.t GROUP BY TIMESTAMP_TRUNC(ts, ISOWEEK)",
+                TimeGranularity::Week,
+            ),
+            (
+                Dialect::BigQuery,
+                "SELECT MIN(a) FROM d.t GROUP BY DATETIME_TRUNC(ts, YEAR)",
+                TimeGranularity::Year,
+            ),
+            (
+                Dialect::PostgreSql,
+                "SELECT COUNT(a) FROM t GROUP BY date_trunc('QUARTER', (ts))",
+                TimeGranularity::Quarter,
+            ),
+        ];
+        for (dialect, query, granularity) in cases {
+            let options = options.clone().with_dialect(dialect);
+            let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+            let bucket = metadata.time_bucket.as_ref().unwrap();
+            assert_eq!(bucket.granularity, granularity, "{query}");
+            //the bucket is rendered in the dialect, and reads back the same
+            let sql = metadata.to_sql(dialect);
+            let parsed = QueryMetadata::parse_with(&sql, &options).unwrap();
+            assert_eq!(parsed.time_bucket.as_ref(), Some(bucket), "{sql}");
+        }
+
+        //the queries computed per period return a row per period, starting with it
+        let query = "SELECT AVG(a) FROM t GROUP BY DATE(ts)";
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            metadata.data_count_query.as_deref(),
+            Some("SELECT CAST(COUNT(a) AS TEXT) FROM t GROUP BY DATE(ts)")
+        );
+        assert_eq!(
+            metadata.shard_query(Dialect::Generic).as_deref(),
+            Some("SELECT DATE_TRUNC('day', ts), SUM(a), COUNT(a) FROM t GROUP BY DATE_TRUNC('day', ts)")
+        );
+        let median = metadata.with_aggregation(
+            Aggregation {
+                function: KoronFunction::Median,
+                column: "a".into(),
+                alias: None,
+                window: None,
+            },
+            &options,
+        );
+        assert_eq!(
+            median.partial_plan(Dialect::Generic).unwrap().source_query,
+            "SELECT DATE_TRUNC('day', ts), a, COUNT(a) FROM t GROUP BY DATE_TRUNC('day', ts), a"
+        );
+
+        //BigQuery truncates the dates and datetimes with their own functions, kept as written
+        let bigquery = options.clone().with_dialect(Dialect::BigQuery);
+        let query = "SELECT SUM(a) FROM d.t GROUP BY DATE_TRUNC(created, MONTH)";
+        let metadata = QueryMetadata::parse_with(query, &bigquery).unwrap();
+        assert_eq!(
+            metadata.time_bucket.as_ref().map(|bucket| bucket.time_type),
+            Some(TimeType::Date)
+        );
+        assert_eq!(
+            metadata.to_sql(Dialect::BigQuery),
+            "SELECT DATE_TRUNC(created, MONTH), SUM(a) FROM d.t \
+             GROUP BY DATE_TRUNC(created, MONTH)"
+        );
+        assert_eq!(
+            metadata.shard_query(Dialect::BigQuery).as_deref(),
+            Some(
+                "SELECT DATE_TRUNC(created, MONTH), SUM(a) FROM d.t \
+                 GROUP BY DATE_TRUNC(created, MONTH)"
+            )
+        );
+        let timestamps = QueryMetadata::parse_with(
+            "SELECT SUM(a) FROM d.t GROUP BY TIMESTAMP_TRUNC(created, MONTH)",
+            &bigquery,
+        )
+        .unwrap();
+        assert_ne!(
+            timestamps.fingerprint(true, true),
+            metadata.fingerprint(true, true)
+        );
+        let median = metadata.with_aggregation(
+            Aggregation {
+                function: KoronFunction::Median,
+                column: "a".into(),
+                alias: None,
+                window: None,
+            },
+            &bigquery,
+        );
+        assert_eq!(
+            median.partial_plan(Dialect::BigQuery).unwrap().source_query,
+            "SELECT DATE_TRUNC(created, MONTH), a, COUNT(a) FROM d.t \
+             GROUP BY DATE_TRUNC(created, MONTH), a"
+        );
+
+        let cases = [
+            (
+                "SELECT SUM(a) FROM t GROUP BY b",
+                ErrorCode::UnsupportedGroupBy,
+            ),
+            (
+                "SELECT SUM(a) FROM t GROUP BY DATE_TRUNC('decade', ts)",
+                ErrorCode::UnsupportedGroupBy,
+            ),
+            //the weeks of toStartOfWeek start on Sunday
+            (
+                "SELECT SUM(a) FROM t GROUP BY toStartOfWeek(ts)",
+                ErrorCode::UnsupportedGroupBy,
+            ),
+            (
+                "SELECT SUM(a) FROM t GROUP BY DATE(ts), b",
+                ErrorCode::UnsupportedGroupBy,
+            ),
+            (
+                "SELECT SUM(a) FROM t AS x GROUP BY DATE(y.ts)",
+                ErrorCode::ColumnNotInTable,
+            ),
+            //the selected bucket isn't the one of the GROUP BY clause
+            (
+                "SELECT DATE_TRUNC('month', ts), SUM(a) FROM t GROUP BY DATE_TRUNC('day', ts)",
+                ErrorCode::UnsupportedProjection,
+            ),
+        ];
+        for (query, code) in cases {
+            let error = QueryMetadata::parse_with(query, &options).unwrap_err();
+            assert_eq!(error.code(), code, "{query}");
+        }
+    }
+
+    #[test]
+    fn running_aggregates() {
+        let query = "SELECT SUM(t.a) OVER (ORDER BY t.ts ROWS BETWEEN UNBOUNDED PRECEDING AND \
+                     CURRENT ROW) AS total FROM db.s.tbl AS t WHERE t.b > 1";
+        let error = QueryMetadata::parse_with(query, &ParseOptions::new()).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::UnsupportedWindowFunction);
+
+        let options = ParseOptions::new().with_feature_policy(FeaturePolicy {
+            allow_running_aggregates: true,
+            ..FeaturePolicy::default()
+        });
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(
+            metadata.aggregation.window,
+            Some(WindowSpec {
+                order_by: "ts".into(),
+                descending: false,
+            })
+        );
+        assert_eq!(
+            metadata.data_extraction_query,
+            "SELECT a, ts, b FROM db.s.tbl"
+        );
+        //the window is kept as written in the data aggregation query
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some(
+                "SELECT CAST(SUM(t.a) OVER (ORDER BY t.ts ROWS BETWEEN UNBOUNDED PRECEDING AND \
+                 CURRENT ROW) AS TEXT) AS total FROM db.s.tbl AS t WHERE t.b > 1"
+            )
+        );
+        assert_eq!(
+            metadata.to_sql(Dialect::Generic),
+            "SELECT SUM(a) OVER (ORDER BY ts ROWS UNBOUNDED PRECEDING) AS total FROM db.s.tbl \
+             WHERE b > 1"
+        );
+        assert_eq!(
+            metadata.explain(),
+            "Sum of a accumulated over the rows ordered by ts from db.s.tbl where b > 1"
+        );
+        //the result of a row depends on the rows of the other shards
+        assert_eq!(metadata.shard_query(Dialect::Generic), None);
+        assert_eq!(metadata.partial_plan(Dialect::Generic), None);
+        let total =
+            QueryMetadata::parse("SELECT SUM(a) AS total FROM db.s.tbl WHERE b > 1", None).unwrap();
+        assert!(!metadata.semantically_eq(&total));
+        assert_ne!(
+            metadata.fingerprint(true, true),
+            total.fingerprint(true, true)
+        );
+
+        let query = "SELECT AVG(a) OVER (ORDER BY ts DESC ROWS UNBOUNDED PRECEDING) FROM t";
+        let metadata = QueryMetadata::parse_with(query, &options).unwrap();
+        assert_eq!(metadata.data_count_query, None);
+        let options = options.with_cast_to_text(false);
+        let metadata = metadata.with_filter(None, &options);
+        assert_eq!(
+            metadata.data_aggregation_query.as_deref(),
+            Some("SELECT AVG(a) OVER (ORDER BY ts DESC ROWS UNBOUNDED PRECEDING) FROM t")
+        );
+
+        for query in [
+            "SELECT SUM(a) OVER (PARTITION BY b ORDER BY ts ROWS UNBOUNDED PRECEDING) FROM t",
+            "SELECT SUM(a) OVER (ORDER BY ts RANGE UNBOUNDED PRECEDING) FROM t",
+            "SELECT SUM(a) OVER (ORDER BY ts) FROM t",
+            "SELECT SUM(a) OVER (ORDER BY ts, id ROWS UNBOUNDED PRECEDING) FROM t",
+            "SELECT SUM(a) OVER (ORDER BY ts ROWS 2 PRECEDING) FROM t",
+            "SELECT SUM(a) OVER (ORDER BY ts + 1 ROWS UNBOUNDED PRECEDING) FROM t",
+            "SELECT MEDIAN(a) OVER (ORDER BY ts ROWS UNBOUNDED PRECEDING) FROM t",
+        ] {
+            let error = QueryMetadata::parse_with(query, &options).unwrap_err();
+            assert_eq!(
+                error.code(),
+                ErrorCode::UnsupportedWindowFunction,
+                "{query}"
+            );
+        }
+
+        //a running aggregate isn't computed per period
+        let options = options.with_feature_policy(FeaturePolicy {
+            allow_running_aggregates: true,
+            allow_time_buckets: true,
+            ..FeaturePolicy::default()
+        });
+        let query = "SELECT SUM(a) OVER (ORDER BY ts ROWS UNBOUNDED PRECEDING) FROM t \
+                     GROUP BY DATE(ts)";
+        let error = QueryMetadata::parse_with(query, &options).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::UnsupportedWindowFunction);
+        let partial = QueryMetadata::parse_lenient(query, &options).unwrap();
+        assert_eq!(partial.aggregation, None);
+        assert_eq!(partial.errors.len(), 1);
+    }
+
+    #[test]
+    fn unquoted_result_alias_case_insensitive() {
+        let query = "SELECT SUM(test_column_2) AS S FROM test_db.test_schema.test_table_1";
+        let expected = Ok(QueryMetadata {
+            table: sample_tab_ident(),
+            aggregation: Aggregation {
+                function: KoronFunction::Sum,
+                column: "test_column_2".into(),
+                alias: Some("s".to_string()),
+                window: None,
+            },
+            filter: None,
+            time_bucket: None,
+            data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
+            data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) AS S FROM test_db.test_schema.test_table_1")),
+            data_count_query: None,
+            aliases: aliases(&[], &[("s", "SUM(test_column_2)")]),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
+        });
+        assert_eq!(QueryMetadata::parse(query, None), expected);
+    }
+
+    #[test]
+    fn quoted_result_alias_case_sensitive() {
+        let query = "SELECT SUM(test_column_2) AS \"S\" FROM test_db.test_schema.test_table_1";
+        let expected = Ok(QueryMetadata {
+            table: sample_tab_ident(),
+            aggregation: Aggregation {
+                function: KoronFunction::Sum,
+                column: "test_column_2".into(),
+                alias: Some("S".to_string()),
+                window: None,
+            },
+            filter: None,
+            time_bucket: None,
+            data_extraction_query:String::from("SELECT test_column_2 FROM test_db.test_schema.test_table_1"),
+            data_aggregation_query: Some(String::from("SELECT CAST(SUM(test_column_2) AS TEXT) AS \"S\" FROM test_db.test_schema.test_table_1")),
+            data_count_query: None,
+            aliases: aliases(&[], &[("S", "SUM(test_column_2)")]),
+            table_version: None,
+            table_hints: Vec::new(),
+            annotations: Vec::new(),
+                empty_result: false,
+                schema_version: QueryMetadata::SCHEMA_VERSION,
+        });
+        assert_eq!(QueryMetadata::parse(query, None), expected);
+    }
+
+    #[test]
+    fn quoted_table_alias_case_sensitive() {
+        for (column, alias, extracted_alias) in [
+            ("t.test_column_2", "\"T\"", "T"),
+            ("\"T\".test_column_2", "\"t\"", "t"),
+        ] {
+            let query =
+                &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1 AS {alias}");
+
+            let expected = Err(malformed_query!(
+                ErrorCode::ColumnNotInTable,
+                format!(
+                    "the {column} column is not part of \
+                     the table that's listed in the FROM clause ({extracted_alias}).",
+                )
+            ));
+            assert_eq!(
+                QueryMetadata::parse(query, None),
+                expected,
+                "\nfailed for query {query:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn qualified_column_from_different_table() {
+        for column in [
+            "\"test_table_2\".test_column_2",
+            "\"test_schema\".test_table_2.test_column_2",
+        ] {
+            let query = &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1");
+
+            let expected = Err(malformed_query!(
+                ErrorCode::ColumnNotInTable,
+                format!(
+                    "the {column} column is not part of \
+                     the table that's listed in the FROM clause (test_db.test_schema.test_table_1).",
+                )));
+            assert_eq!(
+                QueryMetadata::parse(query, None),
+                expected,
+                "\nfailed for query {query:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn qualified_column_not_from_table_alias() {
+        for column in [
+            "test_table_1.test_column_2",
+            "test_schema.test_table_1.test_column_2",
+        ] {
+            let query = &format!("SELECT SUM({column}) FROM test_db.test_schema.test_table_1 AS t");
+            let expected = Err(malformed_query!(
+                ErrorCode::ColumnNotInTable,
+                format!(
+                    "the {column} column is not part of \
                      the table that's listed in the FROM clause (t).",
-            )));
+                )
+            ));
             assert_eq!(
                 QueryMetadata::parse(query, None),
                 expected,
@@ -278,6 +4050,7 @@ mod tests {
     fn sql_syntax_error() {
         let query = "SELECT * FROM";
         let expected = Err(malformed_query!(
+            ErrorCode::Syntax,
             "sql parser error: Expected identifier, found: EOF".to_string()
         ));
         assert_eq!(QueryMetadata::parse(query, None), expected);
@@ -313,7 +4086,10 @@ mod tests {
 
         for (projection, reason) in cases {
             let query = &format!("SELECT {projection} FROM test_db.test_schema.test_table_1");
-            let expected = Err(malformed_query!(reason.to_string()));
+            let expected = Err(malformed_query!(
+                ErrorCode::WrongArgumentCount,
+                reason.to_string()
+            ));
             assert_eq!(
                 QueryMetadata::parse(query, None),
                 expected,
@@ -327,194 +4103,238 @@ mod tests {
         let cases = [
             (
                 "SELECT * FROM test_db.test_schema.test_table_1; SELECT * FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement.",
             ),
             (
                 "DELETE FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement.",
             ),
             (
                 "WITH t AS (SELECT 1) SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Cte,
                 "CTEs (i.e., WITH clause).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 ORDER BY SUM",
+                UnsupportedFeature::OrderBy,
                 "ORDER BY.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 LIMIT 1",
+                UnsupportedFeature::Limit,
                 "LIMIT.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 OFFSET 1",
+                UnsupportedFeature::Offset,
                 "OFFSET.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 FETCH FIRST 1 ROW ONLY",
+                UnsupportedFeature::Fetch,
                 "FETCH.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 drda FOR UPDATE",
+                UnsupportedFeature::Locking,
                 "locking clauses (i.e., FOR UPDATE).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 \
                 UNION \
                 SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::SetOperation("UNION".to_string()),
                 "set operations (i.e., UNION).",
             ),
-            ("VALUES (1)", "VALUES."),
+            ("VALUES (1)", UnsupportedFeature::Values, "VALUES."),
             (
                 "INSERT INTO test_table_1(test_column_2) VALUES(1)",
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement."
             ),
             (
                 "SELECT DISTINCT SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Distinct,
                 "DISTINCT.",
             ),
             // TOP is MSSQL syntax.
             (
                 "SELECT TOP 1 SUM(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Top,
                 "TOP.",
             ),
             (
                 "SELECT SUM(test_column_2) INTO t FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::SelectInto,
                 "SELECT INTO.",
             ),
             // LATERAL VIEW is HiveQL syntax.
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 LATERAL VIEW (SELECT 1) t",
+                UnsupportedFeature::LateralView,
                 "LATERAL VIEW.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 GROUP BY SUM",
+                UnsupportedFeature::GroupBy,
                 "GROUP BY.",
             ),
             // CLUSTER BY is HiveQL syntax.
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 CLUSTER BY SUM",
+                UnsupportedFeature::ClusterBy,
                 "CLUSTER BY.",
             ),
             // DISTRIBUTE BY is HiveQL syntax.
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 DISTRIBUTE BY SUM",
+                UnsupportedFeature::DistributeBy,
                 "DISTRIBUTE BY.",
             ),
             // SORT BY is HiveQL syntax.
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 SORT BY SUM",
+                UnsupportedFeature::SortBy,
                 "SORT BY.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 HAVING sum > 0",
+                UnsupportedFeature::Having,
                 "HAVING.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1, treasury.attachment",
+                UnsupportedFeature::Join,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 CROSS JOIN treasury.attachment",
+                UnsupportedFeature::Join,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM f('arg')",
+                UnsupportedFeature::TableFunction,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             // table hints are MSSQL syntax.
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WITH (NOLOCK)",
+                UnsupportedFeature::TableHints,
                 "table hints (WITH in FROM clauses).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM (SELECT * FROM test_db.test_schema.test_table_1)",
+                UnsupportedFeature::Subquery,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM TABLE(f())",
+                UnsupportedFeature::TableFunction,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             (
                 "SELECT SUM(test_column_2) FROM (test_schema.test_table_1 CROSS JOIN treasury.attachment)",
+                UnsupportedFeature::Join,
                 "the FROM clause has multiple tables (no JOINs, subqueries or functions allowed).",
             ),
             (
                 "SELECT SUM(f) FROM test_db.test_schema.test_table_1 AS d (f, g)",
+                UnsupportedFeature::AliasColumns,
                 "table aliases with columns (such as d (f, g)).",
             ),
             (
                 "SELECT SUM(test_column_2), AVG(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Projection,
                 "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.",
             ),
             (
                 "SELECT drda.* FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Projection,
                 "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.",
             ),
             (
                 "SELECT * FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Projection,
                 "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.",
             ),
             (
                 "SELECT id FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Projection,
                 "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.",
             ),
             (
                 "SELECT SUM(test_column_2) OVER (PARTITION BY id) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::WindowFunction,
                 "window functions (OVER).",
             ),
             (
                 "SELECT SUM(DISTINCT test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Distinct,
                 "DISTINCT.",
             ),
             (
                 "SELECT custom.aggregation(test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::UnknownFunction("custom.aggregation".to_string()),
                 "unrecognized or unsupported function: custom.aggregation.",
             ),
             (
                 "SELECT SUM(x => test_column_2) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::NamedArgument,
                 "named function arguments (such as x => test_column_2).",
             ),
             (
                 "SELECT SUM(1) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Argument,
                 "only a column name is supported as the argument of the SUM function.",
             ),
             (
                 "SELECT SUM(test_table_1.*) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Argument,
                 "only a column name is supported as the argument of the SUM function.",
             ),
             (
                 "SELECT SUM(*) FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Argument,
                 "only a column name is supported as the argument of the SUM function.",
             ),
             (
                 "INSERT INTO test_table_1 SELECT * FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement.",
             ),
             (
                 "CREATE TABLE test_table_1 AS SELECT * FROM test_db.test_schema.test_table_1",
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WHERE test_column_2 BETWEEN 1 AND 2",
+                UnsupportedFeature::Expression,
                 "unsupported expression in the WHERE clause: test_column_2 BETWEEN 1 AND 2.",
             ),
             (
-                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WHERE 2 < 1",
-                "2 < 1. Only comparisons between a column and a constant are supported.",
+                "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WHERE 'b' < 'a'",
+                UnsupportedFeature::Comparison,
+                "'b' < 'a'. Only comparisons between a column and a constant are supported.",
             ),
             (
                 "SELECT SUM(test_column_2) FROM test_db.test_schema.test_table_1 WHERE test_column_2 < test_column_3",
+                UnsupportedFeature::Comparison,
                 "test_column_2 < test_column_3. Only comparisons between a column and a constant are supported.",
             ),
             // Unsupported functions
             (
                 "SELECT KTHELEMENT(test_column_2, 3) FROM test_db.test_schema.test_table_1;",
+                UnsupportedFeature::UnknownFunction("KTHELEMENT".to_string()),
                 "unrecognized or unsupported function: KTHELEMENT."
             )
         ];
 
-        for (query, reason) in cases {
-            let expected = Err(unsupported!(reason.to_string()));
+        for (query, code, reason) in cases {
+            let expected = Err(unsupported!(code, reason.to_string()));
             assert_eq!(
                 QueryMetadata::parse(query, None),
                 expected,
@@ -529,232 +4349,313 @@ mod tests {
             (
                 "test_column_2 < 1",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Lt {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "1 < test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Gt {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 <= 1",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::LtEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "1 <= test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::GtEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 > 1",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Gt {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "1 > test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Lt {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 >= 1",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::GtEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "1 >= test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::LtEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_3 > '2021-04-02T05:02:16.04+03:00'",
                 Filter {
-                    column: "test_column_3".to_string(),
+                    column: "test_column_3".into(),
                     comparison: CompareOp::Gt {
                         value: "2021-04-02T05:02:16.04+03:00".to_string(),
                     },
+                    value_type: Some(ValueType::String),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "-1 >= test_column_4",
                 Filter {
-                    column: "test_column_4".to_string(),
+                    column: "test_column_4".into(),
                     comparison: CompareOp::LtEq {
                         value: "-1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "+1 >= test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::LtEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "+1 = test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Eq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "+1 <> test_column_2",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::NotEq {
                         value: "1".to_string(),
                     },
+                    value_type: Some(ValueType::Number),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 IS NULL",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::IsNull,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 IS NOT NULL",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::IsNotNull,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_1 = NULL",
                 Filter {
-                    column: "test_column_1".to_string(),
+                    column: "test_column_1".into(),
                     comparison: CompareOp::Eq {
                         value: "Null".to_string(),
                     },
+                    value_type: Some(ValueType::Null),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_2 = NULL",
                 Filter {
-                    column: "test_column_2".to_string(),
+                    column: "test_column_2".into(),
                     comparison: CompareOp::Eq {
                         value: "Null".to_string(),
                     },
+                    value_type: Some(ValueType::Null),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_3 = NULL",
                 Filter {
-                    column: "test_column_3".to_string(),
+                    column: "test_column_3".into(),
                     comparison: CompareOp::Eq {
                         value: "Null".to_string(),
                     },
+                    value_type: Some(ValueType::Null),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_4 = NULL",
                 Filter {
-                    column: "test_column_4".to_string(),
+                    column: "test_column_4".into(),
                     comparison: CompareOp::Eq {
                         value: "Null".to_string(),
                     },
+                    value_type: Some(ValueType::Null),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 IS TRUE",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::IsTrue,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 IS NOT TRUE",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::IsNotTrue,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 = true",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::Eq {
                         value: "true".to_string(),
                     },
+                    value_type: Some(ValueType::Boolean),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 <> true",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::NotEq {
                         value: "true".to_string(),
                     },
+                    value_type: Some(ValueType::Boolean),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 IS FALSE",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::IsFalse,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 IS NOT FALSE",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::IsNotFalse,
+                    value_type: None,
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 = false",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::Eq {
                         value: "false".to_string(),
                     },
+                    value_type: Some(ValueType::Boolean),
+                    json_path: None,
+                    collation: None,
                 },
             ),
             (
                 "test_column_5 <> false",
                 Filter {
-                    column: "test_column_5".to_string(),
+                    column: "test_column_5".into(),
                     comparison: CompareOp::NotEq {
                         value: "false".to_string(),
                     },
+                    value_type: Some(ValueType::Boolean),
+                    json_path: None,
+                    collation: None,
                 },
             ),
         ];
@@ -778,8 +4679,16 @@ mod tests {
                     table: sample_tab_ident(),
                     aggregation,
                     filter: Some(filter.clone()),
+                    time_bucket: None,
                     data_extraction_query: expected_query,
                     data_aggregation_query: None,
+                    data_count_query: None,
+                    aliases: Aliases::default(),
+                    table_version: None,
+                    table_hints: Vec::new(),
+                    annotations: Vec::new(),
+                    empty_result: false,
+                    schema_version: QueryMetadata::SCHEMA_VERSION,
                 };
                 let result = QueryMetadata::parse(query, None).unwrap();
                 assert_eq!(
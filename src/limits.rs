@@ -0,0 +1,178 @@
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+use crate::{
+    dialect::Dialect,
+    error::{ErrorCode, ParseError},
+    limit_exceeded,
+};
+
+/// Limits on the size of the queries, checked before they're parsed.
+///
+/// Untrusted input fails fast with [`ParseError::LimitExceeded`] (e.g. deeply nested
+/// parentheses, which would exhaust the stack, or huge IN lists, which would stall the parser).
+/// The defaults are generous for hand-written queries; any limit can be lifted with `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    query_length: Option<usize>,
+    depth: Option<usize>,
+    list_size: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            query_length: Some(1 << 20),
+            depth: Some(32),
+            list_size: Some(1024),
+        }
+    }
+}
+
+impl Limits {
+    /// Creates the default limits: queries up to 1 MiB, nested up to 32 parentheses deep and with
+    /// lists of up to 1024 items.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates limits that accept any query.
+    #[must_use]
+    pub const fn unlimited() -> Self {
+        Self {
+            query_length: None,
+            depth: None,
+            list_size: None,
+        }
+    }
+
+    /// Sets the maximum length of the queries, in bytes.
+    #[must_use]
+    pub const fn with_max_query_length(mut self, max_query_length: Option<usize>) -> Self {
+        self.query_length = max_query_length;
+        self
+    }
+
+    /// Sets the maximum nesting depth of the parentheses (and brackets) in the queries.
+    #[must_use]
+    pub const fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of items of the comma-separated lists in the queries (e.g. the
+    /// values of an IN list or the arguments of a function).
+    #[must_use]
+    pub const fn with_max_list_size(mut self, max_list_size: Option<usize>) -> Self {
+        self.list_size = max_list_size;
+        self
+    }
+
+    /// The maximum length of the queries, in bytes.
+    #[must_use]
+    pub const fn max_query_length(&self) -> Option<usize> {
+        self.query_length
+    }
+
+    /// The maximum nesting depth of the parentheses in the queries.
+    #[must_use]
+    pub const fn max_depth(&self) -> Option<usize> {
+        self.depth
+    }
+
+    /// The maximum number of items of the lists in the queries.
+    #[must_use]
+    pub const fn max_list_size(&self) -> Option<usize> {
+        self.list_size
+    }
+
+    // checks the limits on the tokens of `sql`, leaving the queries that can't be tokenized to
+    // the SQL parser, which reports the error
+    pub(crate) fn check(&self, sql: &str, dialect: Dialect) -> Result<(), ParseError> {
+        if let Some(max_query_length) = self.query_length {
+            if sql.len() > max_query_length {
+                return Err(limit_exceeded!(
+                    ErrorCode::QueryTooLong,
+                    format!("the query is longer than {max_query_length} bytes.")
+                ));
+            }
+        }
+        if self.depth.is_none() && self.list_size.is_none() {
+            return Ok(());
+        }
+        let Ok(tokens) = Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() else {
+            return Ok(());
+        };
+        //the number of items of the lists being scanned, the innermost last
+        let mut lists = vec![1_usize];
+        for token in tokens {
+            match token {
+                Token::LParen | Token::LBracket => {
+                    lists.push(1);
+                    if let Some(max_depth) = self.depth {
+                        if lists.len() - 1 > max_depth {
+                            return Err(limit_exceeded!(
+                                ErrorCode::ExpressionTooDeep,
+                                format!("the query is nested more than {max_depth} levels deep.")
+                            ));
+                        }
+                    }
+                }
+                Token::RParen | Token::RBracket if lists.len() > 1 => {
+                    lists.pop();
+                }
+                Token::Comma => {
+                    let items = lists.last_mut().map_or(1, |items| {
+                        *items += 1;
+                        *items
+                    });
+                    if let Some(max_list_size) = self.list_size {
+                        if items > max_list_size {
+                            return Err(limit_exceeded!(
+                                ErrorCode::ListTooLong,
+                                format!("the query has a list of more than {max_list_size} items.")
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Limits;
+    use crate::{dialect::Dialect, error::ErrorCode};
+
+    #[test]
+    fn check() {
+        let limits = Limits::new()
+            .with_max_query_length(Some(64))
+            .with_max_depth(Some(2))
+            .with_max_list_size(Some(3));
+        let code = |sql: &str| limits.check(sql, Dialect::Generic).err().map(|e| e.code());
+        assert_eq!(code("SELECT SUM(a) FROM t WHERE ((a > 1))"), None);
+        assert_eq!(code("SELECT SUM(a) FROM t WHERE b IN (1, 2, 3)"), None);
+        assert_eq!(
+            code("SELECT SUM(a) FROM t WHERE (((a > 1)))"),
+            Some(ErrorCode::ExpressionTooDeep)
+        );
+        assert_eq!(
+            code("SELECT SUM(a) FROM t WHERE b IN (1, 2, 3, 4)"),
+            Some(ErrorCode::ListTooLong)
+        );
+        assert_eq!(
+            code(&format!("SELECT SUM(a) FROM t -- {}", "x".repeat(64))),
+            Some(ErrorCode::QueryTooLong)
+        );
+        //the parentheses in literals and comments don't count
+        assert_eq!(code("SELECT SUM(a) FROM t WHERE b = '((((,,,,'"), None);
+        assert_eq!(
+            Limits::unlimited().check(&"(".repeat(100), Dialect::Generic),
+            Ok(())
+        );
+    }
+}
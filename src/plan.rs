@@ -0,0 +1,101 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::aggregation::{Decomposition, KoronFunction, MedianState};
+
+/// A plan to compute a query over several sources, e.g. the shards of a table.
+///
+/// Each source computes a partial state, and the states are merged into the result (see
+/// [`crate::query_metadata::QueryMetadata::partial_plan`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct PartialPlan {
+    /// The query computing the partial state of a source.
+    pub source_query: String,
+    /// How the partial states of the sources are merged.
+    pub merge: MergeFunction,
+}
+
+/// How the partial states of the sources of a [`PartialPlan`] are merged into the result.
+///
+/// It's displayed as a description of the merge, e.g. for the logs of a query planner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum MergeFunction {
+    /// Each source returns a row of partial aggregates, recombined with
+    /// [`Decomposition::combine`].
+    Combine {
+        /// The partial aggregates, in the order of the columns of the rows.
+        decomposition: Decomposition,
+    },
+    /// Each source returns the histogram of its values, as rows of a value and its count,
+    /// merged with [`merge_histograms`] (for the median).
+    Histogram,
+}
+
+impl Display for MergeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self::Combine { decomposition } = self else {
+            return f.write_str(
+                "sum the counts of each value over the sources, then take the middle value, or \
+                 the mean of the two middle values for an even count",
+            );
+        };
+        f.write_str(match decomposition.function {
+            KoronFunction::Sum => "sum the sums of the sources",
+            KoronFunction::Count => "sum the counts of the sources",
+            KoronFunction::Min => "take the minimum of the minimums of the sources",
+            KoronFunction::Max => "take the maximum of the maximums of the sources",
+            KoronFunction::Average => {
+                "sum the sums and the counts of the sources, then divide the sum by the count"
+            }
+            KoronFunction::Variance => {
+                "sum the sums, the sums of squares and the counts of the sources, then compute \
+                 (squares - sum * sum / count) / (count - 1)"
+            }
+            KoronFunction::StandardDeviation => {
+                "sum the sums, the sums of squares and the counts of the sources, then compute \
+                 the square root of (squares - sum * sum / count) / (count - 1)"
+            }
+            KoronFunction::Median => "recombine the partial aggregates of the sources",
+        })
+    }
+}
+
+/// Computes the median of the values of the sources from their histograms, i.e. the values with
+/// their counts (see [`MergeFunction::Histogram`]), `None` if there are no values.
+///
+/// The histograms are merged as [`MedianState`]s.
+#[must_use]
+pub fn merge_histograms(histograms: &[Vec<(f64, u64)>]) -> Option<f64> {
+    let mut state = MedianState::default();
+    for &(value, count) in histograms.iter().flatten() {
+        state.add_count(value, count);
+    }
+    state.result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_histograms;
+
+    #[test]
+    fn histograms() {
+        assert_eq!(merge_histograms(&[]), None);
+        assert_eq!(merge_histograms(&[vec![(1.0, 0)]]), None);
+        //1, 2, 2, 5 and 9
+        let histograms = [vec![(2.0, 1), (9.0, 1)], vec![(5.0, 1), (1.0, 1), (2.0, 1)]];
+        assert_eq!(merge_histograms(&histograms), Some(2.0));
+        //1, 2, 2, 5, 9 and 10
+        let histograms = [
+            histograms[0].clone(),
+            histograms[1].clone(),
+            vec![(10.0, 1)],
+        ];
+        assert_eq!(merge_histograms(&histograms), Some(3.5));
+        assert_eq!(merge_histograms(&[vec![(-1.5, 4)]]), Some(-1.5));
+    }
+}
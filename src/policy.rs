@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{ErrorCode, ParseError},
+    forbidden,
+    identifier::Identifier,
+    table::TabIdent,
+};
+
+/// A pattern matching table names, where a missing database or schema matches any.
+///
+/// E.g. `TablePattern { db: None, schema: Some("public"), table: "users" }` matches the
+/// `users` table of the `public` schema in any database.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct TablePattern {
+    /// The database of the table, any if `None`.
+    pub db: Option<String>,
+    /// The schema of the table, any if `None`.
+    pub schema: Option<String>,
+    /// The name of the table.
+    pub table: String,
+}
+
+impl TablePattern {
+    /// Returns `true` if `table` matches the pattern.
+    #[must_use]
+    pub fn matches(&self, table: &TabIdent) -> bool {
+        let part_matches = |pattern: &Option<String>, part: &Option<Identifier>| {
+            pattern
+                .as_deref()
+                .is_none_or(|pattern| part.as_ref().is_some_and(|part| part == pattern))
+        };
+        part_matches(&self.db, &table.db)
+            && part_matches(&self.schema, &table.schema)
+            && self.table == table.table
+    }
+}
+
+/// Sensitive columns of the tables matching a pattern, which may never be aggregated nor filtered
+/// on (e.g. personal data).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct SensitiveColumns {
+    /// The tables the columns belong to.
+    pub table: TablePattern,
+    /// The names of the columns, as they're stored (i.e. already case folded).
+    pub columns: Vec<String>,
+}
+
+/// The clauses that are accepted on top of a single aggregation, all rejected by default.
+///
+/// Most of them don't change its result, so the metadata doesn't represent them and the allowed
+/// ones are left out of the derived queries. The time buckets and the running aggregates do, so
+/// they're recorded in the metadata and kept in the derived queries instead.
+///
+/// E.g. `FeaturePolicy { allow_order_by: true, ..FeaturePolicy::default() }` accepts the ORDER
+/// BY clauses appended by BI tools.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct FeaturePolicy {
+    /// Accept an ORDER BY clause.
+    pub allow_order_by: bool,
+    /// Accept a LIMIT clause with a positive number of rows.
+    pub allow_limit: bool,
+    /// Accept DISTINCT in the SELECT clause (but not in the aggregation, e.g. `SUM(DISTINCT a)`,
+    /// which changes its result).
+    pub allow_distinct: bool,
+    /// Accept locking clauses (e.g. FOR UPDATE).
+    pub allow_locking: bool,
+    /// Accept table hints (e.g. `WITH (NOLOCK)` in T-SQL). They're recorded in
+    /// [`crate::query_metadata::QueryMetadata::table_hints`] and left out of the data extraction
+    /// query, but the data aggregation query keeps them, with the FROM clause as written.
+    #[serde(default)]
+    pub allow_table_hints: bool,
+    /// Accept a GROUP BY clause grouping by a single time bucket (e.g.
+    /// `GROUP BY DATE_TRUNC('day', ts)`), so that the aggregation is computed over each period.
+    /// Unlike the other clauses, it's represented in the metadata (see
+    /// [`crate::query_metadata::QueryMetadata::time_bucket`]) and kept in the derived queries.
+    #[serde(default)]
+    pub allow_time_buckets: bool,
+    /// Accept a running aggregate, i.e. the aggregation over a window of the rows up to each row
+    /// (e.g. `SUM(a) OVER (ORDER BY ts ROWS UNBOUNDED PRECEDING)`), recorded in
+    /// [`crate::aggregation::Aggregation::window`]. The other window functions are rejected.
+    #[serde(default)]
+    pub allow_running_aggregates: bool,
+}
+
+/// How strictly a column qualified with its table (e.g. `s.t.col`) must match the table of the
+/// FROM clause.
+///
+/// The parts of the qualifier are compared to the ones of the table as they're written in the
+/// FROM clause, once case folded. A column qualified with another table is always rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum Qualification {
+    /// The qualifier must be the table as written in the FROM clause, e.g. only `s.t.col` for
+    /// `FROM s.t` (not `t.col`).
+    Strict,
+    /// The qualifier may leave out the database and the schema of the table, e.g. `t.col` or
+    /// `s.t.col` for `FROM db.s.t`, but not name parts the FROM clause doesn't have, e.g. not
+    /// `s.t.col` for `FROM t`.
+    #[default]
+    Standard,
+    /// Like [`Self::Standard`], but the qualifier may also name a database or a schema the FROM
+    /// clause doesn't have, whatever it is, e.g. `s.t.col` for `FROM t` (the table being read
+    /// from the default schema).
+    Permissive,
+}
+
+impl Qualification {
+    // whether a part of the qualifier (`None` if it's left out) matches the same part of the
+    // table of the FROM clause (`None` if it's not written)
+    pub(crate) fn part_matches(self, expected: Option<&Identifier>, part: Option<&str>) -> bool {
+        match (expected, part) {
+            (Some(expected), Some(part)) => expected == part,
+            (Some(_), None) => self != Self::Strict,
+            (None, Some(_)) => self == Self::Permissive,
+            (None, None) => true,
+        }
+    }
+}
+
+/// Whether a query may read a table of another database than the session one (see
+/// [`crate::config::ParseOptions::with_default_db`]).
+///
+/// A table whose database isn't written is read from the session database. Without a session
+/// database, any table whose database is written is a cross-database reference.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum CrossDatabase {
+    /// Any database can be queried.
+    #[default]
+    Allow,
+    /// Only the session database can be queried.
+    Deny,
+    /// Only the session database and the listed ones, as they're stored (i.e. already case
+    /// folded), can be queried.
+    AllowList(Vec<String>),
+}
+
+impl CrossDatabase {
+    /// Returns `true` if a table of the database `db` may be queried when the session database
+    /// is `session_db`.
+    #[must_use]
+    pub fn allows(&self, db: &str, session_db: Option<&str>) -> bool {
+        session_db == Some(db)
+            || match self {
+                Self::Allow => true,
+                Self::Deny => false,
+                Self::AllowList(databases) => databases.iter().any(|allowed| allowed == db),
+            }
+    }
+}
+
+// checks that the database of `table` (as written in the query) may be queried
+pub(crate) fn check_database_access(
+    table: &TabIdent,
+    session_db: Option<&str>,
+    policy: &CrossDatabase,
+) -> Result<(), ParseError> {
+    match &table.db {
+        Some(db) if !policy.allows(db.as_str(), session_db) => Err(forbidden!(
+            ErrorCode::CrossDatabase,
+            format!("the table `{table}` isn't in the session database.")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `table` may be queried: it must match one of the `allowed` patterns (if any are
+/// given) and none of the `denied` ones.
+pub(crate) fn check_table_access(
+    table: &TabIdent,
+    allowed: Option<&[TablePattern]>,
+    denied: &[TablePattern],
+) -> Result<(), ParseError> {
+    let is_allowed = allowed.is_none_or(|allowed| allowed.iter().any(|p| p.matches(table)));
+    if !is_allowed || denied.iter().any(|pattern| pattern.matches(table)) {
+        return Err(forbidden!(
+            ErrorCode::ForbiddenTable,
+            format!("the table `{table}` can't be queried.")
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that none of `columns` of `table` is declared as sensitive.
+pub(crate) fn check_column_access<'a>(
+    table: &TabIdent,
+    columns: impl IntoIterator<Item = &'a str>,
+    sensitive: &[SensitiveColumns],
+) -> Result<(), ParseError> {
+    let sensitive = sensitive
+        .iter()
+        .filter(|sensitive| sensitive.table.matches(table))
+        .flat_map(|sensitive| &sensitive.columns)
+        .collect::<Vec<_>>();
+    for column in columns {
+        if sensitive.iter().any(|sensitive| *sensitive == column) {
+            return Err(forbidden!(
+                ErrorCode::ForbiddenColumn,
+                format!("the column `{column}` of the table `{table}` can't be queried.")
+            ));
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,54 @@
-use std::fmt::{self, Display};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+};
 
 use serde::{Deserialize, Serialize};
-use sqlparser::{ast, dialect::GenericDialect, parser::Parser};
+use sqlparser::ast;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    aggregation::{Aggregation, KoronFunction},
+    aggregation::{Aggregation, KoronFunction, PartialAggregate, WindowSpec},
+    annotation::{extract_annotations, Annotation},
+    comparison::{CompareOp, ValueType},
+    config::ParseOptions,
     destructured_query::DestructuredQuery,
-    error::ParseError,
-    filter::{Filter, FilterExtractor},
-    support::case_fold_identifier,
-    table::{TabIdent, TableIdentWithAlias},
+    dialect::{CaseFolding, Dialect, TableQualifier},
+    error::{ErrorCode, ParseError, UnsupportedFeature},
+    expression::Value,
+    filter::{Filter, FilterExtractor, Predicate},
+    identifier::Identifier,
+    internal, malformed_query,
+    plan::{MergeFunction, PartialPlan},
+    policy::{check_column_access, check_database_access, check_table_access, Qualification},
+    schema::{Catalog, ColumnDef, ColumnType, TableSchema},
+    span::locate,
+    stats::QueryStats,
+    support::{
+        case_fold_identifier, cast_to_text, format_query, lowercase_keywords,
+        parse_statements_within, remove_outer_parens, rename_table, render_identifier,
+        render_select, select_query, stable_hash, table_with_joins, SelectedExpr,
+    },
+    table::{table_hints, TabIdent, TableIdentWithAlias, TableVersion},
+    time_bucket::{without_bucket, TimeBucket, TimeType},
     unsupported,
 };
 
 /// QueryMetadata extracted from the query.
+///
+/// With the `camel-case` feature, it's serialized with its fields and the ones of the nested
+/// types in camelCase (e.g. `dataExtractionQuery`), as are the names of the functions and of
+/// the value types (e.g. `"standardDeviation"`); the comparisons and the annotations are
+/// objects tagged with their `type` either way.
+///
+/// The serialized metadata of a query is the same on every run: the fields are in the order
+/// they're declared, the aliases are sorted by name, and the table hints and the annotations are
+/// in the order of the query. [`Self::to_canonical_json`] also sorts the keys, so that the JSON
+/// doesn't depend on the declaration order either (e.g. to sign it).
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema, IntoParams)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
 pub struct QueryMetadata {
     /// Aggregation performed.
     pub aggregation: Aggregation,
@@ -23,65 +56,803 @@ pub struct QueryMetadata {
     pub table: TabIdent,
     /// Filter applied.
     pub filter: Option<Filter>,
+    /// The time bucket the query is grouped by, if the feature policy allows it (see
+    /// [`crate::policy::FeaturePolicy::allow_time_buckets`]): the aggregation is then computed
+    /// over each period, and the derived queries return a row per period, starting with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_bucket: Option<TimeBucket>,
     /// Data Extraction Query in SQL
     pub data_extraction_query: String,
     /// Data Aggregation Query in SQL
     pub data_aggregation_query: Option<String>,
+    /// The query counting the values the aggregation is computed over, with the same FROM and
+    /// WHERE clauses as the data aggregation query (e.g. `SELECT COUNT(a) FROM t WHERE b > 1`),
+    /// to weight or recombine the results. It's only generated for AVG, VARIANCE and STDDEV, when
+    /// they aren't running aggregates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_count_query: Option<String>,
+    /// Aliases used in the query.
+    #[serde(default)]
+    pub aliases: Aliases,
+    /// The point in time the table is read at, if any.
+    #[serde(default)]
+    pub table_version: Option<TableVersion>,
+    /// The hints of the table (e.g. `NOLOCK`), accepted if the feature policy allows them and
+    /// left out of the data extraction query.
+    #[serde(default)]
+    pub table_hints: Vec<String>,
+    /// The comments leading the query and the hints passed in its comments (see [`Annotation`]).
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Whether the WHERE clause never holds (e.g. `WHERE 1 = 0`), so that the aggregation is
+    /// computed over no rows. The predicates of constants are evaluated rather than kept in the
+    /// filter, which is `None` if there's no other predicate.
+    #[serde(default)]
+    pub empty_result: bool,
+    /// The version of the serialized representation of the metadata (see
+    /// [`Self::SCHEMA_VERSION`]), 0 for the metadata serialized before it was versioned.
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
-impl QueryMetadata {
-    /// Generates `QueryMetadata` from a SQL query using [`crate::config::Config`].
-    pub fn parse(
-        sql_query: &str,
-        quote_style: Option<char>, /* e.g. "'" for PostgreSQL, "`" for MySQL */
+/// The metadata extracted by a lenient parse (see [`QueryMetadata::parse_lenient`]).
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct PartialQueryMetadata {
+    /// Aggregation performed, if it could be extracted.
+    pub aggregation: Option<Aggregation>,
+    /// Table subject to query, if it could be extracted.
+    pub table: Option<TabIdent>,
+    /// Filter applied, if any and if it could be extracted.
+    pub filter: Option<Filter>,
+    /// The time bucket the query is grouped by, if any and if it could be extracted.
+    pub time_bucket: Option<TimeBucket>,
+    /// Whether the WHERE clause never holds (see [`QueryMetadata::empty_result`]).
+    pub empty_result: bool,
+    /// The point in time the table is read at, if any and if it could be extracted.
+    pub table_version: Option<TableVersion>,
+    /// The hints of the table, if the feature policy allows them.
+    pub table_hints: Vec<String>,
+    /// The unsupported constructs and the other errors found in the query, in order.
+    pub errors: Vec<ParseError>,
+}
+
+/// The outcome of the analysis of a query (see [`QueryMetadata::analyze`]).
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Analysis {
+    /// The query is supported, with its metadata.
+    Supported(QueryMetadata),
+    /// The query is rejected, with all the errors found in it, located in the query.
+    Rejected(Vec<ParseError>),
+}
+
+impl Analysis {
+    /// Returns `true` if the query is supported.
+    #[must_use]
+    pub const fn is_supported(&self) -> bool {
+        matches!(self, Self::Supported(_))
+    }
+}
+
+/// The aliases used in a query, mapped to what they stand for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct Aliases {
+    /// Table aliases, mapped to the name of the table (e.g. `t` to `db.schema.table`).
+    pub tables: BTreeMap<String, String>,
+    /// Result aliases, mapped to the aggregation (e.g. `s` to `SUM(column)`).
+    pub results: BTreeMap<String, String>,
+}
+
+/// The metadata of a query, borrowing the statement it's extracted from and the options.
+///
+/// Unlike [`QueryMetadata`], the derived queries are only rendered on demand, which saves most
+/// of the allocations when only the aggregation, the table and the filter are inspected (e.g.
+/// to authorize the queries).
+#[derive(Clone, Debug)]
+pub struct QueryMetadataRef<'a> {
+    /// Aggregation performed.
+    pub aggregation: Aggregation,
+    /// Table subject to query.
+    pub table: TabIdent,
+    /// Filter applied.
+    pub filter: Option<Filter>,
+    /// The time bucket the query is grouped by, if any (see [`QueryMetadata::time_bucket`]).
+    pub time_bucket: Option<TimeBucket>,
+    /// Whether the WHERE clause never holds (see [`QueryMetadata::empty_result`]).
+    pub empty_result: bool,
+    /// Aliases used in the query.
+    pub aliases: Aliases,
+    /// The point in time the table is read at, if any.
+    pub table_version: Option<TableVersion>,
+    /// The hints of the table, if the feature policy allows them.
+    pub table_hints: Vec<String>,
+    query: DestructuredQuery<'a>,
+    options: &'a ParseOptions,
+}
+
+impl<'a> QueryMetadataRef<'a> {
+    raw_ast! {
+        /// Extracts the metadata of `statement`, as [`QueryMetadata::parse_with`] does.
+        ///
+        /// The statement can be parsed with [`crate::support::parse_statements`]. Since the SQL
+        /// text isn't available, the errors aren't located.
+        pub fn extract(
+            statement: &'a ast::Statement,
+            options: &'a ParseOptions,
+        ) -> Result<Self, ParseError> {
+            let query = QueryMetadata::extract_select_query(std::slice::from_ref(statement))?;
+            Self::from_query(query, options, None)
+        }
+    }
+
+    fn from_query(
+        statement: &'a ast::Query,
+        options: &'a ParseOptions,
+        catalog: Option<&dyn Catalog>,
     ) -> Result<Self, ParseError> {
-        //extract all the statement from the sql query.
-        let statements = Parser::parse_sql(&GenericDialect {}, sql_query)?;
-        //check if the sql query is: single, and is a select.
-        let statement = Self::extract_select_query(&statements)?;
+        let case_folding = options.case_folding();
         //check and extract query clauses from statement
+        let query =
+            DestructuredQuery::destructure_with_policy(statement, options.feature_policy())?;
         let DestructuredQuery {
             projection,
             from,
             selection,
-        } = DestructuredQuery::destructure(statement)?;
+            group_by,
+        } = query;
         //check and extract table informations from FROM clause
-        let TableIdentWithAlias(table_name, table_alias) = TableIdentWithAlias::extract(from)?;
+        let TableIdentWithAlias(table_name, table_alias) =
+            TableIdentWithAlias::extract_with_policy(
+                from,
+                case_folding,
+                options.table_qualifier(),
+                options.feature_policy(),
+            )?;
+        let table_version = TableVersion::extract(from)?;
+        let table_hints = table_hints(from);
         //extract table name to be used in the SELECT clause
         let from_clause_identifier = table_alias.as_deref().map_or_else(
-            || FromClauseIdentifier::Base(&table_name),
+            || FromClauseIdentifier::Base {
+                table: &table_name,
+                qualification: options.qualification(),
+                table_qualifier: options.table_qualifier(),
+            },
             |x| FromClauseIdentifier::Alias { alias: x },
         );
 
+        //extract the time bucket, which the SELECT clause may select besides the aggregation
+        let time_bucket = group_by
+            .first()
+            .map(|expr| TimeBucket::extract(from_clause_identifier, expr, case_folding))
+            .transpose()?;
+
         //extract analytic functions
-        let aggregation = Aggregation::extract(from_clause_identifier, projection)?;
+        let aggregation = Aggregation::extract(
+            from_clause_identifier,
+            without_bucket(projection, group_by.first()),
+            case_folding,
+            options.case_insensitive_functions(),
+            options.feature_policy().allow_running_aggregates,
+        )
+        .and_then(|aggregation| ungrouped_window(aggregation, time_bucket.as_ref()))?;
 
-        let filter = selection
-            .map(|selection| FilterExtractor::new(from_clause_identifier).extract(selection))
-            .transpose()?;
+        //the filters dropped along with a constant are checked as well
+        let mut dropped = Vec::new();
+        let (filter, empty_result) = selection
+            .map(|selection| {
+                FilterExtractor::new(from_clause_identifier, case_folding)
+                    .extract(selection, &mut dropped)
+            })
+            .transpose()?
+            .map_or((None, false), Predicate::into_filter);
+        let filters = filter.iter().chain(&dropped).collect::<Vec<_>>();
+        options.identifier_rules().check(query_names(
+            &table_name,
+            table_alias.as_deref(),
+            Some(&aggregation),
+            &filters,
+            time_bucket.as_ref(),
+        ))?;
+
+        let table_name = qualify_with_defaults(table_name, options);
+        check_database_access(&table_name, options.default_db(), options.cross_database())?;
+        let table_name = match catalog {
+            Some(catalog) => catalog.qualify(&table_name),
+            None => table_name,
+        };
+        check_table_access(
+            &table_name,
+            options.allowed_tables(),
+            options.denied_tables(),
+        )?;
+        let columns = query_columns(Some(&aggregation), &filters, time_bucket.as_ref());
+        check_column_access(&table_name, columns, options.sensitive_columns())?;
+        if let Some(catalog) = catalog {
+            let schema = catalog.resolve_table(&table_name).ok_or_else(|| {
+                malformed_query!(
+                    ErrorCode::UnknownTable,
+                    format!("the table `{table_name}` doesn't exist.")
+                )
+            })?;
+            schema.check(&aggregation, &filters, time_bucket.as_ref())?;
+        }
+        let table_name = match options.table_rewriter() {
+            Some(rewrite) => rewrite(&table_name),
+            None => table_name,
+        };
 
-        let data_extraction_query =
-            Self::create_data_extraction_query(&aggregation, &table_name, &filter, quote_style);
-        let data_aggregation_query = match aggregation.function {
-            KoronFunction::Median => None,
-            _ => Some(Self::create_data_aggregation_query(
-                projection, from, selection,
-            )?),
+        let aliases = Aliases {
+            tables: table_alias
+                .into_iter()
+                .map(|alias| (alias, table_name.to_string()))
+                .collect(),
+            results: result_aliases(&aggregation),
         };
         Ok(Self {
             aggregation,
             table: table_name,
             filter,
+            time_bucket,
+            empty_result,
+            aliases,
+            table_version,
+            table_hints,
+            query,
+            options,
+        })
+    }
+
+    /// Renders the data extraction query (see [`QueryMetadata::data_extraction_query`]).
+    #[must_use]
+    pub fn data_extraction_query(&self) -> String {
+        format_query(
+            &QueryMetadata::build_data_extraction_query(
+                &extraction_identifiers(
+                    &self.aggregation,
+                    self.filter.as_ref(),
+                    self.time_bucket.as_ref(),
+                ),
+                &self.table,
+                self.options
+                    .filter_pushdown()
+                    .then(|| {
+                        pushed_down_selection(
+                            self.filter.as_ref(),
+                            self.empty_result,
+                            self.options.dialect(),
+                            self.options.effective_quote_style(),
+                        )
+                    })
+                    .flatten(),
+                self.table_version
+                    .as_ref()
+                    .filter(|_| self.options.dialect().supports_table_versions()),
+                self.options.dialect(),
+                self.options.effective_quote_style(),
+            ),
+            self.options.pretty_print(),
+        )
+    }
+
+    /// Renders the data aggregation query (see [`QueryMetadata::data_aggregation_query`]).
+    pub fn data_aggregation_query(&self) -> Result<Option<String>, ParseError> {
+        let DestructuredQuery {
+            projection,
+            from,
+            selection,
+            group_by,
+        } = self.query;
+        //MEDIAN has no portable SQL form: the aggregation query is only generated if the dialect
+        //has an equivalent (which replaces the function as written), otherwise there's none
+        let aggregation_expr = match self.aggregation.function {
+            KoronFunction::Median => self
+                .aggregation
+                .median_expr(self.options.dialect(), None)
+                .map(Some),
+            _ => Some(None),
+        };
+        let from = self.rewritten_from(from);
+        aggregation_expr
+            .map(|aggregation_expr| {
+                QueryMetadata::render_data_aggregation_query(
+                    projection,
+                    &from,
+                    selection,
+                    group_by,
+                    self.options.cast_to_text(),
+                    aggregation_expr.as_ref(),
+                    self.options.pretty_print(),
+                )
+            })
+            .transpose()
+    }
+
+    /// Renders the data count query (see [`QueryMetadata::data_count_query`]).
+    pub fn data_count_query(&self) -> Result<Option<String>, ParseError> {
+        if !self.aggregation.function.is_weighted() || self.aggregation.window.is_some() {
+            return Ok(None);
+        }
+        let DestructuredQuery {
+            projection,
+            from,
+            selection,
+            group_by,
+        } = self.query;
+        QueryMetadata::render_data_count_query(
+            projection,
+            &self.rewritten_from(from),
+            selection,
+            group_by,
+            self.options.cast_to_text(),
+            self.options.pretty_print(),
+        )
+        .map(Some)
+    }
+
+    // the FROM clause as written, except for the table if it's rewritten
+    fn rewritten_from<'b>(
+        &self,
+        from: &'b [ast::TableWithJoins],
+    ) -> Cow<'b, [ast::TableWithJoins]> {
+        match self.options.table_rewriter() {
+            Some(_) => Cow::Owned(rename_table(
+                from,
+                self.table
+                    .into_object_name(self.options.dialect(), self.options.effective_quote_style()),
+            )),
+            None => Cow::Borrowed(from),
+        }
+    }
+
+    /// Renders the derived queries and returns the owned metadata.
+    pub fn into_owned(self) -> Result<QueryMetadata, ParseError> {
+        let data_extraction_query = self.data_extraction_query();
+        let data_aggregation_query = self.data_aggregation_query()?;
+        let data_count_query = self.data_count_query()?;
+        Ok(QueryMetadata {
+            aggregation: self.aggregation,
+            table: self.table,
+            filter: self.filter,
+            time_bucket: self.time_bucket,
             data_extraction_query,
             data_aggregation_query,
+            data_count_query,
+            aliases: self.aliases,
+            table_version: self.table_version,
+            table_hints: self.table_hints,
+            annotations: Vec::new(),
+            empty_result: self.empty_result,
+            schema_version: QueryMetadata::SCHEMA_VERSION,
+        })
+    }
+}
+
+impl QueryMetadata {
+    /// The current version of the serialized representation of the metadata, incremented when
+    /// a change needs the documents serialized before to be migrated (see
+    /// [`Self::from_json_any_version`]).
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    /// Reads metadata serialized as JSON by any version of the crate, e.g. stored in a database,
+    /// migrating it to the current representation (built with the `json` feature).
+    ///
+    /// The metadata serialized before it was versioned only had the aggregation, the table, the
+    /// filter and the derived queries: the other fields take their default values, and the value
+    /// of the filter, which had no type, is typed as a number if it reads as one, as a string
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the JSON isn't serialized metadata, or if it was serialized by a newer version of
+    /// the crate.
+    #[cfg(feature = "json")]
+    pub fn from_json_any_version(json: &str) -> Result<Self, serde_json::Error> {
+        use serde::de::Error;
+
+        let mut document: serde_json::Value = serde_json::from_str(json)?;
+        let version_key = json_key("schema_version", "schemaVersion");
+        let version = match document.get(version_key) {
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| Error::custom(format!("invalid schema version {version}.")))?,
+            None => 0,
+        };
+        if version > Self::SCHEMA_VERSION {
+            return Err(Error::custom(format!(
+                "the schema version {version} is newer than the supported one ({}).",
+                Self::SCHEMA_VERSION
+            )));
+        }
+        for migrate in MIGRATIONS
+            .iter()
+            .skip(usize::try_from(version).unwrap_or_default())
+        {
+            migrate(&mut document);
+        }
+        if let Some(fields) = document.as_object_mut() {
+            fields.insert(version_key.to_string(), Self::SCHEMA_VERSION.into());
+        }
+        serde_json::from_value(document)
+    }
+
+    /// Serializes the metadata as compact JSON with the keys of the objects sorted, so that the
+    /// same metadata always has the same bytes, e.g. to sign it (built with the `json` feature).
+    ///
+    /// The keys are sorted by their UTF-8 bytes, whatever the order of the fields and the
+    /// features of `serde_json`. The optional fields added by later versions of the crate are
+    /// left out while they're unset (like [`Filter::collation`]), so that the JSON of the
+    /// metadata that doesn't use them doesn't change.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the metadata can't be serialized, which doesn't happen with the metadata
+    /// extracted by the parser.
+    #[cfg(feature = "json")]
+    pub fn to_canonical_json(&self) -> Result<String, serde_json::Error> {
+        let mut document = serde_json::to_value(self)?;
+        sort_keys(&mut document);
+        serde_json::to_string(&document)
+    }
+
+    /// Generates `QueryMetadata` from a SQL query using the default [`ParseOptions`] and the given
+    /// quote style for the generated queries.
+    pub fn parse(
+        sql_query: &str,
+        quote_style: Option<char>, /* e.g. "'" for PostgreSQL, "`" for MySQL */
+    ) -> Result<Self, ParseError> {
+        Self::parse_with(
+            sql_query,
+            &ParseOptions::new().with_quote_style(quote_style),
+        )
+    }
+
+    /// Generates `QueryMetadata` from a SQL query using the given [`ParseOptions`].
+    pub fn parse_with(sql_query: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        Self::parse_and_bind(sql_query, options, None)
+    }
+
+    /// Generates `QueryMetadata` from a SQL query like [`Self::parse_with`], but never panics: a
+    /// panic while parsing (a bug of this crate or of the SQL parser) is caught and returned as a
+    /// [`ParseError::Internal`], e.g. so that an untrusted query can't take a server worker down.
+    ///
+    /// The panic hook still runs, and nothing is caught if panics abort the process.
+    pub fn parse_catching(sql_query: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        //the options are only read, although the rejection hook may hold state of its own
+        let parse = std::panic::AssertUnwindSafe(|| Self::parse_with(sql_query, options));
+        std::panic::catch_unwind(parse)
+            .unwrap_or_else(|panic| Err(ParseError::from_panic(panic.as_ref())))
+    }
+
+    /// Generates `QueryMetadata` from a SQL query using the given [`ParseOptions`], binding it to
+    /// the tables of `catalog`.
+    ///
+    /// The table must exist in the catalog and the query is validated against its schema (see
+    /// [`Self::validate_against`]). The table is replaced by its fully-qualified name, which is
+    /// also used in the data extraction query.
+    pub fn parse_with_catalog(
+        sql_query: &str,
+        options: &ParseOptions,
+        catalog: &dyn Catalog,
+    ) -> Result<Self, ParseError> {
+        Self::parse_and_bind(sql_query, options, Some(catalog))
+    }
+
+    fn parse_and_bind(
+        sql_query: &str,
+        options: &ParseOptions,
+        catalog: Option<&dyn Catalog>,
+    ) -> Result<Self, ParseError> {
+        Self::parse_unlocated(sql_query, options, catalog)
+            .map(|(metadata, _)| metadata)
+            .map_err(|error| report_error(error, sql_query, options))
+    }
+
+    /// Generates `QueryMetadata` from a SQL query like [`Self::parse_with`], also returning
+    /// measures of its complexity (see [`QueryStats`]).
+    pub fn parse_with_stats(
+        sql_query: &str,
+        options: &ParseOptions,
+    ) -> Result<(Self, QueryStats), ParseError> {
+        Self::parse_with_stats_unlocated(sql_query, options)
+            .map_err(|error| report_error(error, sql_query, options))
+    }
+
+    fn parse_with_stats_unlocated(
+        sql_query: &str,
+        options: &ParseOptions,
+    ) -> Result<(Self, QueryStats), ParseError> {
+        let statements = parse_statements_within(sql_query, options.dialect(), options.limits())?;
+        let query = Self::extract_select_query(&statements)?;
+        let metadata = QueryMetadataRef::from_query(query, options, None)?;
+        let stats = QueryStats::measure(sql_query, metadata.query, metadata.filter.as_ref());
+        let mut metadata = metadata.into_owned()?;
+        metadata.annotations = extract_annotations(sql_query, options.dialect());
+        Ok((metadata, stats))
+    }
+
+    raw_ast! {
+        /// Generates `QueryMetadata` from a SQL query like [`Self::parse_with`], also returning the
+        /// statement parsed, e.g. to inspect what the metadata doesn't carry without parsing the
+        /// query again.
+        pub fn parse_keep_ast(
+            sql_query: &str,
+            options: &ParseOptions,
+        ) -> Result<(Self, ast::Statement), ParseError> {
+            Self::parse_unlocated(sql_query, options, None)
+                .map_err(|error| report_error(error, sql_query, options))
+        }
+    }
+
+    fn parse_unlocated(
+        sql_query: &str,
+        options: &ParseOptions,
+        catalog: Option<&dyn Catalog>,
+    ) -> Result<(Self, ast::Statement), ParseError> {
+        //extract all the statement from the sql query.
+        let statements = parse_statements_within(sql_query, options.dialect(), options.limits())?;
+        //check if the sql query is: single, and is a select.
+        let query = Self::extract_select_query(&statements)?;
+        let mut metadata = Self::from_query(query, options, catalog)?;
+        metadata.annotations = extract_annotations(sql_query, options.dialect());
+        let statement = statements
+            .into_iter()
+            .next()
+            .ok_or_else(|| internal!("found no statement in a parsed query.".to_string()))?;
+        Ok((metadata, statement))
+    }
+
+    /// Parses a script of semicolon-separated SELECT statements, each one as
+    /// [`Self::parse_with`] does, and returns their metadata in order.
+    ///
+    /// Fails at the first statement that isn't a supported SELECT.
+    pub fn parse_many(sql_script: &str, options: &ParseOptions) -> Result<Vec<Self>, ParseError> {
+        Self::parse_many_unlocated(sql_script, options)
+            .map_err(|error| report_error(error, sql_script, options))
+    }
+
+    /// Parses each query of `sql_queries` independently, as [`Self::parse_with`] does, and
+    /// returns the results in the same order.
+    ///
+    /// With the `parallel` feature, the queries are split among the available cores.
+    #[must_use]
+    pub fn parse_batch(
+        sql_queries: &[&str],
+        options: &ParseOptions,
+    ) -> Vec<Result<Self, ParseError>> {
+        #[cfg(feature = "parallel")]
+        {
+            let threads = std::thread::available_parallelism().map_or(1, usize::from);
+            let chunk_size = sql_queries.len().div_ceil(threads).max(1);
+            std::thread::scope(|scope| {
+                //all the threads are spawned before any is joined
+                #[allow(clippy::needless_collect)]
+                let handles = sql_queries
+                    .chunks(chunk_size)
+                    .map(|chunk| scope.spawn(move || Self::parse_sequentially(chunk, options)))
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                    .collect()
+            })
+        }
+        #[cfg(not(feature = "parallel"))]
+        Self::parse_sequentially(sql_queries, options)
+    }
+
+    fn parse_sequentially(
+        sql_queries: &[&str],
+        options: &ParseOptions,
+    ) -> Vec<Result<Self, ParseError>> {
+        sql_queries
+            .iter()
+            .map(|sql_query| Self::parse_with(sql_query, options))
+            .collect()
+    }
+
+    fn parse_many_unlocated(
+        sql_script: &str,
+        options: &ParseOptions,
+    ) -> Result<Vec<Self>, ParseError> {
+        let statements = parse_statements_within(sql_script, options.dialect(), options.limits())?;
+        statements
+            .iter()
+            .map(|statement| {
+                Self::extract_select_query(std::slice::from_ref(statement))
+                    .and_then(|query| Self::from_query(query, options, None))
+            })
+            .collect()
+    }
+
+    fn from_query(
+        statement: &ast::Query,
+        options: &ParseOptions,
+        catalog: Option<&dyn Catalog>,
+    ) -> Result<Self, ParseError> {
+        QueryMetadataRef::from_query(statement, options, catalog)?.into_owned()
+    }
+
+    /// Parses a SQL query leniently: the unsupported constructs (e.g. an ORDER BY clause) are
+    /// reported and skipped instead of failing at the first one, and as much metadata as possible
+    /// is extracted from the rest of the query.
+    ///
+    /// Fails only if the query isn't a single SELECT statement. The derived queries aren't
+    /// generated, since they'd be meaningless for a query that isn't fully supported.
+    pub fn parse_lenient(
+        sql_query: &str,
+        options: &ParseOptions,
+    ) -> Result<PartialQueryMetadata, ParseError> {
+        let mut partial = Self::parse_lenient_unlocated(sql_query, options)
+            .map_err(|error| report_error(error, sql_query, options))?;
+        partial.errors = partial
+            .errors
+            .into_iter()
+            .map(|error| report_error(error, sql_query, options))
+            .collect();
+        Ok(partial)
+    }
+
+    /// Parses a SQL query like [`Self::parse_with`], but if it fails, the analysis goes on after
+    /// the first error and all of them are returned (e.g. both an unsupported DISTINCT and a
+    /// column qualified with another table), instead of only the first one.
+    pub fn parse_with_all_errors(
+        sql_query: &str,
+        options: &ParseOptions,
+    ) -> Result<Self, Vec<ParseError>> {
+        //the errors of both parses are only reported once
+        let unreported = options.without_on_reject();
+        Self::parse_with(sql_query, &unreported).map_err(|error| {
+            let errors = match Self::parse_lenient(sql_query, &unreported) {
+                Ok(partial) if !partial.errors.is_empty() => partial.errors,
+                //the lenient parse doesn't perform every check (e.g. against a catalog)
+                _ => vec![error],
+            };
+            for error in &errors {
+                report_rejection(error, options);
+            }
+            errors
         })
     }
 
-    fn extract_select_query(statements: &[ast::Statement]) -> Result<&ast::Query, ParseError> {
+    /// Analyzes a SQL query without failing: the query is either supported, with its metadata,
+    /// or rejected with all the errors found in it (see [`Self::parse_with_all_errors`]), e.g. to
+    /// list everything that has to change in a query.
+    ///
+    /// The errors are always located, whether the options locate them or not.
+    #[must_use]
+    pub fn analyze(sql_query: &str, options: &ParseOptions) -> Analysis {
+        let options = options.clone().with_error_spans(true);
+        match Self::parse_with_all_errors(sql_query, &options) {
+            Ok(metadata) => Analysis::Supported(metadata),
+            Err(errors) => Analysis::Rejected(errors),
+        }
+    }
+
+    fn parse_lenient_unlocated(
+        sql_query: &str,
+        options: &ParseOptions,
+    ) -> Result<PartialQueryMetadata, ParseError> {
+        let case_folding = options.case_folding();
+        let statements = parse_statements_within(sql_query, options.dialect(), options.limits())?;
+        let statement = Self::extract_select_query(&statements)?;
+        let mut errors = Vec::new();
+        let DestructuredQuery {
+            projection,
+            from,
+            selection,
+            group_by,
+        } = DestructuredQuery::destructure_lenient_with_policy(
+            statement,
+            options.feature_policy(),
+            &mut errors,
+        )?;
+        let mut partial = PartialQueryMetadata::default();
+        match TableIdentWithAlias::extract_with_policy(
+            from,
+            case_folding,
+            options.table_qualifier(),
+            options.feature_policy(),
+        ) {
+            Ok(TableIdentWithAlias(table_name, table_alias)) => {
+                let from_clause_identifier = table_alias.as_deref().map_or_else(
+                    || FromClauseIdentifier::Base {
+                        table: &table_name,
+                        qualification: options.qualification(),
+                        table_qualifier: options.table_qualifier(),
+                    },
+                    |x| FromClauseIdentifier::Alias { alias: x },
+                );
+                partial.time_bucket = group_by.first().and_then(|expr| {
+                    TimeBucket::extract(from_clause_identifier, expr, case_folding)
+                        .map_err(|error| errors.push(error))
+                        .ok()
+                });
+                partial.aggregation = Aggregation::extract(
+                    from_clause_identifier,
+                    without_bucket(projection, group_by.first()),
+                    case_folding,
+                    options.case_insensitive_functions(),
+                    options.feature_policy().allow_running_aggregates,
+                )
+                .and_then(|aggregation| ungrouped_window(aggregation, partial.time_bucket.as_ref()))
+                .map_err(|error| errors.push(error))
+                .ok();
+                let mut dropped = Vec::new();
+                (partial.filter, partial.empty_result) = selection
+                    .and_then(|selection| {
+                        FilterExtractor::new(from_clause_identifier, case_folding)
+                            .extract(selection, &mut dropped)
+                            .map_err(|error| errors.push(error))
+                            .ok()
+                    })
+                    .map_or((None, false), Predicate::into_filter);
+                partial.table_hints = table_hints(from);
+                partial.table_version = TableVersion::extract(from)
+                    .map_err(|error| errors.push(error))
+                    .ok()
+                    .flatten();
+                let filters = partial.filter.iter().chain(&dropped).collect::<Vec<_>>();
+                let names = query_names(
+                    &table_name,
+                    table_alias.as_deref(),
+                    partial.aggregation.as_ref(),
+                    &filters,
+                    partial.time_bucket.as_ref(),
+                );
+                if let Err(error) = options.identifier_rules().check(names) {
+                    errors.push(error);
+                }
+                let table_name = qualify_with_defaults(table_name, options);
+                let access = check_database_access(
+                    &table_name,
+                    options.default_db(),
+                    options.cross_database(),
+                )
+                .and_then(|()| {
+                    check_table_access(
+                        &table_name,
+                        options.allowed_tables(),
+                        options.denied_tables(),
+                    )
+                })
+                .and_then(|()| {
+                    let columns = query_columns(
+                        partial.aggregation.as_ref(),
+                        &filters,
+                        partial.time_bucket.as_ref(),
+                    );
+                    check_column_access(&table_name, columns, options.sensitive_columns())
+                });
+                if let Err(error) = access {
+                    errors.push(error);
+                }
+                partial.table = Some(table_name);
+            }
+            Err(error) => errors.push(error),
+        }
+        partial.errors = errors;
+        Ok(partial)
+    }
+
+    pub(crate) fn extract_select_query(
+        statements: &[ast::Statement],
+    ) -> Result<&ast::Query, ParseError> {
         if let [ast::Statement::Query(query)] = statements {
             Ok(query)
+        } else if statements.is_empty() {
+            Err(malformed_query!(
+                ErrorCode::EmptyQuery,
+                "the query has no statement.".to_string()
+            ))
         } else {
             Err(unsupported!(
+                UnsupportedFeature::Statement,
                 "statements different from single SELECT statement.".to_string()
             ))
         }
@@ -94,130 +865,1073 @@ impl QueryMetadata {
         filter: &Option<Filter>,
         quote_style: Option<char>, // e.g. "'" for PostgreSQL, "`" for MySQL
     ) -> String {
-        let mut projection = Vec::default();
-        let aggregation_column_ident =
-            ast::SelectItem::UnnamedExpr(ast::Expr::Identifier(ast::Ident {
-                value: aggregation.column.clone(),
-                quote_style,
-            }));
-        projection.push(aggregation_column_ident);
-        if let Some(filter) = &filter {
-            if filter.column != aggregation.column {
-                let filter_column_ident =
-                    ast::SelectItem::UnnamedExpr(ast::Expr::Identifier(ast::Ident {
-                        value: filter.column.clone(),
-                        quote_style,
-                    }));
-                projection.push(filter_column_ident);
-            }
-        }
-        let from = vec![ast::TableWithJoins {
-            relation: ast::TableFactor::Table {
-                name: table.into_object_name(quote_style),
-                alias: None,
-                args: None,
-                with_hints: Vec::default(),
-                version: None,
-                partitions: Vec::default(),
-            },
-            joins: Vec::default(),
-        }];
-        let select_expr = ast::Select {
-            distinct: None,
-            top: None,
-            projection,
-            into: None,
-            from,
-            lateral_views: Vec::default(),
-            selection: None,
-            group_by: ast::GroupByExpr::Expressions(Vec::default()),
-            cluster_by: Vec::default(),
-            distribute_by: Vec::default(),
-            sort_by: Vec::default(),
-            having: None,
-            qualify: None,
-            named_window: Vec::default(),
+        Self::build_data_extraction_query(
+            &extraction_identifiers(aggregation, filter.as_ref(), None),
+            table,
+            None,
+            None,
+            Dialect::Generic,
+            quote_style,
+        )
+        .to_string()
+    }
+
+    // builds `SELECT columns FROM table [WHERE selection]`, with the selection of the filter
+    // only if it's pushed down (see `pushed_down_selection`)
+    fn build_data_extraction_query(
+        columns: &[&Identifier],
+        table: &TabIdent,
+        selection: Option<ast::Expr>,
+        table_version: Option<&TableVersion>,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> ast::Query {
+        let projection = columns
+            .iter()
+            .map(|column| {
+                ast::SelectItem::UnnamedExpr(ast::Expr::Identifier(
+                    column.to_ident(dialect, quote_style),
+                ))
+            })
+            .collect();
+        let from = table_with_joins(
+            table.into_object_name(dialect, quote_style),
+            table_version.map(TableVersion::to_ast),
+        );
+        select_query(projection, vec![from], selection)
+    }
+
+    // renders the query as written, only replacing its aggregation: the clauses are displayed
+    // in place rather than copied into a new query, since the WHERE clause may be large
+    fn render_data_aggregation_query(
+        projection: &[ast::SelectItem],
+        from: &[ast::TableWithJoins],
+        selection: Option<&ast::Expr>,
+        group_by: &[ast::Expr],
+        cast: bool,
+        aggregation_expr: Option<&ast::Expr>,
+        pretty: bool,
+    ) -> Result<String, ParseError> {
+        //the aggregation is either kept as written or replaced by its dialect-specific form
+        let item = aggregation_item(projection, group_by)?;
+        let (expr, alias) = match item {
+            ast::SelectItem::UnnamedExpr(expr) => (expr, None),
+            ast::SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias)),
+            _ => return Err(internal!("the aggregation isn't an expression.".to_string())),
         };
-        let query_body = ast::SetExpr::Select(Box::new(select_expr));
-        let query = ast::Query {
-            with: None,
-            body: Box::new(query_body),
-            order_by: Vec::default(),
-            limit: None,
-            offset: None,
-            fetch: None,
-            locks: Vec::default(),
-            limit_by: Vec::default(),
-            for_clause: None,
+        let aggregation = SelectedExpr {
+            expr: aggregation_expr.unwrap_or(expr),
+            alias,
+            cast,
         };
-        let select_statement = ast::Statement::Query(Box::new(query));
-        select_statement.to_string()
+        let items = selected_with_bucket(projection, item, aggregation);
+        Ok(render_select(&items, from, selection, group_by, pretty))
     }
 
-    fn create_data_aggregation_query(
+    // renders the query as written, only replacing its aggregation by the count of its argument
+    // (e.g. `COUNT(t.a)` for `AVG(t.a) AS m`)
+    fn render_data_count_query(
         projection: &[ast::SelectItem],
         from: &[ast::TableWithJoins],
         selection: Option<&ast::Expr>,
+        group_by: &[ast::Expr],
+        cast: bool,
+        pretty: bool,
     ) -> Result<String, ParseError> {
-        let projection = match projection {
-            [ast::SelectItem::UnnamedExpr(expr)] => {
-                vec![ast::SelectItem::UnnamedExpr(ast::Expr::Cast {
-                    expr: Box::new(expr.clone()),
-                    data_type: ast::DataType::Text,
-                    format: None,
-                })]
-            }
-            [ast::SelectItem::ExprWithAlias { expr, alias }] => {
-                vec![ast::SelectItem::ExprWithAlias {
-                    expr: ast::Expr::Cast {
-                        expr: Box::new(expr.clone()),
-                        data_type: ast::DataType::Text,
-                        format: None,
-                    },
-                    alias: alias.clone(),
-                }]
+        let item = aggregation_item(projection, group_by)?;
+        let (ast::SelectItem::UnnamedExpr(expr) | ast::SelectItem::ExprWithAlias { expr, .. }) =
+            item
+        else {
+            return Err(internal!("the aggregation isn't an expression.".to_string()));
+        };
+        let ast::Expr::Function(ast::Function { args, .. }) = remove_outer_parens(expr) else {
+            return Err(internal!("the aggregation isn't a function.".to_string()));
+        };
+        let [ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(argument))] = &args[..] else {
+            return Err(internal!(
+                "the aggregation doesn't have a single argument.".to_string()
+            ));
+        };
+        let count = SelectedExpr {
+            expr: &PartialAggregate::Count.to_expr(argument.clone()),
+            alias: None,
+            cast,
+        };
+        let items = selected_with_bucket(projection, item, count);
+        Ok(render_select(&items, from, selection, group_by, pretty))
+    }
+
+    /// Reconstructs the restricted query (aggregation, FROM and WHERE clauses, and the GROUP BY
+    /// clause of the time bucket) from the extracted metadata, in the given dialect.
+    ///
+    /// Identifiers are quoted only when they wouldn't read back to the same name otherwise.
+    #[must_use]
+    pub fn to_sql(&self, dialect: Dialect) -> String {
+        let projection = vec![self.aggregation.to_select_item(dialect, None)];
+        let query = self.select_grouped(projection, dialect, None);
+        ast::Statement::Query(Box::new(query)).to_string()
+    }
+
+    /// Generates the query computing the partial aggregates of the aggregation over a shard of
+    /// the table, in the given dialect, e.g. `SELECT SUM(a), COUNT(a) FROM t WHERE b > 1` for
+    /// `AVG(a)` (see [`Aggregation::decompose`]).
+    ///
+    /// Returns `None` for the median, which can't be recombined from partial aggregates, and for
+    /// a running aggregate, which depends on the rows of the other shards. Like in
+    /// [`Self::to_sql`], identifiers are quoted only when they wouldn't read back to the same name
+    /// otherwise, and a query grouped by a time bucket returns a row per period, starting with
+    /// it, to recombine period by period.
+    #[must_use]
+    pub fn shard_query(&self, dialect: Dialect) -> Option<String> {
+        let decomposition = self.aggregation.decompose()?;
+        let projection = decomposition.select_items(&self.aggregation.column, dialect, None);
+        let query = self.select_grouped(projection, dialect, None);
+        Some(ast::Statement::Query(Box::new(query)).to_string())
+    }
+
+    /// Plans the query over several sources (e.g. the shards of a table stored in several
+    /// databases), as a query computing a partial state on each source, in the given dialect,
+    /// and the function merging the states into the result.
+    ///
+    /// The partial state is the row of [`Self::shard_query`], or for the median, which can't be
+    /// recombined from partial aggregates, the histogram of the values of the column (e.g.
+    /// `SELECT a, COUNT(a) FROM t GROUP BY a`). For a query grouped by a time bucket, the rows
+    /// start with their period, and the states are merged period by period.
+    ///
+    /// Returns `None` for a running aggregate, whose result for a row depends on the rows of the
+    /// other sources.
+    #[must_use]
+    pub fn partial_plan(&self, dialect: Dialect) -> Option<PartialPlan> {
+        if self.aggregation.window.is_some() {
+            return None;
+        }
+        let plan = |query: ast::Query, merge| {
+            Some(PartialPlan {
+                source_query: ast::Statement::Query(Box::new(query)).to_string(),
+                merge,
+            })
+        };
+        let column = &self.aggregation.column;
+        let Some(decomposition) = self.aggregation.decompose() else {
+            let column = ast::Expr::Identifier(column.render(dialect, None));
+            let projection = vec![
+                ast::SelectItem::UnnamedExpr(column.clone()),
+                ast::SelectItem::UnnamedExpr(PartialAggregate::Count.to_expr(column.clone())),
+            ];
+            let mut query = self.select_grouped(projection, dialect, None);
+            if let ast::SetExpr::Select(select) = query.body.as_mut() {
+                if let ast::GroupByExpr::Expressions(group_by) = &mut select.group_by {
+                    group_by.push(column);
+                }
             }
-            _ => {
-                return Err(unsupported!("the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.".to_string()));
+            return plan(query, MergeFunction::Histogram);
+        };
+        let projection = decomposition.select_items(column, dialect, None);
+        let query = self.select_grouped(projection, dialect, None);
+        plan(query, MergeFunction::Combine { decomposition })
+    }
+
+    /// Replaces the filter, and regenerates the derived queries with `options` (see
+    /// [`Self::recompute_queries`]).
+    ///
+    /// The filter replaces the whole WHERE clause, which then holds unless it's empty (see
+    /// [`Self::empty_result`]).
+    #[must_use]
+    pub fn with_filter(mut self, filter: Option<Filter>, options: &ParseOptions) -> Self {
+        self.filter = filter;
+        self.empty_result = false;
+        self.recompute_queries(options);
+        self
+    }
+
+    /// Replaces the table, and regenerates the derived queries with `options` (see
+    /// [`Self::recompute_queries`]).
+    ///
+    /// The aliases of the table then stand for the new one.
+    #[must_use]
+    pub fn with_table(mut self, table: TabIdent, options: &ParseOptions) -> Self {
+        for name in self.aliases.tables.values_mut() {
+            *name = table.to_string();
+        }
+        self.table = table;
+        self.recompute_queries(options);
+        self
+    }
+
+    /// Replaces the time bucket the query is grouped by, and regenerates the derived queries with
+    /// `options` (see [`Self::recompute_queries`]).
+    #[must_use]
+    pub fn with_time_bucket(
+        mut self,
+        time_bucket: Option<TimeBucket>,
+        options: &ParseOptions,
+    ) -> Self {
+        self.time_bucket = time_bucket;
+        self.recompute_queries(options);
+        self
+    }
+
+    /// Replaces the aggregation, and regenerates the derived queries with `options` (see
+    /// [`Self::recompute_queries`]).
+    #[must_use]
+    pub fn with_aggregation(mut self, aggregation: Aggregation, options: &ParseOptions) -> Self {
+        self.aliases.results = result_aliases(&aggregation);
+        self.aggregation = aggregation;
+        self.recompute_queries(options);
+        self
+    }
+
+    /// Regenerates [`Self::data_extraction_query`], [`Self::data_aggregation_query`] and
+    /// [`Self::data_count_query`] from the aggregation, the table, the filter and the time
+    /// bucket, as they're generated when parsing with `options`, e.g. after the fields are
+    /// changed.
+    ///
+    /// The data aggregation query is rebuilt from the metadata instead of the query as written,
+    /// so that the clauses the metadata doesn't represent (e.g. the hints of the table) are left
+    /// out.
+    pub fn recompute_queries(&mut self, options: &ParseOptions) {
+        let dialect = options.dialect();
+        let extraction_query = Self::build_data_extraction_query(
+            &extraction_identifiers(
+                &self.aggregation,
+                self.filter.as_ref(),
+                self.time_bucket.as_ref(),
+            ),
+            &self.table,
+            options
+                .filter_pushdown()
+                .then(|| {
+                    pushed_down_selection(
+                        self.filter.as_ref(),
+                        self.empty_result,
+                        dialect,
+                        options.effective_quote_style(),
+                    )
+                })
+                .flatten(),
+            self.table_version
+                .as_ref()
+                .filter(|_| dialect.supports_table_versions()),
+            dialect,
+            options.effective_quote_style(),
+        );
+        self.data_extraction_query = format_query(&extraction_query, options.pretty_print());
+        let cast = |expr| {
+            if options.cast_to_text() {
+                cast_to_text(expr)
+            } else {
+                expr
             }
         };
-        let select_expr = ast::Select {
-            distinct: None,
-            top: None,
-            projection,
-            into: None,
-            from: from.to_vec(),
-            lateral_views: Vec::default(),
-            selection: selection.cloned(),
-            group_by: ast::GroupByExpr::Expressions(Vec::default()),
-            cluster_by: Vec::default(),
-            distribute_by: Vec::default(),
-            sort_by: Vec::default(),
-            having: None,
-            qualify: None,
-            named_window: Vec::default(),
+        let item = match self.aggregation.function {
+            KoronFunction::Median => self.aggregation.median_expr(dialect, None).map(|median| {
+                self.aggregation
+                    .wrap_select_item(dialect, None, |_| cast(median))
+            }),
+            _ => Some(self.aggregation.wrap_select_item(dialect, None, cast)),
+        };
+        self.data_aggregation_query = item.map(|item| {
+            let query = self.select_grouped(vec![item], dialect, None);
+            format_query(&query, options.pretty_print())
+        });
+        let weighted = self.aggregation.function.is_weighted() && self.aggregation.window.is_none();
+        self.data_count_query = weighted.then(|| {
+            let column = ast::Expr::Identifier(self.aggregation.column.render(dialect, None));
+            let count = ast::SelectItem::UnnamedExpr(cast(PartialAggregate::Count.to_expr(column)));
+            let query = self.select_grouped(vec![count], dialect, None);
+            format_query(&query, options.pretty_print())
+        });
+    }
+
+    /// Describes the query in English, e.g. `Sum of test_column_2 from
+    /// test_db.test_schema.test_table_1 where test_column_3 > 5` for a data access reviewer.
+    ///
+    /// The filter is written in SQL, with its value escaped.
+    #[must_use]
+    pub fn explain(&self) -> String {
+        let Aggregation {
+            function,
+            column,
+            window,
+            ..
+        } = &self.aggregation;
+        let mut explanation = format!("{} of {column}", function.description());
+        if let Some(WindowSpec {
+            order_by,
+            descending,
+        }) = window
+        {
+            explanation.push_str(" accumulated over the rows ordered by ");
+            explanation.push_str(order_by.as_str());
+            if *descending {
+                explanation.push_str(" descending");
+            }
+        }
+        explanation.push_str(" from ");
+        explanation.push_str(&self.table.to_string());
+        if let Some(TableVersion { as_of }) = &self.table_version {
+            explanation.push_str(" as of ");
+            explanation.push_str(as_of);
+        }
+        if let Some(TimeBucket {
+            column,
+            granularity,
+            ..
+        }) = &self.time_bucket
+        {
+            explanation.push_str(" per ");
+            explanation.push_str(granularity.unit());
+            explanation.push_str(" of ");
+            explanation.push_str(column.as_str());
+        }
+        if let Some(filter) = &self.filter {
+            explanation.push_str(" where ");
+            explanation.push_str(&filter.to_sql(Dialect::Generic, None));
+        }
+        if self.empty_result {
+            explanation.push_str(", over no rows since the filter never holds");
+        }
+        explanation
+    }
+
+    /// Checks the query against the schema of its table: the referenced columns must exist, and
+    /// their types must fit the aggregation, the filter and the time bucket (e.g. no SUM over a
+    /// text column).
+    pub fn validate_against(&self, schema: &TableSchema) -> Result<(), ParseError> {
+        let filters = self.filter.iter().collect::<Vec<_>>();
+        schema.check(&self.aggregation, &filters, self.time_bucket.as_ref())
+    }
+
+    /// Describes the columns returned by the data extraction query, in order, with their types
+    /// taken from `schema` if given ([`ColumnType::Other`] otherwise, or for the columns it
+    /// doesn't have), e.g. to build the schema of the record batches of the extracted rows.
+    #[must_use]
+    pub fn extraction_schema(&self, schema: Option<&TableSchema>) -> Vec<ColumnDef> {
+        self.extraction_columns()
+            .into_iter()
+            .map(|name| ColumnDef {
+                name: name.to_string(),
+                data_type: schema
+                    .and_then(|schema| schema.column(name))
+                    .map_or(ColumnType::Other, |column| column.data_type),
+            })
+            .collect()
+    }
+
+    /// Renders the restricted query in a canonical form, so that queries that only differ in how
+    /// they're written (whitespace, keyword case, aliases of the table, redundant parentheses,
+    /// order of the comparison operands, ...) can be compared as text.
+    ///
+    /// Keywords and unquoted identifiers are lowercase, and identifiers are quoted only when they
+    /// wouldn't read back to the same name otherwise.
+    #[must_use]
+    pub fn canonicalize(&self) -> String {
+        lowercase_keywords(&self.to_sql(Dialect::Generic))
+    }
+
+    raw_ast! {
+        /// Builds the data extraction query as an AST, so that it can be adjusted before rendering.
+        ///
+        /// Identifiers are quoted with `quote_style` if given, otherwise only when the dialect
+        /// requires it.
+        #[must_use]
+        pub fn data_extraction_ast(
+            &self,
+            dialect: Dialect,
+            quote_style: Option<char>,
+        ) -> ast::Query {
+            let projection = self
+                .extraction_columns()
+                .into_iter()
+                .map(|name| column_item(name, dialect, quote_style))
+                .collect();
+            self.select_from_table(projection, dialect, quote_style, false)
+        }
+    }
+
+    /// Builds one page of the data extraction query, for extracting huge tables in chunks with
+    /// keyset pagination:
+    /// `SELECT ... FROM table [WHERE order_column > cursor] ORDER BY order_column LIMIT page_size`.
+    ///
+    /// `order_column` should be a unique column, given as its (folded) name, and `cursor` is its
+    /// last value in the previous page (`None` for the first page). The ordering column is added
+    /// to the projection if it's not already there, so that the next cursor can be read from the
+    /// results.
+    ///
+    /// The cursor is rejected with [`ParseError::MalformedQuery`] if it can't be embedded in SQL
+    /// as the given kind of literal (e.g. a string with a NUL character, see
+    /// [`crate::support::escape_literal`], or a number that isn't one).
+    pub fn paged_extraction_query(
+        &self,
+        dialect: Dialect,
+        order_column: &str,
+        page_size: u64,
+        cursor: Option<(&str, ValueType)>,
+    ) -> Result<String, ParseError> {
+        if let Some((value, value_type)) = cursor {
+            value_type.check(value)?;
+        }
+        let mut columns = self.extraction_columns();
+        if !columns.contains(&order_column) {
+            columns.push(order_column);
+        }
+        let projection = columns
+            .into_iter()
+            .map(|name| column_item(name, dialect, None))
+            .collect();
+        let order_column = ast::Expr::Identifier(render_identifier(order_column, dialect, None));
+        let selection = cursor.map(|(value, value_type)| ast::Expr::BinaryOp {
+            left: Box::new(order_column.clone()),
+            op: ast::BinaryOperator::Gt,
+            right: Box::new(Value::new(value, value_type).to_ast(dialect)),
+        });
+        let from = vec![table_with_joins(
+            self.table.render_object_name(dialect, None),
+            self.rendered_table_version(dialect),
+        )];
+        let mut query = select_query(projection, from, selection);
+        query.order_by = vec![ast::OrderByExpr {
+            expr: order_column,
+            asc: Some(true),
+            nulls_first: None,
+        }];
+        query.limit = Some(ast::Expr::Value(ast::Value::Number(
+            page_size.to_string(),
+            false,
+        )));
+        Ok(query.to_string())
+    }
+
+    /// Builds a sampled variant of the data extraction query, to cheaply estimate the cost of the
+    /// full extraction.
+    ///
+    /// Dialects that support it (PostgreSQL, Snowflake) use `TABLESAMPLE`, the others fall back
+    /// to `WHERE RANDOM() < fraction` or `ORDER BY RANDOM() LIMIT rows`.
+    ///
+    /// A fraction that isn't between 0 and 1 (e.g. NaN) is rejected with
+    /// [`ParseError::MalformedQuery`].
+    pub fn sampled_extraction_query(
+        &self,
+        dialect: Dialect,
+        sample: Sample,
+    ) -> Result<String, ParseError> {
+        if let Sample::Fraction(fraction) = sample {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(malformed_query!(
+                    ErrorCode::InvalidLiteral,
+                    format!("the sampled fraction {fraction} isn't between 0 and 1.")
+                ));
+            }
+        }
+        let mut query = self.data_extraction_ast(dialect, None);
+        let random = || {
+            let name = match dialect {
+                Dialect::MySql | Dialect::BigQuery => "RAND",
+                _ => "RANDOM",
+            };
+            ast::Expr::Function(ast::Function {
+                name: ast::ObjectName(vec![ast::Ident::new(name)]),
+                args: Vec::default(),
+                filter: None,
+                null_treatment: None,
+                over: None,
+                distinct: false,
+                special: false,
+                order_by: Vec::default(),
+            })
         };
-        let query_body = ast::SetExpr::Select(Box::new(select_expr));
-        let query = ast::Query {
-            with: None,
-            body: Box::new(query_body),
+        match (sample, dialect) {
+            (Sample::Fraction(fraction), Dialect::PostgreSql | Dialect::Snowflake) => {
+                let percent = format_decimal(fraction * 100.0);
+                sample_table(&mut query, &format!("TABLESAMPLE BERNOULLI ({percent})"));
+            }
+            (Sample::Rows(rows), Dialect::Snowflake) => {
+                sample_table(&mut query, &format!("TABLESAMPLE ({rows} ROWS)"));
+            }
+            (Sample::Fraction(fraction), _) => {
+                if let ast::SetExpr::Select(select) = query.body.as_mut() {
+                    select.selection = Some(ast::Expr::BinaryOp {
+                        left: Box::new(random()),
+                        op: ast::BinaryOperator::Lt,
+                        right: Box::new(ast::Expr::Value(ast::Value::Number(
+                            format_decimal(fraction),
+                            false,
+                        ))),
+                    });
+                }
+            }
+            (Sample::Rows(rows), _) => {
+                query.order_by = vec![ast::OrderByExpr {
+                    expr: random(),
+                    asc: None,
+                    nulls_first: None,
+                }];
+                query.limit = Some(ast::Expr::Value(ast::Value::Number(
+                    rows.to_string(),
+                    false,
+                )));
+            }
+        }
+        Ok(query.to_string())
+    }
+
+    // the columns read by the data extraction query (see `extraction_identifiers`)
+    fn extraction_columns(&self) -> Vec<&str> {
+        extraction_identifiers(
+            &self.aggregation,
+            self.filter.as_ref(),
+            self.time_bucket.as_ref(),
+        )
+        .into_iter()
+        .map(Identifier::as_str)
+        .collect()
+    }
+
+    raw_ast! {
+        /// Builds the data aggregation query as an AST, so that it can be adjusted before
+        /// rendering.
+        ///
+        /// Returns `None` when the dialect can't compute the function (i.e. MEDIAN outside of
+        /// PostgreSQL, Snowflake and BigQuery).
+        #[must_use]
+        pub fn data_aggregation_ast(
+            &self,
+            dialect: Dialect,
+            quote_style: Option<char>,
+        ) -> Option<ast::Query> {
+            let item = if self.aggregation.function == KoronFunction::Median {
+                let median = self.aggregation.median_expr(dialect, quote_style)?;
+                self.aggregation
+                    .wrap_select_item(dialect, quote_style, |_| cast_to_text(median))
+            } else {
+                self.aggregation
+                    .wrap_select_item(dialect, quote_style, cast_to_text)
+            };
+            Some(self.select_grouped(vec![item], dialect, quote_style))
+        }
+    }
+
+    /// Renders the derived queries in the given dialect, with the identifiers quoted with
+    /// `quote_style` if given, otherwise only when the dialect requires it.
+    #[must_use]
+    pub fn render(&self, dialect: Dialect, quote_style: Option<char>) -> RenderedQueries {
+        RenderedQueries {
+            data_extraction_query: self.data_extraction_ast(dialect, quote_style).to_string(),
+            data_aggregation_query: self
+                .data_aggregation_ast(dialect, quote_style)
+                .map(|query| query.to_string()),
+        }
+    }
+
+    /// Builds the row-count query `SELECT COUNT(*) FROM table [WHERE filter]`, to check the
+    /// cardinality of the data before extracting it.
+    #[must_use]
+    pub fn count_query(&self, dialect: Dialect, quote_style: Option<char>) -> String {
+        let count = ast::Expr::Function(ast::Function {
+            name: ast::ObjectName(vec![ast::Ident::new("COUNT")]),
+            args: vec![ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Wildcard)],
+            filter: None,
+            null_treatment: None,
+            over: None,
+            distinct: false,
+            special: false,
             order_by: Vec::default(),
-            limit: None,
-            offset: None,
-            fetch: None,
-            locks: Vec::default(),
-            limit_by: Vec::default(),
-            for_clause: None,
+        });
+        let projection = vec![ast::SelectItem::UnnamedExpr(count)];
+        self.select_from_table(projection, dialect, quote_style, true)
+            .to_string()
+    }
+
+    /// Returns `true` if both queries compute the same result, whatever their aliases, their
+    /// annotations and their derived queries, e.g. to deduplicate scheduled queries.
+    ///
+    /// How the queries are written doesn't matter either: the names are compared once case
+    /// folded (whether they were quoted or not), and the values once unescaped or normalized
+    /// (e.g. `1e1` and `10.0`). The metadata hashes the same when it's semantically equal.
+    #[must_use]
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.semantic_key() == other.semantic_key()
+    }
+
+    // what the result of the query depends on
+    const fn semantic_key(&self) -> SemanticKey<'_> {
+        (
+            self.aggregation.function,
+            &self.aggregation.column,
+            self.aggregation.window.as_ref(),
+            &self.table,
+            self.table_version.as_ref(),
+            self.filter.as_ref(),
+            self.time_bucket.as_ref(),
+            self.empty_result,
+        )
+    }
+
+    /// Computes a stable hash of the query shape: the aggregation (with its window), the table,
+    /// the filter, the time bucket and whether the filter never holds.
+    ///
+    /// The fingerprint doesn't depend on how the query was written (whitespace, keyword case,
+    /// table aliases, ...). The literal compared in the filter and the alias of the result are
+    /// part of it only if `with_literals` and `with_alias` are set, so that e.g. queries that
+    /// only differ in the filtered value can share the same fingerprint.
+    #[must_use]
+    pub fn fingerprint(&self, with_literals: bool, with_alias: bool) -> u64 {
+        let Aggregation {
+            function,
+            column,
+            alias,
+            window,
+        } = &self.aggregation;
+        let TabIdent { db, schema, table } = &self.table;
+        let mut parts = vec![
+            function.to_string(),
+            column.to_string(),
+            db.as_ref().map(ToString::to_string).unwrap_or_default(),
+            schema.as_ref().map(ToString::to_string).unwrap_or_default(),
+            table.to_string(),
+        ];
+        //reading the table at another point in time is another query
+        if let Some(table_version) = &self.table_version {
+            parts.push(table_version.as_of.clone());
+        }
+        if with_alias {
+            parts.push(alias.clone().unwrap_or_default());
+        }
+        //the window is tagged as well, it's only part of the running aggregates
+        if let Some(WindowSpec {
+            order_by,
+            descending,
+        }) = window
+        {
+            parts.extend([
+                "OVER".to_string(),
+                order_by.to_string(),
+                descending.to_string(),
+            ]);
+        }
+        //the time bucket is tagged, so that it can't be confused with a filter
+        if let Some(TimeBucket {
+            column,
+            granularity,
+            time_type,
+        }) = &self.time_bucket
+        {
+            parts.extend([
+                "GROUP BY".to_string(),
+                granularity.to_string(),
+                column.to_string(),
+            ]);
+            //only the BigQuery dates and datetimes, so that the other fingerprints are kept
+            if *time_type != TimeType::Timestamp {
+                parts.push(time_type.trunc_function().to_string());
+            }
+        }
+        if let Some(filter) = &self.filter {
+            parts.push(filter.column.to_string());
+            parts.extend(filter.json_path.iter().flatten().cloned());
+            parts.extend(filter.collation.clone());
+            parts.push(filter.comparison.to_string());
+            if let CompareOp::ElementEq { index, .. } | CompareOp::ElementNotEq { index, .. } =
+                &filter.comparison
+            {
+                parts.push(index.to_string());
+            }
+            if with_literals {
+                let value_type = filter.value_type.map(ValueType::fingerprint_tag);
+                parts.push(value_type.unwrap_or("NONE").to_string());
+                parts.push(filter.comparison.value().unwrap_or_default().to_string());
+            }
+        }
+        //tagged too, so that a query over no rows isn't confused with the unfiltered one
+        if self.empty_result {
+            parts.push("EMPTY".to_string());
+        }
+        //the unit separator can't be confused with the content of the parts
+        stable_hash(&parts.join("\u{1f}"))
+    }
+
+    // the version qualifier of the table, if the dialect supports it
+    fn rendered_table_version(&self, dialect: Dialect) -> Option<ast::TableVersion> {
+        self.table_version
+            .as_ref()
+            .filter(|_| dialect.supports_table_versions())
+            .map(TableVersion::to_ast)
+    }
+
+    // builds `SELECT projection FROM table [WHERE filter]`
+    fn select_from_table(
+        &self,
+        projection: Vec<ast::SelectItem>,
+        dialect: Dialect,
+        quote_style: Option<char>,
+        with_filter: bool,
+    ) -> ast::Query {
+        let from = vec![table_with_joins(
+            self.table.render_object_name(dialect, quote_style),
+            self.rendered_table_version(dialect),
+        )];
+        //a WHERE clause that never holds is rendered as such, rather than dropped
+        let selection = match &self.filter {
+            _ if !with_filter => None,
+            Some(filter) => Some(filter.to_expr(dialect, quote_style)),
+            None => self
+                .empty_result
+                .then_some(ast::Expr::Value(ast::Value::Boolean(false))),
+        };
+        select_query(projection, from, selection)
+    }
+
+    // builds `SELECT projection FROM table [WHERE filter]`, grouped by the time bucket if any,
+    // which is then selected first
+    fn select_grouped(
+        &self,
+        mut projection: Vec<ast::SelectItem>,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> ast::Query {
+        let Some(time_bucket) = &self.time_bucket else {
+            return self.select_from_table(projection, dialect, quote_style, true);
+        };
+        let bucket = time_bucket.to_expr(dialect, quote_style);
+        projection.insert(0, ast::SelectItem::UnnamedExpr(bucket.clone()));
+        let mut query = self.select_from_table(projection, dialect, quote_style, true);
+        if let ast::SetExpr::Select(select) = query.body.as_mut() {
+            select.group_by = ast::GroupByExpr::Expressions(vec![bucket]);
+        }
+        query
+    }
+}
+
+// only hashes what the result of the query depends on (see `QueryMetadata::semantically_eq`),
+// which equal metadata has in common too
+impl Hash for QueryMetadata {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.semantic_key().hash(state);
+    }
+}
+
+// the migrations of the serialized metadata, from each schema version to the next one
+#[cfg(feature = "json")]
+pub(crate) const MIGRATIONS: &[fn(&mut serde_json::Value)] = &[type_filter_value];
+
+// the name of a field of the serialized metadata, which depends on the `camel-case` feature
+#[cfg(feature = "json")]
+const fn json_key(snake_case: &'static str, camel_case: &'static str) -> &'static str {
+    if cfg!(feature = "camel-case") {
+        camel_case
+    } else {
+        snake_case
+    }
+}
+
+// sorts the keys of the objects of `value`, which `serde_json` keeps in insertion order if its
+// `preserve_order` feature is enabled (e.g. by another crate); the recursion is bounded by the
+// depth of the metadata
+#[cfg(feature = "json")]
+fn sort_keys(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            fields.sort_keys();
+            fields.values_mut().for_each(sort_keys);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+// types the value of the filter serialized before the schema was versioned: an untyped value is
+// rendered as is, so it's only typed as a number if it reads as one
+#[cfg(feature = "json")]
+fn type_filter_value(document: &mut serde_json::Value) {
+    let Some(filter) = document
+        .get_mut("filter")
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return;
+    };
+    let value_type_key = json_key("value_type", "valueType");
+    let value = filter
+        .get("comparison")
+        .and_then(|comparison| comparison.get("value"))
+        .and_then(serde_json::Value::as_str);
+    let untyped = filter
+        .get(value_type_key)
+        .is_none_or(serde_json::Value::is_null);
+    if let Some(value) = value.filter(|_| untyped) {
+        let value_type = if crate::comparison::normalize_number(value).is_ok() {
+            ValueType::Number
+        } else {
+            ValueType::String
         };
-        let select_statement = ast::Statement::Query(Box::new(query));
-        Ok(select_statement.to_string())
+        let value_type = serde_json::to_value(value_type).unwrap_or_default();
+        filter.insert(value_type_key.to_string(), value_type);
+    }
+}
+
+// attaches to the error the part of the query it's about, if asked to, and reports it to the
+// rejection hook
+fn report_error(error: ParseError, sql_query: &str, options: &ParseOptions) -> ParseError {
+    let error = if options.error_spans() {
+        let span = locate(&error, sql_query, options.dialect());
+        error.with_span(span)
+    } else {
+        error
+    };
+    report_rejection(&error, options);
+    error
+}
+
+// calls the rejection hook, if any, for an error reporting an unsupported construct
+fn report_rejection(error: &ParseError, options: &ParseOptions) {
+    if let (
+        Some(on_reject),
+        ParseError::Unsupported {
+            feature, message, ..
+        },
+    ) = (options.on_reject(), error)
+    {
+        on_reject(feature, message);
     }
 }
 
+// maps the alias of the result, if any, to the aggregation (e.g. `s` to `SUM(column)`)
+fn result_aliases(aggregation: &Aggregation) -> BTreeMap<String, String> {
+    aggregation
+        .alias
+        .iter()
+        .map(|alias| {
+            let function = format!("{}({})", aggregation.function, aggregation.column);
+            (alias.clone(), function)
+        })
+        .collect()
+}
+
+// qualifies the table with the default database and schema, like a search path would
+fn qualify_with_defaults(table: TabIdent, options: &ParseOptions) -> TabIdent {
+    TabIdent {
+        db: table
+            .db
+            .or_else(|| options.default_db().map(Identifier::new)),
+        schema: table
+            .schema
+            .or_else(|| options.default_schema().map(Identifier::new)),
+        table: table.table,
+    }
+}
+
+// the item of the SELECT clause computing the aggregation, besides the time bucket the query is
+// grouped by, if any
+fn aggregation_item<'a>(
+    projection: &'a [ast::SelectItem],
+    group_by: &[ast::Expr],
+) -> Result<&'a ast::SelectItem, ParseError> {
+    match without_bucket(projection, group_by.first()) {
+        [item] => Ok(item),
+        _ => Err(unsupported!(UnsupportedFeature::Projection, "the SELECT clause must contain exactly one aggregation / analytic function. Nothing else is accepted.".to_string())),
+    }
+}
+
+// the items of the SELECT clause as written, with `aggregation` in place of `aggregation_item`
+// (the time bucket, if it's selected, is kept as is)
+fn selected_with_bucket<'a>(
+    projection: &'a [ast::SelectItem],
+    aggregation_item: &ast::SelectItem,
+    aggregation: SelectedExpr<'a>,
+) -> Vec<SelectedExpr<'a>> {
+    let mut aggregation = Some(aggregation);
+    projection
+        .iter()
+        .filter_map(|item| match item {
+            _ if item == aggregation_item => aggregation.take(),
+            ast::SelectItem::UnnamedExpr(expr) => Some(SelectedExpr {
+                expr,
+                alias: None,
+                cast: false,
+            }),
+            ast::SelectItem::ExprWithAlias { expr, alias } => Some(SelectedExpr {
+                expr,
+                alias: Some(alias),
+                cast: false,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// the WHERE clause of the data extraction query when the filter is pushed down, rebuilt from the
+// extracted filter, or `WHERE false` if it never holds, so that no row is extracted
+fn pushed_down_selection(
+    filter: Option<&Filter>,
+    empty_result: bool,
+    dialect: Dialect,
+    quote_style: Option<char>,
+) -> Option<ast::Expr> {
+    filter.map_or_else(
+        || empty_result.then_some(ast::Expr::Value(ast::Value::Boolean(false))),
+        |filter| {
+            Some(
+                filter
+                    .expression()
+                    .to_ast(dialect, &|column| column.to_ident(dialect, quote_style)),
+            )
+        },
+    )
+}
+
+// the parts of the metadata the result of a query depends on: the function, the aggregated column,
+// the window, the table, its version, the filter, the time bucket and whether the filter holds
+type SemanticKey<'a> = (
+    KoronFunction,
+    &'a Identifier,
+    Option<&'a WindowSpec>,
+    &'a TabIdent,
+    Option<&'a TableVersion>,
+    Option<&'a Filter>,
+    Option<&'a TimeBucket>,
+    bool,
+);
+
+// the columns read by the data extraction query, once each: the aggregated one, the one ordering
+// the window, the filtered one and the one of the time bucket
+fn extraction_identifiers<'a>(
+    aggregation: &'a Aggregation,
+    filter: Option<&'a Filter>,
+    time_bucket: Option<&'a TimeBucket>,
+) -> Vec<&'a Identifier> {
+    let mut columns = vec![&aggregation.column];
+    let others = aggregation
+        .window
+        .as_ref()
+        .map(|window| &window.order_by)
+        .into_iter()
+        .chain(filter.map(|filter| &filter.column))
+        .chain(time_bucket.map(|bucket| &bucket.column));
+    for column in others {
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+    columns
+}
+
+// the columns a query reads: the aggregated one, the one ordering the window, the filtered ones
+// (including the filters dropped along with a constant) and the one of the time bucket
+fn query_columns<'a>(
+    aggregation: Option<&'a Aggregation>,
+    filters: &'a [&'a Filter],
+    time_bucket: Option<&'a TimeBucket>,
+) -> impl Iterator<Item = &'a str> {
+    let window = aggregation.and_then(|aggregation| aggregation.window.as_ref());
+    let aggregation = aggregation.map(|aggregation| aggregation.column.as_str());
+    let window = window.map(|window| window.order_by.as_str());
+    let filters = filters.iter().map(|filter| filter.column.as_str());
+    let time_bucket = time_bucket.map(|bucket| bucket.column.as_str());
+    aggregation
+        .into_iter()
+        .chain(window)
+        .chain(filters)
+        .chain(time_bucket)
+}
+
+// rejects a running aggregate in a query grouped by a time bucket, since it's computed over the
+// rows of the table rather than over the periods
+fn ungrouped_window(
+    aggregation: Aggregation,
+    time_bucket: Option<&TimeBucket>,
+) -> Result<Aggregation, ParseError> {
+    if aggregation.window.is_some() && time_bucket.is_some() {
+        return Err(unsupported!(
+            UnsupportedFeature::WindowFunction,
+            "running aggregates (OVER) in a query grouped by a time bucket.".to_string()
+        ));
+    }
+    Ok(aggregation)
+}
+
+// the names of a query: the parts of the table, its alias, the columns and the alias of the
+// result
+fn query_names<'a>(
+    table: &'a TabIdent,
+    table_alias: Option<&'a str>,
+    aggregation: Option<&'a Aggregation>,
+    filters: &'a [&'a Filter],
+    time_bucket: Option<&'a TimeBucket>,
+) -> Vec<&'a str> {
+    let TabIdent { db, schema, table } = table;
+    [db.as_ref(), schema.as_ref(), Some(table)]
+        .into_iter()
+        .flatten()
+        .map(Identifier::as_str)
+        .chain(table_alias)
+        .chain(aggregation.and_then(|aggregation| aggregation.alias.as_deref()))
+        .chain(query_columns(aggregation, filters, time_bucket))
+        .collect()
+}
+
+// formats a number without float noise (e.g. 7 rather than 7.000000000000001)
+// appends the sampling clause `clause` to the table of the FROM clause of `query`, after its
+// version if any: sqlparser can't represent TABLESAMPLE, so the table is emitted as a name that's
+// printed verbatim, followed by the clause
+fn sample_table(query: &mut ast::Query, clause: &str) {
+    if let ast::SetExpr::Select(select) = query.body.as_mut() {
+        for table in &mut select.from {
+            let sampled = format!("{} {clause}", table.relation);
+            table.relation = ast::TableFactor::Table {
+                name: ast::ObjectName(vec![ast::Ident::new(sampled)]),
+                alias: None,
+                args: None,
+                with_hints: Vec::default(),
+                version: None,
+                partitions: Vec::default(),
+            };
+        }
+    }
+}
+
+fn format_decimal(value: f64) -> String {
+    let formatted = format!("{value:.6}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn column_item(name: &str, dialect: Dialect, quote_style: Option<char>) -> ast::SelectItem {
+    ast::SelectItem::UnnamedExpr(ast::Expr::Identifier(render_identifier(
+        name,
+        dialect,
+        quote_style,
+    )))
+}
+
+/// How much of the table a sampled extraction query reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sample {
+    /// A fraction of the rows, between 0 and 1.
+    Fraction(f64),
+    /// A fixed number of rows.
+    Rows(u64),
+}
+
+/// The derived queries of a [`QueryMetadata`], rendered as SQL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderedQueries {
+    /// Data Extraction Query in SQL
+    pub data_extraction_query: String,
+    /// Data Aggregation Query in SQL
+    pub data_aggregation_query: Option<String>,
+}
+
 #[derive(Clone, Copy)]
 pub(crate) enum FromClauseIdentifier<'a> {
-    Base(&'a TabIdent),
-    Alias { alias: &'a str },
+    Base {
+        table: &'a TabIdent,
+        qualification: Qualification,
+        table_qualifier: TableQualifier,
+    },
+    Alias {
+        alias: &'a str,
+    },
 }
 
 impl FromClauseIdentifier<'_> {
@@ -226,35 +1940,30 @@ impl FromClauseIdentifier<'_> {
         db: Option<&ast::Ident>,
         schema: Option<&ast::Ident>,
         table: &ast::Ident,
+        case_folding: CaseFolding,
     ) -> bool {
         match self {
-            FromClauseIdentifier::Base(expected) => {
-                let db_matches = if expected.db.is_none() {
-                    true
-                } else {
-                    db.map_or(true, |db| {
-                        expected
-                            .db
-                            .as_ref()
-                            .map_or(true, |expected_db| &case_fold_identifier(db) == expected_db)
-                    })
-                };
-                let schema_matches = if expected.schema.is_none() {
-                    true
-                } else {
-                    schema.map_or(true, |schema| {
-                        expected.schema.as_ref().map_or(true, |expected_schema| {
-                            &case_fold_identifier(schema) == expected_schema
-                        })
-                    })
+            FromClauseIdentifier::Base {
+                table: expected,
+                qualification,
+                table_qualifier,
+            } => {
+                let fold = |part: &ast::Ident| case_fold_identifier(part, case_folding);
+                //a two-part qualifier of the column (e.g. `mydb.events.col`) names the table
+                //like a two-part table name does
+                let (db, schema) = match (db, table_qualifier) {
+                    (None, TableQualifier::Database) => (schema, None),
+                    _ => (db, schema),
                 };
-                let table_matches = case_fold_identifier(table) == expected.table;
-                db_matches && schema_matches && table_matches
+                qualification.part_matches(expected.db.as_ref(), db.map(fold).as_deref())
+                    && qualification
+                        .part_matches(expected.schema.as_ref(), schema.map(fold).as_deref())
+                    && fold(table) == expected.table
             }
             FromClauseIdentifier::Alias { alias, .. } => {
                 // An alias name is always unqualified, so it can never match a schema-qualified
                 // table name.
-                schema.is_none() && case_fold_identifier(table) == alias
+                schema.is_none() && case_fold_identifier(table, case_folding) == alias
             }
         }
     }
@@ -263,7 +1972,7 @@ impl FromClauseIdentifier<'_> {
 impl Display for FromClauseIdentifier<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            FromClauseIdentifier::Base(table_info) => write!(f, "{table_info}"),
+            FromClauseIdentifier::Base { table, .. } => write!(f, "{table}"),
             FromClauseIdentifier::Alias { alias } => {
                 write!(f, "{alias}")
             }
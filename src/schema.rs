@@ -0,0 +1,193 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    aggregation::Aggregation,
+    comparison::{CompareOp, ValueType},
+    error::{ErrorCode, ParseError},
+    filter::Filter,
+    identifier::Identifier,
+    malformed_query,
+    table::TabIdent,
+    time_bucket::TimeBucket,
+};
+
+/// Resolves the tables referenced by the queries, so that they can be bound while being parsed
+/// (see [`crate::query_metadata::QueryMetadata::parse_with_catalog`]).
+///
+/// A catalog that's `Sync` can be shared across threads along with the options, e.g. behind an
+/// [`std::sync::Arc`], since the queries only borrow it.
+pub trait Catalog {
+    /// Returns the schema of `table`, or `None` if there's no such table.
+    fn resolve_table(&self, table: &TabIdent) -> Option<TableSchema>;
+
+    /// Returns the fully-qualified name of `table` (e.g. filling the database and the schema
+    /// of an unqualified table). By default, the name is kept as written.
+    fn qualify(&self, table: &TabIdent) -> TabIdent {
+        table.clone()
+    }
+}
+
+/// The type of a column, as far as the checks on the queries are concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub enum ColumnType {
+    /// Integer, decimal and floating point columns.
+    Number,
+    /// Character columns.
+    Text,
+    /// Boolean columns.
+    Boolean,
+    /// Date, time and timestamp columns.
+    Temporal,
+    /// Any other type, on which no check is performed.
+    #[default]
+    Other,
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::Text => write!(f, "text"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Temporal => write!(f, "temporal"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// The definition of a column of a table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct ColumnDef {
+    /// The name of the column, as it's stored (i.e. already case folded).
+    pub name: String,
+    /// The type of the column.
+    pub data_type: ColumnType,
+}
+
+/// The columns of a table, used to validate the queries over it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct TableSchema {
+    /// The columns of the table.
+    pub columns: Vec<ColumnDef>,
+}
+
+impl TableSchema {
+    /// Returns the definition of the column named `name`, if any.
+    #[must_use]
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    pub(crate) fn check(
+        &self,
+        aggregation: &Aggregation,
+        filters: &[&Filter],
+        time_bucket: Option<&TimeBucket>,
+    ) -> Result<(), ParseError> {
+        self.check_aggregation(aggregation)?;
+        if let Some(time_bucket) = time_bucket {
+            self.check_time_bucket(time_bucket)?;
+        }
+        for filter in filters {
+            //some dialects resolve the aliases of the SELECT clause in the WHERE clause, but the
+            //result of an aggregation can't be filtered there
+            if self.column(filter.column.as_str()).is_none()
+                && aggregation.alias.as_deref() == Some(filter.column.as_str())
+            {
+                return Err(malformed_query!(
+                    ErrorCode::AliasInWhere,
+                    format!(
+                        "the alias `{}` of {}({}) can't be used in the WHERE clause.",
+                        filter.column.raw, aggregation.function, aggregation.column
+                    )
+                ));
+            }
+            self.check_filter(filter)?;
+        }
+        Ok(())
+    }
+
+    fn check_aggregation(&self, aggregation: &Aggregation) -> Result<(), ParseError> {
+        let column = self.existing_column(&aggregation.column)?;
+        if aggregation.function.requires_numeric_input()
+            && !matches!(column.data_type, ColumnType::Number | ColumnType::Other)
+        {
+            return Err(malformed_query!(
+                ErrorCode::TypeMismatch,
+                format!(
+                    "{} only applies to numeric columns, but `{}` is of type {}.",
+                    aggregation.function, column.name, column.data_type
+                )
+            ));
+        }
+        if let Some(window) = &aggregation.window {
+            self.existing_column(&window.order_by)?;
+        }
+        Ok(())
+    }
+
+    fn check_time_bucket(&self, time_bucket: &TimeBucket) -> Result<(), ParseError> {
+        let column = self.existing_column(&time_bucket.column)?;
+        if !matches!(column.data_type, ColumnType::Temporal | ColumnType::Other) {
+            return Err(malformed_query!(
+                ErrorCode::TypeMismatch,
+                format!(
+                    "the time bucket {time_bucket} only applies to temporal columns, but `{}` is \
+                     of type {}.",
+                    column.name, column.data_type
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_filter(&self, filter: &Filter) -> Result<(), ParseError> {
+        let column = self.existing_column(&filter.column)?;
+        //the type of a JSON field isn't known
+        if filter.json_path.is_some() {
+            return Ok(());
+        }
+        let expected = match (&filter.comparison, filter.value_type) {
+            (
+                CompareOp::IsTrue
+                | CompareOp::IsNotTrue
+                | CompareOp::IsFalse
+                | CompareOp::IsNotFalse,
+                _,
+            )
+            | (_, Some(ValueType::Boolean)) => ColumnType::Boolean,
+            (_, Some(ValueType::Number)) => ColumnType::Number,
+            (
+                _,
+                Some(
+                    ValueType::Date | ValueType::Time | ValueType::Timestamp | ValueType::Symbolic,
+                ),
+            ) => ColumnType::Temporal,
+            //strings are accepted for any type, e.g. dates are written as strings
+            _ => return Ok(()),
+        };
+        if column.data_type != expected && column.data_type != ColumnType::Other {
+            return Err(malformed_query!(
+                ErrorCode::TypeMismatch,
+                format!(
+                    "the column `{}` of type {} can't be compared to a {expected} value.",
+                    column.name, column.data_type
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    // the column named `name`, which is reported as written in the query if it doesn't exist
+    fn existing_column(&self, name: &Identifier) -> Result<&ColumnDef, ParseError> {
+        self.column(name.as_str()).ok_or_else(|| {
+            malformed_query!(
+                ErrorCode::UnknownColumn,
+                format!("the column `{}` doesn't exist in the table.", name.raw)
+            )
+        })
+    }
+}
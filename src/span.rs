@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer};
+use utoipa::ToSchema;
+
+use crate::{
+    dialect::Dialect,
+    error::{ParseError, UnsupportedFeature},
+    support::normalize_query,
+};
+
+/// A position in the SQL query: line and column, both starting from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct Location {
+    /// The line, starting from 1.
+    pub line: u64,
+    /// The column within the line, starting from 1.
+    pub column: u64,
+}
+
+/// A range of the SQL query, from `start` (included) to `end` (excluded).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct Span {
+    /// Where the range starts.
+    pub start: Location,
+    /// Where the range ends, i.e. the position right after its last character.
+    pub end: Location,
+}
+
+/// Finds the part of `sql` that `error` is about, if it can be located: the token the SQL parser
+/// stopped at for syntax errors, the keywords of the clause for unsupported clauses.
+pub(crate) fn locate(error: &ParseError, sql: &str, dialect: Dialect) -> Option<Span> {
+    let (ParseError::MalformedQuery { message, .. } | ParseError::Unsupported { message, .. }) =
+        error
+    else {
+        return None;
+    };
+    let sql = normalize_query(sql, dialect);
+    let tokens = Tokenizer::new(dialect.parser_dialect().as_ref(), &sql)
+        .tokenize_with_location()
+        .ok()?;
+    if let Some(location) = parser_error_location(message) {
+        let token = tokens.iter().find(|token| {
+            token.location.line == location.line && token.location.column == location.column
+        })?;
+        return Some(token_span(token, token));
+    }
+    let keywords = clause_keywords(error.unsupported_feature()?)?;
+    let words = tokens
+        .iter()
+        .filter(|token| !matches!(token.token, Token::Whitespace(_)))
+        .collect::<Vec<_>>();
+    words
+        .windows(keywords.len())
+        .find(|window| {
+            window.iter().zip(keywords).all(|(token, keyword)| {
+                matches!(&token.token, Token::Word(word)
+                    if word.quote_style.is_none() && word.value.eq_ignore_ascii_case(keyword))
+            })
+        })
+        .and_then(|window| Some(token_span(window.first()?, window.last()?)))
+}
+
+// the location the SQL parser appends to its messages, i.e. "... at Line: 1, Column 8"
+fn parser_error_location(message: &str) -> Option<Location> {
+    let (_, location) = message.rsplit_once(" at Line: ")?;
+    let (line, column) = location.split_once(", Column ")?;
+    Some(Location {
+        line: line.parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+// the keywords that start the unsupported clause reported by the error
+fn clause_keywords(feature: &UnsupportedFeature) -> Option<&'static [&'static str]> {
+    let keywords: &[&str] = match feature {
+        UnsupportedFeature::Cte => &["WITH"],
+        UnsupportedFeature::OrderBy | UnsupportedFeature::AggregationOrderBy => &["ORDER", "BY"],
+        UnsupportedFeature::Limit => &["LIMIT"],
+        UnsupportedFeature::Offset => &["OFFSET"],
+        UnsupportedFeature::Fetch => &["FETCH"],
+        UnsupportedFeature::For | UnsupportedFeature::Locking => &["FOR"],
+        UnsupportedFeature::Values => &["VALUES"],
+        UnsupportedFeature::Distinct => &["DISTINCT"],
+        UnsupportedFeature::Top => &["TOP"],
+        UnsupportedFeature::SelectInto => &["INTO"],
+        UnsupportedFeature::LateralView => &["LATERAL", "VIEW"],
+        UnsupportedFeature::GroupBy => &["GROUP", "BY"],
+        UnsupportedFeature::ClusterBy => &["CLUSTER", "BY"],
+        UnsupportedFeature::DistributeBy => &["DISTRIBUTE", "BY"],
+        UnsupportedFeature::SortBy => &["SORT", "BY"],
+        UnsupportedFeature::Having => &["HAVING"],
+        UnsupportedFeature::Qualify => &["QUALIFY"],
+        UnsupportedFeature::NamedWindow => &["WINDOW"],
+        UnsupportedFeature::WindowFunction => &["OVER"],
+        UnsupportedFeature::AggregationFilter => &["FILTER"],
+        UnsupportedFeature::NullTreatment => &["IGNORE", "NULLS"],
+        UnsupportedFeature::SetOperation(operator) => match &operator[..] {
+            "UNION" => &["UNION"],
+            "EXCEPT" => &["EXCEPT"],
+            "INTERSECT" => &["INTERSECT"],
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(keywords)
+}
+
+/// Renders the line of `sql` where `span` starts, with the span underlined (up to the end of
+/// the line, if it spans several lines).
+pub(crate) fn snippet(sql: &str, span: Span) -> String {
+    let index = |value: u64| usize::try_from(value).unwrap_or(usize::MAX);
+    let Some(line) = index(span.start.line)
+        .checked_sub(1)
+        .and_then(|line| sql.lines().nth(line))
+    else {
+        return String::new();
+    };
+    let number = span.start.line.to_string();
+    let gutter = " ".repeat(number.len());
+    let length = line.chars().count();
+    let start = index(span.start.column).clamp(1, length + 1);
+    let end = if span.end.line == span.start.line {
+        index(span.end.column).min(length + 1)
+    } else {
+        length + 1
+    };
+    let padding = " ".repeat(start - 1);
+    let carets = "^".repeat(end.saturating_sub(start).max(1));
+    format!("\n{gutter} |\n{number} | {line}\n{gutter} | {padding}{carets}")
+}
+
+fn token_span(first: &TokenWithLocation, last: &TokenWithLocation) -> Span {
+    let length = last.token.to_string().chars().count() as u64;
+    Span {
+        start: Location {
+            line: first.location.line,
+            column: first.location.column,
+        },
+        end: Location {
+            line: last.location.line,
+            column: last.location.column + length,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parser_error_location, snippet, Location, Span};
+
+    #[test]
+    fn parser_location() {
+        assert_eq!(
+            parser_error_location(
+                "sql parser error: Expected end of statement, found: x at Line: 2, Column 13"
+            ),
+            Some(Location {
+                line: 2,
+                column: 13
+            })
+        );
+        assert_eq!(
+            parser_error_location("sql parser error: Expected identifier, found: EOF"),
+            None
+        );
+    }
+
+    #[test]
+    fn line_snippet() {
+        let span = |start: (u64, u64), end: (u64, u64)| Span {
+            start: Location {
+                line: start.0,
+                column: start.1,
+            },
+            end: Location {
+                line: end.0,
+                column: end.1,
+            },
+        };
+        let sql = "SELECT SUM(a) FROM t\nORDER BY a\nLIMIT 5";
+        assert_eq!(
+            snippet(sql, span((2, 1), (2, 9))),
+            "\n  |\n2 | ORDER BY a\n  | ^^^^^^^^"
+        );
+        assert_eq!(
+            snippet(sql, span((1, 20), (2, 3))),
+            "\n  |\n1 | SELECT SUM(a) FROM t\n  |                    ^"
+        );
+        assert_eq!(
+            snippet(sql, span((3, 8), (3, 9))),
+            "\n  |\n3 | LIMIT 5\n  |        ^"
+        );
+        assert_eq!(snippet(sql, span((4, 1), (4, 2))), "");
+    }
+}
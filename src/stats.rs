@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::ast;
+use utoipa::ToSchema;
+
+use crate::{destructured_query::DestructuredQuery, filter::Filter};
+
+/// Measures of the complexity of a query, e.g. for admission control (see
+/// [`crate::query_metadata::QueryMetadata::parse_with_stats`]).
+///
+/// The expressions are measured in the SELECT, FROM and WHERE clauses and in the GROUP BY clause of
+/// a time bucket, the ones the metadata is extracted from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+pub struct QueryStats {
+    /// The length of the query, in bytes.
+    pub byte_length: usize,
+    /// The depth of the most nested expression, e.g. 3 for `(a > 1)`: the parentheses, the
+    /// comparison and its operands.
+    pub expression_depth: usize,
+    /// The number of comparisons in the filter.
+    pub filter_predicates: usize,
+    /// The number of literals (numbers, strings, booleans, NULL).
+    pub literal_count: usize,
+    /// The number of identifiers, counting each part of the qualified names (e.g. the names of
+    /// the function, the columns and the table, and the aliases).
+    pub identifier_count: usize,
+}
+
+impl QueryStats {
+    pub(crate) fn measure(
+        sql: &str,
+        query: DestructuredQuery<'_>,
+        filter: Option<&Filter>,
+    ) -> Self {
+        let DestructuredQuery {
+            projection,
+            from,
+            selection,
+            group_by,
+        } = query;
+        let mut stats = Self {
+            byte_length: sql.len(),
+            filter_predicates: usize::from(filter.is_some()),
+            ..Self::default()
+        };
+        for item in projection {
+            let depth = match item {
+                ast::SelectItem::UnnamedExpr(expr) => stats.measure_expr(expr),
+                ast::SelectItem::ExprWithAlias { expr, .. } => {
+                    stats.identifier_count += 1;
+                    stats.measure_expr(expr)
+                }
+                _ => 0,
+            };
+            stats.expression_depth = stats.expression_depth.max(depth);
+        }
+        for table in from {
+            if let ast::TableFactor::Table { name, alias, .. } = &table.relation {
+                stats.identifier_count += name.0.len() + usize::from(alias.is_some());
+            }
+        }
+        for expr in selection.into_iter().chain(group_by) {
+            let depth = stats.measure_expr(expr);
+            stats.expression_depth = stats.expression_depth.max(depth);
+        }
+        stats
+    }
+
+    // counts the literals and the identifiers of `expr`, and returns its depth; the recursion is
+    // bounded by the recursion limit of the SQL parser
+    fn measure_expr(&mut self, expr: &ast::Expr) -> usize {
+        let depth = match expr {
+            ast::Expr::Identifier(_) => {
+                self.identifier_count += 1;
+                0
+            }
+            ast::Expr::CompoundIdentifier(parts) => {
+                self.identifier_count += parts.len();
+                0
+            }
+            ast::Expr::Value(_) | ast::Expr::TypedString { .. } => {
+                self.literal_count += 1;
+                0
+            }
+            ast::Expr::Nested(expr)
+            | ast::Expr::UnaryOp { expr, .. }
+            | ast::Expr::Cast { expr, .. }
+            | ast::Expr::IsNull(expr)
+            | ast::Expr::IsNotNull(expr)
+            | ast::Expr::IsTrue(expr)
+            | ast::Expr::IsNotTrue(expr)
+            | ast::Expr::IsFalse(expr)
+            | ast::Expr::IsNotFalse(expr) => self.measure_expr(expr),
+            ast::Expr::BinaryOp { left, right, .. } => {
+                let left = self.measure_expr(left);
+                left.max(self.measure_expr(right))
+            }
+            ast::Expr::Function(function) => {
+                self.identifier_count += function.name.0.len();
+                let mut depth = 0;
+                for arg in &function.args {
+                    let (ast::FunctionArg::Unnamed(arg) | ast::FunctionArg::Named { arg, .. }) =
+                        arg;
+                    if let ast::FunctionArgExpr::Expr(expr) = arg {
+                        depth = depth.max(self.measure_expr(expr));
+                    }
+                }
+                depth
+            }
+            _ => 0,
+        };
+        depth + 1
+    }
+}
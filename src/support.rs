@@ -1,13 +1,32 @@
-use sqlparser::ast;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+};
 
-use crate::{error::ParseError, internal, malformed_query, query_metadata::FromClauseIdentifier};
+use sqlparser::{
+    ast,
+    dialect::GenericDialect,
+    keywords::ALL_KEYWORDS,
+    parser::{Parser, ParserError},
+    tokenizer::{Token, Tokenizer},
+};
 
-//recursively removes outer parenthesis
-pub(crate) fn remove_outer_parens(expr: &ast::Expr) -> &ast::Expr {
-    match expr {
-        ast::Expr::Nested(inner) => remove_outer_parens(inner),
-        _ => expr,
+use crate::{
+    dialect::{CaseFolding, Dialect},
+    error::{ErrorCode, ParseError},
+    identifier::Identifier,
+    internal, limit_exceeded,
+    limits::Limits,
+    malformed_query,
+    query_metadata::FromClauseIdentifier,
+};
+
+//removes outer parenthesis
+pub(crate) fn remove_outer_parens(mut expr: &ast::Expr) -> &ast::Expr {
+    while let ast::Expr::Nested(inner) = expr {
+        expr = inner;
     }
+    expr
 }
 
 //extract column name from name_parts and if table/schema/db identifier are there, checks if it corresponds to FROM clause
@@ -15,9 +34,12 @@ pub(crate) fn extract_qualified_column(
     from_clause_identifier: FromClauseIdentifier<'_>,
     compound_identifier: &ast::Expr,
     name_parts: &[ast::Ident],
-) -> Result<String, ParseError> {
+    case_folding: CaseFolding,
+) -> Result<Identifier, ParseError> {
     let unknown_column = || {
-        Err(malformed_query!(format!(
+        Err(malformed_query!(
+                ErrorCode::ColumnNotInTable,
+                format!(
                 "the {compound_identifier} column is not part of the table that's listed in the FROM clause ({from_clause_identifier}).",
             )))
     };
@@ -27,12 +49,12 @@ pub(crate) fn extract_qualified_column(
     let column = name_parts.next_back().ok_or_else(|| {
         internal!("found empty column name (CompoundIdentifier) in query AST.".to_string())
     })?;
-    let column = case_fold_identifier(column);
+    let column = Identifier::fold(column, case_folding);
 
     if let Some(table) = name_parts.next_back() {
         let schema = name_parts.next_back();
         let db = name_parts.next_back();
-        if !from_clause_identifier.matches(db, schema, table) {
+        if !from_clause_identifier.matches(db, schema, table, case_folding) {
             return unknown_column();
         }
     }
@@ -45,13 +67,659 @@ pub(crate) fn extract_qualified_column(
     Ok(column)
 }
 
-pub(crate) fn case_fold_identifier(ident: &ast::Ident) -> String {
-    // Fold unquoted identifiers to lowercase, like PostgreSQL does (see
-    // https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-IDENTIFIERS).
+pub(crate) fn case_fold_identifier(ident: &ast::Ident, case_folding: CaseFolding) -> String {
+    // Fold unquoted identifiers according to the dialect: lowercase like PostgreSQL does (see
+    // https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-IDENTIFIERS),
+    // or uppercase like Snowflake does.
     let ast::Ident { value, quote_style } = ident;
     if quote_style.is_none() {
-        value.to_ascii_lowercase()
+        case_folding.fold(value)
     } else {
-        value.to_string()
+        value.clone()
+    }
+}
+
+// builds an identifier that reads back as `value` in the given dialect: it's always quoted with
+// `quote_style` when one is given, otherwise it's quoted only when it's not a plain identifier or
+// it isn't spelled in the dialect's folded case
+pub(crate) fn render_identifier(
+    value: &str,
+    dialect: Dialect,
+    quote_style: Option<char>,
+) -> ast::Ident {
+    if quote_style.is_some() {
+        return quote_identifier(value, quote_style);
+    }
+    let mut chars = value.chars();
+    let is_plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && dialect.case_folding().is_folded(value)
+        //keywords are quoted so that they're parsed back as identifiers (e.g. a table `order`),
+        //whether the SQL parser knows them or they're reserved in the dialect
+        && ALL_KEYWORDS
+            .binary_search(&&value.to_ascii_uppercase()[..])
+            .is_err()
+        && !dialect.is_reserved_keyword(value);
+    quote_identifier(value, (!is_plain).then_some(dialect.identifier_quote()))
+}
+
+// builds an identifier quoted with `quote_style`, if any, so that it reads back as `value`: a
+// closing quote in the name has to be doubled, which `ast::Ident` only does for `"`, `'` and
+// `` ` `` (not for the `]` of `[name]`)
+pub(crate) fn quote_identifier(value: &str, quote_style: Option<char>) -> ast::Ident {
+    let value = match quote_style {
+        Some('[') => value.replace(']', "]]"),
+        _ => value.to_string(),
+    };
+    ast::Ident { value, quote_style }
+}
+
+// whether `value` may not be written unquoted in some dialect: it's empty, starts with a digit
+// or `$`, or has other characters than ASCII letters, digits, `_` and `$` (e.g. spaces, quotes
+// or accented letters)
+pub(crate) fn needs_quotes(value: &str) -> bool {
+    let mut chars = value.chars();
+    !(chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+}
+
+// wraps `expr` into `CAST(expr AS TEXT)`
+pub(crate) fn cast_to_text(expr: ast::Expr) -> ast::Expr {
+    ast::Expr::Cast {
+        expr: Box::new(expr),
+        data_type: ast::DataType::Text,
+        format: None,
+    }
+}
+
+// wraps a single table, with its version qualifier if any, into a FROM clause item
+pub(crate) fn table_with_joins(
+    name: ast::ObjectName,
+    version: Option<ast::TableVersion>,
+) -> ast::TableWithJoins {
+    ast::TableWithJoins {
+        relation: ast::TableFactor::Table {
+            name,
+            alias: None,
+            args: None,
+            with_hints: Vec::default(),
+            version,
+            partitions: Vec::default(),
+        },
+        joins: Vec::default(),
+    }
+}
+
+// replaces the name of the table of a FROM clause, keeping its alias, version and hints
+pub(crate) fn rename_table(
+    from: &[ast::TableWithJoins],
+    table_name: ast::ObjectName,
+) -> Vec<ast::TableWithJoins> {
+    let mut from = from.to_vec();
+    if let Some(ast::TableFactor::Table { name, .. }) =
+        from.first_mut().map(|table| &mut table.relation)
+    {
+        *name = table_name;
+    }
+    from
+}
+
+// builds a plain `SELECT projection FROM from [WHERE selection]` query
+pub(crate) fn select_query(
+    projection: Vec<ast::SelectItem>,
+    from: Vec<ast::TableWithJoins>,
+    selection: Option<ast::Expr>,
+) -> ast::Query {
+    let select_expr = ast::Select {
+        distinct: None,
+        top: None,
+        projection,
+        into: None,
+        from,
+        lateral_views: Vec::default(),
+        selection,
+        group_by: ast::GroupByExpr::Expressions(Vec::default()),
+        cluster_by: Vec::default(),
+        distribute_by: Vec::default(),
+        sort_by: Vec::default(),
+        having: None,
+        qualify: None,
+        named_window: Vec::default(),
+    };
+    ast::Query {
+        with: None,
+        body: Box::new(ast::SetExpr::Select(Box::new(select_expr))),
+        order_by: Vec::default(),
+        limit: None,
+        offset: None,
+        fetch: None,
+        locks: Vec::default(),
+        limit_by: Vec::default(),
+        for_clause: None,
+    }
+}
+
+const INDENT: &str = "    ";
+
+raw_ast! {
+    /// Renders `query` as SQL, either on a single line or, if `pretty` is set, with each clause on
+    /// its own line and its items indented.
+    #[must_use]
+    pub fn format_query(query: &ast::Query, pretty: bool) -> String {
+        if pretty {
+            pretty_print(query)
+        } else {
+            query.to_string()
+        }
+    }
+}
+
+raw_ast! {
+    /// Renders `query` as SQL with each clause on its own line and its items indented.
+    ///
+    /// Only plain `SELECT` queries are laid out, anything else is rendered on a single line.
+    #[must_use]
+    pub fn pretty_print(query: &ast::Query) -> String {
+        let ast::SetExpr::Select(select) = query.body.as_ref() else {
+            return query.to_string();
+        };
+        let ast::GroupByExpr::Expressions(group_by) = &select.group_by else {
+            return query.to_string();
+        };
+        let is_plain = query.with.is_none()
+            && query.fetch.is_none()
+            && query.locks.is_empty()
+            && query.limit_by.is_empty()
+            && query.for_clause.is_none()
+            && select.distinct.is_none()
+            && select.top.is_none()
+            && select.into.is_none()
+            && select.lateral_views.is_empty()
+            && select.cluster_by.is_empty()
+            && select.distribute_by.is_empty()
+            && select.sort_by.is_empty()
+            && select.qualify.is_none()
+            && select.named_window.is_empty();
+        if !is_plain {
+            return query.to_string();
+        }
+
+        let mut sql = String::new();
+        push_clause(&mut sql, "SELECT", &select.projection);
+        push_clause(&mut sql, "FROM", &select.from);
+        push_clause(&mut sql, "WHERE", select.selection.as_slice());
+        push_clause(&mut sql, "GROUP BY", group_by);
+        push_clause(&mut sql, "HAVING", select.having.as_slice());
+        push_clause(&mut sql, "ORDER BY", &query.order_by);
+        push_clause(&mut sql, "LIMIT", query.limit.as_slice());
+        let offset = query
+            .offset
+            .as_ref()
+            .map(|offset| format!("{}{}", offset.value, offset.rows));
+        push_clause(&mut sql, "OFFSET", offset.as_slice());
+        sql
+    }
+}
+
+// an expression of a SELECT clause, displayed as `expr [AS alias]`, cast to text if `cast`
+pub(crate) struct SelectedExpr<'a> {
+    pub expr: &'a ast::Expr,
+    pub alias: Option<&'a ast::Ident>,
+    pub cast: bool,
+}
+
+impl Display for SelectedExpr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cast {
+            write!(f, "CAST({} AS {})", self.expr, ast::DataType::Text)?;
+        } else {
+            write!(f, "{}", self.expr)?;
+        }
+        if let Some(alias) = self.alias {
+            write!(f, " AS {alias}")?;
+        }
+        Ok(())
+    }
+}
+
+// renders `SELECT items FROM from [WHERE selection] [GROUP BY group_by]` as the query built from
+// these clauses would be (see `format_query`), without building it
+pub(crate) fn render_select(
+    items: &[SelectedExpr<'_>],
+    from: &[ast::TableWithJoins],
+    selection: Option<&ast::Expr>,
+    group_by: &[ast::Expr],
+    pretty: bool,
+) -> String {
+    fn join<T: Display>(items: &[T]) -> String {
+        let items = items.iter().map(ToString::to_string);
+        items.collect::<Vec<_>>().join(", ")
+    }
+
+    let mut sql = String::new();
+    if pretty {
+        push_clause(&mut sql, "SELECT", items);
+        push_clause(&mut sql, "FROM", from);
+        push_clause(&mut sql, "WHERE", selection.as_slice());
+        push_clause(&mut sql, "GROUP BY", group_by);
+        return sql;
+    }
+    sql.push_str("SELECT ");
+    sql.push_str(&join(items));
+    if !from.is_empty() {
+        sql.push_str(" FROM ");
+        sql.push_str(&join(from));
+    }
+    if let Some(selection) = selection {
+        sql.push_str(" WHERE ");
+        sql.push_str(&selection.to_string());
+    }
+    if !group_by.is_empty() {
+        sql.push_str(" GROUP BY ");
+        sql.push_str(&join(group_by));
+    }
+    sql
+}
+
+raw_ast! {
+    /// Parses the statements of `sql` in `dialect`, as the queries are before their metadata is
+    /// extracted (e.g. a leading byte order mark and `#` comments in MySQL are accepted).
+    pub fn parse_statements(
+        sql: &str,
+        dialect: Dialect,
+    ) -> Result<Vec<ast::Statement>, ParseError> {
+        parse_statements_within(sql, dialect, Limits::unlimited())
+    }
+}
+
+// parses the statements of `sql` like `parse_statements`, failing fast if the query exceeds
+// `limits`; the recursion limit of the SQL parser is reported as a limit too
+pub(crate) fn parse_statements_within(
+    sql: &str,
+    dialect: Dialect,
+    limits: Limits,
+) -> Result<Vec<ast::Statement>, ParseError> {
+    let sql = normalize_query(sql, dialect);
+    limits.check(&sql, dialect)?;
+    let sql = rewrite_unicode_strings(&sql, dialect)?;
+    Parser::parse_sql(dialect.parser_dialect().as_ref(), &sql).map_err(|error| match error {
+        ParserError::RecursionLimitExceeded => limit_exceeded!(
+            ErrorCode::ExpressionTooDeep,
+            "the query is nested too deep to be parsed.".to_string()
+        ),
+        error => ParseError::syntax(&error),
+    })
+}
+
+// rewrites the cosmetic content the SQL parser doesn't handle, keeping the positions of the
+// tokens: a leading byte order mark is replaced by a space, and the `#` line comments of MySQL
+// and BigQuery are turned into `--` comments (which only shifts the rest of their line)
+pub(crate) fn normalize_query(sql: &str, dialect: Dialect) -> Cow<'_, str> {
+    let sql = sql
+        .strip_prefix('\u{feff}')
+        .map_or(Cow::Borrowed(sql), |sql| Cow::Owned(format!(" {sql}")));
+    if !matches!(dialect, Dialect::MySql | Dialect::BigQuery) || !sql.contains('#') {
+        return sql;
+    }
+    let mut normalized = String::with_capacity(sql.len() + 1);
+    let mut chars = sql.chars().peekable();
+    let mut scanning = Scanning::Code;
+    while let Some(c) = chars.next() {
+        normalized.push(c);
+        match (scanning, c) {
+            (Scanning::Code, '\'' | '"' | '`') => scanning = Scanning::Quoted(c),
+            (Scanning::Code, '-') if chars.peek() == Some(&'-') => {
+                scanning = Scanning::LineComment;
+            }
+            (Scanning::Code, '/') if chars.peek() == Some(&'*') => {
+                normalized.extend(chars.next());
+                scanning = Scanning::BlockComment;
+            }
+            (Scanning::Code, '#') => {
+                normalized.pop();
+                normalized.push_str("--");
+                scanning = Scanning::LineComment;
+            }
+            //both dialects escape quotes with backslashes in strings
+            (Scanning::Quoted('\'' | '"'), '\\') => normalized.extend(chars.next()),
+            (Scanning::Quoted(quote), _) if c == quote => scanning = Scanning::Code,
+            (Scanning::LineComment, '\n') => scanning = Scanning::Code,
+            (Scanning::BlockComment, '*') if chars.peek() == Some(&'/') => {
+                normalized.extend(chars.next());
+                scanning = Scanning::Code;
+            }
+            _ => (),
+        }
+    }
+    Cow::Owned(normalized)
+}
+
+// replaces the Unicode strings of the dialects that have them (e.g. `U&'d\0061ta'`, with no
+// space before the quote) by the plain strings they stand for, padded with spaces to keep the
+// positions of the tokens: the SQL parser reads them as the bitwise AND of a column `U` and a
+// string, which can't be told apart from a real column `u` (e.g. `u & 'x'`) once parsed
+fn rewrite_unicode_strings(sql: &str, dialect: Dialect) -> Result<Cow<'_, str>, ParseError> {
+    if !matches!(dialect, Dialect::Generic | Dialect::PostgreSql) || !sql.contains("&'") {
+        return Ok(Cow::Borrowed(sql));
+    }
+    let chars = sql.chars().collect::<Vec<_>>();
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut scanning = Scanning::Code;
+    let mut index = 0;
+    let word_char = |c: char| c.is_alphanumeric() || "_$.\"`".contains(c);
+    while let Some(&c) = chars.get(index) {
+        let previous = index.checked_sub(1).map(|previous| chars[previous]);
+        let after_word = previous.is_some_and(word_char);
+        match (scanning, c) {
+            (Scanning::Code, 'u' | 'U')
+                if !after_word && chars.get(index + 1..index + 3) == Some(&['&', '\'']) =>
+            {
+                //the content, with its doubled quotes unescaped
+                let mut escaped = String::new();
+                let mut end = index + 3;
+                loop {
+                    match (chars.get(end), chars.get(end + 1)) {
+                        (Some('\''), Some('\'')) => {
+                            escaped.push('\'');
+                            end += 2;
+                        }
+                        (Some('\''), _) => break,
+                        (Some(&c), _) => {
+                            escaped.push(c);
+                            end += 1;
+                        }
+                        //left to the SQL parser, which reports the unterminated string
+                        (None, _) => {
+                            rewritten.extend(&chars[index..]);
+                            return Ok(Cow::Owned(rewritten));
+                        }
+                    }
+                }
+                let literal = escape_literal(&unescape_unicode_literal(&escaped)?, dialect)?;
+                let padding = (end + 1 - index).saturating_sub(literal.chars().count());
+                rewritten.push_str(&literal);
+                rewritten.extend(std::iter::repeat_n(' ', padding));
+                index = end + 1;
+                continue;
+            }
+            //backslashes escape the quotes of the PostgreSQL escape strings (`E'...'`)
+            (Scanning::Code, '\'') => {
+                let escape = matches!(previous, Some('e' | 'E'))
+                    && index
+                        .checked_sub(2)
+                        .is_none_or(|before| !word_char(chars[before]));
+                scanning = Scanning::Quoted(if escape { '\\' } else { '\'' });
+            }
+            (Scanning::Code, '"') => scanning = Scanning::Quoted('"'),
+            (Scanning::Code, '-') if chars.get(index + 1) == Some(&'-') => {
+                scanning = Scanning::LineComment;
+            }
+            (Scanning::Code, '/') if chars.get(index + 1) == Some(&'*') => {
+                rewritten.push_str("/*");
+                index += 2;
+                scanning = Scanning::BlockComment;
+                continue;
+            }
+            (Scanning::Quoted('\\'), '\\') => {
+                rewritten.push(c);
+                rewritten.extend(chars.get(index + 1));
+                index += 2;
+                continue;
+            }
+            (Scanning::Quoted('\\'), '\'') | (Scanning::LineComment, '\n') => {
+                scanning = Scanning::Code;
+            }
+            (Scanning::Quoted(quote), _) if c == quote => scanning = Scanning::Code,
+            (Scanning::BlockComment, '*') if chars.get(index + 1) == Some(&'/') => {
+                rewritten.push_str("*/");
+                index += 2;
+                scanning = Scanning::Code;
+                continue;
+            }
+            _ => (),
+        }
+        rewritten.push(c);
+        index += 1;
+    }
+    Ok(Cow::Owned(rewritten))
+}
+
+#[derive(Clone, Copy)]
+enum Scanning {
+    Code,
+    Quoted(char),
+    LineComment,
+    BlockComment,
+}
+
+/// Renders `value` as a string literal of `dialect`, e.g. `'it''s'`, so that it can be embedded
+/// in SQL without changing the meaning of the query.
+///
+/// Quotes are doubled, and so are backslashes in the dialects that treat them as escapes in
+/// strings (MySQL, Snowflake, BigQuery). Values with control characters other than tabs and line
+/// breaks (e.g. NUL) are rejected with [`ParseError::MalformedQuery`], since they're cut or
+/// mangled by some drivers.
+pub fn escape_literal(value: &str, dialect: Dialect) -> Result<String, ParseError> {
+    check_literal(value)?;
+    Ok(quote_literal(value, dialect))
+}
+
+// rejects the string values with control characters other than tabs and line breaks
+pub(crate) fn check_literal(value: &str) -> Result<(), ParseError> {
+    let control = value
+        .chars()
+        .find(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'));
+    control.map_or(Ok(()), |c| {
+        Err(malformed_query!(
+            ErrorCode::InvalidLiteral,
+            format!(
+                "the string {value:?} contains the control character U+{:04X}.",
+                u32::from(c)
+            )
+        ))
+    })
+}
+
+// quotes and escapes `value` as a string literal of `dialect`, without checking its characters:
+// every rendering of a value goes through it (see `escape_literal`)
+pub(crate) fn quote_literal(value: &str, dialect: Dialect) -> String {
+    let escape_backslashes = matches!(
+        dialect,
+        Dialect::MySql | Dialect::Snowflake | Dialect::BigQuery
+    );
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('\'');
+    for c in value.chars() {
+        match c {
+            '\'' => literal.push_str("''"),
+            '\\' if escape_backslashes => literal.push_str("\\\\"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('\'');
+    literal
+}
+
+// unescapes the content of a Unicode string literal (`U&'...'`): `\XXXX` and `\+XXXXXX` stand for
+// the code points with these hexadecimal digits, and `\\` for a backslash
+pub(crate) fn unescape_unicode_literal(escaped: &str) -> Result<String, ParseError> {
+    let invalid = || {
+        malformed_query!(
+            ErrorCode::InvalidLiteral,
+            format!("invalid escape sequence in the Unicode string U&'{escaped}'.")
+        )
+    };
+    let mut value = String::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(index) = rest.find('\\') {
+        value.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        if let Some(after) = rest.strip_prefix('\\') {
+            value.push('\\');
+            rest = after;
+            continue;
+        }
+        let (digits, length) = rest.strip_prefix('+').map_or((rest, 4), |after| (after, 6));
+        let after = digits.get(length..);
+        let digits = digits.get(..length);
+        let c = digits
+            .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(invalid)?;
+        value.push(c);
+        rest = after.ok_or_else(invalid)?;
+    }
+    value.push_str(rest);
+    Ok(value)
+}
+
+// lowercases the keywords and the unquoted identifiers of a generated query, leaving the quoted
+// identifiers and the literals as they are
+pub(crate) fn lowercase_keywords(sql: &str) -> String {
+    let Ok(tokens) = Tokenizer::new(&GenericDialect {}, sql).tokenize() else {
+        return sql.to_string();
+    };
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            Token::Word(word) => match word.quote_style {
+                None => word.value.to_ascii_lowercase(),
+                Some(quote) => ast::Ident::with_quote(quote, word.value).to_string(),
+            },
+            //the tokenizer unescapes the literals, the AST escapes them back
+            Token::SingleQuotedString(value) => ast::Value::SingleQuotedString(value).to_string(),
+            token => token.to_string(),
+        })
+        .collect()
+}
+
+// 64-bit FNV-1a hash: unlike `DefaultHasher`, it's stable across Rust releases and platforms
+pub(crate) fn stable_hash(data: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    data.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+// appends `keyword` followed by its items, one per indented line; nothing if there are no items
+fn push_clause<T: std::fmt::Display>(sql: &mut String, keyword: &str, items: &[T]) {
+    if items.is_empty() {
+        return;
+    }
+    if !sql.is_empty() {
+        sql.push('\n');
+    }
+    sql.push_str(keyword);
+    let items = items
+        .iter()
+        .map(|item| format!("{INDENT}{item}"))
+        .collect::<Vec<String>>()
+        .join(",\n");
+    sql.push('\n');
+    sql.push_str(&items);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_literal, rewrite_unicode_strings, unescape_unicode_literal};
+    use crate::{dialect::Dialect, error::ErrorCode};
+
+    #[test]
+    fn escape_literals() {
+        let all = [
+            Dialect::Generic,
+            Dialect::PostgreSql,
+            Dialect::MySql,
+            Dialect::Snowflake,
+            Dialect::BigQuery,
+        ];
+        for dialect in all {
+            let escape = |value| escape_literal(value, dialect).unwrap();
+            assert_eq!(escape(""), "''");
+            assert_eq!(escape("abc"), "'abc'");
+            assert_eq!(escape("it's"), "'it''s'");
+            assert_eq!(escape("''"), "''''''");
+            assert_eq!(escape("x'; DROP TABLE t; --"), "'x''; DROP TABLE t; --'");
+            assert_eq!(escape("a\tb\r\nc"), "'a\tb\r\nc'");
+            assert_eq!(escape("\"q\" `b` %_ é"), "'\"q\" `b` %_ é'");
+            for value in ["\0", "a\0b", "\u{1a}", "\u{7f}", "\u{85}", "\u{8}"] {
+                assert_eq!(
+                    escape_literal(value, dialect).map_err(|error| error.code()),
+                    Err(ErrorCode::InvalidLiteral),
+                    "{value:?} in {dialect:?}"
+                );
+            }
+        }
+        for dialect in [Dialect::Generic, Dialect::PostgreSql] {
+            let escape = |value| escape_literal(value, dialect).unwrap();
+            assert_eq!(escape("a\\b"), "'a\\b'");
+            assert_eq!(escape("\\' OR 1=1 --"), "'\\'' OR 1=1 --'");
+        }
+        for dialect in [Dialect::MySql, Dialect::Snowflake, Dialect::BigQuery] {
+            let escape = |value| escape_literal(value, dialect).unwrap();
+            assert_eq!(escape("a\\b"), "'a\\\\b'");
+            //an escaped quote can't close the literal
+            assert_eq!(escape("\\' OR 1=1 --"), "'\\\\'' OR 1=1 --'");
+        }
+    }
+
+    #[test]
+    fn rewrite_unicode_strings_in_place() {
+        let rewrite =
+            |sql, dialect| rewrite_unicode_strings(sql, dialect).map_err(|error| error.code());
+        assert_eq!(
+            rewrite(r"a = U&'d\0061ta' AND b", Dialect::PostgreSql).as_deref(),
+            Ok("a = 'data'       AND b")
+        );
+        assert_eq!(
+            rewrite(r"a = u&'it''s' AND E'\'U&''' = U&'\0041'", Dialect::Generic).as_deref(),
+            Ok(r"a = 'it''s'   AND E'\'U&''' = 'A'      ")
+        );
+        for unchanged in [r"a = u & 'x'", r"a = au&'x'", r"/* U&'\0' */ a"] {
+            assert_eq!(
+                rewrite(unchanged, Dialect::PostgreSql).as_deref(),
+                Ok(unchanged)
+            );
+        }
+        assert_eq!(
+            rewrite(r"a = U&'\0041'", Dialect::MySql).as_deref(),
+            Ok(r"a = U&'\0041'")
+        );
+        assert_eq!(
+            rewrite(r"a = U&'\00'", Dialect::PostgreSql),
+            Err(ErrorCode::InvalidLiteral)
+        );
+    }
+
+    #[test]
+    fn unescape_unicode_literals() {
+        let unescape = |escaped| unescape_unicode_literal(escaped).map_err(|error| error.code());
+        assert_eq!(unescape(r"d\0061t\+000061"), Ok("data".to_string()));
+        assert_eq!(unescape("plain"), Ok("plain".to_string()));
+        assert_eq!(unescape(r"\00e9t\00E9"), Ok("été".to_string()));
+        assert_eq!(unescape(r"\+01F600!"), Ok("\u{1f600}!".to_string()));
+        assert_eq!(unescape(r"a\\b"), Ok(r"a\b".to_string()));
+        assert_eq!(unescape(r"\\0061"), Ok(r"\0061".to_string()));
+        for escaped in [
+            r"\",
+            r"\006",
+            r"\00g1",
+            r"\+0061",
+            r"\D800",
+            r"\+110000",
+            r"\x",
+        ] {
+            assert_eq!(
+                unescape(escaped),
+                Err(ErrorCode::InvalidLiteral),
+                "{escaped}"
+            );
+        }
     }
 }
@@ -1,89 +1,248 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
-use crate::error::ParseError;
+use crate::{
+    dialect::{CaseFolding, Dialect, TableQualifier},
+    error::{ErrorCode, ParseError, UnsupportedFeature},
+    policy::FeaturePolicy,
+};
 use serde::{Deserialize, Serialize};
-use sqlparser::ast;
+use sqlparser::{ast, parser::Parser, tokenizer::Token};
 use utoipa::{IntoParams, ToSchema};
 
-use super::{internal, unsupported};
+use super::{internal, malformed_query, unsupported};
 
-use super::support::case_fold_identifier;
+use super::{identifier::Identifier, support::case_fold_identifier};
 
-pub(crate) struct TableIdentWithAlias(pub TabIdent, pub Option<String>);
+/// The table of the FROM clause of a query, with its alias if any.
+pub struct TableIdentWithAlias(pub TabIdent, pub Option<String>);
 
 impl TableIdentWithAlias {
-    pub(crate) fn extract(from: &[ast::TableWithJoins]) -> Result<Self, ParseError> {
-        let multi_tables = || {
-            Err(unsupported!("the FROM clause has multiple tables \
-                         (no JOINs, subqueries or functions allowed)."
-                .to_string()))
-        };
+    raw_ast! {
+        /// Extracts the table from the FROM clause, which must list a single table: no JOINs,
+        /// subqueries or table functions, no table hints or partitions, and no alias columns.
+        /// The names are case folded with `case_folding`, and a two-part name is read as
+        /// `schema.table`.
+        ///
+        /// The version qualifier of the table is extracted by [`TableVersion::extract`].
+        pub fn extract(
+            from: &[ast::TableWithJoins],
+            case_folding: CaseFolding,
+        ) -> Result<Self, ParseError> {
+            Self::extract_with_policy(
+                from,
+                case_folding,
+                TableQualifier::Schema,
+                FeaturePolicy::default(),
+            )
+        }
+    }
 
-        let relation = match from {
-            [ast::TableWithJoins { relation, joins }] if joins.is_empty() => relation,
-            _ => return multi_tables(),
-        };
+    raw_ast! {
+        /// Like [`Self::extract`], but the first part of a two-part name is read as
+        /// `table_qualifier` says, and the table hints are accepted if `policy` allows them (see
+        /// [`table_hints`]).
+        pub fn extract_with_policy(
+            from: &[ast::TableWithJoins],
+            case_folding: CaseFolding,
+            table_qualifier: TableQualifier,
+            policy: FeaturePolicy,
+        ) -> Result<Self, ParseError> {
+            let multi_tables = |feature| {
+                Err(unsupported!(
+                    feature,
+                    "the FROM clause has multiple tables \
+                             (no JOINs, subqueries or functions allowed)."
+                        .to_string()
+                ))
+            };
 
-        match relation {
-            ast::TableFactor::Table {
-                name,
-                alias,
-                args,
-                with_hints,
-                version,
-                partitions,
-            } => {
-                if args.is_some() {
-                    return multi_tables();
-                }
-                if !with_hints.is_empty() {
-                    return Err(unsupported!(
-                        "table hints (WITH in FROM clauses).".to_string()
-                    ));
+            let relation = match from {
+                [ast::TableWithJoins { relation, joins }] if joins.is_empty() => relation,
+                _ => return multi_tables(UnsupportedFeature::Join),
+            };
+
+            match relation {
+                ast::TableFactor::Table {
+                    name,
+                    alias,
+                    args,
+                    with_hints,
+                    version: _,
+                    partitions,
+                } => {
+                    if args.is_some() {
+                        return multi_tables(UnsupportedFeature::TableFunction);
+                    }
+                    if !with_hints.is_empty() && !policy.allow_table_hints {
+                        return Err(unsupported!(
+                            UnsupportedFeature::TableHints,
+                            "table hints (WITH in FROM clauses).".to_string()
+                        ));
+                    }
+                    if !partitions.is_empty() {
+                        return Err(unsupported!(
+                            UnsupportedFeature::Partitions,
+                            "table partitions.".to_string()
+                        ));
+                    }
+                    let table = TabIdent::from_object_name(name, case_folding, table_qualifier)?;
+                    let alias = alias
+                        .as_ref()
+                        .map(|alias| {
+                            let ast::TableAlias { name, columns } = alias;
+                            if columns.is_empty() {
+                                Ok(case_fold_identifier(name, case_folding))
+                            } else {
+                                Err(unsupported!(
+                                    UnsupportedFeature::AliasColumns,
+                                    format!("table aliases with columns (such as {alias}).")
+                                ))
+                            }
+                        })
+                        .transpose()?;
+                    Ok(Self(table, alias))
                 }
-                if version.is_some() {
-                    return Err(unsupported!("version qualifier.".to_string()));
+                ast::TableFactor::Derived { .. } => multi_tables(UnsupportedFeature::Subquery),
+                ast::TableFactor::TableFunction { .. }
+                | ast::TableFactor::UNNEST { .. }
+                | ast::TableFactor::Function { .. }
+                | ast::TableFactor::JsonTable { .. } => {
+                    multi_tables(UnsupportedFeature::TableFunction)
                 }
-                if !partitions.is_empty() {
-                    return Err(unsupported!("table partitions.".to_string()));
+                ast::TableFactor::NestedJoin { .. } => multi_tables(UnsupportedFeature::Join),
+                ast::TableFactor::Pivot { .. } | ast::TableFactor::Unpivot { .. } => {
+                    multi_tables(UnsupportedFeature::Pivot)
                 }
-                let table = TabIdent::from_object_name(name)?;
-                let alias = alias
-                    .as_ref()
-                    .map(|alias| {
-                        let ast::TableAlias { name, columns } = alias;
-                        if columns.is_empty() {
-                            Ok(case_fold_identifier(name))
-                        } else {
-                            Err(unsupported!(format!(
-                                "table aliases with columns (such as {alias})."
-                            )))
-                        }
-                    })
-                    .transpose()?;
-                Ok(Self(table, alias))
             }
-            ast::TableFactor::Derived { .. }
-            | ast::TableFactor::TableFunction { .. }
-            | ast::TableFactor::UNNEST { .. }
-            | ast::TableFactor::NestedJoin { .. }
-            | ast::TableFactor::Pivot { .. }
-            | ast::TableFactor::Function { .. }
-            | ast::TableFactor::JsonTable { .. }
-            | ast::TableFactor::Unpivot { .. } => multi_tables(),
         }
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize, Default, ToSchema, IntoParams)]
+raw_ast! {
+    /// The hints of the table of the FROM clause (e.g. `NOLOCK` for `WITH (NOLOCK)`), as written.
+    #[must_use]
+    pub fn table_hints(from: &[ast::TableWithJoins]) -> Vec<String> {
+        match from {
+            [ast::TableWithJoins {
+                relation: ast::TableFactor::Table { with_hints, .. },
+                ..
+            }] => with_hints.iter().map(ToString::to_string).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A version qualifier of the table: the query reads the table as it was at a point in time,
+/// e.g. `FROM t FOR SYSTEM_TIME AS OF TIMESTAMP '2024-01-01 00:00:00'`.
+///
+/// It's parsed in the dialects that support temporal tables (see
+/// [`Dialect::supports_table_versions`]), and rendered back in the queries generated for them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct TableVersion {
+    /// The point in time, as written in the timestamp literal.
+    pub as_of: String,
+}
+
+impl TableVersion {
+    raw_ast! {
+        /// Extracts the version qualifier of the table of the FROM clause, if any: only a timestamp
+        /// (or string) literal is supported as the point in time.
+        pub fn extract(from: &[ast::TableWithJoins]) -> Result<Option<Self>, ParseError> {
+            let [ast::TableWithJoins {
+                relation:
+                    ast::TableFactor::Table {
+                        version: Some(ast::TableVersion::ForSystemTimeAsOf(as_of)),
+                        ..
+                    },
+                ..
+            }] = from
+            else {
+                return Ok(None);
+            };
+            match as_of {
+                ast::Expr::Value(ast::Value::SingleQuotedString(value))
+                | ast::Expr::TypedString {
+                    data_type: ast::DataType::Timestamp(..),
+                    value,
+                } => Ok(Some(Self {
+                    as_of: value.clone(),
+                })),
+                _ => Err(unsupported!(
+                    UnsupportedFeature::TableVersion,
+                    format!("the version qualifier AS OF {as_of} (only timestamp literals).")
+                )),
+            }
+        }
+    }
+
+    // rebuilds `FOR SYSTEM_TIME AS OF TIMESTAMP 'as_of'`
+    pub(crate) fn to_ast(&self) -> ast::TableVersion {
+        ast::TableVersion::ForSystemTimeAsOf(ast::Expr::TypedString {
+            data_type: ast::DataType::Timestamp(None, ast::TimezoneInfo::None),
+            value: self.as_of.clone(),
+        })
+    }
+}
+
+/// Maps the table of a query to the one the derived queries read.
+///
+/// E.g. it can route the queries of a tenant by rewriting `analytics.events` to
+/// `tenant_42.analytics.events` (see [`crate::config::ParseOptions::with_table_rewriter`]).
+pub type TableRewriter = fn(&TabIdent) -> TabIdent;
+
+#[derive(
+    PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize, Default, ToSchema, IntoParams,
+)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
 pub struct TabIdent {
-    pub db: Option<String>,
-    pub schema: Option<String>,
-    pub table: String,
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
+    pub db: Option<Identifier>,
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
+    pub schema: Option<Identifier>,
+    #[schema(value_type = String)]
+    #[param(value_type = String)]
+    pub table: Identifier,
 }
 
 impl TabIdent {
-    fn from_object_name(object_name: &ast::ObjectName) -> Result<Self, ParseError> {
+    /// Parses a table name written as in the queries of `dialect`, e.g. `db.schema.table`
+    /// (quoted parts may contain dots): the unquoted parts are case folded like in the queries, and
+    /// the first part of a two-part name is a database or a schema depending on the dialect (see
+    /// [`Dialect::table_qualifier`]).
+    pub fn parse(name: &str, dialect: Dialect) -> Result<Self, ParseError> {
+        let parser_dialect = dialect.parser_dialect();
+        let object_name = Parser::new(parser_dialect.as_ref())
+            .try_with_sql(name)
+            .and_then(|mut parser| {
+                let object_name = parser.parse_object_name()?;
+                parser.expect_token(&Token::EOF)?;
+                Ok(object_name)
+            })
+            .map_err(|error| ParseError::syntax(&error))?;
+        if object_name.0.len() > 3 {
+            return Err(malformed_query!(
+                ErrorCode::Syntax,
+                format!("the table name {object_name} has more than 3 parts.")
+            ));
+        }
+        Self::from_object_name(
+            &object_name,
+            dialect.case_folding(),
+            dialect.table_qualifier(),
+        )
+    }
+
+    fn from_object_name(
+        object_name: &ast::ObjectName,
+        case_folding: CaseFolding,
+        table_qualifier: TableQualifier,
+    ) -> Result<Self, ParseError> {
         let ast::ObjectName(name_parts) = object_name;
         match &name_parts[..] {
             [] => Err(internal!(
@@ -92,17 +251,28 @@ impl TabIdent {
             [table] => Ok(Self {
                 db: None,
                 schema: None,
-                table: case_fold_identifier(table),
-            }),
-            [schema, table] => Ok(Self {
-                db: None,
-                schema: Some(case_fold_identifier(schema)),
-                table: case_fold_identifier(table),
+                table: Identifier::fold(table, case_folding),
             }),
+            [qualifier, table] => {
+                let qualifier = Some(Identifier::fold(qualifier, case_folding));
+                let table = Identifier::fold(table, case_folding);
+                Ok(match table_qualifier {
+                    TableQualifier::Schema => Self {
+                        db: None,
+                        schema: qualifier,
+                        table,
+                    },
+                    TableQualifier::Database => Self {
+                        db: qualifier,
+                        schema: None,
+                        table,
+                    },
+                })
+            }
             [db, schema, table] => Ok(Self {
-                db: Some(case_fold_identifier(db)),
-                schema: Some(case_fold_identifier(schema)),
-                table: case_fold_identifier(table),
+                db: Some(Identifier::fold(db, case_folding)),
+                schema: Some(Identifier::fold(schema, case_folding)),
+                table: Identifier::fold(table, case_folding),
             }),
             [..] => Err(internal!(format!(
                 "found too many ident in table name (i.e., {object_name}) in query AST."
@@ -110,26 +280,67 @@ impl TabIdent {
         }
     }
 
-    #[must_use]
-    pub fn into_object_name(&self, quote_style: Option<char>) -> ast::ObjectName {
-        let mut objects = vec![];
-        if let Some(db) = self.db.clone() {
-            objects.push(ast::Ident {
-                value: db,
-                quote_style,
-            });
-        }
-        if let Some(schema) = self.schema.clone() {
-            objects.push(ast::Ident {
-                value: schema,
-                quote_style,
-            });
+    raw_ast! {
+        /// Builds the object name of the table from the stored names: every part is quoted with
+        /// `quote_style` if given, otherwise each part is quoted as it was in the query (e.g.
+        /// `"My Schema".t1`), and the reserved keywords of `dialect` are quoted with its quote
+        /// character (see [`Dialect::is_reserved_keyword`]).
+        #[must_use]
+        #[allow(clippy::wrong_self_convention)]
+        pub fn into_object_name(
+            &self,
+            dialect: Dialect,
+            quote_style: Option<char>,
+        ) -> ast::ObjectName {
+            let parts = [self.db.as_ref(), self.schema.as_ref(), Some(&self.table)];
+            ast::ObjectName(
+                parts
+                    .into_iter()
+                    .flatten()
+                    .map(|part| part.to_ident(dialect, quote_style))
+                    .collect(),
+            )
         }
-        objects.push(ast::Ident {
-            value: self.table.clone(),
-            quote_style,
-        });
-        ast::ObjectName(objects)
+    }
+
+    /// Renders the name of the table so that it's parsed back as is in `dialect`: the parts are
+    /// quoted only if needed (e.g. mixed-case names in a dialect that folds them, or keywords).
+    #[must_use]
+    pub fn render(&self, dialect: Dialect) -> String {
+        self.render_object_name(dialect, None).to_string()
+    }
+
+    // builds the object name, quoting only the parts that need it in the given dialect
+    pub(crate) fn render_object_name(
+        &self,
+        dialect: Dialect,
+        quote_style: Option<char>,
+    ) -> ast::ObjectName {
+        let parts = [self.db.as_ref(), self.schema.as_ref(), Some(&self.table)];
+        ast::ObjectName(
+            parts
+                .into_iter()
+                .flatten()
+                .map(|part| part.render(dialect, quote_style))
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for TabIdent {
+    type Err = ParseError;
+
+    /// Parses a table name as in the queries of the generic dialect (see [`TabIdent::parse`]).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, Dialect::default())
+    }
+}
+
+impl TryFrom<&str> for TabIdent {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
 
@@ -151,3 +362,98 @@ impl Display for TabIdent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TabIdent;
+    use crate::{dialect::Dialect, error::ErrorCode};
+
+    #[test]
+    fn parse() {
+        let tab_ident = |db: Option<&str>, schema: Option<&str>, table: &str| TabIdent {
+            db: db.map(Into::into),
+            schema: schema.map(Into::into),
+            table: table.into(),
+        };
+        assert_eq!(
+            "Test_Db.test_schema.test_table_1".parse(),
+            Ok(tab_ident(
+                Some("test_db"),
+                Some("test_schema"),
+                "test_table_1"
+            ))
+        );
+        assert_eq!(
+            TabIdent::try_from("\"my.schema\".\"Table\""),
+            Ok(tab_ident(None, Some("my.schema"), "Table"))
+        );
+        assert_eq!(
+            TabIdent::parse("test_schema.Test_Table", Dialect::Snowflake),
+            Ok(tab_ident(None, Some("TEST_SCHEMA"), "TEST_TABLE"))
+        );
+        assert_eq!(
+            TabIdent::parse("`my-db`.Test_Table", Dialect::BigQuery),
+            Ok(tab_ident(Some("my-db"), None, "Test_Table"))
+        );
+
+        for name in ["", "a.b.c.d", "a b", "a.", "\"a"] {
+            assert_eq!(
+                name.parse::<TabIdent>().map_err(|error| error.code()),
+                Err(ErrorCode::Syntax),
+                "\nfailed for {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn render() {
+        let table = TabIdent {
+            db: Some("test_db".into()),
+            schema: Some("My Schema".into()),
+            table: "Order".into(),
+        };
+        assert_eq!(
+            table.render(Dialect::Generic),
+            "test_db.\"My Schema\".\"Order\""
+        );
+        assert_eq!(table.render(Dialect::MySql), "test_db.`My Schema`.`Order`");
+        assert_eq!(
+            table.render(Dialect::Snowflake),
+            "\"test_db\".\"My Schema\".\"Order\""
+        );
+        let table = TabIdent {
+            db: None,
+            schema: None,
+            table: "order".into(),
+        };
+        assert_eq!(table.render(Dialect::PostgreSql), "\"order\"");
+        assert_eq!(table.render(Dialect::BigQuery), "`order`");
+
+        //the rendered names are parsed back as is
+        for dialect in [Dialect::Generic, Dialect::Snowflake, Dialect::BigQuery] {
+            assert_eq!(
+                TabIdent::parse(&table.render(dialect), dialect),
+                Ok(table.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn object_name_quotes() {
+        //the quotes in the names are doubled
+        let table = TabIdent::parse("s.\"say \"\"hi\"\"\"", Dialect::Generic).unwrap();
+        let object_name =
+            |dialect, quote_style| table.into_object_name(dialect, quote_style).to_string();
+        assert_eq!(object_name(Dialect::Generic, None), "s.\"say \"\"hi\"\"\"");
+        assert_eq!(object_name(Dialect::MySql, Some('`')), "`s`.`say \"hi\"`");
+        let table = TabIdent {
+            db: None,
+            schema: None,
+            table: "back`tick".into(),
+        };
+        assert_eq!(
+            table.into_object_name(Dialect::MySql, None).to_string(),
+            "`back``tick`"
+        );
+    }
+}
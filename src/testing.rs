@@ -0,0 +1,178 @@
+use crate::{
+    aggregation::{Aggregation, KoronFunction},
+    comparison::{CompareOp, ValueType},
+    config::ParseOptions,
+    dialect::Dialect,
+    filter::Filter,
+    query_metadata::QueryMetadata,
+    table::TabIdent,
+};
+
+// names as they're stored, including ones that have to be quoted to read back the same
+const NAMES: &[&str] = &[
+    "id",
+    "amount",
+    "created_at",
+    "col_2",
+    "Mixed",
+    "with space",
+    "order",
+    "quote\"d",
+    "back`tick",
+    "it's",
+];
+const NUMBERS: &[&str] = &["0", "42", "-7", "3.5", "-0.25"];
+const STRINGS: &[&str] = &["", "a", "it's", "2024-01-01", "x; DROP TABLE t"];
+
+/// Generates arbitrary but valid metadata from a seed (built with the `testing` feature).
+///
+/// E.g. it can property-test that the metadata rendered with [`QueryMetadata::to_sql`] parses
+/// back to the same metadata. The same seed always generates the same values.
+#[derive(Clone, Debug)]
+pub struct MetadataGenerator {
+    state: u64,
+}
+
+impl MetadataGenerator {
+    /// Creates a generator from `seed`.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generates a table name, with or without its database and schema.
+    pub fn tab_ident(&mut self) -> TabIdent {
+        let schema = self.chance(2).then(|| self.name().into());
+        TabIdent {
+            db: schema
+                .as_ref()
+                .filter(|_| self.chance(2))
+                .map(|_| self.name().into()),
+            schema,
+            table: self.name().into(),
+        }
+    }
+
+    /// Generates an aggregation, with or without an alias.
+    pub fn aggregation(&mut self) -> Aggregation {
+        Aggregation {
+            function: *self.pick(KoronFunction::all()),
+            column: self.name().into(),
+            alias: self.chance(3).then(|| self.name()),
+            window: None,
+        }
+    }
+
+    /// Generates a comparison, with a number if it compares the column to a value.
+    pub fn compare_op(&mut self) -> CompareOp {
+        let value = (*self.pick(NUMBERS)).to_string();
+        self.comparison(value)
+    }
+
+    /// Generates a filter, comparing the column to a number, a string or a boolean, or checking
+    /// whether it's NULL, TRUE or FALSE.
+    pub fn filter(&mut self) -> Filter {
+        let column = self.name().into();
+        let (value, value_type) = match self.next() % 3 {
+            0 => ((*self.pick(NUMBERS)).to_string(), ValueType::Number),
+            1 => ((*self.pick(STRINGS)).to_string(), ValueType::String),
+            _ => (self.chance(2).to_string(), ValueType::Boolean),
+        };
+        let comparison = self.comparison(value);
+        let value_type = comparison.value().map(|_| value_type);
+        Filter {
+            column,
+            comparison,
+            value_type,
+            json_path: None,
+            collation: None,
+        }
+    }
+
+    /// Generates the metadata of a query, with or without a filter, as parsed with the default
+    /// options from its SQL.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the generated metadata doesn't parse back, which is a bug of the crate.
+    pub fn query_metadata(&mut self) -> QueryMetadata {
+        let metadata = QueryMetadata {
+            aggregation: self.aggregation(),
+            table: self.tab_ident(),
+            filter: self.chance(2).then(|| self.filter()),
+            ..QueryMetadata::default()
+        };
+        let sql = metadata.to_sql(Dialect::Generic);
+        QueryMetadata::parse_with(&sql, &ParseOptions::new())
+            .unwrap_or_else(|error| panic!("the generated query `{sql}` doesn't parse: {error}"))
+    }
+
+    fn comparison(&mut self, value: String) -> CompareOp {
+        match self.next() % 12 {
+            0 => CompareOp::Lt { value },
+            1 => CompareOp::LtEq { value },
+            2 => CompareOp::Gt { value },
+            3 => CompareOp::GtEq { value },
+            4 => CompareOp::Eq { value },
+            5 => CompareOp::NotEq { value },
+            6 => CompareOp::IsNull,
+            7 => CompareOp::IsNotNull,
+            8 => CompareOp::IsTrue,
+            9 => CompareOp::IsNotTrue,
+            10 => CompareOp::IsFalse,
+            _ => CompareOp::IsNotFalse,
+        }
+    }
+
+    fn name(&mut self) -> String {
+        (*self.pick(NAMES)).to_string()
+    }
+
+    fn pick<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+        let index = usize::try_from(self.next() % values.len() as u64).unwrap_or_default();
+        &values[index]
+    }
+
+    // true once in `n` times
+    const fn chance(&mut self, n: u64) -> bool {
+        self.next().is_multiple_of(n)
+    }
+
+    // the next value of a SplitMix64 sequence
+    const fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetadataGenerator;
+    use crate::{
+        config::ParseOptions,
+        dialect::{Dialect, TableQualifier},
+        query_metadata::QueryMetadata,
+    };
+
+    #[test]
+    fn parse_rendered_metadata() {
+        let mut generator = MetadataGenerator::new(7);
+        for _ in 0..500 {
+            let metadata = generator.query_metadata();
+            for dialect in [Dialect::Generic, Dialect::PostgreSql, Dialect::MySql] {
+                //two-part names are schema-qualified, like in the generic dialect
+                let options = ParseOptions::new()
+                    .with_dialect(dialect)
+                    .with_table_qualifier(TableQualifier::Schema);
+                let parsed = QueryMetadata::parse_with(&metadata.to_sql(dialect), &options);
+                let parsed = parsed.unwrap();
+                assert_eq!(parsed.aggregation, metadata.aggregation);
+                assert_eq!(parsed.table, metadata.table);
+                assert_eq!(parsed.filter, metadata.filter);
+            }
+        }
+    }
+}
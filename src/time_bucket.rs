@@ -0,0 +1,394 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast;
+use utoipa::ToSchema;
+
+use crate::{
+    aggregation::function_call,
+    dialect::{CaseFolding, Dialect},
+    error::{ParseError, UnsupportedFeature},
+    identifier::Identifier,
+    query_metadata::FromClauseIdentifier,
+    support::{extract_qualified_column, remove_outer_parens},
+    unsupported,
+};
+
+/// The unit a [`TimeBucket`] truncates its column to, e.g. `day` in `DATE_TRUNC('day', ts)`.
+///
+/// The weeks start on Monday, as in ISO 8601.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum TimeGranularity {
+    /// The second.
+    Second,
+    /// The minute.
+    Minute,
+    /// The hour.
+    Hour,
+    /// The day.
+    #[default]
+    Day,
+    /// The week, starting on Monday.
+    Week,
+    /// The month.
+    Month,
+    /// The quarter of the year.
+    Quarter,
+    /// The year.
+    Year,
+}
+
+impl TimeGranularity {
+    /// All the granularities, from the finest to the coarsest.
+    #[must_use]
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Second,
+            Self::Minute,
+            Self::Hour,
+            Self::Day,
+            Self::Week,
+            Self::Month,
+            Self::Quarter,
+            Self::Year,
+        ]
+    }
+
+    /// The name of the unit in `DATE_TRUNC`, e.g. `day`.
+    #[must_use]
+    pub const fn unit(self) -> &'static str {
+        match self {
+            Self::Second => "second",
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Quarter => "quarter",
+            Self::Year => "year",
+        }
+    }
+
+    // the granularity of the unit `unit` of DATE_TRUNC, whatever its case
+    fn from_unit(unit: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|granularity| unit.eq_ignore_ascii_case(granularity.unit()))
+    }
+
+    // the granularity of a part of BigQuery (e.g. `DAY`), where only the ISO weeks start on
+    // Monday
+    fn from_date_part(part: &str) -> Option<Self> {
+        match Self::from_unit(part) {
+            Some(Self::Week) => None,
+            None if part.eq_ignore_ascii_case("isoweek") => Some(Self::Week),
+            granularity => granularity,
+        }
+    }
+
+    // the granularity of a rounding function of ClickHouse (e.g. `toStartOfDay`), whatever its
+    // case, where only `toMonday` starts the weeks on Monday
+    fn from_clickhouse_function(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("toMonday") {
+            return Some(Self::Week);
+        }
+        let unit = name
+            .get(.."toStartOf".len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case("toStartOf"))
+            .map(|_| &name["toStartOf".len()..])?;
+        Self::from_unit(unit).filter(|granularity| *granularity != Self::Week)
+    }
+}
+
+impl Display for TimeGranularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.unit())
+    }
+}
+
+/// The type of the column of a [`TimeBucket`], as told by the BigQuery function truncating it,
+/// whose result has the same type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub enum TimeType {
+    /// The timestamps, truncated with `TIMESTAMP_TRUNC` (and by the other spellings).
+    #[default]
+    Timestamp,
+    /// The dates, truncated with `DATE_TRUNC`.
+    Date,
+    /// The dates and times without a time zone, truncated with `DATETIME_TRUNC`.
+    Datetime,
+}
+
+impl TimeType {
+    /// The BigQuery function truncating the columns of the type, e.g. `DATE_TRUNC`.
+    #[must_use]
+    pub const fn trunc_function(self) -> &'static str {
+        match self {
+            Self::Timestamp => "TIMESTAMP_TRUNC",
+            Self::Date => "DATE_TRUNC",
+            Self::Datetime => "DATETIME_TRUNC",
+        }
+    }
+}
+
+/// A column of timestamps truncated to a [`TimeGranularity`], which a query is grouped by to
+/// compute its aggregation over each period (e.g. `GROUP BY DATE_TRUNC('day', ts)`).
+///
+/// It's recognized in the GROUP BY clause if the feature policy allows it (see
+/// [`crate::policy::FeaturePolicy::allow_time_buckets`]), written as `DATE_TRUNC('unit', column)`,
+/// `DATE_TRUNC(column, UNIT)` as in BigQuery (or `TIMESTAMP_TRUNC` and `DATETIME_TRUNC`),
+/// `DATE(column)` for the days, or a rounding function of ClickHouse (e.g.
+/// `toStartOfDay(column)` or `toMonday(column)`). The SELECT clause may select it too, as written
+/// in the GROUP BY clause.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default, ToSchema)]
+#[cfg_attr(feature = "camel-case", serde(rename_all = "camelCase"))]
+pub struct TimeBucket {
+    /// The truncated column.
+    #[schema(value_type = String)]
+    pub column: Identifier,
+    /// The unit the column is truncated to.
+    pub granularity: TimeGranularity,
+    /// The type of the column, which picks the function the time bucket is rendered with in
+    /// BigQuery (e.g. `DATE_TRUNC(column, MONTH)` for the dates).
+    #[serde(default)]
+    pub time_type: TimeType,
+}
+
+impl TimeBucket {
+    // identifies the time bucket a query is grouped by
+    pub(crate) fn extract(
+        from_clause_identifier: FromClauseIdentifier<'_>,
+        expr: &ast::Expr,
+        case_folding: CaseFolding,
+    ) -> Result<Self, ParseError> {
+        let unsupported = || {
+            unsupported!(
+                UnsupportedFeature::GroupBy,
+                format!("GROUP BY other than a time bucket (i.e., {expr}).")
+            )
+        };
+        let ast::Expr::Function(ast::Function {
+            name: ast::ObjectName(name),
+            args,
+            filter: None,
+            null_treatment: None,
+            over: None,
+            distinct: false,
+            special: false,
+            order_by,
+        }) = remove_outer_parens(expr)
+        else {
+            return Err(unsupported());
+        };
+        let [name] = &name[..] else {
+            return Err(unsupported());
+        };
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(arg)) => {
+                    Some(remove_outer_parens(arg))
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        let (Some(args), None, true) = (args, name.quote_style, order_by.is_empty()) else {
+            return Err(unsupported());
+        };
+        let name = name.value.as_str();
+        let is = |function: &str| name.eq_ignore_ascii_case(function);
+        let bigquery_type = [TimeType::Timestamp, TimeType::Date, TimeType::Datetime]
+            .into_iter()
+            .find(|time_type| is(time_type.trunc_function()));
+        let (granularity, column, time_type) = match (&args[..], bigquery_type) {
+            ([ast::Expr::Value(ast::Value::SingleQuotedString(unit)), column], _)
+                if is("DATE_TRUNC") =>
+            {
+                (
+                    TimeGranularity::from_unit(unit),
+                    column,
+                    TimeType::Timestamp,
+                )
+            }
+            ([column, ast::Expr::Identifier(part)], Some(time_type)) => (
+                TimeGranularity::from_date_part(&part.value),
+                column,
+                time_type,
+            ),
+            ([column], _) if is("DATE") => {
+                (Some(TimeGranularity::Day), column, TimeType::Timestamp)
+            }
+            ([column], _) => (
+                TimeGranularity::from_clickhouse_function(name),
+                column,
+                TimeType::Timestamp,
+            ),
+            _ => return Err(unsupported()),
+        };
+        let Some(granularity) = granularity else {
+            return Err(unsupported());
+        };
+        let column = match *column {
+            ast::Expr::Identifier(ident) => Identifier::fold(ident, case_folding),
+            ast::Expr::CompoundIdentifier(name_parts) => {
+                extract_qualified_column(from_clause_identifier, column, name_parts, case_folding)?
+            }
+            _ => return Err(unsupported()),
+        };
+        Ok(Self {
+            column,
+            granularity,
+            time_type,
+        })
+    }
+
+    /// Renders the time bucket as SQL of `dialect`, with the column quoted only when it wouldn't
+    /// read back the same otherwise, e.g. `DATE_TRUNC('day', ts)`.
+    #[must_use]
+    pub fn to_sql(&self, dialect: Dialect) -> String {
+        self.to_expr(dialect, None).to_string()
+    }
+
+    // the time bucket as an expression of the SQL parser, in the form `dialect` supports: MySQL
+    // has no DATE_TRUNC, so its periods are rebuilt from the parts of the timestamps
+    pub(crate) fn to_expr(&self, dialect: Dialect, quote_style: Option<char>) -> ast::Expr {
+        let column = ast::Expr::Identifier(self.column.render(dialect, quote_style));
+        let string = |value: &str| ast::Expr::Value(ast::Value::SingleQuotedString(value.into()));
+        let format = |format: &str| ast::Expr::Cast {
+            expr: Box::new(function_call(
+                "DATE_FORMAT",
+                vec![column.clone(), string(format)],
+            )),
+            data_type: ast::DataType::Datetime(None),
+            format: None,
+        };
+        match (dialect, self.granularity) {
+            (Dialect::BigQuery, granularity) => {
+                let part = match granularity {
+                    TimeGranularity::Week => "ISOWEEK".to_string(),
+                    granularity => granularity.unit().to_ascii_uppercase(),
+                };
+                let part = ast::Expr::Identifier(ast::Ident::new(part));
+                function_call(self.time_type.trunc_function(), vec![column, part])
+            }
+            (Dialect::MySql, TimeGranularity::Second) => format("%Y-%m-%d %H:%i:%s"),
+            (Dialect::MySql, TimeGranularity::Minute) => format("%Y-%m-%d %H:%i:00"),
+            (Dialect::MySql, TimeGranularity::Hour) => format("%Y-%m-%d %H:00:00"),
+            (Dialect::MySql, TimeGranularity::Day) => function_call("DATE", vec![column]),
+            (Dialect::MySql, TimeGranularity::Week) => {
+                let date = function_call("DATE", vec![column.clone()]);
+                function_call(
+                    "SUBDATE",
+                    vec![date, function_call("WEEKDAY", vec![column])],
+                )
+            }
+            (Dialect::MySql, TimeGranularity::Month) => format("%Y-%m-01"),
+            (Dialect::MySql, TimeGranularity::Quarter) => {
+                let year = function_call("YEAR", vec![column.clone()]);
+                let first_day = ast::Expr::Value(ast::Value::Number("1".to_string(), false));
+                let quarters = ast::Expr::BinaryOp {
+                    left: Box::new(function_call("QUARTER", vec![column])),
+                    op: ast::BinaryOperator::Minus,
+                    right: Box::new(first_day.clone()),
+                };
+                ast::Expr::BinaryOp {
+                    left: Box::new(function_call("MAKEDATE", vec![year, first_day])),
+                    op: ast::BinaryOperator::Plus,
+                    right: Box::new(ast::Expr::Interval(ast::Interval {
+                        value: Box::new(ast::Expr::Nested(Box::new(quarters))),
+                        leading_field: Some(ast::DateTimeField::Quarter),
+                        leading_precision: None,
+                        last_field: None,
+                        fractional_seconds_precision: None,
+                    })),
+                }
+            }
+            (Dialect::MySql, TimeGranularity::Year) => format("%Y-01-01"),
+            (Dialect::Generic | Dialect::PostgreSql | Dialect::Snowflake, granularity) => {
+                function_call("DATE_TRUNC", vec![string(granularity.unit()), column])
+            }
+        }
+    }
+}
+
+impl Display for TimeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_sql(Dialect::Generic))
+    }
+}
+
+// the items of the SELECT clause other than the time bucket `bucket` the query is grouped by,
+// if it's selected too, first or last (e.g. `DATE_TRUNC('day', ts) AS day, SUM(a)`)
+pub(crate) fn without_bucket<'a>(
+    projection: &'a [ast::SelectItem],
+    bucket: Option<&ast::Expr>,
+) -> &'a [ast::SelectItem] {
+    let Some(bucket) = bucket.map(remove_outer_parens) else {
+        return projection;
+    };
+    let is_bucket = |item: &ast::SelectItem| {
+        matches!(item, ast::SelectItem::UnnamedExpr(expr)
+            | ast::SelectItem::ExprWithAlias { expr, .. } if remove_outer_parens(expr) == bucket)
+    };
+    match projection {
+        [first, rest @ ..] if is_bucket(first) => rest,
+        [rest @ .., last] if is_bucket(last) => rest,
+        _ => projection,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeBucket, TimeGranularity, TimeType};
+    use crate::{dialect::Dialect, identifier::Identifier};
+
+    #[test]
+    fn render_time_buckets() {
+        let bucket = |granularity| TimeBucket {
+            column: Identifier::from("created at"),
+            granularity,
+            time_type: TimeType::Timestamp,
+        };
+        assert_eq!(
+            bucket(TimeGranularity::Day).to_string(),
+            "DATE_TRUNC('day', \"created at\")"
+        );
+        assert_eq!(
+            bucket(TimeGranularity::Week).to_sql(Dialect::BigQuery),
+            "TIMESTAMP_TRUNC(`created at`, ISOWEEK)"
+        );
+        let dates = TimeBucket {
+            time_type: TimeType::Date,
+            ..bucket(TimeGranularity::Month)
+        };
+        assert_eq!(
+            dates.to_sql(Dialect::BigQuery),
+            "DATE_TRUNC(`created at`, MONTH)"
+        );
+        assert_eq!(
+            dates.to_sql(Dialect::PostgreSql),
+            "DATE_TRUNC('month', \"created at\")"
+        );
+        let cases = [
+            (
+                TimeGranularity::Hour,
+                "CAST(DATE_FORMAT(`created at`, '%Y-%m-%d %H:00:00') AS DATETIME)",
+            ),
+            (TimeGranularity::Day, "DATE(`created at`)"),
+            (
+                TimeGranularity::Week,
+                "SUBDATE(DATE(`created at`), WEEKDAY(`created at`))",
+            ),
+            (
+                TimeGranularity::Quarter,
+                "MAKEDATE(YEAR(`created at`), 1) + INTERVAL (QUARTER(`created at`) - 1) QUARTER",
+            ),
+        ];
+        for (granularity, sql) in cases {
+            assert_eq!(bucket(granularity).to_sql(Dialect::MySql), sql);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    aggregation::{Aggregation, WindowSpec},
+    filter::Filter,
+    query_metadata::{Aliases, QueryMetadata},
+    table::TabIdent,
+    time_bucket::TimeBucket,
+};
+
+/// Visits the parts of the metadata of a query (see [`QueryMetadata::walk`]).
+///
+/// Every hook does nothing by default, so that a visitor only implements the ones it's
+/// interested in, and keeps compiling when hooks are added for new parts of the queries.
+pub trait MetadataVisitor {
+    /// Visits the table subject to the query.
+    fn visit_table(&mut self, _table: &TabIdent) {}
+
+    /// Visits the aggregation performed.
+    fn visit_aggregation(&mut self, _aggregation: &Aggregation) {}
+
+    /// Visits the window of the aggregation, if it's a running aggregate.
+    fn visit_window(&mut self, _window: &WindowSpec) {}
+
+    /// Visits the filter applied, if any.
+    fn visit_filter(&mut self, _filter: &Filter) {}
+
+    /// Visits the time bucket the query is grouped by, if any.
+    fn visit_time_bucket(&mut self, _time_bucket: &TimeBucket) {}
+
+    /// Visits the aliases used in the query.
+    fn visit_aliases(&mut self, _aliases: &Aliases) {}
+}
+
+impl QueryMetadata {
+    /// Walks the metadata with `visitor`, visiting the table, the aggregation, its window, the
+    /// filter and the time bucket (if any) and the aliases, in this order.
+    pub fn walk(&self, visitor: &mut impl MetadataVisitor) {
+        visitor.visit_table(&self.table);
+        visitor.visit_aggregation(&self.aggregation);
+        if let Some(window) = &self.aggregation.window {
+            visitor.visit_window(window);
+        }
+        if let Some(filter) = &self.filter {
+            visitor.visit_filter(filter);
+        }
+        if let Some(time_bucket) = &self.time_bucket {
+            visitor.visit_time_bucket(time_bucket);
+        }
+        visitor.visit_aliases(&self.aliases);
+    }
+}